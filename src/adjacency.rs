@@ -0,0 +1,107 @@
+use cgmath::BaseFloat;
+#[cfg(feature = "petgraph")]
+use petgraph::graph::{Graph, NodeIndex};
+#[cfg(feature = "petgraph")]
+use petgraph::Undirected;
+
+use diagram::{Diagram, FaceId};
+#[cfg(feature = "petgraph")]
+use diagram::HalfEdgeId;
+
+/// The neighbours of every face, see `Diagram::adjacency`
+pub fn adjacency<T: BaseFloat>(diagram: &Diagram<T>) -> Vec<Vec<FaceId>> {
+    (0..diagram.faces.len() as u32).map(|face_id| {
+        let mut neighbours = Vec::new();
+        for neighbour in diagram.neighbours(FaceId(face_id)) {
+            // Faces only share more than one edge after editing, so the list is short
+            if !neighbours.contains(&neighbour) {
+                neighbours.push(neighbour);
+            }
+        }
+        neighbours
+    }).collect()
+}
+
+/// A colour for every face, see `Diagram::color_faces`
+pub fn color_faces<T: BaseFloat>(diagram: &Diagram<T>, max_colors: u32) -> Option<Vec<u32>> {
+    let adjacency = adjacency(diagram);
+    let count = adjacency.len();
+
+    // Faces are coloured in the reverse of the order they come off the graph
+    // taking the one with the fewest neighbours left each time. Each face then has
+    // at most five neighbours coloured before it, as the graph is planar, so no
+    // more than six colours are needed.
+    let mut degree = adjacency.iter().map(|neighbours| neighbours.len()).collect::<Vec<_>>();
+    let mut buckets = vec![Vec::new(); count + 1];
+    for (face, &degree) in degree.iter().enumerate() {
+        buckets[degree].push(face);
+    }
+    let mut removed = vec![false; count];
+    let mut order = Vec::with_capacity(count);
+    let mut lowest = 0;
+    while order.len() < count {
+        // Faces whose degree has dropped stay in their old bucket too, so those
+        // are skipped
+        let face = match buckets[lowest].pop() {
+            Some(face) if !removed[face] && degree[face] == lowest => face,
+            Some(_) => continue,
+            None => {
+                lowest += 1;
+                continue;
+            }
+        };
+        removed[face] = true;
+        order.push(face);
+        for neighbour in &adjacency[face] {
+            let neighbour = neighbour.0 as usize;
+            if !removed[neighbour] {
+                degree[neighbour] -= 1;
+                buckets[degree[neighbour]].push(neighbour);
+                lowest = lowest.min(degree[neighbour]);
+            }
+        }
+    }
+
+    let mut colors: Vec<Option<u32>> = vec![None; count];
+    let mut used = Vec::new();
+    for &face in order.iter().rev() {
+        used.clear();
+        used.extend(adjacency[face].iter().filter_map(|neighbour| colors[neighbour.0 as usize]));
+        let color = (0..max_colors).find(|color| !used.contains(color))?;
+        colors[face] = Some(color);
+    }
+    Some(colors.into_iter().map(|color| color.unwrap_or(0)).collect())
+}
+
+/// The faces and the edges between them as a graph, see `Diagram::to_petgraph`
+#[cfg(feature = "petgraph")]
+pub fn to_petgraph<T: BaseFloat>(diagram: &Diagram<T>) -> Graph<FaceId, T, Undirected> {
+    let mut graph = Graph::with_capacity(diagram.faces.len(), diagram.halfedges.len() / 2);
+    for face_id in 0..diagram.faces.len() as u32 {
+        graph.add_node(FaceId(face_id));
+    }
+
+    // Each edge is counted from the half-edge of the face with the lower id, and
+    // added to the graph edge for that pair of faces if there already is one
+    for (index, halfedge) in diagram.halfedges.iter().enumerate() {
+        let other = match halfedge.twin.and_then(|twin| diagram.halfedges.get(twin.0 as usize)) {
+            Some(twin) => twin.incident_face,
+            None => continue,
+        };
+        let (face, other) = (halfedge.incident_face, other);
+        let valid = face.0 < other.0 && (other.0 as usize) < diagram.faces.len();
+        if !valid {
+            continue;
+        }
+
+        let length = diagram.edge_length(HalfEdgeId(index as u32)).unwrap_or(T::zero());
+        let (a, b) = (NodeIndex::new(face.0 as usize), NodeIndex::new(other.0 as usize));
+        match graph.find_edge(a, b) {
+            Some(edge) => graph[edge] += length,
+            None => {
+                graph.add_edge(a, b, length);
+            }
+        }
+    }
+    graph
+}
@@ -0,0 +1,472 @@
+//! Apollonius diagrams, where each site has an additive weight
+//!
+//! The weighted distance from a point to a site is the distance between them
+//! minus the site's weight, so a site with a larger weight reaches further. Edges
+//! are arcs of hyperbolas with their foci at the two sites, or straight lines
+//! where the weights are the same. A site can be hidden by a site whose weight is
+//! larger by more than the distance between them, and then has no cell.
+//!
+//! Seen from a site, one over the distance to any bisector with another site is a
+//! linear function of the direction, and so is one over the distance to a side of
+//! the clip region. Each cell is where its site is nearer than every other site and
+//! inside every side, so its boundary is traced by going around the site and
+//! finding the angle where each of these functions overtakes the largest one so far.
+
+use std::f64::consts::PI;
+
+use cgmath::{Point2, Vector2, EuclideanSpace, InnerSpace, BaseFloat};
+use cgmath::num_traits::cast;
+
+use build::{ClipRegion, Site};
+use cells::{Bound, CellAssembler};
+use clip::strictly_inside_boundary;
+use diagram::{Diagram, HalfEdgeId, Face, FaceId};
+
+/// Number of the sites nearest to each cell that it's first traced against
+const FIRST_CANDIDATES: usize = 16;
+
+/// Vertices closer together than this, relative to the size of the clip region,
+/// are the same vertex
+const VERTEX_TOLERANCE: f64 = 1e-9;
+
+/// A conic section, which is the set of points where
+/// `a x² + b xy + c y² + d x + e y + f = 0`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Conic<T = f32> {
+    pub a: T,
+    pub b: T,
+    pub c: T,
+    pub d: T,
+    pub e: T,
+    pub f: T,
+}
+
+impl<T: BaseFloat> Conic<T> {
+    /// Evaluates the left hand side of the equation at a point, which is zero for
+    /// points on the conic
+    pub fn evaluate(&self, point: Point2<T>) -> T {
+        let Point2 { x, y } = point;
+        self.a * x * x + self.b * x * y + self.c * y * y + self.d * x + self.e * y + self.f
+    }
+}
+
+/// Builds the Apollonius diagram of weighted sites, clipped to a region
+///
+/// The weight of each site is taken away from distances to it. Sites outside the
+/// clip region are left out, and the diagram has a face for each of the others in
+/// the order they were given. Faces of hidden sites have no half-edges. Of sites
+/// at the same position with the same weight, the first one gets the cell.
+///
+/// Cells are traced one at a time against the sites near enough to reach them.
+/// Finding those sites checks every pair, so this takes time quadratic in the
+/// number of sites.
+#[derive(Debug, Clone)]
+pub struct ApolloniusBuilder<T = f32> {
+    offset: Point2<f64>,
+    scale: f64,
+
+    /// Corners of the clip region after scaling, in counter-clockwise order
+    boundary: Vec<Point2<f64>>,
+
+    faces: Vec<Face<T>>,
+
+    /// Scaled position of the site of each face
+    sites: Vec<Point2<f64>>,
+
+    /// Scaled weight of the site of each face
+    weights: Vec<f64>,
+}
+
+/// A diagram built by `ApolloniusBuilder`
+///
+/// The faces have the sites they were built from. `Diagram::locate` ignores their
+/// weights, so use `ApolloniusDiagram::locate` instead.
+#[derive(Debug, Clone)]
+pub struct ApolloniusDiagram<T = f32> {
+    diagram: Diagram<T>,
+
+    /// The conic each half-edge lies on, or None for half-edges along the clip region
+    conics: Vec<Option<Conic<T>>>,
+}
+
+/// A bound, along with one over the distance to it from the site of a cell, which
+/// is `gradient · direction + constant` for a unit direction
+#[derive(Debug, Clone, Copy)]
+struct InverseDistance {
+    bound: Bound,
+    gradient: Vector2<f64>,
+    constant: f64,
+}
+
+impl InverseDistance {
+    fn at(&self, angle: f64) -> f64 {
+        self.gradient.dot(direction(angle)) + self.constant
+    }
+
+    /// How fast this increases with the angle
+    fn slope(&self, angle: f64) -> f64 {
+        let direction = direction(angle);
+        self.gradient.dot(Vector2::new(-direction.y, direction.x))
+    }
+}
+
+/// The cell of a site, traced against the bounds that can reach it
+struct TracedCell {
+    inverse_distances: Vec<InverseDistance>,
+
+    /// The angle at which each bound of the cell starts going around the site,
+    /// with the index of the bound
+    starts: Vec<(f64, usize)>,
+}
+
+fn direction(angle: f64) -> Vector2<f64> {
+    Vector2::new(angle.cos(), angle.sin())
+}
+
+impl<T: BaseFloat> ApolloniusBuilder<T> {
+    /// Creates a builder for the Apollonius diagram of the sites inside the clip
+    /// region
+    pub fn new<R: Into<ClipRegion<T>>>(clip_region: R, sites: Vec<Site<T>>) -> ApolloniusBuilder<T> {
+        // Scale the same way as DiagramBuilder. Weights are distances, so they are
+        // scaled once
+        let clip_region = clip_region.into();
+        let bounding_rect = clip_region.bounding_rect();
+        let offset = bounding_rect.position.cast::<f64>();
+        let scale = 1.0 / cast::<T, f64>(bounding_rect.size.x.max(bounding_rect.size.y)).unwrap();
+        let boundary = clip_region.boundary(T::one()).into_iter()
+            .map(|corner| corner.cast::<f64>() * scale)
+            .collect::<Vec<_>>();
+
+        let mut faces = Vec::new();
+        let mut scaled_sites = Vec::new();
+        let mut weights = Vec::new();
+        for site in sites {
+            let position = Point2::from_vec((site.position.cast::<f64>() - offset) * scale);
+
+            if strictly_inside_boundary(&boundary, position) {
+                faces.push(Face {
+                    first_halfedge: None,
+                    site,
+                });
+                scaled_sites.push(position);
+                weights.push(cast::<T, f64>(site.weight).unwrap() * scale);
+            }
+        }
+
+        ApolloniusBuilder {
+            offset,
+            scale,
+            boundary,
+            faces,
+            sites: scaled_sites,
+            weights,
+        }
+    }
+
+    /// Traces the cell of a site, or returns None if the site is hidden
+    fn trace(&self, index: usize) -> Option<TracedCell> {
+        let site = self.sites[index];
+        let sides = self.boundary.iter().enumerate().map(|(corner_index, &corner)| {
+            let next = self.boundary[(corner_index + 1) % self.boundary.len()];
+            let edge = next - corner;
+            let normal = Vector2::new(edge.y, -edge.x).normalize();
+            InverseDistance {
+                bound: Bound::Side(corner_index),
+                gradient: normal / normal.dot(corner - site),
+                constant: 0.0,
+            }
+        }).collect::<Vec<_>>();
+
+        // Along a ray from this site, the bisector with another site is where
+        // |offset + t u| = t + weight_difference, which can be solved for t. It comes
+        // nearest on the way to the other site, halfway across the gap between them
+        // once the weight difference is taken off
+        let mut candidates = Vec::new();
+        for (other_index, &other) in self.sites.iter().enumerate() {
+            if other_index == index {
+                continue;
+            }
+
+            let offset = site - other;
+            let distance = offset.magnitude();
+            let weight_difference = self.weights[other_index] - self.weights[index];
+            if distance < weight_difference || (distance == weight_difference && (weight_difference > 0.0 || other_index < index)) {
+                return None;
+            }
+
+            // Sites hidden by this one are never nearer
+            if distance <= -weight_difference {
+                continue;
+            }
+
+            let numerator = distance * distance - weight_difference * weight_difference;
+            candidates.push(((distance - weight_difference) / 2.0, InverseDistance {
+                bound: Bound::Site(other_index as u32),
+                gradient: offset * (-2.0 / numerator),
+                constant: 2.0 * weight_difference / numerator,
+            }));
+        }
+
+        // The cell traced against the sites that come nearest contains the actual
+        // cell, so only sites that come nearer than it reaches can cut it down. These
+        // are moved up to join the ones already used, and it's traced again
+        let mut count = FIRST_CANDIDATES.min(candidates.len());
+        if count < candidates.len() {
+            candidates.select_nth_unstable_by(count, |a, b| a.0.partial_cmp(&b.0).unwrap());
+        }
+        loop {
+            let inverse_distances = sides.iter().cloned()
+                .chain(candidates[..count].iter().map(|&(_, inverse_distance)| inverse_distance))
+                .collect::<Vec<_>>();
+            let starts = trace_cell(&inverse_distances);
+            let reach = cell_reach(&inverse_distances, &starts);
+
+            let used = count;
+            for index in used..candidates.len() {
+                if candidates[index].0 < reach {
+                    candidates.swap(count, index);
+                    count += 1;
+                }
+            }
+            if count == used {
+                return Some(TracedCell {
+                    inverse_distances,
+                    starts,
+                });
+            }
+        }
+    }
+
+    pub fn finish(self) -> ApolloniusDiagram<T> {
+        let mut assembler = CellAssembler::new(VERTEX_TOLERANCE);
+        for (index, face) in self.faces.iter().enumerate() {
+            let TracedCell { inverse_distances, starts } = match self.trace(index) {
+                Some(traced) => traced,
+                None => {
+                    assembler.add_cell(face.site, &[]);
+                    continue;
+                }
+            };
+
+            let corners = starts.iter().enumerate().map(|(start_index, &(angle, bound_index))| {
+                let previous = starts[(start_index + starts.len() - 1) % starts.len()].1;
+                let position = match (inverse_distances[previous].bound, inverse_distances[bound_index].bound) {
+                    (Bound::Side(_), Bound::Side(side)) => self.boundary[side],
+                    _ => self.sites[index] + direction(angle) / inverse_distances[bound_index].at(angle),
+                };
+                (position, inverse_distances[bound_index].bound)
+            }).collect::<Vec<_>>();
+            assembler.add_cell(face.site, &corners);
+        }
+
+        // Scale vertices back to the coordinates of the bounding rect
+        let mut diagram = assembler.finish(|position| (self.offset + position.to_vec() / self.scale).cast::<T>());
+
+        let conics = diagram.halfedges.iter().map(|halfedge| {
+            halfedge.twin.map(|twin| {
+                let site = diagram.faces[halfedge.incident_face.0 as usize].site;
+                let other = diagram.faces[diagram.halfedges[twin.0 as usize].incident_face.0 as usize].site;
+                bisector(site, other)
+            })
+        }).collect();
+
+        diagram.index_sites(false);
+        ApolloniusDiagram {
+            diagram,
+            conics,
+        }
+    }
+}
+
+/// Goes around a site, finding the angle at which each bound of its cell starts
+fn trace_cell(inverse_distances: &[InverseDistance]) -> Vec<(f64, usize)> {
+    // The bound nearest the site is the one whose inverse distance is largest.
+    // Of bounds that meet at the same angle, the one that increases fastest is
+    // nearest just after it
+    let mut current = 0;
+    for (index, inverse_distance) in inverse_distances.iter().enumerate() {
+        let (value, current_value) = (inverse_distance.at(0.0), inverse_distances[current].at(0.0));
+        if value > current_value || (value == current_value && inverse_distance.slope(0.0) > inverse_distances[current].slope(0.0)) {
+            current = index;
+        }
+    }
+    let mut angle = 0.0;
+    let mut starts = Vec::new();
+
+    // Each bound can only start once, apart from bisectors of sites that can be
+    // on both sides of a third
+    for _ in 0..2 * inverse_distances.len() + 2 {
+        let active = inverse_distances[current];
+
+        // Another bound overtakes this one where the difference between them,
+        // R cos(θ - φ) - C, goes from negative to positive, at θ = φ - acos(C / R)
+        let mut next: Option<(f64, f64, usize)> = None;
+        for (index, other) in inverse_distances.iter().enumerate() {
+            if index == current {
+                continue;
+            }
+
+            let gradient = other.gradient - active.gradient;
+            let amplitude = gradient.magnitude();
+            let threshold = active.constant - other.constant;
+            if amplitude == 0.0 || threshold.abs() >= amplitude {
+                continue;
+            }
+
+            let crossing = gradient.y.atan2(gradient.x) - (threshold / amplitude).acos();
+            let turn = (crossing - angle).rem_euclid(2.0 * PI);
+            let slope = other.slope(crossing);
+            match next {
+                Some((best_turn, best_slope, _)) if best_turn < turn || (best_turn == turn && best_slope >= slope) => {}
+                _ => next = Some((turn, slope, index)),
+            }
+        }
+
+        let (turn, _, index) = match next {
+            Some(next) => next,
+            None => break,
+        };
+        angle += turn;
+        if angle >= 2.0 * PI {
+            break;
+        }
+
+        current = index;
+        starts.push((angle, index));
+    }
+
+    starts
+}
+
+/// The furthest a traced cell reaches from its site
+fn cell_reach(inverse_distances: &[InverseDistance], starts: &[(f64, usize)]) -> f64 {
+    starts.iter().enumerate().map(|(start_index, &(from, bound_index))| {
+        let to = starts[(start_index + 1) % starts.len()].0;
+        let span = if starts.len() == 1 { 2.0 * PI } else { (to - from).rem_euclid(2.0 * PI) };
+
+        // One over the distance is smallest at one of the ends, or at the bottom of
+        // its sinusoid if that's in between
+        let bound = inverse_distances[bound_index];
+        let bottom = bound.gradient.y.atan2(bound.gradient.x) + PI;
+        let mut smallest = bound.at(from).min(bound.at(to));
+        if (bottom - from).rem_euclid(2.0 * PI) < span {
+            smallest = smallest.min(bound.at(bottom));
+        }
+        1.0 / smallest
+    }).fold(0.0, f64::max)
+}
+
+/// The conic through the points whose weighted distances from two sites are equal
+///
+/// For distances d1 and d2 to the sites and weights w1 and w2, squaring
+/// d1 - d2 = w1 - w2 twice leaves a quadratic. It covers both branches of the
+/// hyperbola, and for equal weights it's the square of the line between the sites.
+fn bisector<T: BaseFloat>(site: Site<T>, other: Site<T>) -> Conic<T> {
+    let p = site.position.cast::<f64>();
+    let q = other.position.cast::<f64>();
+    let k = cast::<T, f64>(site.weight - other.weight).unwrap();
+
+    // d1² - d2² - k² = g · x + h, and (g · x + h)² = 4 k² d2²
+    let g = (q - p) * 2.0;
+    let h = p.to_vec().magnitude2() - q.to_vec().magnitude2() - k * k;
+    let k2 = 4.0 * k * k;
+    let conic = [
+        g.x * g.x - k2,
+        2.0 * g.x * g.y,
+        g.y * g.y - k2,
+        2.0 * g.x * h + 2.0 * k2 * q.x,
+        2.0 * g.y * h + 2.0 * k2 * q.y,
+        h * h - k2 * q.to_vec().magnitude2(),
+    ];
+    let coefficient = |index: usize| cast::<f64, T>(conic[index]).unwrap();
+
+    Conic {
+        a: coefficient(0),
+        b: coefficient(1),
+        c: coefficient(2),
+        d: coefficient(3),
+        e: coefficient(4),
+        f: coefficient(5),
+    }
+}
+
+impl<T: BaseFloat> ApolloniusDiagram<T> {
+    pub fn diagram(&self) -> &Diagram<T> {
+        &self.diagram
+    }
+
+    pub fn into_diagram(self) -> Diagram<T> {
+        self.diagram
+    }
+
+    /// The conic a half-edge lies on, which is None for half-edges along the edge of
+    /// the clip region
+    pub fn conic(&self, halfedge_id: HalfEdgeId) -> Option<&Conic<T>> {
+        self.conics.get(halfedge_id.0 as usize).and_then(|conic| conic.as_ref())
+    }
+
+    /// Finds the face whose site is nearest to a point by weighted distance
+    pub fn locate(&self, point: Point2<T>) -> Option<FaceId> {
+        let distance = |site: &Site<T>| (point - site.position).magnitude() - site.weight;
+        self.diagram.faces.iter().enumerate()
+            .filter(|&(_, face)| face.first_halfedge.is_some())
+            .fold(None, |nearest: Option<(T, usize)>, (index, face)| {
+                let face_distance = distance(&face.site);
+                match nearest {
+                    Some((nearest_distance, _)) if nearest_distance <= face_distance => nearest,
+                    _ => Some((face_distance, index)),
+                }
+            })
+            .map(|(_, index)| FaceId(index as u32))
+    }
+
+    /// Finds points along a half-edge from its origin to its destination, splitting
+    /// it into the given number of pieces
+    ///
+    /// The pieces of curved edges cover equal angles as seen from the site of the
+    /// half-edge's face.
+    pub fn edge_points(&self, halfedge_id: HalfEdgeId, pieces: usize) -> Vec<Point2<T>> {
+        let diagram = &self.diagram;
+        let halfedge = match diagram.get_half_edge(halfedge_id) {
+            Some(halfedge) => halfedge,
+            None => return Vec::new(),
+        };
+        let (start, end) = match (halfedge.origin, diagram.halfedges[halfedge.next.0 as usize].origin) {
+            (Some(start), Some(end)) => (start, end),
+            _ => return Vec::new(),
+        };
+        let pieces = pieces.max(1);
+        let start = diagram.vertices[start.0 as usize].coordinates.cast::<f64>();
+        let end = diagram.vertices[end.0 as usize].coordinates.cast::<f64>();
+        let fraction = |index: usize| index as f64 / pieces as f64;
+
+        let points = match halfedge.twin {
+            None => (0..pieces + 1).map(|index| start + (end - start) * fraction(index)).collect::<Vec<_>>(),
+            Some(twin) => {
+                let site = diagram.faces[halfedge.incident_face.0 as usize].site;
+                let other = diagram.faces[diagram.halfedges[twin.0 as usize].incident_face.0 as usize].site;
+                let position = site.position.cast::<f64>();
+                let offset = position - other.position.cast::<f64>();
+                let weight_difference = cast::<T, f64>(other.weight - site.weight).unwrap();
+                let numerator = offset.magnitude2() - weight_difference * weight_difference;
+
+                let angle = |point: Point2<f64>| (point.y - position.y).atan2(point.x - position.x);
+                let from = angle(start);
+                let turn = (angle(end) - from).rem_euclid(2.0 * PI);
+                (0..pieces + 1).map(|index| {
+                    if index == 0 {
+                        return start;
+                    }
+                    if index == pieces {
+                        return end;
+                    }
+
+                    let direction = direction(from + turn * fraction(index));
+                    position + direction * (numerator / (2.0 * weight_difference - 2.0 * offset.dot(direction)))
+                }).collect()
+            }
+        };
+
+        points.into_iter().map(|point| point.cast::<T>()).collect()
+    }
+}
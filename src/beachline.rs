@@ -0,0 +1,666 @@
+use std::cmp::Ordering;
+
+use cgmath::{Point2, BaseFloat};
+use cgmath::num_traits::cast;
+
+use build::Site;
+use diagram::{HalfEdgeId, FaceId};
+use predicates::orient2d;
+#[cfg(feature = "exact")]
+use exact;
+#[cfg(feature = "simd")]
+use simd;
+#[cfg(feature = "serde")]
+use serde::{Serialize, Deserialize};
+
+/// Finds the centre of the circle through three points and the y coordinate of its
+/// bottom, where the sweep line leaves it
+pub(crate) fn circumcircle_of_points<T: BaseFloat>(a: Point2<T>, b: Point2<T>, c: Point2<T>) -> Option<(Point2<T>, T)> {
+    let (a, b, c) = (a.cast::<f64>(), b.cast::<f64>(), c.cast::<f64>());
+
+    // The orientation is exact, so this catches every collinear triple however
+    // close the points are
+    let d = 2.0 * orient2d(a, b, c);
+    if d == 0.0 {
+        return None;
+    }
+
+    // http://en.wikipedia.org/wiki/Circumscribed_circle#Cartesian_coordinates
+    // Relative to a to keep the numbers small
+    let (bx, by) = (b.x - a.x, b.y - a.y);
+    let (cx, cy) = (c.x - a.x, c.y - a.y);
+    let b2 = bx * bx + by * by;
+    let c2 = cx * cx + cy * cy;
+
+    let x = (cy * b2 - by * c2) / d;
+    let y = (bx * c2 - cx * b2) / d;
+
+    Some(circle_around(a, x, y))
+}
+
+/// The centre of a circle at an offset from a point on it, and the y coordinate
+/// of its bottom
+fn circle_around<T: BaseFloat>(a: Point2<f64>, x: f64, y: f64) -> (Point2<T>, T) {
+    let centroid = Point2::new(a.x + x, a.y + y).cast();
+
+    // The sweep line leaves the circle at its bottom. Nearly collinear points have a
+    // huge circle, so when its centre is above them the bottom is rearranged to
+    // avoid subtracting the radius from a centre that is almost as far away
+    let radius = x.hypot(y);
+    let bottom = if y < 0.0 {
+        x * x / (radius - y)
+    } else {
+        y + radius
+    };
+
+    (centroid, cast(a.y + bottom).unwrap())
+}
+
+fn intersection<T: BaseFloat>(left_focus: Point2<T>, right_focus: Point2<T>, directrix: T) -> Point2<T> {
+    // Based on intersection function from https://www.cs.hmc.edu/~mbrubeck/voronoi.html
+    // Worked out in f64 as the parabolas of nearby sites are almost the same
+    let left_focus = left_focus.cast::<f64>();
+    let right_focus = right_focus.cast::<f64>();
+    let directrix = cast::<T, f64>(directrix).unwrap();
+    let mut p = &left_focus;
+
+    let x = if left_focus.y == right_focus.y {
+        // Focii are at the same height so breakpoint is in the middle
+        (left_focus.x + right_focus.x) / 2.0
+    } else if right_focus.y == directrix {
+        // Right focus is on the directrix
+        right_focus.x
+    } else if left_focus.y == directrix {
+        // Left focus is on the directrix
+        p = &right_focus;
+        left_focus.x
+    } else {
+        // Use the quadratic formula
+        let z_left = 2.0 * (left_focus.y - directrix);
+        let z_right = 2.0 * (right_focus.y - directrix);
+
+        let a = 1.0 / z_left - 1.0 / z_right;
+        let b = -2.0 * (left_focus.x / z_left - right_focus.x / z_right);
+        let c = left_focus.x * left_focus.x / z_left - right_focus.x * right_focus.x / z_right
+                + (left_focus.y - right_focus.y) / 2.0;
+
+        // This is the root (-b - sqrt(b^2 - 4ac)) / 2a, rearranged when b is negative
+        // so the two terms of the numerator don't cancel out
+        let discriminant = (b * b - 4.0 * a * c).abs().sqrt();
+        if b <= 0.0 && discriminant - b > 0.0 {
+            2.0 * c / (discriminant - b)
+        } else {
+            (-b - discriminant) / (2.0 * a)
+        }
+    };
+
+    // Plug back into one of the parabola equations
+    let y = (p.y * p.y + (p.x - x) * (p.x - x) - directrix * directrix) / (2.0 * p.y - 2.0 * directrix);
+
+    Point2::new(x, y).cast()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ArcId(pub u32);
+
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Arc<T> {
+    pub site: Site<T>,
+    pub face: FaceId,
+    pub left: Option<ArcId>,
+    pub right: Option<ArcId>,
+
+    /// Half-edge of this arc's face traced by the left breakpoint
+    pub left_halfedge: Option<HalfEdgeId>,
+
+    /// Half-edge of this arc's face traced by the right breakpoint
+    pub right_halfedge: Option<HalfEdgeId>,
+
+    /// Id of the arc's pending circle event
+    ///
+    /// Events in the queue for this arc with any other id were cancelled.
+    pub circle_event: Option<u32>,
+
+    // Red-black tree links
+    parent: Option<ArcId>,
+    left_child: Option<ArcId>,
+    right_child: Option<ArcId>,
+    red: bool,
+}
+
+impl<T> Arc<T> {
+    fn new(site: Site<T>, face: FaceId) -> Arc<T> {
+        Arc {
+            site,
+            face,
+            left: None,
+            right: None,
+            left_halfedge: None,
+            right_halfedge: None,
+            circle_event: None,
+            parent: None,
+            left_child: None,
+            right_child: None,
+            red: true,
+        }
+    }
+}
+
+/// The beachline, stored as a red-black tree of arcs ordered from left to right
+///
+/// Arcs are never keyed by a stored value as their breakpoints move with the
+/// sweep line. Instead, lookups descend the tree comparing against the breakpoints
+/// at the current directrix, which keeps the order valid without rebalancing.
+///
+/// The left and right links on each arc mirror the in-order sequence of the tree
+/// so neighbours can be found in constant time.
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct BeachLine<T> {
+    arcs: Vec<Arc<T>>,
+    root: Option<ArcId>,
+}
+
+impl<T> Default for BeachLine<T> {
+    fn default() -> BeachLine<T> {
+        BeachLine {
+            arcs: Vec::new(),
+            root: None,
+        }
+    }
+}
+
+impl<T: BaseFloat> BeachLine<T> {
+    /// Removes all the arcs, keeping the memory for them
+    pub fn clear(&mut self) {
+        self.arcs.clear();
+        self.root = None;
+    }
+
+    pub fn get_arc(&self, arc_id: ArcId) -> &Arc<T> {
+        &self.arcs[arc_id.0 as usize]
+    }
+
+    pub fn get_arc_mut(&mut self, arc_id: ArcId) -> &mut Arc<T> {
+        &mut self.arcs[arc_id.0 as usize]
+    }
+
+    fn new_arc(&mut self, site: Site<T>, face: FaceId) -> ArcId {
+        let arc_id = ArcId(self.arcs.len() as u32);
+        self.arcs.push(Arc::new(site, face));
+        arc_id
+    }
+
+    pub fn add_arc(&mut self, site: Site<T>, face: FaceId, current_arc_id: Option<ArcId>) -> ArcId {
+        // If there's an existing arc, split it in two
+        if let Some(current_arc_id) = current_arc_id {
+            let arc_id = self.add_arc_after(site, face, current_arc_id);
+
+            // Copy existing arc and place it to the right of the new one
+            let (current_site, current_face, current_right_halfedge) = {
+                let current_arc = self.get_arc(current_arc_id);
+                (current_arc.site, current_arc.face, current_arc.right_halfedge)
+            };
+            let right_arc_id = self.add_arc_after(current_site, current_face, arc_id);
+            self.get_arc_mut(right_arc_id).right_halfedge = current_right_halfedge;
+
+            arc_id
+        } else {
+            let arc_id = self.new_arc(site, face);
+            self.get_arc_mut(arc_id).red = false;
+            self.root = Some(arc_id);
+            arc_id
+        }
+    }
+
+    pub fn add_arc_after(&mut self, site: Site<T>, face: FaceId, left_arc_id: ArcId) -> ArcId {
+        let arc_id = self.new_arc(site, face);
+
+        // Link new arc between the left arc and its right neighbour
+        let right_arc_id = self.get_arc(left_arc_id).right;
+        self.get_arc_mut(arc_id).left = Some(left_arc_id);
+        self.get_arc_mut(arc_id).right = right_arc_id;
+        self.get_arc_mut(left_arc_id).right = Some(arc_id);
+        if let Some(right_arc_id) = right_arc_id {
+            self.get_arc_mut(right_arc_id).left = Some(arc_id);
+        }
+
+        // Insert into the tree as the in-order successor of the left arc. This is either
+        // the left arc's right child, or the left child of the right neighbour (which is
+        // then the leftmost node in the left arc's right subtree)
+        let parent = match self.get_arc(left_arc_id).right_child {
+            None => {
+                self.get_arc_mut(left_arc_id).right_child = Some(arc_id);
+                left_arc_id
+            }
+            Some(_) => {
+                let right_arc_id = right_arc_id.unwrap();
+                self.get_arc_mut(right_arc_id).left_child = Some(arc_id);
+                right_arc_id
+            }
+        };
+        self.get_arc_mut(arc_id).parent = Some(parent);
+
+        self.insert_fixup(arc_id);
+
+        arc_id
+    }
+
+    pub fn get_left_right_arcs(&self, arc_id: ArcId) -> (Option<ArcId>, Option<ArcId>) {
+        let arc = self.get_arc(arc_id);
+        (arc.left, arc.right)
+    }
+
+    pub fn get_left_breakpoint(&self, arc_id: ArcId, directrix: T) -> T {
+        let right_arc = self.get_arc(arc_id);
+        let left_arc = match right_arc.left {
+            Some(left_arc_id) => self.get_arc(left_arc_id),
+            None => return T::neg_infinity(),
+        };
+
+        intersection(left_arc.site.position, right_arc.site.position, directrix).x
+    }
+
+    pub fn get_right_breakpoint(&self, arc_id: ArcId, directrix: T) -> T {
+        let left_arc = self.get_arc(arc_id);
+        let right_arc = match left_arc.right {
+            Some(right_arc_id) => self.get_arc(right_arc_id),
+            None => return T::infinity(),
+        };
+
+        intersection(left_arc.site.position, right_arc.site.position, directrix).x
+    }
+
+    /// Position of the breakpoint between an arc and the arc to its right
+    pub fn get_right_breakpoint_position(&self, arc_id: ArcId, directrix: T) -> Option<Point2<T>> {
+        let left_arc = self.get_arc(arc_id);
+        let right_arc = self.get_arc(left_arc.right?);
+        let (left, right) = (left_arc.site.position, right_arc.site.position);
+
+        // When both sites are on the directrix their parabolas are rays pointing away
+        // from the sweep, which only meet infinitely far away
+        if left.y == directrix && right.y == directrix {
+            return Some(Point2::new((left.x + right.x) / (T::one() + T::one()), T::neg_infinity()));
+        }
+
+        Some(intersection(left, right, directrix))
+    }
+
+    /// The sites of an arc and its neighbours from left to right, if the arc's
+    /// breakpoints are converging so it will shrink to a point
+    fn converging_sites(&self, middle_arc_id: ArcId) -> Option<[Point2<T>; 3]> {
+        let middle_arc = self.get_arc(middle_arc_id);
+        let (left_arc_id, right_arc_id) = match (middle_arc.left, middle_arc.right) {
+            (Some(left_arc_id), Some(right_arc_id)) => (left_arc_id, right_arc_id),
+            _ => return None
+        };
+
+        let left = self.get_arc(left_arc_id).site.position;
+        let middle = middle_arc.site.position;
+        let right = self.get_arc(right_arc_id).site.position;
+
+        // The middle arc only shrinks to a point if its breakpoints are converging,
+        // which is the case when the three sites turn clockwise. This must be exact,
+        // a wrong answer for nearly collinear sites corrupts the diagram
+        if orient2d(left.cast(), right.cast(), middle.cast()) >= 0.0 {
+            return None;
+        }
+
+        Some([left, middle, right])
+    }
+
+    #[cfg(not(feature = "simd"))]
+    pub fn get_circumcircle(&self, middle_arc_id: ArcId) -> Option<(Point2<T>, T)> {
+        let [left, middle, right] = self.converging_sites(middle_arc_id)?;
+        circumcircle_of_points(left, middle, right)
+    }
+
+    /// The circumcircles of two arcs, see `get_circumcircle`
+    #[cfg(not(feature = "simd"))]
+    pub fn get_circumcircles(&self, arc_ids: [ArcId; 2]) -> [Option<(Point2<T>, T)>; 2] {
+        arc_ids.map(|arc_id| self.get_circumcircle(arc_id))
+    }
+
+    /// The circumcircles of two arcs, worked out together
+    #[cfg(feature = "simd")]
+    pub fn get_circumcircles(&self, arc_ids: [ArcId; 2]) -> [Option<(Point2<T>, T)>; 2] {
+        let triples = arc_ids.map(|arc_id| self.converging_sites(arc_id).map(|sites| sites.map(|site| site.cast::<f64>())));
+        let d = triples.map(|sites| sites.map_or(0.0, |[a, b, c]| 2.0 * orient2d(a, b, c)));
+
+        // Lanes without a circle are filled in with the other one's sites, and their
+        // results are thrown away
+        let filler = triples[0].or(triples[1]).unwrap_or([Point2::new(0.0, 0.0); 3]);
+        let centres = simd::circumcentres(triples.map(|sites| sites.unwrap_or(filler)), d);
+        [0, 1].map(|lane| match triples[lane] {
+            Some([a, _, _]) if d[lane] != 0.0 => Some(circle_around(a, centres[lane].0, centres[lane].1)),
+            _ => None,
+        })
+    }
+
+    #[cfg(not(any(feature = "exact", feature = "simd")))]
+    fn is_left_of_arc(&self, arc_id: ArcId, x: T, directrix: T) -> bool {
+        x < self.get_left_breakpoint(arc_id, directrix)
+    }
+
+    #[cfg(not(any(feature = "exact", feature = "simd")))]
+    fn is_right_of_arc(&self, arc_id: ArcId, x: T, directrix: T) -> bool {
+        self.get_arc(arc_id).right.is_some() && x >= self.get_right_breakpoint(arc_id, directrix)
+    }
+
+    #[cfg(feature = "exact")]
+    fn is_left_of_arc(&self, arc_id: ArcId, x: T, directrix: T) -> bool {
+        let arc = self.get_arc(arc_id);
+        arc.left.is_some_and(|left_arc_id| {
+            let left = self.get_arc(left_arc_id).site.position;
+            exact::compare_to_breakpoint(cast(x).unwrap(), cast(directrix).unwrap(), left.cast(), arc.site.position.cast()) == Ordering::Less
+        })
+    }
+
+    #[cfg(feature = "exact")]
+    fn is_right_of_arc(&self, arc_id: ArcId, x: T, directrix: T) -> bool {
+        let arc = self.get_arc(arc_id);
+        arc.right.is_some_and(|right_arc_id| {
+            let right = self.get_arc(right_arc_id).site.position;
+            exact::compare_to_breakpoint(cast(x).unwrap(), cast(directrix).unwrap(), arc.site.position.cast(), right.cast()) != Ordering::Less
+        })
+    }
+
+    /// The sites of the circle event of an arc, from left to right
+    #[cfg(feature = "exact")]
+    pub fn get_circle_sites(&self, middle_arc_id: ArcId) -> [Point2<f64>; 3] {
+        let middle_arc = self.get_arc(middle_arc_id);
+        let left = self.get_arc(middle_arc.left.unwrap()).site.position;
+        let right = self.get_arc(middle_arc.right.unwrap()).site.position;
+        [left.cast(), middle_arc.site.position.cast(), right.cast()]
+    }
+
+    /// Whether x is left of an arc's left breakpoint, at or right of its right
+    /// breakpoint, or under the arc
+    #[cfg(any(feature = "exact", not(feature = "simd")))]
+    fn compare_to_arc(&self, arc_id: ArcId, x: T, directrix: T) -> Ordering {
+        if self.is_left_of_arc(arc_id, x, directrix) {
+            Ordering::Less
+        } else if self.is_right_of_arc(arc_id, x, directrix) {
+            Ordering::Greater
+        } else {
+            Ordering::Equal
+        }
+    }
+
+    /// Whether x is left of an arc's left breakpoint, at or right of its right
+    /// breakpoint, or under the arc, working out both breakpoints together
+    #[cfg(all(feature = "simd", not(feature = "exact")))]
+    fn compare_to_arc(&self, arc_id: ArcId, x: T, directrix: T) -> Ordering {
+        let arc = self.get_arc(arc_id);
+        let position = arc.site.position.cast::<f64>();
+        let left = arc.left.map(|left_arc_id| self.get_arc(left_arc_id).site.position.cast::<f64>());
+        let right = arc.right.map(|right_arc_id| self.get_arc(right_arc_id).site.position.cast::<f64>());
+        let [left_x, right_x] = simd::breakpoints_x([left.unwrap_or(position), position], [position, right.unwrap_or(position)], cast(directrix).unwrap());
+
+        if left.is_some() && x < cast(left_x).unwrap() {
+            Ordering::Less
+        } else if right.is_some() && x >= cast(right_x).unwrap() {
+            Ordering::Greater
+        } else {
+            Ordering::Equal
+        }
+    }
+
+    pub fn find_arc(&self, x: T, directrix: T) -> Option<ArcId> {
+        let mut current_arc = self.root?;
+
+        loop {
+            let arc = self.get_arc(current_arc);
+
+            // When x is exactly on a breakpoint, the arc to the right is chosen
+            let next_arc = match self.compare_to_arc(current_arc, x, directrix) {
+                Ordering::Less => arc.left_child,
+                Ordering::Greater => arc.right_child,
+                Ordering::Equal => return Some(current_arc),
+            };
+
+            match next_arc {
+                Some(next_arc) => current_arc = next_arc,
+                None => return Some(current_arc),
+            }
+        }
+    }
+
+    pub fn remove_arc(&mut self, arc_id: ArcId) {
+        // Link left and right arcs together
+        let (left_arc_id, right_arc_id) = self.get_left_right_arcs(arc_id);
+        if let Some(left_arc_id) = left_arc_id {
+            self.get_arc_mut(left_arc_id).right = right_arc_id;
+        }
+        if let Some(right_arc_id) = right_arc_id {
+            self.get_arc_mut(right_arc_id).left = left_arc_id;
+        }
+
+        // Remove arc from the tree
+        self.delete(arc_id);
+    }
+
+    fn get_child(&self, arc_id: ArcId, left: bool) -> Option<ArcId> {
+        let arc = self.get_arc(arc_id);
+        if left { arc.left_child } else { arc.right_child }
+    }
+
+    fn is_red(&self, arc_id: Option<ArcId>) -> bool {
+        arc_id.is_some_and(|arc_id| self.get_arc(arc_id).red)
+    }
+
+    fn set_red(&mut self, arc_id: Option<ArcId>, red: bool) {
+        if let Some(arc_id) = arc_id {
+            self.get_arc_mut(arc_id).red = red;
+        }
+    }
+
+    fn set_parent(&mut self, arc_id: Option<ArcId>, parent: Option<ArcId>) {
+        if let Some(arc_id) = arc_id {
+            self.get_arc_mut(arc_id).parent = parent;
+        }
+    }
+
+    /// Replaces the subtree rooted at `old` with the one rooted at `new` in old's parent
+    fn replace_child(&mut self, old: ArcId, new: Option<ArcId>) {
+        let parent = self.get_arc(old).parent;
+        match parent {
+            None => self.root = new,
+            Some(parent) => {
+                if self.get_arc(parent).left_child == Some(old) {
+                    self.get_arc_mut(parent).left_child = new;
+                } else {
+                    self.get_arc_mut(parent).right_child = new;
+                }
+            }
+        }
+        self.set_parent(new, parent);
+    }
+
+    fn rotate_left(&mut self, arc_id: ArcId) {
+        let pivot = self.get_arc(arc_id).right_child.unwrap();
+        let inner = self.get_arc(pivot).left_child;
+
+        self.get_arc_mut(arc_id).right_child = inner;
+        self.set_parent(inner, Some(arc_id));
+        self.replace_child(arc_id, Some(pivot));
+        self.get_arc_mut(pivot).left_child = Some(arc_id);
+        self.get_arc_mut(arc_id).parent = Some(pivot);
+    }
+
+    fn rotate_right(&mut self, arc_id: ArcId) {
+        let pivot = self.get_arc(arc_id).left_child.unwrap();
+        let inner = self.get_arc(pivot).right_child;
+
+        self.get_arc_mut(arc_id).left_child = inner;
+        self.set_parent(inner, Some(arc_id));
+        self.replace_child(arc_id, Some(pivot));
+        self.get_arc_mut(pivot).right_child = Some(arc_id);
+        self.get_arc_mut(arc_id).parent = Some(pivot);
+    }
+
+    fn insert_fixup(&mut self, mut arc_id: ArcId) {
+        while let Some(parent) = self.get_arc(arc_id).parent.filter(|&parent| self.get_arc(parent).red) {
+            // The parent is red so it can't be the root
+            let grandparent = self.get_arc(parent).parent.unwrap();
+            let parent_is_left = self.get_arc(grandparent).left_child == Some(parent);
+            let uncle = if parent_is_left {
+                self.get_arc(grandparent).right_child
+            } else {
+                self.get_arc(grandparent).left_child
+            };
+
+            if self.is_red(uncle) {
+                self.set_red(Some(parent), false);
+                self.set_red(uncle, false);
+                self.set_red(Some(grandparent), true);
+                arc_id = grandparent;
+                continue;
+            }
+
+            let mut parent = parent;
+            if parent_is_left {
+                if self.get_arc(parent).right_child == Some(arc_id) {
+                    self.rotate_left(parent);
+                    parent = arc_id;
+                }
+                self.rotate_right(grandparent);
+            } else {
+                if self.get_arc(parent).left_child == Some(arc_id) {
+                    self.rotate_right(parent);
+                    parent = arc_id;
+                }
+                self.rotate_left(grandparent);
+            }
+            self.set_red(Some(parent), false);
+            self.set_red(Some(grandparent), true);
+            break;
+        }
+
+        let root = self.root;
+        self.set_red(root, false);
+    }
+
+    fn delete(&mut self, arc_id: ArcId) {
+        let (left_child, right_child, parent, red) = {
+            let arc = self.get_arc(arc_id);
+            (arc.left_child, arc.right_child, arc.parent, arc.red)
+        };
+
+        // Node that moves into the removed position, and its new parent. The
+        // node may be None, so the parent is tracked separately for the fixup.
+        let (child, child_parent, removed_red) = match (left_child, right_child) {
+            (None, _) => {
+                self.replace_child(arc_id, right_child);
+                (right_child, parent, red)
+            }
+            (_, None) => {
+                self.replace_child(arc_id, left_child);
+                (left_child, parent, red)
+            }
+            (Some(left_child), Some(right_child)) => {
+                // Swap in the in-order successor, which is the right neighbour
+                let successor = self.get_arc(arc_id).right.unwrap();
+                let (successor_right_child, successor_parent, successor_red) = {
+                    let successor = self.get_arc(successor);
+                    (successor.right_child, successor.parent, successor.red)
+                };
+
+                let child_parent = if successor_parent == Some(arc_id) {
+                    successor
+                } else {
+                    self.replace_child(successor, successor_right_child);
+                    self.get_arc_mut(successor).right_child = Some(right_child);
+                    self.get_arc_mut(right_child).parent = Some(successor);
+                    successor_parent.unwrap()
+                };
+
+                self.replace_child(arc_id, Some(successor));
+                self.get_arc_mut(successor).left_child = Some(left_child);
+                self.get_arc_mut(left_child).parent = Some(successor);
+                self.get_arc_mut(successor).red = red;
+
+                (successor_right_child, Some(child_parent), successor_red)
+            }
+        };
+
+        if !removed_red {
+            self.delete_fixup(child, child_parent);
+        }
+
+        let arc = self.get_arc_mut(arc_id);
+        arc.parent = None;
+        arc.left_child = None;
+        arc.right_child = None;
+    }
+
+    fn delete_fixup(&mut self, mut arc_id: Option<ArcId>, mut parent: Option<ArcId>) {
+        while arc_id != self.root && !self.is_red(arc_id) {
+            // The node is short one black so it can't be the root and must have a sibling
+            let current_parent = parent.unwrap();
+            let is_left = self.get_arc(current_parent).left_child == arc_id;
+
+            let mut sibling = self.get_child(current_parent, !is_left).unwrap();
+            if self.get_arc(sibling).red {
+                self.set_red(Some(sibling), false);
+                self.set_red(Some(current_parent), true);
+                if is_left {
+                    self.rotate_left(current_parent);
+                } else {
+                    self.rotate_right(current_parent);
+                }
+                sibling = self.get_child(current_parent, !is_left).unwrap();
+            }
+
+            let near_child = self.get_child(sibling, is_left);
+            let far_child = self.get_child(sibling, !is_left);
+
+            if !self.is_red(near_child) && !self.is_red(far_child) {
+                self.set_red(Some(sibling), true);
+                arc_id = Some(current_parent);
+                parent = self.get_arc(current_parent).parent;
+                continue;
+            }
+
+            let mut far_child = far_child;
+            if !self.is_red(far_child) {
+                self.set_red(near_child, false);
+                self.set_red(Some(sibling), true);
+                if is_left {
+                    self.rotate_right(sibling);
+                } else {
+                    self.rotate_left(sibling);
+                }
+                far_child = Some(sibling);
+                sibling = self.get_child(current_parent, !is_left).unwrap();
+            }
+
+            let parent_red = self.get_arc(current_parent).red;
+            self.set_red(Some(sibling), parent_red);
+            self.set_red(Some(current_parent), false);
+            self.set_red(far_child, false);
+            if is_left {
+                self.rotate_left(current_parent);
+            } else {
+                self.rotate_right(current_parent);
+            }
+            arc_id = self.root;
+            break;
+        }
+
+        self.set_red(arc_id, false);
+    }
+
+    /// Iterates over the arcs from left to right
+    pub fn arcs_in_order(&self) -> impl Iterator<Item = ArcId> + '_ {
+        // Start at the leftmost node of the tree and follow the right links
+        let mut first_arc = self.root;
+        while let Some(left_child) = first_arc.and_then(|arc_id| self.get_arc(arc_id).left_child) {
+            first_arc = Some(left_child);
+        }
+
+        ::std::iter::successors(first_arc, move |&arc_id| self.get_arc(arc_id).right)
+    }
+}
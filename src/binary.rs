@@ -0,0 +1,249 @@
+//! A compact binary encoding of diagrams, for caching ones too big to go
+//! through serde quickly
+//!
+//! After a header, every field of the vertices, half-edges and faces is written
+//! as a flat array in the same order as `DiagramSoa` keeps them, all little
+//! endian. The header is:
+//!
+//! - the four bytes `VORB`
+//! - the version of the format as a u32, which is 1
+//! - the size of a coordinate in bytes as a u32, 4 for f32 or 8 for f64
+//! - flags as a u32, with bit 0 set if the diagram is weighted, bit 1 set if its
+//!   cells are L1 and bit 2 set if they are L∞
+//! - the numbers of vertices, half-edges and faces as u32s
+//!
+//! Coordinates are written at the precision of the diagram, and converted to
+//! the precision of the diagram being read into. Missing links are written as
+//! `NO_INDEX`. Nothing comes after the last array.
+
+use std::io::{self, BufWriter, Read, Write};
+use std::mem;
+
+use cgmath::{Point2, BaseFloat};
+use cgmath::num_traits::cast;
+
+use build::{Metric, Site};
+use diagram::{Diagram, Face, FaceId, HalfEdge, HalfEdgeId, Vertex, VertexId};
+use soa::{NO_INDEX, link};
+use validate::validate_links;
+
+const MAGIC: &[u8; 4] = b"VORB";
+const VERSION: u32 = 1;
+const WEIGHTED: u32 = 1;
+const L1: u32 = 2;
+const L_INF: u32 = 4;
+
+/// Number of values read at a time, so that a count in a damaged header can't
+/// make the reader allocate more than the data there is
+const CHUNK: usize = 1 << 16;
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn write_u32s<W: Write, I: Iterator<Item = u32>>(writer: &mut W, values: I) -> io::Result<()> {
+    for value in values {
+        writer.write_all(&value.to_le_bytes())?;
+    }
+    Ok(())
+}
+
+fn write_floats<T: BaseFloat, W: Write, I: Iterator<Item = T>>(writer: &mut W, wide: bool, values: I) -> io::Result<()> {
+    for value in values {
+        if wide {
+            writer.write_all(&value.to_f64().unwrap().to_le_bytes())?;
+        } else {
+            writer.write_all(&value.to_f32().unwrap().to_le_bytes())?;
+        }
+    }
+    Ok(())
+}
+
+fn read_u32<R: Read>(reader: &mut R) -> io::Result<u32> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+/// Reads `count` values of `size` bytes each, converting each one as it goes
+fn read_array<R: Read, V, F: Fn(&[u8]) -> V>(reader: &mut R, count: u32, size: usize, convert: F) -> io::Result<Vec<V>> {
+    let count = count as usize;
+    let mut values = Vec::with_capacity(count.min(CHUNK));
+    let mut bytes = vec![0; count.min(CHUNK) * size];
+    while values.len() < count {
+        let chunk = (count - values.len()).min(CHUNK);
+        reader.read_exact(&mut bytes[..chunk * size])?;
+        values.extend(bytes[..chunk * size].chunks_exact(size).map(&convert));
+    }
+    Ok(values)
+}
+
+fn read_u32s<R: Read>(reader: &mut R, count: u32) -> io::Result<Vec<u32>> {
+    read_array(reader, count, 4, |bytes| u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+fn read_floats<T: BaseFloat, R: Read>(reader: &mut R, count: u32, wide: bool) -> io::Result<Vec<T>> {
+    if wide {
+        read_array(reader, count, 8, |bytes| {
+            let mut value = [0; 8];
+            value.copy_from_slice(bytes);
+            cast(f64::from_le_bytes(value)).unwrap_or(T::nan())
+        })
+    } else {
+        read_array(reader, count, 4, |bytes| cast(f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])).unwrap_or(T::nan()))
+    }
+}
+
+/// See `Diagram::write_to`
+pub fn write_to<T: BaseFloat, W: Write>(diagram: &Diagram<T>, writer: W) -> io::Result<()> {
+    let (vertices, halfedges, faces) = (&diagram.vertices, &diagram.halfedges, &diagram.faces);
+    let counts = [vertices.len(), halfedges.len(), faces.len()];
+    if counts.iter().any(|&count| count > u32::MAX as usize) {
+        return Err(invalid("too many elements to count in a u32"));
+    }
+
+    let wide = mem::size_of::<T>() > 4;
+    let metric = match diagram.metric {
+        Metric::Euclidean => 0,
+        Metric::L1 => L1,
+        Metric::LInf => L_INF,
+    };
+    let flags = metric | if diagram.site_index.is_weighted() { WEIGHTED } else { 0 };
+    let mut writer = BufWriter::new(writer);
+    writer.write_all(MAGIC)?;
+    write_u32s(&mut writer, [VERSION, if wide { 8 } else { 4 }, flags].iter().cloned())?;
+    write_u32s(&mut writer, counts.iter().map(|&count| count as u32))?;
+
+    write_floats(&mut writer, wide, vertices.iter().map(|vertex| vertex.coordinates.x))?;
+    write_floats(&mut writer, wide, vertices.iter().map(|vertex| vertex.coordinates.y))?;
+    write_u32s(&mut writer, vertices.iter().map(|vertex| vertex.incident_edge.0))?;
+
+    write_u32s(&mut writer, halfedges.iter().map(|halfedge| halfedge.origin.map_or(NO_INDEX, |origin| origin.0)))?;
+    write_u32s(&mut writer, halfedges.iter().map(|halfedge| halfedge.twin.map_or(NO_INDEX, |twin| twin.0)))?;
+    write_u32s(&mut writer, halfedges.iter().map(|halfedge| halfedge.incident_face.0))?;
+    write_u32s(&mut writer, halfedges.iter().map(|halfedge| halfedge.next.0))?;
+    write_u32s(&mut writer, halfedges.iter().map(|halfedge| halfedge.prev.0))?;
+
+    write_u32s(&mut writer, faces.iter().map(|face| face.first_halfedge.map_or(NO_INDEX, |halfedge| halfedge.0)))?;
+    write_u32s(&mut writer, faces.iter().map(|face| face.site.id))?;
+    write_floats(&mut writer, wide, faces.iter().map(|face| face.site.position.x))?;
+    write_floats(&mut writer, wide, faces.iter().map(|face| face.site.position.y))?;
+    write_floats(&mut writer, wide, faces.iter().map(|face| face.site.weight))?;
+    writer.flush()
+}
+
+/// Whether every link in the arrays read points at something that was read
+fn links_in_range<T: BaseFloat>(counts: [u32; 3], vertices: &[Vertex<T>], halfedges: &[HalfEdge], first_halfedges: &[u32]) -> bool {
+    let [vertex_count, halfedge_count, face_count] = counts;
+    let halfedge = |halfedge: HalfEdgeId| halfedge.0 < halfedge_count;
+    let optional = |index: u32, count: u32| index == NO_INDEX || index < count;
+    vertices.iter().all(|vertex| halfedge(vertex.incident_edge))
+        && halfedges.iter().all(|edge| {
+            edge.origin.is_none_or(|origin| origin.0 < vertex_count)
+                && edge.twin.is_none_or(halfedge)
+                && edge.incident_face.0 < face_count
+                && halfedge(edge.next)
+                && halfedge(edge.prev)
+        })
+        && first_halfedges.iter().all(|&first| optional(first, halfedge_count))
+}
+
+/// See `Diagram::read_from`
+pub fn read_from<T: BaseFloat, R: Read>(mut reader: R) -> io::Result<Diagram<T>> {
+    let truncated = |error: io::Error| match error.kind() {
+        io::ErrorKind::UnexpectedEof => invalid("the data ends before the diagram does"),
+        _ => error,
+    };
+    let diagram = read_diagram(&mut reader).map_err(truncated)?;
+
+    // Anything after the last array means the counts in the header are wrong
+    let mut rest = [0];
+    if reader.read(&mut rest)? > 0 {
+        return Err(invalid("data after the end of the diagram"));
+    }
+    Ok(diagram)
+}
+
+fn read_diagram<T: BaseFloat, R: Read>(reader: &mut R) -> io::Result<Diagram<T>> {
+    let mut magic = [0; 4];
+    reader.read_exact(&mut magic)?;
+    if &magic != MAGIC {
+        return Err(invalid("not a binary diagram"));
+    }
+    if read_u32(reader)? != VERSION {
+        return Err(invalid("unsupported version of the binary diagram format"));
+    }
+    let wide = match read_u32(reader)? {
+        4 => false,
+        8 => true,
+        _ => return Err(invalid("unsupported coordinate size")),
+    };
+    let flags = read_u32(reader)?;
+    let metric = match flags & (L1 | L_INF) {
+        0 => Metric::Euclidean,
+        L1 => Metric::L1,
+        L_INF => Metric::LInf,
+        _ => return Err(invalid("more than one metric in the flags")),
+    };
+    let (vertex_count, halfedge_count, face_count) = (read_u32(reader)?, read_u32(reader)?, read_u32(reader)?);
+
+    let vertex_x = read_floats::<T, _>(reader, vertex_count, wide)?;
+    let vertex_y = read_floats::<T, _>(reader, vertex_count, wide)?;
+    let vertex_incident_edge = read_u32s(reader, vertex_count)?;
+    let vertices: Vec<Vertex<T>> = vertex_x.into_iter().zip(vertex_y).zip(vertex_incident_edge)
+        .map(|((x, y), incident_edge)| Vertex {
+            coordinates: Point2::new(x, y),
+            incident_edge: HalfEdgeId(incident_edge),
+        })
+        .collect();
+
+    let origin = read_u32s(reader, halfedge_count)?;
+    let twin = read_u32s(reader, halfedge_count)?;
+    let face = read_u32s(reader, halfedge_count)?;
+    let next = read_u32s(reader, halfedge_count)?;
+    let prev = read_u32s(reader, halfedge_count)?;
+    let halfedges: Vec<HalfEdge> = origin.into_iter().zip(twin).zip(face).zip(next).zip(prev)
+        .map(|((((origin, twin), face), next), prev)| HalfEdge {
+            origin: link(origin).map(VertexId),
+            twin: link(twin).map(HalfEdgeId),
+            incident_face: FaceId(face),
+            next: HalfEdgeId(next),
+            prev: HalfEdgeId(prev),
+        })
+        .collect();
+
+    let first_halfedge = read_u32s(reader, face_count)?;
+    let site_id = read_u32s(reader, face_count)?;
+    let site_x = read_floats::<T, _>(reader, face_count, wide)?;
+    let site_y = read_floats::<T, _>(reader, face_count, wide)?;
+    let site_weight = read_floats::<T, _>(reader, face_count, wide)?;
+    if !links_in_range([vertex_count, halfedge_count, face_count], &vertices, &halfedges, &first_halfedge) {
+        return Err(invalid("a link points past the end of the diagram"));
+    }
+    let faces = first_halfedge.into_iter().zip(site_id).zip(site_x.into_iter().zip(site_y)).zip(site_weight)
+        .map(|(((first_halfedge, id), (x, y)), weight)| Face {
+            first_halfedge: link(first_halfedge).map(HalfEdgeId),
+            site: Site {
+                id,
+                position: Point2::new(x, y),
+                weight,
+            },
+        })
+        .collect();
+
+    let mut diagram = Diagram {
+        vertices,
+        halfedges,
+        faces,
+        metric,
+        ..Diagram::default()
+    };
+
+    // Half-edges of an unclipped diagram come from infinity, but everything else
+    // has to hold for the diagram to be safe to walk
+    if let Err(error) = validate_links(&diagram, false) {
+        return Err(invalid(&error.to_string()));
+    }
+    diagram.index_sites(flags & WEIGHTED != 0);
+    Ok(diagram)
+}
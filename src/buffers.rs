@@ -0,0 +1,92 @@
+//! A diagram as flat arrays of numbers, for handing to code that can't walk the
+//! links between vertices, half-edges and faces
+//!
+//! Everything is in plain `Vec<f32>`s and `Vec<u32>`s, which can be passed to
+//! JavaScript as typed arrays without copying them element by element, or
+//! uploaded straight to a vertex or index buffer.
+
+use cgmath::{Point2, BaseFloat};
+
+use diagram::{Diagram, Endpoint, FaceId, HalfEdgeId};
+
+/// The vertices, cells and edges of a diagram as flat arrays, see
+/// `Diagram::to_flat_buffers`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FlatBuffers {
+    /// x and y of each vertex, one after the other, in the same order as the
+    /// vertices of the diagram
+    pub positions: Vec<f32>,
+
+    /// x and y of the site of each face, in the same order as the faces
+    pub sites: Vec<f32>,
+
+    /// Indices of the corners of every cell, cell after cell, each in
+    /// counter-clockwise order
+    pub cell_indices: Vec<u32>,
+
+    /// Where each cell's corners start in `cell_indices`, with one more at the end
+    ///
+    /// The corners of face `i` are `cell_indices[cell_offsets[i]..cell_offsets[i + 1]]`.
+    pub cell_offsets: Vec<u32>,
+
+    /// Indices of the two ends of each edge, one pair after another
+    pub edges: Vec<u32>,
+}
+
+impl FlatBuffers {
+    /// Number of cells, which is the number of faces in the diagram
+    pub fn cell_count(&self) -> usize {
+        self.cell_offsets.len().saturating_sub(1)
+    }
+
+    /// Indices of the corners of a cell, or None if there's no such cell
+    pub fn cell(&self, index: usize) -> Option<&[u32]> {
+        let start = *self.cell_offsets.get(index)? as usize;
+        let end = *self.cell_offsets.get(index + 1)? as usize;
+        Some(&self.cell_indices[start..end])
+    }
+}
+
+fn push_point<T: BaseFloat>(buffer: &mut Vec<f32>, point: Point2<T>) {
+    buffer.push(point.x.to_f32().unwrap());
+    buffer.push(point.y.to_f32().unwrap());
+}
+
+/// See `Diagram::to_flat_buffers`
+pub fn to_flat_buffers<T: BaseFloat>(diagram: &Diagram<T>) -> FlatBuffers {
+    let mut buffers = FlatBuffers {
+        positions: Vec::with_capacity(diagram.vertices.len() * 2),
+        sites: Vec::with_capacity(diagram.faces.len() * 2),
+        cell_indices: Vec::with_capacity(diagram.halfedges.len()),
+        cell_offsets: Vec::with_capacity(diagram.faces.len() + 1),
+        edges: Vec::with_capacity(diagram.halfedges.len()),
+    };
+
+    for vertex in &diagram.vertices {
+        push_point(&mut buffers.positions, vertex.coordinates);
+    }
+
+    buffers.cell_offsets.push(0);
+    for (index, face) in diagram.faces.iter().enumerate() {
+        push_point(&mut buffers.sites, face.site.position);
+        buffers.cell_indices.extend(diagram.face_edges(FaceId(index as u32))
+            .take(diagram.halfedges.len())
+            .filter_map(|halfedge_id| diagram.halfedges[halfedge_id.0 as usize].origin)
+            .map(|origin| origin.0));
+        buffers.cell_offsets.push(buffers.cell_indices.len() as u32);
+    }
+
+    // Twins are one edge, and edges that go off to infinity are left out
+    for (index, halfedge) in diagram.halfedges.iter().enumerate() {
+        if halfedge.twin.is_some_and(|twin| index > twin.0 as usize) {
+            continue;
+        }
+        let halfedge_id = HalfEdgeId(index as u32);
+        if let (Some(Endpoint::Vertex(a)), Some(Endpoint::Vertex(b))) = (diagram.origin(halfedge_id), diagram.destination(halfedge_id)) {
+            buffers.edges.push(a.0);
+            buffers.edges.push(b.0);
+        }
+    }
+
+    buffers
+}
@@ -1,475 +1,1814 @@
 use std::collections::BinaryHeap;
 use std::cmp::Ordering;
-use std::f32::INFINITY;
+use std::error::Error;
+use std::fmt;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering as AtomicOrdering};
+use std::time::{Duration, Instant};
+
+use cgmath::{Point2, Vector2, EuclideanSpace, BaseFloat};
+use fnv::FnvHashMap;
+#[cfg(feature = "serde")]
+use serde::{Serialize, Deserialize};
+
+use beachline::{BeachLine, ArcId, circumcircle_of_points};
+use closed::{ClosedDiagram, NotClosedError};
+use clip::{clamp_to_boundary, clip_to_boundary_into, is_convex_boundary, rect_boundary, strictly_inside_boundary};
+use dedup::{SiteSet, jitter};
+use delaunay::Triangulation;
+use diagram::{Diagram, Vertex, VertexId, HalfEdge, HalfEdgeId, Face, FaceId, ValidationError};
+#[cfg(feature = "exact")]
+use exact;
+use metric::build_cells;
+use observer::{ArcSnapshot, BuildObserver, PendingEvent, SweepEdge, SweepState};
+use predicates::orient2d;
+use validate::validate_links;
+
+#[cfg(feature = "geo")]
+pub use interop::sites_from_geo;
+pub use metric::Metric;
+pub use options::DiagramBuilderOptions;
+
+/// An axis-aligned rectangle
+///
+/// This and the other types that hold coordinates are generic over the scalar
+/// type, which is either f32 (the default) or f64.
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Rect<T = f32> {
+    pub position: Point2<T>,
+    pub size: Vector2<T>,
+}
 
-use cgmath::{Point2, Vector2, MetricSpace};
-use fnv::{FnvHashMap, FnvHashSet};
+impl<T: BaseFloat> Rect<T> {
+    /// The smallest rect that contains some points, with `padding` added on each
+    /// side
+    ///
+    /// The rect is also grown by a sliver, so that no point is on its edge and
+    /// left out of a diagram even without any padding. Points that aren't finite
+    /// are ignored, and without any others the rect is around the origin.
+    pub fn around<I: IntoIterator<Item = Point2<T>>>(points: I, padding: T) -> Rect<T> {
+        let bounds = points.into_iter()
+            .filter(|point| point.x.is_finite() && point.y.is_finite())
+            .fold(None, |bounds: Option<(Point2<T>, Point2<T>)>, point| match bounds {
+                Some((min, max)) => Some((Point2::new(min.x.min(point.x), min.y.min(point.y)), Point2::new(max.x.max(point.x), max.y.max(point.y)))),
+                None => Some((point, point)),
+            });
+        let (min, max) = bounds.unwrap_or((Point2::origin(), Point2::origin()));
+
+        let extent = (max.x - min.x).max(max.y - min.y).max(padding);
+        let margin = if extent > T::zero() { padding + extent * T::epsilon().sqrt() } else { T::one() };
+        Rect {
+            position: min + Vector2::new(-margin, -margin),
+            size: max - min + Vector2::new(margin + margin, margin + margin),
+        }
+    }
+}
 
-use diagram::{Diagram, Vertex, HalfEdgeId};
+/// The area a diagram covers, which its cells are clipped to
+///
+/// Sites outside the region are left out of the diagram.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum ClipRegion<T = f32> {
+    Rect(Rect<T>),
+
+    /// A convex polygon, given by its corners in either order
+    ///
+    /// `DiagramBuilder::try_finish` fails with `BuildError::ConcaveClipRegion` if
+    /// the polygon isn't convex, while `finish` clips to it as if it were, which
+    /// gives cells that don't fit it.
+    ConvexPolygon(Vec<Point2<T>>),
+}
 
-#[derive(Debug)]
-pub struct Rect {
-    pub position: Point2<f32>,
-    pub size: Vector2<f32>,
+impl<T: BaseFloat> ClipRegion<T> {
+    /// The smallest rect that contains the region
+    pub fn bounding_rect(&self) -> Rect<T> {
+        match *self {
+            ClipRegion::Rect(rect) => rect,
+            ClipRegion::ConvexPolygon(ref corners) => {
+                let min = corners.iter().fold(Point2::new(T::infinity(), T::infinity()), |min, corner| {
+                    Point2::new(min.x.min(corner.x), min.y.min(corner.y))
+                });
+                let max = corners.iter().fold(Point2::new(T::neg_infinity(), T::neg_infinity()), |max, corner| {
+                    Point2::new(max.x.max(corner.x), max.y.max(corner.y))
+                });
+
+                if corners.is_empty() {
+                    Rect {
+                        position: Point2::new(T::zero(), T::zero()),
+                        size: Vector2::new(T::zero(), T::zero()),
+                    }
+                } else {
+                    Rect {
+                        position: min,
+                        size: max - min,
+                    }
+                }
+            }
+        }
+    }
+
+    /// Corners of the region in counter-clockwise order, relative to the corner of
+    /// its bounding rect and multiplied by `scale`
+    pub(crate) fn boundary(&self, scale: T) -> Vec<Point2<T>> {
+        let bounding_rect = self.bounding_rect();
+        let corners = match *self {
+            ClipRegion::Rect(rect) => return rect_boundary(rect.size * scale),
+            ClipRegion::ConvexPolygon(ref corners) => corners,
+        };
+
+        let mut boundary = corners.iter()
+            .map(|&corner| Point2::from_vec((corner - bounding_rect.position) * scale))
+            .collect::<Vec<_>>();
+        boundary.dedup_by(|a, b| a == b);
+        while boundary.len() > 1 && boundary.first() == boundary.last() {
+            boundary.pop();
+        }
+
+        // Shoelace formula
+        let twice_area = boundary.iter().enumerate().fold(T::zero(), |area, (index, a)| {
+            let b = boundary[(index + 1) % boundary.len()];
+            area + a.x * b.y - b.x * a.y
+        });
+        if twice_area < T::zero() {
+            boundary.reverse();
+        }
+
+        boundary
+    }
+}
+
+impl<T> From<Rect<T>> for ClipRegion<T> {
+    fn from(rect: Rect<T>) -> ClipRegion<T> {
+        ClipRegion::Rect(rect)
+    }
 }
 
 #[derive(Debug, Clone, Copy)]
-pub struct Site {
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Site<T = f32> {
     pub id: u32,
-    pub position: Point2<f32>,
+    pub position: Point2<T>,
+
+    /// Weight of the site in a power diagram, where the distance from a point to
+    /// the site is its squared distance minus the weight
+    ///
+    /// This is zero unless set and is ignored by `DiagramBuilder`, see
+    /// `PowerDiagramBuilder`.
+    pub weight: T,
 }
 
-impl Site {
-    pub fn new(id: u32, position: Point2<f32>) -> Site {
+impl<T: BaseFloat> Site<T> {
+    pub fn new(id: u32, position: Point2<T>) -> Site<T> {
+        Site::with_weight(id, position, T::zero())
+    }
+
+    pub fn with_weight(id: u32, position: Point2<T>, weight: T) -> Site<T> {
         Site {
-            id: id,
-            position: position,
+            id,
+            position,
+            weight,
         }
     }
 }
 
-#[derive(Debug)]
-enum Event {
-    Site(Site),
-    Circle(f32, Point2<f32>, ArcId),
+/// Something that can be turned into a site, given the id it should have
+pub trait IntoSite<T = f32> {
+    fn into_site(self, id: u32) -> Site<T>;
 }
 
-impl Event {
-    fn get_y(&self) -> f32 {
-        match *self {
-            Event::Site(site) => site.position.y,
-            Event::Circle(y, ..) => y,
-        }
+/// Sites keep their own id
+impl<T> IntoSite<T> for Site<T> {
+    fn into_site(self, _id: u32) -> Site<T> {
+        self
     }
 }
 
-impl PartialEq for Event {
-    fn eq(&self, other: &Event) -> bool {
-        self.get_y().eq(&other.get_y())
+impl<T: BaseFloat> IntoSite<T> for Point2<T> {
+    fn into_site(self, id: u32) -> Site<T> {
+        Site::new(id, self)
     }
 }
 
-impl Eq for Event {}
+impl<T: BaseFloat> IntoSite<T> for (T, T) {
+    fn into_site(self, id: u32) -> Site<T> {
+        Site::new(id, Point2::new(self.0, self.1))
+    }
+}
 
-impl PartialOrd for Event {
-    fn partial_cmp(&self, other: &Event) -> Option<Ordering> {
-        let y = -self.get_y();
-        let other_y = -other.get_y();
-        y.partial_cmp(&other_y)
+impl<T: BaseFloat> IntoSite<T> for [T; 2] {
+    fn into_site(self, id: u32) -> Site<T> {
+        Site::new(id, Point2::new(self[0], self[1]))
     }
 }
 
-impl Ord for Event {
-    fn cmp(&self, other: &Event) -> Ordering {
-        self.partial_cmp(other).unwrap_or(Ordering::Less)
+/// What to do with a site that is at the same position as an earlier one
+///
+/// Sites count as duplicates when they are within the tolerance passed to
+/// `DiagramBuilder::with_duplicate_policy` of each other. The sweep can't handle
+/// sites at the same position, so they always have to be dealt with somehow.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum DuplicatePolicy {
+    /// Fail with a `DuplicateSiteError`
+    Error,
+
+    /// Keep the first site and drop the others, which don't get a face
+    MergeFirstWins,
+
+    /// Move the later sites a small distance in a random direction so each gets a
+    /// face of its own. The faces of moved sites hold their new positions.
+    ///
+    /// The direction depends only on the order of the sites, so the same input
+    /// always gives the same diagram. Sites that can't be moved to a free position
+    /// inside the bounding rect are dropped.
+    Jitter,
+}
+
+/// Returned when a site is a duplicate of an earlier one under `DuplicatePolicy::Error`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DuplicateSiteError {
+    /// Id of the duplicate site
+    pub site_id: u32,
+
+    /// Id of the earlier site that it duplicates
+    pub duplicate_of: u32,
+}
+
+impl fmt::Display for DuplicateSiteError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "site {} is a duplicate of site {}", self.site_id, self.duplicate_of)
     }
 }
 
-fn circumcircle_of_points(a: Point2<f32>, b: Point2<f32>, c: Point2<f32>) -> Option<(Point2<f32>, f32)> {
-    // http://en.wikipedia.org/wiki/Circumscribed_circle#Cartesian_coordinates
-    let d = 2.0 * (a.x * (b.y - c.y)
-                + b.x * (c.y - a.y)
-                + c.x * (a.y - b.y));
+impl Error for DuplicateSiteError {}
+
+/// What to do with a site that is outside the clip region
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum OutOfBoundsPolicy {
+    /// Fail with an `OutOfBoundsError`
+    Error,
+
+    /// Drop the site, which doesn't get a face. `DiagramBuilder::site_mapping`
+    /// reports which sites were dropped
+    #[default]
+    Skip,
+
+    /// Move the site to the nearest point inside the clip region. The faces of
+    /// moved sites hold their new positions, and moved sites can end up as
+    /// duplicates of each other
+    Clamp,
+
+    /// Grow the clip region to the smallest rect that contains both it and every
+    /// site, plus a sliver so no site is on its edge. The region is left as it is
+    /// if no site is outside it
+    ExpandBounds,
+}
+
+/// Returned when a site is outside the clip region under `OutOfBoundsPolicy::Error`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OutOfBoundsError {
+    /// Id of the site
+    pub site_id: u32,
+
+    /// Index of the site in the list given to the builder
+    pub index: usize,
+}
 
-    if d == 0.0 {
-        return None;
+impl fmt::Display for OutOfBoundsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "site {} at index {} is outside the clip region", self.site_id, self.index)
     }
+}
 
-    let axy2 = a.x * a.x + a.y * a.y;
-    let bxy2 = b.x * b.x + b.y * b.y;
-    let cxy2 = c.x * c.x + c.y * c.y;
+impl Error for OutOfBoundsError {}
+
+/// What to do with a site that has a coordinate that is NaN or infinite
+///
+/// The sweep can't order such sites, so they never get a face.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum NonFinitePolicy {
+    /// Fail with a `NonFiniteError` before looking at any of the other sites
+    Error,
+
+    /// Drop the site. `DiagramBuilder::try_finish` still fails with
+    /// `BuildError::NotFinite`, but `finish` builds the diagram of the others
+    #[default]
+    Skip,
+}
 
-    let x = axy2 * (b.y - c.y) + bxy2 * (c.y - a.y) + cxy2 * (a.y - b.y);
-    let y = axy2 * (c.x - b.x) + bxy2 * (a.x - c.x) + cxy2 * (b.x - a.x);
+/// Returned when a site isn't finite under `NonFinitePolicy::Error`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NonFiniteError {
+    /// Id of the site
+    pub site_id: u32,
 
-    let centroid = Point2::new(x / d, y / d);
-    let radius = a.distance(centroid);
+    /// Index of the site in the list given to the builder
+    pub index: usize,
+}
 
-    Some((centroid, radius))
+impl fmt::Display for NonFiniteError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "site {} at index {} has a coordinate that isn't finite", self.site_id, self.index)
+    }
 }
 
-fn intersection(left_focus: Point2<f32>, right_focus: Point2<f32>, directrix: f32) -> Point2<f32> {
-    // Based on intersection function from https://www.cs.hmc.edu/~mbrubeck/voronoi.html
-    let mut p = &left_focus;
+impl Error for NonFiniteError {}
 
-    let x = if left_focus.y == right_focus.y {
-        // Focii are at the same height so breakpoint is in the middle
-        (left_focus.x + right_focus.x) / 2.0
-    } else if right_focus.y == directrix {
-        // Right focus is on the directrix
-        right_focus.x
-    } else if left_focus.y == directrix {
-        // Left focus is on the directrix
-        p = &right_focus;
-        left_focus.x
-    } else {
-        // Use the quadratic formula
-        let z_left = 2.0 * (left_focus.y - directrix);
-        let z_right = 2.0 * (right_focus.y - directrix);
+/// A site that one of the policies of a builder rejected
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SiteError {
+    Duplicate(DuplicateSiteError),
+    OutOfBounds(OutOfBoundsError),
+    NonFinite(NonFiniteError),
+}
 
-        let a = 1.0 / z_left - 1.0 / z_right;
-        let b = -2.0 * (left_focus.x / z_left - right_focus.x / z_right);
-        let c = (left_focus.x * left_focus.x + left_focus.y * left_focus.y - directrix * directrix) / z_left
-                - (right_focus.x * right_focus.x + right_focus.y * right_focus.y - directrix * directrix) / z_right;
+impl fmt::Display for SiteError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            SiteError::Duplicate(error) => error.fmt(f),
+            SiteError::OutOfBounds(error) => error.fmt(f),
+            SiteError::NonFinite(error) => error.fmt(f),
+        }
+    }
+}
+
+impl Error for SiteError {}
 
-        (-b - (b * b - 4.0 * a * c).abs().sqrt()) / (2.0 * a)
-    };
+impl From<DuplicateSiteError> for SiteError {
+    fn from(error: DuplicateSiteError) -> SiteError {
+        SiteError::Duplicate(error)
+    }
+}
 
-    // Plug back into one of the parabola equations
-    let y = (p.y * p.y + (p.x - x) * (p.x - x) - directrix * directrix) / (2.0 * p.y - 2.0 * directrix);
+impl From<OutOfBoundsError> for SiteError {
+    fn from(error: OutOfBoundsError) -> SiteError {
+        SiteError::OutOfBounds(error)
+    }
+}
 
-    Point2::new(x, y)
+impl From<NonFiniteError> for SiteError {
+    fn from(error: NonFiniteError) -> SiteError {
+        SiteError::NonFinite(error)
+    }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
-struct ArcId(u32);
+/// Why `DiagramBuilder::add_site` couldn't add a site
+///
+/// The index in the errors is the one the site would have had in the builder's
+/// `SiteMapping`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AddSiteError {
+    /// The site is within the builder's tolerance of a site it already has
+    Duplicate(DuplicateSiteError),
 
-#[derive(Debug)]
-struct Arc {
-    pub site: Site,
-    pub left: Option<ArcId>,
-    pub right: Option<ArcId>,
+    OutOfBounds(OutOfBoundsError),
+
+    NonFinite(NonFiniteError),
+
+    /// The sweep line has already reached the site
+    BehindSweepLine {
+        site_id: u32,
+    },
 }
 
-impl Arc {
-    fn from_site(site: Site) -> Arc {
-        Arc {
-            site: site,
-            left: None,
-            right: None,
+impl fmt::Display for AddSiteError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            AddSiteError::Duplicate(error) => error.fmt(f),
+            AddSiteError::OutOfBounds(error) => error.fmt(f),
+            AddSiteError::NonFinite(error) => error.fmt(f),
+            AddSiteError::BehindSweepLine { site_id } => write!(f, "site {} is behind the sweep line", site_id),
         }
     }
 }
 
-#[derive(Debug, Default)]
-struct BeachLine {
-    next_arc_id: u32,
-    arcs: FnvHashMap<ArcId, Arc>,
-    arc_ordering: Vec<ArcId>,  // TODO: RBTree
+impl Error for AddSiteError {}
+
+impl From<DuplicateSiteError> for AddSiteError {
+    fn from(error: DuplicateSiteError) -> AddSiteError {
+        AddSiteError::Duplicate(error)
+    }
+}
+
+impl From<OutOfBoundsError> for AddSiteError {
+    fn from(error: OutOfBoundsError) -> AddSiteError {
+        AddSiteError::OutOfBounds(error)
+    }
 }
 
-impl BeachLine {
-    fn new_arc_id(&mut self) -> ArcId {
-        let arc_id = ArcId(self.next_arc_id);
-        self.next_arc_id += 1;
-        arc_id
+impl From<NonFiniteError> for AddSiteError {
+    fn from(error: NonFiniteError) -> AddSiteError {
+        AddSiteError::NonFinite(error)
     }
+}
 
-    pub fn add_arc(&mut self, site: Site, current_arc_id: Option<ArcId>) -> ArcId {
-        // Create new arc
-        let arc_id = self.new_arc_id();
-        let mut arc = Arc::from_site(site);
+/// Why `DiagramBuilder::try_finish` couldn't build a diagram
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BuildError {
+    /// A site has a coordinate that is NaN or infinite
+    NotFinite {
+        site_id: u32,
 
-        // If there's an existing arc, split it in two
-        if let Some(current_arc_id) = current_arc_id {
-            // Copy existing arc and place it to the right of the new one
-            let right_arc_id = self.new_arc_id();
-            let right_arc = {
-                let current_arc = self.arcs.get(&current_arc_id).unwrap();
+        /// Position of the site in the list that was given
+        index: usize,
+    },
 
-                let mut right_arc = Arc::from_site(current_arc.site);
-                arc.right = Some(right_arc_id);
-                right_arc.left = Some(arc_id);
-                right_arc.right = current_arc.right;
+    /// None of the sites got a face
+    NoSites,
 
-                right_arc
-            };
-            self.arcs.insert(right_arc_id, right_arc);
+    /// The clip region has no area, or its size isn't finite
+    DegenerateClipRegion,
 
-            // Relink right neighbour's left arc to the new right arc
-            if let Some(right_neighbour_arc_id) = self.arcs.get(&current_arc_id).unwrap().right {
-                let mut right_neighbour_arc = self.arcs.get_mut(&right_neighbour_arc_id).unwrap();
-                right_neighbour_arc.left = Some(right_arc_id);
-            }
+    /// The clip region is a polygon that isn't convex
+    ConcaveClipRegion,
 
-            // current_arc is now to the left of the new arc
-            if let Some(mut current_arc) = self.arcs.get_mut(&current_arc_id) {
-                current_arc.right = Some(arc_id);
-                arc.left = Some(current_arc_id);
-            }
+    /// The finished diagram failed `Diagram::validate`
+    Invalid(ValidationError),
+}
 
-            // Insert new arcs into arc_ordering
-            if let Some(position) = self.arc_ordering.iter().position(|id| *id == current_arc_id) {
-                self.arc_ordering.insert(position + 1, right_arc_id);
-                self.arc_ordering.insert(position + 1, arc_id);
+impl fmt::Display for BuildError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            BuildError::NotFinite { site_id, index } => {
+                write!(f, "site {} (index {}) has a coordinate that isn't finite", site_id, index)
             }
-        } else {
-            self.arc_ordering.push(arc_id);
+            BuildError::NoSites => write!(f, "no sites inside the clip region"),
+            BuildError::DegenerateClipRegion => write!(f, "clip region has no area"),
+            BuildError::ConcaveClipRegion => write!(f, "clip region isn't convex"),
+            BuildError::Invalid(error) => write!(f, "invalid diagram: {}", error),
         }
+    }
+}
 
-        // Insert new arc
-        self.arcs.insert(arc_id, arc);
+impl Error for BuildError {}
 
-        arc_id
+impl From<ValidationError> for BuildError {
+    fn from(error: ValidationError) -> BuildError {
+        BuildError::Invalid(error)
     }
+}
+
+/// What happened to one of the sites given to a builder
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum SiteOutcome {
+    /// The site got a face
+    Kept(FaceId),
 
-    pub fn get_left_right_arcs(&self, arc_id: ArcId) -> (Option<ArcId>, Option<ArcId>) {
-        let arc = self.arcs.get(&arc_id).unwrap();
-        (arc.left, arc.right)
+    /// The site was a duplicate and was moved before getting a face, see
+    /// `DuplicatePolicy::Jitter`
+    Jittered(FaceId),
+
+    /// The site was outside the clip region and was moved inside it before
+    /// getting a face, see `OutOfBoundsPolicy::Clamp`
+    Clamped(FaceId),
+
+    /// The site was outside the clip region, which was grown to fit it, see
+    /// `OutOfBoundsPolicy::ExpandBounds`
+    ExpandedBounds(FaceId),
+
+    /// The site was a duplicate of the site of a face and didn't get one of its
+    /// own. This includes duplicates that couldn't be jittered
+    Merged(FaceId),
+
+    /// The site was outside the clip region and didn't get a face
+    OutOfBounds,
+
+    /// The site has a coordinate that isn't finite and didn't get a face
+    NonFinite,
+}
+
+impl SiteOutcome {
+    /// The face that the site got, if any
+    pub fn face(self) -> Option<FaceId> {
+        match self {
+            SiteOutcome::Kept(face_id)
+            | SiteOutcome::Jittered(face_id)
+            | SiteOutcome::Clamped(face_id)
+            | SiteOutcome::ExpandedBounds(face_id) => Some(face_id),
+            SiteOutcome::Merged(_) | SiteOutcome::OutOfBounds | SiteOutcome::NonFinite => None,
+        }
     }
+}
 
-    pub fn get_left_breakpoint(&self, arc_id: ArcId, directrix: f32) -> f32 {
-        let right_arc = self.arcs.get(&arc_id).unwrap();
-        let left_arc = match right_arc.left {
-            Some(left_arc_id) => self.arcs.get(&left_arc_id).unwrap(),
-            None => return -INFINITY,
-        };
+/// How the sites given to a builder map to the faces of its diagram
+///
+/// Faces are in the same order as the sites they were built from, but dropped
+/// sites don't get one, so face `i` is only built from the `i`th site if no site
+/// before it was dropped. Use this to keep lists that are indexed by site in step
+/// with the faces.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct SiteMapping {
+    /// What happened to each site, by its index in the list given to the builder
+    outcomes: Vec<SiteOutcome>,
+
+    /// Index of the site of each face
+    sites: Vec<usize>,
+}
 
-        intersection(left_arc.site.position, right_arc.site.position, directrix).x
+impl SiteMapping {
+    fn clear(&mut self) {
+        self.outcomes.clear();
+        self.sites.clear();
     }
 
-    pub fn get_right_breakpoint(&self, arc_id: ArcId, directrix: f32) -> f32 {
-        let left_arc = self.arcs.get(&arc_id).unwrap();
-        let right_arc = match left_arc.right {
-            Some(right_arc_id) => self.arcs.get(&right_arc_id).unwrap(),
-            None => return INFINITY,
-        };
+    fn push(&mut self, outcome: SiteOutcome) {
+        if outcome.face().is_some() {
+            self.sites.push(self.outcomes.len());
+        }
+        self.outcomes.push(outcome);
+    }
 
-        intersection(left_arc.site.position, right_arc.site.position, directrix).x
+    /// What happened to the site at an index in the list given to the builder
+    pub fn outcome(&self, index: usize) -> Option<SiteOutcome> {
+        self.outcomes.get(index).cloned()
     }
 
-    pub fn get_circumcircle(&self, middle_arc_id: ArcId) -> Option<(Point2<f32>, f32)> {
-        let middle_arc = self.arcs.get(&middle_arc_id).unwrap();
-        let (left_arc_id, right_arc_id) = match (middle_arc.left, middle_arc.right) {
-            (Some(left_arc_id), Some(right_arc_id)) => (left_arc_id, right_arc_id),
-            _ => return None
-        };
+    pub fn outcomes(&self) -> &[SiteOutcome] {
+        &self.outcomes
+    }
 
-        let left_arc = self.arcs.get(&left_arc_id).unwrap();
-        let right_arc = self.arcs.get(&right_arc_id).unwrap();
+    /// The face of the site at an index, or None if it was dropped
+    pub fn face(&self, index: usize) -> Option<FaceId> {
+        self.outcome(index).and_then(SiteOutcome::face)
+    }
 
-        circumcircle_of_points(left_arc.site.position, middle_arc.site.position, right_arc.site.position)
+    /// The index of the site that a face was built from
+    pub fn site_index(&self, face_id: FaceId) -> Option<usize> {
+        self.sites.get(face_id.0 as usize).cloned()
     }
 
-    pub fn find_arc(&self, x: f32, directrix: f32) -> Option<ArcId> {
-        let mut current_arc = None;
+    /// Indices of the sites that didn't get a face
+    pub fn dropped(&self) -> impl Iterator<Item = usize> + '_ {
+        self.outcomes.iter().enumerate()
+            .filter(|&(_, outcome)| outcome.face().is_none())
+            .map(|(index, _)| index)
+    }
 
-        for arc_id in &self.arc_ordering {
-            if self.get_left_breakpoint(*arc_id, directrix) > x {
-                break;
-            }
+    /// Whether every site got a face, so face ids are the same as site indices
+    pub fn is_identity(&self) -> bool {
+        self.sites.len() == self.outcomes.len()
+    }
+}
+
+/// Counts and timings from building a diagram
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct BuildStats {
+    /// Number of events added to the queue, both site and circle events
+    pub total_events: u32,
+
+    /// Number of circle events added to the queue
+    pub circle_events: u32,
+
+    /// Number of circle events that were cancelled before they were reached,
+    /// because one of their arcs changed
+    pub cancelled_events: u32,
+
+    /// Largest number of events in the queue at once, including cancelled ones
+    pub peak_queue_size: usize,
+
+    /// Time spent sweeping, which is only measured by `finish_with_stats`
+    pub sweep_time: Duration,
+
+    /// Time spent clipping the cells to the clip region, which is only measured by
+    /// `finish_with_stats`
+    pub clip_time: Duration,
+}
+
+/// Number of events between calls of the callback given to
+/// `DiagramBuilder::finish_with_progress`
+pub const PROGRESS_INTERVAL: usize = 1024;
+
+/// How far through its sweep a builder is
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Progress<T = f32> {
+    /// Number of events processed so far, including cancelled circle events
+    pub processed: usize,
+
+    /// Number of events still in the queue. Circle events are added as the sweep
+    /// goes, so this can go up as well as down, and it includes circle events that
+    /// have been cancelled but not reached yet
+    pub remaining: usize,
+
+    /// Position of the sweep line, or None before the first event
+    pub directrix: Option<T>,
+}
+
+impl<T> Progress<T> {
+    /// Number of events so far, both processed and still to go
+    pub fn total(&self) -> usize {
+        self.processed + self.remaining
+    }
+
+    /// Whether there are no events left, so `finish` has nothing more to sweep
+    pub fn is_finished(&self) -> bool {
+        self.remaining == 0
+    }
+}
+
+/// A flag for stopping a build from elsewhere, see
+/// `DiagramBuilder::finish_cancellable`
+///
+/// Clones share the same flag, so one can be kept by the thread doing the build
+/// and another by whatever decides the build isn't wanted any more.
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn new() -> CancelToken {
+        CancelToken::default()
+    }
 
-            current_arc = Some(*arc_id);
+    /// Asks builds that were given this token, or a clone of it, to stop
+    pub fn cancel(&self) {
+        self.0.store(true, AtomicOrdering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(AtomicOrdering::Relaxed)
+    }
+}
+
+/// Returned when a build was stopped with a `CancelToken`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BuildCancelled {
+    /// Number of events that had been processed
+    pub processed: usize,
+
+    /// Counts of the events up to when the build stopped, and how long the sweep
+    /// had been going
+    pub stats: BuildStats,
+}
+
+impl fmt::Display for BuildCancelled {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "build was cancelled after {} events", self.processed)
+    }
+}
+
+impl Error for BuildCancelled {}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+enum Event<T> {
+    Site(Site<T>, FaceId),
+    Circle(T, Point2<T>, ArcId, u32, #[cfg(feature = "exact")] exact::Circle),
+}
+
+impl<T: BaseFloat> Event<T> {
+    #[cfg(not(feature = "exact"))]
+    fn get_y(&self) -> T {
+        match *self {
+            Event::Site(site, _) => site.position.y,
+            Event::Circle(y, ..) => y,
         }
+    }
 
-        current_arc
+    #[cfg(not(feature = "exact"))]
+    fn get_x(&self) -> T {
+        match *self {
+            Event::Site(site, _) => site.position.x,
+            Event::Circle(_, centroid, ..) => centroid.x,
+        }
     }
 
-    pub fn remove_arc(&mut self, arc_id: ArcId) {
-        // Link left and right arcs together
-        let (left_arc_id, right_arc_id) = {
-            let arc = self.arcs.get(&arc_id).unwrap();
-            (arc.left, arc.right)
-        };
-        if let Some(left_arc_id) = left_arc_id {
-            let mut left_arc = self.arcs.get_mut(&left_arc_id).unwrap();
-            left_arc.right = right_arc_id;
+    #[cfg(feature = "exact")]
+    fn get_position(&self) -> exact::Position<'_> {
+        match *self {
+            Event::Site(site, _) => exact::Position::Site(site.position.cast()),
+            Event::Circle(.., ref circle) => exact::Position::Circle(circle),
         }
-        if let Some(right_arc_id) = right_arc_id {
-            let mut right_arc = self.arcs.get_mut(&right_arc_id).unwrap();
-            right_arc.left = left_arc_id;
+    }
+
+    /// Breaks ties between events at the same position. Site events come before
+    /// circle events, and each kind goes in the order it was created in
+    fn get_rank(&self) -> (u8, u32) {
+        match *self {
+            Event::Site(_, face) => (0, face.0),
+            Event::Circle(_, _, _, event_id, ..) => (1, event_id),
         }
+    }
+}
+
+impl<T: BaseFloat> PartialEq for Event<T> {
+    fn eq(&self, other: &Event<T>) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
 
-        // Remove arc data
-        self.arcs.remove(&arc_id);
+impl<T: BaseFloat> Eq for Event<T> {}
 
-        // Remove arc from ordering
-        self.arc_ordering.retain(|a| *a != arc_id)
+impl<T: BaseFloat> PartialOrd for Event<T> {
+    fn partial_cmp(&self, other: &Event<T>) -> Option<Ordering> {
+        Some(self.cmp(other))
     }
+}
 
-    pub fn debug(&self, directrix: f32) {
-        for arc_id in &self.arc_ordering {
-            let xl = self.get_left_breakpoint(*arc_id, directrix);
-            let xr = self.get_right_breakpoint(*arc_id, directrix);
-            let arc = self.arcs.get(arc_id).unwrap();
+impl<T: BaseFloat> Ord for Event<T> {
+    fn cmp(&self, other: &Event<T>) -> Ordering {
+        // BinaryHeap pops the greatest item first, so events with the lowest y
+        // must compare as the greatest. Events on the same line are processed
+        // from left to right, and the rest of the ties are broken by kind and id so
+        // the order never depends on how the heap happens to be laid out.
+        #[cfg(not(feature = "exact"))]
+        let order = compare(other.get_y(), self.get_y()).then(compare(other.get_x(), self.get_x()));
+        #[cfg(feature = "exact")]
+        let order = exact::compare(&other.get_position(), &self.get_position());
+        order.then(other.get_rank().cmp(&self.get_rank()))
+    }
+}
 
-            println!("arc {}: xl={}, xr={}, site={{id: {}, x:{}, y:{}}}", arc_id.0, xl, xr, arc.site.id, arc.site.position.x, arc.site.position.y);
-        }
+/// Orders floats with NaN below everything else, so it stays a total order
+pub(crate) fn compare<T: BaseFloat>(a: T, b: T) -> Ordering {
+    match (a.is_nan(), b.is_nan()) {
+        (true, true) => Ordering::Equal,
+        (true, false) => Ordering::Less,
+        (false, true) => Ordering::Greater,
+        (false, false) => a.partial_cmp(&b).unwrap(),
     }
 }
 
+/// Builds a diagram by sweeping a line across the sites
+///
+/// With the "serde" feature the builder can be serialized part way through a
+/// sweep, and carries on from the same place once it's deserialized, so long
+/// builds can be checkpointed.
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(bound(
+    serialize = "T: BaseFloat + Serialize",
+    deserialize = "T: BaseFloat + Deserialize<'de>",
+)))]
+pub struct DiagramBuilder<T = f32> {
+    diagram: Diagram<T>,
+    event_queue: BinaryHeap<Event<T>>,
+    beachline: BeachLine<T>,
+    stats: BuildStats,
+    offset: Point2<T>,
+    scale: T,
+
+    /// Corners of the clip region after scaling, in counter-clockwise order
+    boundary: Vec<Point2<T>>,
+
+    /// Position of the site of each face
+    sites: Vec<Point2<T>>,
+
+    /// Positions of the sites after scaling, for finding duplicates of sites added
+    /// later
+    placed: SiteSet<T>,
+
+    /// Faces around each vertex, which form the triangles of the Delaunay triangulation
+    triangles: Vec<[FaceId; 3]>,
+
+    /// Arcs whose circle events were cancelled during the current step
+    cancelled_arcs: Vec<ArcId>,
+
+    /// Position of the sweep line after scaling, None until the first step
+    directrix: Option<T>,
+
+    /// Metric the finished diagram is built under
+    metric: Metric,
+
+    /// Whether the finished diagram is clipped to the clip region
+    clipped: bool,
+
+    /// What happened to each of the sites that were given
+    site_mapping: SiteMapping,
+
+    /// Id and index of the first site with a coordinate that isn't finite
+    not_finite: Option<(u32, usize)>,
+}
+
+/// The lists a builder fills in while sweeping, kept between builds so their
+/// memory can be used again
 #[derive(Debug)]
-pub struct DiagramBuilder {
-    diagram: Diagram,
-    event_queue: BinaryHeap<Event>,
-    beachline: BeachLine,
-    step: u32,
-    total_events: u32,
-    cancelled_events: u32,
-    debug: bool,
-    offset: Point2<f32>,
-    scale: Vector2<f32>,
-
-    /// Keeps track of valid future circle events
-    future_circle_events: FnvHashSet<ArcId>,
-}
-
-impl DiagramBuilder {
-    pub fn new(bounding_rect: Rect, sites: Vec<Site>) -> DiagramBuilder {
-        let mut event_queue = BinaryHeap::new();
-
-        for site in sites {
-            let position = Point2::new(
-                (site.position.x - bounding_rect.position.x) / bounding_rect.size.x,
-                (site.position.y - bounding_rect.position.y) / bounding_rect.size.y
-            );
-
-            if position.x > 0.0 && position.y > 0.0 && position.x < 1.0 && position.y < 1.0 {
-                event_queue.push(Event::Site(Site {
-                    id: site.id,
-                    position: position,
-                }));
+pub(crate) struct SweepBuffers<T> {
+    event_queue: BinaryHeap<Event<T>>,
+    beachline: BeachLine<T>,
+    diagram: Diagram<T>,
+    sites: Vec<Point2<T>>,
+    placed: SiteSet<T>,
+    triangles: Vec<[FaceId; 3]>,
+    cancelled_arcs: Vec<ArcId>,
+    site_mapping: SiteMapping,
+}
+
+impl<T: BaseFloat> Default for SweepBuffers<T> {
+    fn default() -> SweepBuffers<T> {
+        SweepBuffers {
+            event_queue: BinaryHeap::new(),
+            beachline: BeachLine::default(),
+            diagram: Diagram::default(),
+            sites: Vec::new(),
+            placed: SiteSet::new(T::zero()),
+            triangles: Vec::new(),
+            cancelled_arcs: Vec::new(),
+            site_mapping: SiteMapping::default(),
+        }
+    }
+}
+
+impl<T> SweepBuffers<T> {
+    /// How the sites of the last build map to the faces of its diagram
+    pub(crate) fn site_mapping(&self) -> &SiteMapping {
+        &self.site_mapping
+    }
+}
+
+impl<T: BaseFloat> DiagramBuilder<T> {
+    /// Creates a builder for the diagram of the sites inside the clip region, which
+    /// is usually a `Rect`
+    ///
+    /// Sites at exactly the same position as an earlier site are dropped, see
+    /// `DuplicatePolicy::MergeFirstWins`, and so are sites outside the clip region
+    /// and sites that aren't finite. `site_mapping` tells which ones those were.
+    pub fn new<R: Into<ClipRegion<T>>>(clip_region: R, sites: Vec<Site<T>>) -> DiagramBuilder<T> {
+        // Merging never fails
+        DiagramBuilder::with_duplicate_policy(clip_region, sites, DuplicatePolicy::MergeFirstWins, T::zero()).unwrap()
+    }
+
+    /// Starts setting up a builder with more than one of the options that the
+    /// other constructors take
+    pub fn options() -> DiagramBuilderOptions<T> {
+        DiagramBuilderOptions::default()
+    }
+
+    /// Creates a builder for the diagram of the sites clipped to the rect around
+    /// them, with `padding` added on each side
+    ///
+    /// See `Rect::around`. Sites that aren't finite are still left out.
+    pub fn with_auto_bounds(sites: Vec<Site<T>>, padding: T) -> DiagramBuilder<T> {
+        let bounds = Rect::around(sites.iter().map(|site| site.position), padding);
+        DiagramBuilder::new(bounds, sites)
+    }
+
+    /// Creates a builder that deals with duplicate sites according to `policy`
+    ///
+    /// Sites closer to each other than `tolerance` count as duplicates. A tolerance
+    /// of zero only catches sites at exactly the same position.
+    pub fn with_duplicate_policy<R: Into<ClipRegion<T>>>(clip_region: R, sites: Vec<Site<T>>, policy: DuplicatePolicy, tolerance: T) -> Result<DiagramBuilder<T>, DuplicateSiteError> {
+        DiagramBuilder::with_policies(clip_region.into(), sites, policy, tolerance, OutOfBoundsPolicy::Skip, NonFinitePolicy::Skip).map_err(|error| match error {
+            SiteError::Duplicate(error) => error,
+            SiteError::OutOfBounds(_) | SiteError::NonFinite(_) => unreachable!("skipping sites never fails"),
+        })
+    }
+
+    /// Creates a builder that deals with sites outside the clip region according
+    /// to `policy`
+    ///
+    /// Sites at exactly the same position as an earlier site are dropped, like
+    /// with `new`.
+    pub fn with_out_of_bounds_policy<R: Into<ClipRegion<T>>>(clip_region: R, sites: Vec<Site<T>>, policy: OutOfBoundsPolicy) -> Result<DiagramBuilder<T>, OutOfBoundsError> {
+        DiagramBuilder::with_policies(clip_region.into(), sites, DuplicatePolicy::MergeFirstWins, T::zero(), policy, NonFinitePolicy::Skip).map_err(|error| match error {
+            SiteError::OutOfBounds(error) => error,
+            SiteError::Duplicate(_) => unreachable!("merging never fails"),
+            SiteError::NonFinite(_) => unreachable!("skipping sites never fails"),
+        })
+    }
+
+    /// Creates a builder that deals with sites that have a coordinate that is NaN
+    /// or infinite according to `policy`
+    ///
+    /// Other sites are dealt with like with `new`.
+    pub fn with_non_finite_policy<R: Into<ClipRegion<T>>>(clip_region: R, sites: Vec<Site<T>>, policy: NonFinitePolicy) -> Result<DiagramBuilder<T>, NonFiniteError> {
+        DiagramBuilder::with_policies(clip_region.into(), sites, DuplicatePolicy::MergeFirstWins, T::zero(), OutOfBoundsPolicy::Skip, policy).map_err(|error| match error {
+            SiteError::NonFinite(error) => error,
+            SiteError::Duplicate(_) => unreachable!("merging never fails"),
+            SiteError::OutOfBounds(_) => unreachable!("skipping sites never fails"),
+        })
+    }
+
+    pub(crate) fn with_policies(clip_region: ClipRegion<T>, sites: Vec<Site<T>>, policy: DuplicatePolicy, tolerance: T, out_of_bounds: OutOfBoundsPolicy, non_finite: NonFinitePolicy) -> Result<DiagramBuilder<T>, SiteError> {
+        DiagramBuilder::with_buffers(clip_region, &sites, policy, tolerance, out_of_bounds, non_finite, SweepBuffers::default())
+    }
+
+    /// Same as `with_policies`, but keeps its lists in buffers left over from an
+    /// earlier build
+    pub(crate) fn with_buffers(clip_region: ClipRegion<T>, sites: &[Site<T>], policy: DuplicatePolicy, tolerance: T, out_of_bounds: OutOfBoundsPolicy, non_finite: NonFinitePolicy, buffers: SweepBuffers<T>) -> Result<DiagramBuilder<T>, SiteError> {
+        // A NaN would make the events impossible to order, so these sites are
+        // found before anything else is done with them
+        let finite = sites.iter()
+            .map(|site| site.position.x.is_finite() && site.position.y.is_finite())
+            .collect::<Vec<_>>();
+        let not_finite = finite.iter().position(|&finite| !finite).map(|index| (sites[index].id, index));
+        if let (NonFinitePolicy::Error, Some((site_id, index))) = (non_finite, not_finite) {
+            return Err(SiteError::NonFinite(NonFiniteError {
+                site_id,
+                index,
+            }));
+        }
+
+        let SweepBuffers { mut event_queue, beachline, mut diagram, sites: mut scaled_sites, mut placed, triangles, cancelled_arcs, mut site_mapping } = buffers;
+
+        // Scale sites so the longest side of the bounding rect has length one. Both
+        // axes must be scaled by the same amount to preserve distances. Exact
+        // arithmetic is only exact if scaling doesn't round, so there the longest
+        // side is instead between a half and one
+        #[cfg(not(feature = "exact"))]
+        let scale_of = |rect: Rect<T>| T::one() / rect.size.x.max(rect.size.y);
+        #[cfg(feature = "exact")]
+        let scale_of = |rect: Rect<T>| exact::power_of_two(T::one() / rect.size.x.max(rect.size.y));
+        let mut bounding_rect = clip_region.bounding_rect();
+        let mut boundary = clip_region.boundary(scale_of(bounding_rect));
+        let scaled = |rect: Rect<T>, position: Point2<T>| Point2::from_vec((position - rect.position) * scale_of(rect));
+
+        let outside = sites.iter().zip(&finite)
+            .map(|(site, &finite)| finite && !strictly_inside_boundary(&boundary, scaled(bounding_rect, site.position)))
+            .collect::<Vec<_>>();
+        if out_of_bounds == OutOfBoundsPolicy::ExpandBounds && outside.contains(&true) {
+            let corners = vec![bounding_rect.position, bounding_rect.position + bounding_rect.size];
+            let rect = Rect::around(corners.into_iter().chain(sites.iter().map(|site| site.position)), T::zero());
+            bounding_rect = rect;
+            boundary = ClipRegion::Rect(rect).boundary(scale_of(rect));
+        }
+        let scale = scale_of(bounding_rect);
+        let inside = |position: Point2<T>| strictly_inside_boundary(&boundary, position);
+
+        // Duplicates are looked for after scaling, as sites that are only slightly
+        // apart can end up at the same position
+        placed.reset(tolerance * scale);
+        site_mapping.clear();
+
+        // Jittered sites are moved at least past the tolerance, or by a distance that
+        // is small but still well above the precision of the scalar type
+        let jitter_radius = if tolerance > T::zero() {
+            tolerance * scale * (T::one() + T::one())
+        } else {
+            T::epsilon().sqrt()
+        };
+
+        for (index, mut site) in sites.iter().cloned().enumerate() {
+            if !finite[index] {
+                site_mapping.push(SiteOutcome::NonFinite);
+                continue;
+            }
+
+            let mut position = scaled(bounding_rect, site.position);
+
+            // Faces are created in the same order as the sites were given
+            let face = FaceId(diagram.faces.len() as u32);
+            let mut outcome = SiteOutcome::Kept(face);
+            if outside[index] {
+                match out_of_bounds {
+                    OutOfBoundsPolicy::Error => {
+                        return Err(SiteError::OutOfBounds(OutOfBoundsError {
+                            site_id: site.id,
+                            index,
+                        }));
+                    }
+                    OutOfBoundsPolicy::Skip => {}
+                    OutOfBoundsPolicy::Clamp => {
+                        if let Some(clamped) = clamp_to_boundary(&boundary, position) {
+                            position = clamped;
+                            site.position = bounding_rect.position + position.to_vec() / scale;
+                            outcome = SiteOutcome::Clamped(face);
+                        }
+                    }
+                    OutOfBoundsPolicy::ExpandBounds => outcome = SiteOutcome::ExpandedBounds(face),
+                }
+            }
+
+            if !inside(position) {
+                site_mapping.push(SiteOutcome::OutOfBounds);
+                continue;
+            }
+
+            if let Some(duplicate_of) = placed.find(position) {
+                match policy {
+                    DuplicatePolicy::Error => {
+                        return Err(SiteError::Duplicate(DuplicateSiteError {
+                            site_id: site.id,
+                            duplicate_of: diagram.faces[duplicate_of as usize].site.id,
+                        }));
+                    }
+                    DuplicatePolicy::MergeFirstWins => {
+                        site_mapping.push(SiteOutcome::Merged(FaceId(duplicate_of)));
+                        continue;
+                    }
+                    DuplicatePolicy::Jitter => {
+                        let accept = |candidate| inside(candidate) && placed.find(candidate).is_none();
+                        match jitter(position, jitter_radius, index as u64, accept) {
+                            Some(jittered) => {
+                                position = jittered;
+                                site.position = bounding_rect.position + position.to_vec() / scale;
+                                if outcome == SiteOutcome::Kept(face) {
+                                    outcome = SiteOutcome::Jittered(face);
+                                }
+                            }
+                            None => {
+                                site_mapping.push(SiteOutcome::Merged(FaceId(duplicate_of)));
+                                continue;
+                            }
+                        }
+                    }
+                }
             }
+
+            placed.insert(position, face.0);
+            site_mapping.push(outcome);
+
+            diagram.faces.push(Face {
+                first_halfedge: None,
+                site,
+            });
+            scaled_sites.push(position);
+
+            event_queue.push(Event::Site(Site {
+                position,
+                ..site
+            }, face));
         }
 
-        DiagramBuilder {
-            diagram: Diagram::default(),
-            beachline: BeachLine::default(),
-            step: 0,
-            total_events: 0,
-            cancelled_events: 0,
-            debug: false,
-            event_queue: event_queue,
-            future_circle_events: FnvHashSet::default(),
+        Ok(DiagramBuilder {
+            diagram,
+            beachline,
+            stats: BuildStats {
+                total_events: event_queue.len() as u32,
+                peak_queue_size: event_queue.len(),
+                ..BuildStats::default()
+            },
+            event_queue,
             offset: bounding_rect.position,
-            scale: 1.0 / bounding_rect.size,
+            scale,
+            boundary,
+            sites: scaled_sites,
+            placed,
+            triangles,
+            cancelled_arcs,
+            directrix: None,
+            metric: Metric::Euclidean,
+            clipped: true,
+            site_mapping,
+            not_finite,
+        })
+    }
+
+    /// How the sites that were given map to the faces of the diagram
+    pub fn site_mapping(&self) -> &SiteMapping {
+        &self.site_mapping
+    }
+
+    /// Adds a site to a builder that may have already started sweeping, and
+    /// returns the face it got
+    ///
+    /// This lets sites be streamed in without having them all at once, as long as
+    /// they are sorted by y, or at least each one comes before the sweep line gets
+    /// to it. The sweep line moves towards increasing y, and a site that isn't
+    /// strictly past it can't be added any more. `sweep_to` processes the events
+    /// before a line, which leaves everything from the line on free to be added.
+    ///
+    /// The builder's policies aren't applied here. Sites outside the clip region,
+    /// sites that aren't finite and duplicates of a site the builder already has are
+    /// all errors, and leave the builder as it was. Sites that were added come after
+    /// the sites that were given in `site_mapping`.
+    pub fn add_site(&mut self, site: Site<T>) -> Result<FaceId, AddSiteError> {
+        let index = self.site_mapping.outcomes().len();
+        if !site.position.x.is_finite() || !site.position.y.is_finite() {
+            return Err(AddSiteError::NonFinite(NonFiniteError {
+                site_id: site.id,
+                index,
+            }));
+        }
+
+        let position = Point2::from_vec((site.position - self.offset) * self.scale);
+        if !strictly_inside_boundary(&self.boundary, position) {
+            return Err(AddSiteError::OutOfBounds(OutOfBoundsError {
+                site_id: site.id,
+                index,
+            }));
+        }
+
+        // Events on the sweep line may already have been processed, and the order
+        // of those after them can't be known without keeping the last one around
+        if self.directrix.is_some_and(|directrix| position.y <= directrix) {
+            return Err(AddSiteError::BehindSweepLine {
+                site_id: site.id,
+            });
+        }
+
+        if let Some(duplicate_of) = self.placed.find(position) {
+            return Err(AddSiteError::Duplicate(DuplicateSiteError {
+                site_id: site.id,
+                duplicate_of: self.diagram.faces[duplicate_of as usize].site.id,
+            }));
+        }
+
+        let face = FaceId(self.diagram.faces.len() as u32);
+        self.placed.insert(position, face.0);
+        self.site_mapping.push(SiteOutcome::Kept(face));
+        self.diagram.faces.push(Face {
+            first_halfedge: None,
+            site,
+        });
+        self.sites.push(position);
+
+        self.event_queue.push(Event::Site(Site {
+            position,
+            ..site
+        }, face));
+        self.stats.total_events += 1;
+        self.stats.peak_queue_size = self.stats.peak_queue_size.max(self.event_queue.len());
+        Ok(face)
+    }
+
+    /// Processes every event before the line at `y`, so sites from it on can
+    /// still be added with `add_site`
+    pub fn sweep_to(&mut self, y: T) {
+        let y = (y - self.offset.y) * self.scale;
+        while let Some(event) = self.event_queue.peek() {
+            let event_y = match *event {
+                Event::Site(site, _) => site.position.y,
+                Event::Circle(event_y, ..) => event_y,
+            };
+            if event_y.partial_cmp(&y) != Some(Ordering::Less) {
+                break;
+            }
+            self.step();
+        }
+    }
+
+    fn add_vertex(&mut self, position: Point2<T>, incident_edge: HalfEdgeId) -> VertexId {
+        self.diagram.push_vertex(Vertex {
+            coordinates: position,
+            incident_edge,
+        })
+    }
+
+    /// Adds a pair of twin half-edges along the boundary between two faces
+    ///
+    /// Neither half-edge has an origin yet. The next and prev pointers point back at
+    /// the half-edge itself until they are linked up when the endpoints are found.
+    fn add_edge(&mut self, left_face: FaceId, right_face: FaceId) -> (HalfEdgeId, HalfEdgeId) {
+        let left_halfedge = HalfEdgeId(self.diagram.halfedges.len() as u32);
+        let right_halfedge = HalfEdgeId(left_halfedge.0 + 1);
+
+        for &(halfedge, twin, face) in &[(left_halfedge, right_halfedge, left_face), (right_halfedge, left_halfedge, right_face)] {
+            self.diagram.halfedges.push(HalfEdge {
+                origin: None,
+                twin: Some(twin),
+                incident_face: face,
+                next: halfedge,
+                prev: halfedge,
+            });
+
+            let face = &mut self.diagram.faces[face.0 as usize];
+            if face.first_halfedge.is_none() {
+                face.first_halfedge = Some(halfedge);
+            }
+        }
+
+        (left_halfedge, right_halfedge)
+    }
+
+    fn link_halfedges(&mut self, halfedge: HalfEdgeId, next: HalfEdgeId) {
+        self.diagram.halfedges[halfedge.0 as usize].next = next;
+        self.diagram.halfedges[next.0 as usize].prev = halfedge;
+    }
+
+    fn set_origin(&mut self, halfedge: HalfEdgeId, vertex: VertexId) {
+        self.diagram.halfedges[halfedge.0 as usize].origin = Some(vertex);
+    }
+
+    fn twin(&self, halfedge: HalfEdgeId) -> HalfEdgeId {
+        self.diagram.halfedges[halfedge.0 as usize].twin.unwrap()
+    }
+
+    /// Cancels the arc's pending circle event
+    ///
+    /// The event stays in the queue and is skipped when it's popped, as its id no
+    /// longer matches the one on the arc.
+    fn cancel_circle_event(&mut self, arc: ArcId) {
+        if self.beachline.get_arc_mut(arc).circle_event.take().is_some() {
+            self.stats.cancelled_events += 1;
+            self.cancelled_arcs.push(arc);
         }
     }
 
-    pub fn set_debug(&mut self, enable: bool) {
-        self.debug = enable;
+    /// Rechecks the circle events of the arcs either side of one that changed
+    fn check_circle_events(&mut self, left_arc: ArcId, right_arc: ArcId) {
+        let circles = self.beachline.get_circumcircles([left_arc, right_arc]);
+        self.check_circle_event(left_arc, circles[0]);
+        self.check_circle_event(right_arc, circles[1]);
     }
 
-    fn handle_site_event(&mut self, site: Site) {
+    fn check_circle_event(&mut self, arc: ArcId, circle: Option<(Point2<T>, T)>) {
+        // Cancel existing circle event if one exists
+        self.cancel_circle_event(arc);
+
+        if let Some((centroid, bottom)) = circle {
+            let event_id = self.stats.circle_events;
+            #[cfg(not(feature = "exact"))]
+            let event = Event::Circle(bottom, centroid, arc, event_id);
+            #[cfg(feature = "exact")]
+            let event = Event::Circle(bottom, centroid, arc, event_id, exact::Circle::new(self.beachline.get_circle_sites(arc)));
+            self.event_queue.push(event);
+            self.beachline.get_arc_mut(arc).circle_event = Some(event_id);
+            self.stats.circle_events += 1;
+            self.stats.total_events += 1;
+            self.stats.peak_queue_size = self.stats.peak_queue_size.max(self.event_queue.len());
+        }
+    }
+
+    fn handle_site_event(&mut self, site: Site<T>, face: FaceId) {
+
         // Find existing arc directly above this site
-        let current_arc = self.beachline.find_arc(site.position.x, site.position.y);
+        let current_arc = match self.beachline.find_arc(site.position.x, site.position.y) {
+            Some(current_arc) => current_arc,
+            None => {
+                // This is the first site
+                self.beachline.add_arc(site, face, None);
+                return;
+            }
+        };
+
+        let (current_site, current_face) = {
+            let arc = self.beachline.get_arc(current_arc);
+            (arc.site, arc.face)
+        };
+
+        if current_site.position.y == site.position.y {
+            // The arc above belongs to a site on the sweep line as well. This only happens
+            // while processing the first row of sites, which have no parabola to split yet.
+            // The new arc goes to the right and the edge between them runs straight down.
+            let new_arc = self.beachline.add_arc_after(site, face, current_arc);
+            let (left_halfedge, right_halfedge) = self.add_edge(current_face, face);
+
+            let old_right_halfedge = self.beachline.get_arc(current_arc).right_halfedge;
+            self.beachline.get_arc_mut(current_arc).right_halfedge = Some(left_halfedge);
+            let arc = self.beachline.get_arc_mut(new_arc);
+            arc.left_halfedge = Some(right_halfedge);
+            arc.right_halfedge = old_right_halfedge;
+            return;
+        }
 
         // Insert arc for this site
-        let new_arc = self.beachline.add_arc(site, current_arc);
+        let new_arc = self.beachline.add_arc(site, face, Some(current_arc));
+
+        // Cancel existing circle event if one exists
+        self.cancel_circle_event(current_arc);
 
-        if let Some(current_arc) = current_arc {
-            // Cancel existing circle event if one exists
-            if self.future_circle_events.remove(&current_arc) {
-                self.cancelled_events += 1;
+        // Create the edge that is traced by both of the new arc's breakpoints
+        let (current_halfedge, new_halfedge) = self.add_edge(current_face, face);
+        let (left_arc, right_arc) = self.beachline.get_left_right_arcs(new_arc);
+        let (left_arc, right_arc) = (left_arc.unwrap(), right_arc.unwrap());
+
+        self.beachline.get_arc_mut(left_arc).right_halfedge = Some(current_halfedge);
+        self.beachline.get_arc_mut(right_arc).left_halfedge = Some(current_halfedge);
+        {
+            let arc = self.beachline.get_arc_mut(new_arc);
+            arc.left_halfedge = Some(new_halfedge);
+            arc.right_halfedge = Some(new_halfedge);
+        }
+
+        // Check for circle events on the left and right
+        self.check_circle_events(left_arc, right_arc);
+    }
+
+    fn handle_circle_event(&mut self, centroid: Point2<T>, arc: ArcId) {
+        let (left_arc, right_arc) = self.beachline.get_left_right_arcs(arc);
+        let (left_arc, right_arc) = (left_arc.unwrap(), right_arc.unwrap());
+        let (arc_left_halfedge, arc_right_halfedge) = {
+            let arc = self.beachline.get_arc(arc);
+            (arc.left_halfedge.unwrap(), arc.right_halfedge.unwrap())
+        };
+
+        // Create the edge that is traced by the new breakpoint between the left and right arcs
+        let left_face = self.beachline.get_arc(left_arc).face;
+        let right_face = self.beachline.get_arc(right_arc).face;
+        let (left_halfedge, right_halfedge) = self.add_edge(left_face, right_face);
+
+        // Add vertex into diagram
+        let vertex = self.add_vertex(centroid, left_halfedge);
+        let arc_face = self.beachline.get_arc(arc).face;
+        self.triangles.push([arc_face, right_face, left_face]);
+
+        // The two edges traced by the removed arc's breakpoints end at the vertex
+        // and the new edge starts from it
+        self.set_origin(arc_left_halfedge, vertex);
+        let twin = self.twin(arc_right_halfedge);
+        self.set_origin(twin, vertex);
+        self.set_origin(left_halfedge, vertex);
+
+        self.link_halfedges(arc_right_halfedge, arc_left_halfedge);
+        let twin = self.twin(arc_left_halfedge);
+        self.link_halfedges(twin, left_halfedge);
+        let twin = self.twin(arc_right_halfedge);
+        self.link_halfedges(right_halfedge, twin);
+
+        self.beachline.get_arc_mut(left_arc).right_halfedge = Some(left_halfedge);
+        self.beachline.get_arc_mut(right_arc).left_halfedge = Some(right_halfedge);
+
+        // Remove the arc
+        self.beachline.remove_arc(arc);
+
+        // The left and right arcs have new neighbours so their circle events need rechecking
+        self.check_circle_events(left_arc, right_arc);
+    }
+
+    /// Closes the cycles of unbounded faces
+    ///
+    /// After the sweep, the boundary of each unbounded face is made up of one or
+    /// two chains of half-edges that start and end at infinity (there are two
+    /// chains when the face is a strip between parallel edges). This links the end
+    /// of each chain to the start of the next one and points the face at a chain
+    /// start.
+    fn link_unbounded_faces(&mut self) {
+        let mut chain_starts: FnvHashMap<FaceId, Vec<HalfEdgeId>> = FnvHashMap::default();
+        for (index, halfedge) in self.diagram.halfedges.iter().enumerate() {
+            if halfedge.origin.is_none() {
+                chain_starts.entry(halfedge.incident_face).or_default().push(HalfEdgeId(index as u32));
             }
         }
 
-        let (left_arc, right_arc) = self.beachline.get_left_right_arcs(new_arc);
+        for (face, starts) in chain_starts {
+            // Find the end of each chain by following next pointers until we reach
+            // a half-edge that has no destination
+            let ends = starts.iter().map(|&start| {
+                let mut halfedge = start;
+                while self.diagram.halfedges[self.twin(halfedge).0 as usize].origin.is_some() {
+                    halfedge = self.diagram.halfedges[halfedge.0 as usize].next;
+                }
+                halfedge
+            }).collect::<Vec<_>>();
+
+            for (index, &end) in ends.iter().enumerate() {
+                self.link_halfedges(end, starts[(index + 1) % starts.len()]);
+            }
+
+            self.diagram.faces[face.0 as usize].first_halfedge = Some(starts[0]);
+        }
+    }
+
+    /// Builds the diagram of up to three sites straight away, without sweeping
+    ///
+    /// Two sites are split by their bisector, three in a line by two parallel
+    /// bisectors, and any other three by bisectors that meet at the centre of the
+    /// circle through them. Returns false and leaves the builder as it was if there
+    /// are more sites, or some events have already been processed.
+    fn build_small(&mut self) -> bool {
+        let count = self.diagram.faces.len();
+        if count > 3 || self.directrix.is_some() || self.event_queue.len() != count {
+            return false;
+        }
+        self.event_queue.clear();
 
-        // Check for circle event on the left
-        if let Some(left_arc) = left_arc {
-            // Cancel existing circle event if one exists
-            if self.future_circle_events.remove(&left_arc) {
-                self.cancelled_events += 1;
+        let mut faces = (0..count as u32).map(FaceId).collect::<Vec<_>>();
+        if count < 2 {
+            return true;
+        }
+        if count == 2 {
+            self.add_edge(faces[0], faces[1]);
+            self.link_unbounded_faces();
+            return true;
+        }
+
+        let sites = faces.iter().map(|face| self.sites[face.0 as usize]).collect::<Vec<_>>();
+        let centre = match circumcircle_of_points(sites[0], sites[1], sites[2]) {
+            Some((centre, _)) => centre,
+            None => {
+                // The middle face is a strip between two parallel edges
+                let sites = &self.sites;
+                faces.sort_by(|a, b| {
+                    let (a, b) = (sites[a.0 as usize], sites[b.0 as usize]);
+                    compare(a.x, b.x).then(compare(a.y, b.y))
+                });
+                self.add_edge(faces[0], faces[1]);
+                self.add_edge(faces[1], faces[2]);
+                self.link_unbounded_faces();
+                return true;
             }
+        };
+
+        // Going counter-clockwise round the vertex, each face comes in from
+        // infinity along its edge with the next face and leaves along its edge
+        // with the one before
+        if orient2d(sites[0].cast(), sites[1].cast(), sites[2].cast()) < 0.0 {
+            faces.swap(1, 2);
+        }
+        let edges = (0..3).map(|index| self.add_edge(faces[index], faces[(index + 1) % 3])).collect::<Vec<_>>();
+        let vertex = self.add_vertex(centre, edges[0].1);
+        for index in 0..3 {
+            let (incoming, outgoing) = (edges[index].0, edges[(index + 2) % 3].1);
+            self.set_origin(outgoing, vertex);
+            self.link_halfedges(incoming, outgoing);
+        }
+        self.triangles.push([faces[0], faces[1], faces[2]]);
+        self.link_unbounded_faces();
+        true
+    }
+
+    /// Builds the diagram of the sites, without reporting the events to anything
+    fn sweep_all(&mut self) {
+        if !self.build_small() {
+            self.sweep(&mut ());
+        }
+    }
+
+    /// Processes the next event, returning true once there are none left
+    pub fn step(&mut self) -> bool {
+        self.step_with_observer(&mut ())
+    }
+
+    /// Processes the next event and reports what happened to an observer
+    ///
+    /// Returns true once there are no events left.
+    pub fn step_with_observer<O: BuildObserver<T>>(&mut self, observer: &mut O) -> bool {
+        self.cancelled_arcs.clear();
 
-            if let Some((centroid, radius)) = self.beachline.get_circumcircle(left_arc) {
-                if centroid.y + radius > site.position.y {
-                    // Add to event_queue
-                    self.event_queue.push(Event::Circle(centroid.y + radius, centroid, left_arc));
+        let directrix = match self.event_queue.pop() {
+            Some(Event::Site(site, face)) => {
+                self.handle_site_event(site, face);
 
-                    // Add to future_circle_events set
-                    // This allows us to remove the event at any time before processing,
-                    // which is difficult to do with just the event queue.
-                    self.future_circle_events.insert(left_arc);
-                    self.total_events += 1;
+                // Sites in the queue are scaled, the diagram's faces have the originals
+                observer.site_event(&self.diagram.faces[face.0 as usize].site);
+                site.position.y
+            }
+            Some(Event::Circle(y, centroid, id, event_id, ..)) => {
+                // Only run handle_circle_event if this is still the arc's pending event
+                if self.beachline.get_arc(id).circle_event == Some(event_id) {
+                    self.beachline.get_arc_mut(id).circle_event = None;
+                    self.handle_circle_event(centroid, id);
+                    observer.circle_event(self.to_world(centroid), self.to_world_y(y));
                 }
+                y
             }
+            None => return true,
+        };
+        self.directrix = Some(directrix);
+
+        for index in 0..self.cancelled_arcs.len() {
+            let face = self.beachline.get_arc(self.cancelled_arcs[index]).face;
+            observer.circle_event_cancelled(&self.diagram.faces[face.0 as usize].site);
+        }
+
+        if observer.wants_beachline() {
+            let arcs = self.beachline_snapshot(directrix);
+            observer.beachline(self.to_world_y(directrix), &arcs);
         }
 
-        // Check for circle event on the right
-        if let Some(right_arc) = right_arc {
-            // Cancel existing circle event if one exists
-            if self.future_circle_events.remove(&right_arc) {
-                self.cancelled_events += 1;
+        false
+    }
+
+    /// Processes up to `count` events, for spreading a build over several frames
+    pub fn step_n(&mut self, count: usize) -> Progress<T> {
+        for _ in 0..count {
+            if self.step() {
+                break;
             }
+        }
+        self.progress()
+    }
 
-            if let Some((centroid, radius)) = self.beachline.get_circumcircle(right_arc) {
-                if centroid.y + radius > site.position.y {
-                    // Add to event_queue
-                    self.event_queue.push(Event::Circle(centroid.y + radius, centroid, right_arc));
+    /// Processes events until `budget` has passed, for spreading a build over
+    /// several frames
+    ///
+    /// The time is checked after each event, so this goes over the budget by up to
+    /// one event, and always processes at least one if there are any.
+    pub fn step_for(&mut self, budget: Duration) -> Progress<T> {
+        let start = Instant::now();
+        while !self.step() && start.elapsed() < budget {}
+        self.progress()
+    }
 
-                    // Add to future_circle_events set
-                    // This allows us to remove the event at any time before processing,
-                    // which is difficult to do with just the event queue.
-                    self.future_circle_events.insert(right_arc);
-                    self.total_events += 1;
-                }
+    /// How far through its sweep the builder is
+    pub fn progress(&self) -> Progress<T> {
+        let remaining = self.event_queue.len();
+        Progress {
+            processed: self.stats.total_events as usize - remaining,
+            remaining,
+            directrix: self.directrix.map(|directrix| self.to_world_y(directrix)),
+        }
+    }
+
+    /// Takes a snapshot of the sweep, for showing how the diagram is built
+    ///
+    /// This takes time proportional to the size of the diagram so far.
+    pub fn sweep_state(&self) -> SweepState<T> {
+        let directrix = match self.directrix {
+            Some(directrix) => directrix,
+            None => {
+                return SweepState {
+                    directrix: None,
+                    arcs: Vec::new(),
+                    pending_events: self.pending_events(),
+                    edges: Vec::new(),
+                };
+            }
+        };
+
+        // Edge ends that are still moving are traced by a breakpoint. The half-edge of
+        // the arc to the left of a breakpoint ends there, so its twin starts there.
+        let mut breakpoints = FnvHashMap::default();
+        for arc_id in self.beachline.arcs_in_order() {
+            if let Some(position) = self.beachline.get_right_breakpoint_position(arc_id, directrix) {
+                let halfedge = self.beachline.get_arc(arc_id).right_halfedge.unwrap();
+                breakpoints.insert(self.twin(halfedge), self.to_world(position));
             }
         }
+
+        let end = |halfedge: HalfEdgeId| {
+            match self.diagram.halfedges[halfedge.0 as usize].origin {
+                Some(vertex) => Some((self.to_world(self.diagram.vertices[vertex.0 as usize].coordinates), true)),
+                None => breakpoints.get(&halfedge).map(|&position| (position, false)),
+            }
+        };
+
+        // Half-edges are added in twin pairs
+        let edges = (0..self.diagram.halfedges.len() / 2).filter_map(|index| {
+            let halfedge = HalfEdgeId(index as u32 * 2);
+            let twin = self.twin(halfedge);
+            let (start, start_fixed) = end(halfedge)?;
+            let (end, end_fixed) = end(twin)?;
+
+            Some(SweepEdge {
+                left_face: self.diagram.halfedges[halfedge.0 as usize].incident_face,
+                right_face: self.diagram.halfedges[twin.0 as usize].incident_face,
+                start,
+                end,
+                start_fixed,
+                end_fixed,
+            })
+        }).collect();
+
+        SweepState {
+            directrix: Some(self.to_world_y(directrix)),
+            arcs: self.beachline_snapshot(directrix),
+            pending_events: self.pending_events(),
+            edges,
+        }
     }
 
-    fn handle_circle_event(&mut self, y: f32, centroid: Point2<f32>, arc: ArcId) {
-        // Add vertex into diagram
-        self.diagram.vertices.push(
-            Vertex {
-                coordinates: Point2::new(
-                    centroid.x / self.scale.x + self.offset.x,
-                    centroid.y / self.scale.y + self.offset.y,
-                ),
-                incident_edge: HalfEdgeId(0),
+    /// Events left in the queue in the order they will be processed, leaving out
+    /// cancelled circle events
+    fn pending_events(&self) -> Vec<PendingEvent<T>> {
+        let mut events = self.event_queue.iter().filter(|event| {
+            match **event {
+                Event::Site(..) => true,
+                Event::Circle(_, _, arc, event_id, ..) => self.beachline.get_arc(arc).circle_event == Some(event_id),
+            }
+        }).collect::<Vec<_>>();
+
+        // The queue pops the greatest event first
+        events.sort_by(|a, b| b.cmp(a));
+
+        events.into_iter().map(|event| {
+            match *event {
+                Event::Site(_, face) => PendingEvent::Site(self.diagram.faces[face.0 as usize].site),
+                Event::Circle(y, centroid, ..) => PendingEvent::Circle {
+                    vertex: self.to_world(centroid),
+                    directrix: self.to_world_y(y),
+                },
             }
-        );
+        }).collect()
+    }
 
-        // Remove the arc
-        self.beachline.remove_arc(arc);
+    /// The arcs of the beachline from left to right, in world coordinates
+    fn beachline_snapshot(&self, directrix: T) -> Vec<ArcSnapshot<T>> {
+        self.beachline.arcs_in_order().map(|arc_id| {
+            let arc = self.beachline.get_arc(arc_id);
+            let left_breakpoint = self.beachline.get_left_breakpoint(arc_id, directrix);
+            let right_breakpoint = self.beachline.get_right_breakpoint(arc_id, directrix);
+
+            ArcSnapshot {
+                site: self.diagram.faces[arc.face.0 as usize].site,
+                left_breakpoint: self.offset.x + left_breakpoint / self.scale,
+                right_breakpoint: self.offset.x + right_breakpoint / self.scale,
+            }
+        }).collect()
     }
 
-    fn debug_beachline(&self, directrix: f32) {
-        self.beachline.debug(directrix);
+    /// Sets the metric the finished diagram is built under, which is Euclidean by
+    /// default
+    ///
+    /// Only the finished diagram uses it. Stepping, observers and the Delaunay
+    /// triangulation all come from the sweep, which is always Euclidean, and
+    /// `Diagram::locate` finds the face of the nearest site by Euclidean distance.
+    pub fn set_metric(&mut self, metric: Metric) {
+        self.metric = metric;
     }
 
-    pub fn step(&mut self) -> bool {
-        self.step += 1;
+    /// Sets whether the finished diagram is clipped to the clip region, which it
+    /// is by default
+    ///
+    /// An unclipped diagram is the same as the one from `finish_unclipped`, but
+    /// comes from any of the other ways of finishing. Only Euclidean diagrams can
+    /// be left unclipped, as the cells under other metrics are only found inside
+    /// the clip region.
+    pub fn set_clipped(&mut self, clipped: bool) {
+        self.clipped = clipped;
+    }
+
+    /// Counts of the events so far
+    pub fn stats(&self) -> BuildStats {
+        self.stats
+    }
+
+    fn to_world(&self, position: Point2<T>) -> Point2<T> {
+        self.offset + position.to_vec() / self.scale
+    }
+
+    fn to_world_y(&self, y: T) -> T {
+        self.offset.y + y / self.scale
+    }
+
+    fn sweep<O: BuildObserver<T>>(&mut self, observer: &mut O) {
+        while !self.step_with_observer(observer) {}
+        self.link_unbounded_faces();
+    }
+
+    fn clip(&self) -> Diagram<T> {
+        let mut diagram = Diagram::default();
+        self.clip_into(&mut diagram);
+        diagram
+    }
+
+    fn clip_into(&self, diagram: &mut Diagram<T>) {
+        match self.metric {
+            Metric::Euclidean if !self.clipped => diagram.clone_from(&self.diagram),
+            Metric::Euclidean => clip_to_boundary_into(&self.diagram, &self.sites, None, &self.boundary, diagram),
+            metric => *diagram = build_cells(metric, &self.diagram.faces, &self.sites, &self.boundary),
+        }
+        diagram.metric = self.metric;
+
+        // Scale vertices back to the coordinates of the bounding rect
+        for vertex in &mut diagram.vertices {
+            vertex.coordinates = self.to_world(vertex.coordinates);
+        }
 
-        if self.debug {
-            println!("step {}", self.step);
+        diagram.index_sites(false);
+    }
+
+    /// Builds the diagram into `output`, replacing what was there, and hands back
+    /// the builder's lists emptied so the next build can use them
+    pub(crate) fn finish_into(mut self, output: &mut Diagram<T>) -> SweepBuffers<T> {
+        if self.metric == Metric::Euclidean {
+            self.sweep_all();
         }
+        self.clip_into(output);
+
+        let DiagramBuilder { mut event_queue, mut beachline, mut diagram, mut sites, placed, mut triangles, mut cancelled_arcs, site_mapping, .. } = self;
+        event_queue.clear();
+        beachline.clear();
+        diagram.vertices.clear();
+        diagram.halfedges.clear();
+        diagram.faces.clear();
+        sites.clear();
+        triangles.clear();
+        cancelled_arcs.clear();
+
+        // The mapping is kept until the next build, which clears it along with the
+        // duplicate set
+        SweepBuffers { event_queue, beachline, diagram, sites, placed, triangles, cancelled_arcs, site_mapping }
+    }
 
-        let event = self.event_queue.pop();
+    pub fn finish(mut self) -> Diagram<T> {
+        // Other metrics don't need the sweep
+        if self.metric == Metric::Euclidean {
+            self.sweep_all();
+        }
+        self.clip()
+    }
 
-        match event {
-            Some(Event::Site(site)) => {
-                self.handle_site_event(site);
+    /// Builds the diagram, or says why the input can't make a useful one
+    ///
+    /// `finish` drops sites that aren't finite or are outside the clip region and
+    /// goes on with the rest, which can leave an empty diagram. This fails instead,
+    /// and also checks the links of the diagram it built.
+    pub fn try_finish(self) -> Result<Diagram<T>, BuildError> {
+        if let Some((site_id, index)) = self.not_finite {
+            return Err(BuildError::NotFinite { site_id, index });
+        }
+        let boundary = &self.boundary;
+        let twice_area = boundary.iter().enumerate().fold(T::zero(), |area, (index, a)| {
+            let b = boundary[(index + 1) % boundary.len()];
+            area + a.x * b.y - b.x * a.y
+        });
+        if !self.scale.is_finite() || twice_area.partial_cmp(&T::zero()) != Some(Ordering::Greater) {
+            return Err(BuildError::DegenerateClipRegion);
+        }
+        if !is_convex_boundary(boundary) {
+            return Err(BuildError::ConcaveClipRegion);
+        }
+        if self.diagram.faces.is_empty() {
+            return Err(BuildError::NoSites);
+        }
 
-                if self.debug {
-                    println!("directrix={}", site.position.y);
-                    println!("site event: id={} x={}, y={}", site.id, site.position.x, site.position.y);
-                    self.debug_beachline(site.position.y);
+        // Half-edges of an unclipped diagram come from infinity
+        let require_origins = self.clipped || self.metric != Metric::Euclidean;
+        let diagram = self.finish();
+        validate_links(&diagram, require_origins)?;
+        Ok(diagram)
+    }
+
+    /// Builds the diagram and checks that every face is a closed polygon
+    ///
+    /// Every site that's kept gets a cell inside the clip region, so this only
+    /// fails if sites are so close together that rounding leaves a cell without
+    /// any area.
+    pub fn finish_closed(self) -> Result<ClosedDiagram<T>, NotClosedError> {
+        ClosedDiagram::new(self.finish())
+    }
+
+    /// Builds the diagram, reporting each step of the sweep to an observer
+    pub fn finish_with_observer<O: BuildObserver<T>>(mut self, observer: &mut O) -> Diagram<T> {
+        self.sweep(observer);
+        self.clip()
+    }
+
+    /// Builds the diagram, calling `callback` with how far the sweep has got
+    /// every `PROGRESS_INTERVAL` events and once more when it's done
+    ///
+    /// The total number of events isn't known up front, as circle events are
+    /// found during the sweep, so `Progress::total` grows as it goes. Clipping the
+    /// cells afterwards isn't reported.
+    pub fn finish_with_progress<F: FnMut(Progress<T>)>(mut self, mut callback: F) -> Diagram<T> {
+        if self.metric == Metric::Euclidean {
+            loop {
+                let progress = self.step_n(PROGRESS_INTERVAL);
+                callback(progress);
+                if progress.is_finished() {
+                    break;
                 }
             }
-            Some(Event::Circle(y, centroid, id)) => {
-                // Only run handle_circle_event if the ID is still in future_circle_events
-                if self.future_circle_events.remove(&id) {
-                    self.handle_circle_event(y, centroid, id);
-
-                    if self.debug {
-                        println!("directrix={}", y);
-                        println!("circle event: arc={}, cx={}, cy={}", id.0, centroid.x, centroid.y);
-                        self.debug_beachline(y);
-                    }
-                } else {
-                    if self.debug {
-                        println!("directrix={}", y);
-                        println!("cancelled circle event (skipping)");
-                    }
+            self.link_unbounded_faces();
+        }
+        self.clip()
+    }
+
+    /// Builds the diagram, unless the token is cancelled first
+    ///
+    /// The token is checked before the sweep starts and then every
+    /// `PROGRESS_INTERVAL` events. Clipping the cells once the sweep is done
+    /// can't be cancelled.
+    pub fn finish_cancellable(mut self, token: &CancelToken) -> Result<Diagram<T>, BuildCancelled> {
+        let start = Instant::now();
+        if self.metric == Metric::Euclidean {
+            loop {
+                if token.is_cancelled() {
+                    self.stats.sweep_time = start.elapsed();
+                    return Err(BuildCancelled {
+                        processed: self.progress().processed,
+                        stats: self.stats,
+                    });
+                }
+                if self.step_n(PROGRESS_INTERVAL).is_finished() {
+                    break;
                 }
             }
-            None => return true,
+            self.link_unbounded_faces();
         }
+        Ok(self.clip())
+    }
 
-        if self.debug {
-            println!("total_events={}, cancelled_events={}", self.total_events, self.cancelled_events);
+    /// Builds the diagram and returns it along with counts and timings
+    pub fn finish_with_stats(mut self) -> (Diagram<T>, BuildStats) {
+        let start = Instant::now();
+        self.sweep_all();
+        self.stats.sweep_time = start.elapsed();
 
-            println!("end step");
-            println!("");
-        }
+        let start = Instant::now();
+        let diagram = self.clip();
+        self.stats.clip_time = start.elapsed();
 
-        false
+        (diagram, self.stats)
     }
 
-    pub fn finish(mut self) -> Diagram {
-        while !self.step() {}
-        self.diagram
+    /// Builds the diagram along with its dual, the Delaunay triangulation of the sites
+    pub fn finish_with_delaunay(mut self) -> (Diagram<T>, Triangulation<T>) {
+        self.sweep_all();
+
+        let points = self.diagram.faces.iter().map(|face| face.site.position).collect();
+        let triangulation = Triangulation::from_diagram(&self.diagram, points, &self.triangles);
+
+        (self.clip(), triangulation)
+    }
+
+    /// Builds the diagram without clipping it
+    ///
+    /// Edges between the cells on the convex hull of the sites go off to
+    /// infinity, and their half-edges have no origin at that end. Use
+    /// `Diagram::origin` and `Diagram::destination` to find which way they go.
+    /// These diagrams don't pass `Diagram::validate`, which needs every half-edge
+    /// to have an origin.
+    ///
+    /// The sites still have to be inside the clip region, and the metric is
+    /// always Euclidean.
+    pub fn finish_unclipped(mut self) -> Diagram<T> {
+        self.sweep_all();
+
+        let (offset, scale) = (self.offset, self.scale);
+        let mut diagram = self.diagram;
+        for vertex in &mut diagram.vertices {
+            vertex.coordinates = offset + vertex.coordinates.to_vec() / scale;
+        }
+
+        diagram.index_sites(false);
+        diagram
     }
 }
@@ -0,0 +1,156 @@
+//! Renumbering a diagram into an order that only depends on what it looks like
+//!
+//! The builder creates vertices and half-edges in the order the sweep reaches
+//! them, so rounding differences or sites that were given in another order can
+//! change every id without changing the diagram. Canonical ids follow from the
+//! sites and the coordinates of the corners alone.
+
+use std::cmp::Ordering;
+
+use cgmath::BaseFloat;
+
+use build::{compare, Site};
+use diagram::{Diagram, HalfEdgeId, FaceId, VertexId};
+
+fn compare_sites<T: BaseFloat>(a: &Site<T>, b: &Site<T>) -> Ordering {
+    a.id.cmp(&b.id)
+        .then(compare(a.position.y, b.position.y))
+        .then(compare(a.position.x, b.position.x))
+        .then(compare(a.weight, b.weight))
+}
+
+/// Orders half-edges by where they start, lowest first and then leftmost. Ones
+/// without an origin come last
+fn compare_origins<T: BaseFloat>(diagram: &Diagram<T>, a: HalfEdgeId, b: HalfEdgeId) -> Ordering {
+    let coordinates = |halfedge_id: HalfEdgeId| {
+        diagram.halfedges[halfedge_id.0 as usize].origin.map(|origin| diagram.vertices[origin.0 as usize].coordinates)
+    };
+    match (coordinates(a), coordinates(b)) {
+        (Some(a), Some(b)) => compare(a.y, b.y).then(compare(a.x, b.x)),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => Ordering::Equal,
+    }
+}
+
+/// Where each item goes, given the old indices in their new order
+fn positions(order: &[usize]) -> Vec<u32> {
+    let mut positions = vec![0; order.len()];
+    for (position, &index) in order.iter().enumerate() {
+        positions[index] = position as u32;
+    }
+    positions
+}
+
+/// See `Diagram::canonicalize`
+pub fn canonicalize<T: BaseFloat>(diagram: &mut Diagram<T>) {
+    let mut face_order = (0..diagram.faces.len()).collect::<Vec<_>>();
+    face_order.sort_by(|&a, &b| compare_sites(&diagram.faces[a].site, &diagram.faces[b].site));
+
+    // Half-edges go face by face, each face starting from its lowest corner. The
+    // walk is cut short in case the links don't come back round
+    let mut halfedge_order = Vec::with_capacity(diagram.halfedges.len());
+    let mut placed = vec![false; diagram.halfedges.len()];
+    let mut first_halfedges = vec![None; diagram.faces.len()];
+    for &face in &face_order {
+        let cycle = diagram.face_edges(FaceId(face as u32)).take(diagram.halfedges.len()).collect::<Vec<_>>();
+        let start = match (0..cycle.len()).min_by(|&a, &b| compare_origins(diagram, cycle[a], cycle[b])) {
+            Some(start) => start,
+            None => continue,
+        };
+
+        first_halfedges[face] = Some(cycle[start]);
+        for offset in 0..cycle.len() {
+            let halfedge_id = cycle[(start + offset) % cycle.len()];
+            if !placed[halfedge_id.0 as usize] {
+                placed[halfedge_id.0 as usize] = true;
+                halfedge_order.push(halfedge_id.0 as usize);
+            }
+        }
+    }
+    halfedge_order.extend((0..diagram.halfedges.len()).filter(|&index| !placed[index]));
+
+    // Vertices go in the order that the half-edges first leave them, and each one
+    // keeps that half-edge
+    let mut vertex_order = Vec::with_capacity(diagram.vertices.len());
+    let mut incident_edges = vec![None; diagram.vertices.len()];
+    for &index in &halfedge_order {
+        if let Some(origin) = diagram.halfedges[index].origin {
+            let incident_edge = &mut incident_edges[origin.0 as usize];
+            if incident_edge.is_none() {
+                *incident_edge = Some(HalfEdgeId(index as u32));
+                vertex_order.push(origin.0 as usize);
+            }
+        }
+    }
+    vertex_order.extend((0..diagram.vertices.len()).filter(|&index| incident_edges[index].is_none()));
+
+    let face_ids = positions(&face_order);
+    let halfedge_ids = positions(&halfedge_order);
+    let vertex_ids = positions(&vertex_order);
+    let face_id = |face_id: FaceId| FaceId(face_ids[face_id.0 as usize]);
+    let halfedge_id = |halfedge_id: HalfEdgeId| HalfEdgeId(halfedge_ids[halfedge_id.0 as usize]);
+    let vertex_id = |vertex_id: VertexId| VertexId(vertex_ids[vertex_id.0 as usize]);
+
+    let vertices = vertex_order.iter().map(|&index| {
+        let mut vertex = diagram.vertices[index].clone();
+        vertex.incident_edge = halfedge_id(incident_edges[index].unwrap_or(vertex.incident_edge));
+        vertex
+    }).collect();
+    let halfedges = halfedge_order.iter().map(|&index| {
+        let mut halfedge = diagram.halfedges[index].clone();
+        halfedge.origin = halfedge.origin.map(vertex_id);
+        halfedge.twin = halfedge.twin.map(halfedge_id);
+        halfedge.incident_face = face_id(halfedge.incident_face);
+        halfedge.next = halfedge_id(halfedge.next);
+        halfedge.prev = halfedge_id(halfedge.prev);
+        halfedge
+    }).collect();
+    let faces = face_order.iter().map(|&index| {
+        let mut face = diagram.faces[index].clone();
+        face.first_halfedge = first_halfedges[index].or(face.first_halfedge).map(halfedge_id);
+        face
+    }).collect();
+
+    diagram.vertices = vertices;
+    diagram.halfedges = halfedges;
+    diagram.faces = faces;
+
+    let weighted = diagram.site_index.is_weighted();
+    diagram.index_sites(weighted);
+}
+
+/// The face on the other side of each edge around a face, from its first
+/// half-edge, and whether the edge has an origin
+fn surroundings<T: BaseFloat>(diagram: &Diagram<T>, face: usize) -> Vec<(Option<u32>, bool)> {
+    diagram.face_edges(FaceId(face as u32))
+        .take(diagram.halfedges.len())
+        .map(|halfedge_id| {
+            let halfedge = &diagram.halfedges[halfedge_id.0 as usize];
+            let neighbour = halfedge.twin.map(|twin| diagram.halfedges[twin.0 as usize].incident_face.0);
+            (neighbour, halfedge.origin.is_some())
+        })
+        .collect()
+}
+
+fn equal_up_to_rotation<X: PartialEq>(a: &[X], b: &[X]) -> bool {
+    a.len() == b.len() && (a.is_empty() || (0..b.len()).any(|start| {
+        a.iter().enumerate().all(|(index, item)| *item == b[(start + index) % b.len()])
+    }))
+}
+
+/// See `Diagram::eq_topology`
+pub fn eq_topology<T: BaseFloat>(a: &Diagram<T>, b: &Diagram<T>) -> bool {
+    if a.vertices.len() != b.vertices.len() || a.halfedges.len() != b.halfedges.len() || a.faces.len() != b.faces.len() {
+        return false;
+    }
+
+    let (mut a, mut b) = (a.clone(), b.clone());
+    canonicalize(&mut a);
+    canonicalize(&mut b);
+
+    // Which corner of a face is lowest depends on the coordinates, so the edges
+    // around each face are compared from every starting point
+    a.faces.iter().zip(&b.faces).all(|(face, other)| face.site.id == other.site.id)
+        && (0..a.faces.len()).all(|face| equal_up_to_rotation(&surroundings(&a, face), &surroundings(&b, face)))
+}
@@ -0,0 +1,130 @@
+use cgmath::{Point2, BaseFloat};
+use fnv::FnvHashMap;
+
+use build::Site;
+use dedup::SiteSet;
+use diagram::{Diagram, Vertex, VertexId, HalfEdge, HalfEdgeId, Face, FaceId};
+
+/// What the edge of a cell runs along
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Bound {
+    /// The edge between the cell and the cell of another face
+    Site(u32),
+
+    /// A side of the clip region, by the index of the corner it starts at
+    Side(usize),
+}
+
+/// Builds a diagram from the outlines of its cells, which are worked out one at a
+/// time
+///
+/// Each edge between two cells is found from both sides, so its ends are only
+/// joined up if they are within a tolerance of each other.
+pub struct CellAssembler<T> {
+    diagram: Diagram<T>,
+    vertex_set: SiteSet<f64>,
+    vertices: Vec<Point2<f64>>,
+
+    /// What each half-edge runs along
+    bounds: Vec<Bound>,
+
+    /// Half-edges by their ends and face. A cell can be a lens between two
+    /// vertices, so the ends alone aren't enough to find a half-edge
+    edges: FnvHashMap<(VertexId, VertexId, FaceId), HalfEdgeId>,
+}
+
+impl<T: BaseFloat> CellAssembler<T> {
+    pub fn new(tolerance: f64) -> CellAssembler<T> {
+        CellAssembler {
+            diagram: Diagram::default(),
+            vertex_set: SiteSet::new(tolerance),
+            vertices: Vec::new(),
+            bounds: Vec::new(),
+            edges: FnvHashMap::default(),
+        }
+    }
+
+    fn add_vertex(&mut self, position: Point2<f64>) -> VertexId {
+        if let Some(id) = self.vertex_set.find(position) {
+            return VertexId(id);
+        }
+
+        let id = self.vertices.len() as u32;
+        self.vertex_set.insert(position, id);
+        self.vertices.push(position);
+        VertexId(id)
+    }
+
+    /// Adds the next face, given the corners of its cell in counter-clockwise order
+    /// along with what the edge from each one runs along
+    ///
+    /// Edges too short to have two different vertices are left out, and a cell
+    /// without any corners gives a face with no half-edges.
+    pub fn add_cell(&mut self, site: Site<T>, corners: &[(Point2<f64>, Bound)]) {
+        let face_id = FaceId(self.diagram.faces.len() as u32);
+        let corners = corners.iter()
+            .map(|&(position, bound)| (self.add_vertex(position), bound))
+            .collect::<Vec<_>>();
+
+        let first_halfedge = self.diagram.halfedges.len() as u32;
+        for (index, &(origin, bound)) in corners.iter().enumerate() {
+            let destination = corners[(index + 1) % corners.len()].0;
+            if origin == destination {
+                continue;
+            }
+
+            let halfedge_id = HalfEdgeId(self.diagram.halfedges.len() as u32);
+            self.diagram.halfedges.push(HalfEdge {
+                origin: Some(origin),
+                twin: None,
+                incident_face: face_id,
+                next: halfedge_id,
+                prev: halfedge_id,
+            });
+            self.bounds.push(bound);
+            self.edges.insert((origin, destination, face_id), halfedge_id);
+        }
+
+        let last_halfedge = self.diagram.halfedges.len() as u32;
+        for halfedge in first_halfedge..last_halfedge {
+            let next = if halfedge + 1 == last_halfedge { first_halfedge } else { halfedge + 1 };
+            self.diagram.halfedges[halfedge as usize].next = HalfEdgeId(next);
+            self.diagram.halfedges[next as usize].prev = HalfEdgeId(halfedge);
+        }
+
+        self.diagram.faces.push(Face {
+            first_halfedge: if first_halfedge < last_halfedge { Some(HalfEdgeId(first_halfedge)) } else { None },
+            site,
+        });
+    }
+
+    /// Joins each half-edge between two cells to its twin and converts the vertices
+    /// to the coordinates of the diagram
+    ///
+    /// The sites of the diagram are left for the caller to index. Every half-edge
+    /// comes from `add_cell` and starts at one of its corners, so all of them have
+    /// an origin.
+    pub fn finish<F: Fn(Point2<f64>) -> Point2<T>>(self, convert: F) -> Diagram<T> {
+        let mut diagram = self.diagram;
+        for (index, &bound) in self.bounds.iter().enumerate() {
+            if let Bound::Site(other) = bound {
+                let origin = diagram.halfedges[index].origin.unwrap();
+                let destination = diagram.halfedges[diagram.halfedges[index].next.0 as usize].origin.unwrap();
+                diagram.halfedges[index].twin = self.edges.get(&(destination, origin, FaceId(other))).cloned();
+            }
+        }
+
+        let mut incident_edges = vec![HalfEdgeId(0); self.vertices.len()];
+        for (index, halfedge) in diagram.halfedges.iter().enumerate() {
+            incident_edges[halfedge.origin.unwrap().0 as usize] = HalfEdgeId(index as u32);
+        }
+        diagram.vertices = self.vertices.into_iter().zip(incident_edges).map(|(position, incident_edge)| {
+            Vertex {
+                coordinates: convert(position),
+                incident_edge,
+            }
+        }).collect();
+
+        diagram
+    }
+}
@@ -0,0 +1,880 @@
+use std::cmp::Ordering;
+use std::iter;
+use std::mem;
+
+use cgmath::{Point2, Vector2, EuclideanSpace, InnerSpace, BaseFloat};
+use cgmath::num_traits::cast;
+use fnv::FnvHashMap;
+
+use diagram::{Diagram, Vertex, VertexId, HalfEdge, HalfEdgeId, Face, FaceId};
+use predicates::{orient2d, compare_crossings};
+
+/// Which side of the boundary edge from `a` along `edge` a point is on
+///
+/// This is positive on the inside, which is to the left as the boundary runs
+/// counter-clockwise.
+fn edge_side<T: BaseFloat>(a: Point2<T>, edge: Vector2<T>, point: Point2<T>) -> T {
+    edge.x * (point.y - a.y) - edge.y * (point.x - a.x)
+}
+
+fn boundary_edges<'b, T: BaseFloat>(boundary: &'b [Point2<T>]) -> impl Iterator<Item = (Point2<T>, Vector2<T>)> + 'b {
+    boundary.iter().enumerate().map(move |(index, &a)| (a, boundary[(index + 1) % boundary.len()] - a))
+}
+
+/// Whether a point is inside a boundary or on it
+///
+/// The boundary is a convex polygon given by its corners in counter-clockwise
+/// order.
+pub fn inside_boundary<T: BaseFloat>(boundary: &[Point2<T>], point: Point2<T>) -> bool {
+    boundary.len() >= 3 && boundary_edges(boundary).all(|(a, edge)| edge_side(a, edge, point) >= T::zero())
+}
+
+/// Whether a point is inside a boundary and not on it
+pub fn strictly_inside_boundary<T: BaseFloat>(boundary: &[Point2<T>], point: Point2<T>) -> bool {
+    boundary.len() >= 3 && boundary_edges(boundary).all(|(a, edge)| edge_side(a, edge, point) > T::zero())
+}
+
+/// Whether a boundary given by its corners in counter-clockwise order is convex
+///
+/// It has to turn left or go straight on at every corner, and only go round once.
+/// Turns to the right small enough to come from rounding the corners count as
+/// going straight on.
+pub fn is_convex_boundary<T: BaseFloat>(boundary: &[Point2<T>]) -> bool {
+    let edges = boundary_edges(boundary).map(|(_, edge)| edge).collect::<Vec<_>>();
+    let mut turning = T::zero();
+    for (index, &edge) in edges.iter().enumerate() {
+        let next = edges[(index + 1) % edges.len()];
+        let tolerance = edge.magnitude() * next.magnitude() * T::epsilon() * cast(16.0).unwrap();
+        if edge.perp_dot(next) < -tolerance {
+            return false;
+        }
+        turning += edge.perp_dot(next).atan2(edge.dot(next));
+    }
+    turning < cast(3.0 * ::std::f64::consts::PI).unwrap()
+}
+
+/// The point strictly inside a boundary that is nearest to a point outside it
+///
+/// This is the nearest point on the boundary, moved a little towards the middle.
+/// Returns None if that still isn't inside, which only happens for a boundary
+/// without any area or a point that isn't finite.
+pub fn clamp_to_boundary<T: BaseFloat>(boundary: &[Point2<T>], point: Point2<T>) -> Option<Point2<T>> {
+    if !point.x.is_finite() || !point.y.is_finite() {
+        return None;
+    }
+
+    let nearest = boundary_edges(boundary).map(|(a, edge)| {
+        let t = ((point - a).dot(edge) / edge.magnitude2()).max(T::zero()).min(T::one());
+        a + edge * t
+    }).fold(None, |nearest: Option<Point2<T>>, candidate| match nearest {
+        Some(nearest) if (nearest - point).magnitude2() <= (candidate - point).magnitude2() => Some(nearest),
+        _ => Some(candidate),
+    })?;
+
+    let count = cast::<usize, T>(boundary.len()).unwrap();
+    let middle = boundary.iter().fold(Point2::origin(), |sum, corner| sum + corner.to_vec() / count);
+    let clamped = nearest + (middle - nearest) * T::epsilon().sqrt();
+    if strictly_inside_boundary(boundary, clamped) { Some(clamped) } else { None }
+}
+
+/// Corners of the rectangle from the origin to `size`, in counter-clockwise order
+pub fn rect_boundary<T: BaseFloat>(size: Vector2<T>) -> Vec<Point2<T>> {
+    vec![
+        Point2::new(T::zero(), T::zero()),
+        Point2::new(size.x, T::zero()),
+        Point2::new(size.x, size.y),
+        Point2::new(T::zero(), size.y),
+    ]
+}
+
+/// Clips a line segment against a boundary
+///
+/// The segment covers `start + direction * t` for t between `t_min` and `t_max`,
+/// either of which may be infinite. Returns the range of t that lies inside the
+/// boundary, or None if the segment misses it.
+fn clip_segment<T: BaseFloat>(start: Point2<T>, direction: Vector2<T>, mut t_min: T, mut t_max: T, boundary: &[Point2<T>]) -> Option<(T, T)> {
+    // Cyrus-Beck, which is Liang-Barsky for any convex polygon
+    for (a, edge) in boundary_edges(boundary) {
+        let q = edge_side(a, edge, start);
+        let p = edge.x * direction.y - edge.y * direction.x;
+
+        if p == T::zero() {
+            // Parallel to this edge
+            if q < T::zero() {
+                return None;
+            }
+        } else if p > T::zero() {
+            // Entering
+            t_min = t_min.max(-q / p);
+        } else {
+            // Leaving
+            t_max = t_max.min(-q / p);
+        }
+    }
+
+    if t_min < t_max {
+        Some((t_min, t_max))
+    } else {
+        None
+    }
+}
+
+struct Clipper<'a, T: 'a> {
+    input: &'a Diagram<T>,
+    sites: &'a [Point2<T>],
+    weights: Option<&'a [T]>,
+    boundary: &'a [Point2<T>],
+
+    /// Distance around the boundary from the first corner to each of the others,
+    /// followed by the length of the whole boundary
+    corner_positions: Vec<T>,
+
+    /// Vertices of the output, which get their incident edges once all the
+    /// half-edges are there
+    vertices: Vec<Vertex<T>>,
+    halfedges: Vec<HalfEdge>,
+    faces: Vec<Face<T>>,
+
+    /// Vertex ids in the input diagram mapped to ids in the output
+    vertex_map: Vec<Option<VertexId>>,
+}
+
+impl<'a, T: BaseFloat> Clipper<'a, T> {
+    /// Power distance from a point to the site of a face, which is the squared
+    /// distance for unweighted sites
+    fn power_distance(&self, face: FaceId, point: Point2<T>) -> T {
+        let weight = self.weights.map_or(T::zero(), |weights| weights[face.0 as usize]);
+        (point - self.sites[face.0 as usize]).magnitude2() - weight
+    }
+
+    /// Position of a point on the boundary, measured counter-clockwise from the
+    /// first corner
+    fn perimeter_position(&self, point: Point2<T>) -> T {
+        // The point may be slightly off the boundary, so pick the closest edge
+        let closest = boundary_edges(self.boundary).enumerate().fold(None, |closest: Option<(T, T)>, (index, (a, edge))| {
+            let length = edge.magnitude();
+            let along = ((point - a).dot(edge) / (length * length)).max(T::zero()).min(T::one());
+            let distance = (a + edge * along - point).magnitude();
+            let position = self.corner_positions[index] + along * length;
+
+            match closest {
+                Some((closest_distance, _)) if closest_distance <= distance => closest,
+                _ => Some((distance, position)),
+            }
+        });
+
+        closest.map_or(T::zero(), |(_, position)| position)
+    }
+
+    fn add_vertex(&mut self, coordinates: Point2<T>) -> VertexId {
+        let vertex_id = VertexId(self.vertices.len() as u32);
+        self.vertices.push(Vertex {
+            coordinates,
+            incident_edge: HalfEdgeId(0),
+        });
+        vertex_id
+    }
+
+    fn map_vertex(&mut self, vertex_id: VertexId) -> VertexId {
+        if let Some(mapped) = self.vertex_map[vertex_id.0 as usize] {
+            return mapped;
+        }
+
+        let coordinates = self.input.vertices[vertex_id.0 as usize].coordinates;
+        let mapped = self.add_vertex(coordinates);
+        self.vertex_map[vertex_id.0 as usize] = Some(mapped);
+        mapped
+    }
+
+    fn link_halfedges(&mut self, halfedge: HalfEdgeId, next: HalfEdgeId) {
+        self.halfedges[halfedge.0 as usize].next = next;
+        self.halfedges[next.0 as usize].prev = halfedge;
+    }
+
+    fn add_boundary_halfedge(&mut self, origin: VertexId, face: FaceId) -> HalfEdgeId {
+        let halfedge = HalfEdgeId(self.halfedges.len() as u32);
+        self.halfedges.push(HalfEdge {
+            origin: Some(origin),
+            twin: None,
+            incident_face: face,
+            next: halfedge,
+            prev: halfedge,
+        });
+        halfedge
+    }
+
+    /// Adds half-edges that run counter-clockwise along the boundary between two
+    /// vertices that lie on it, inserting vertices at any corners passed
+    ///
+    /// If both vertices are the same, the chain goes all the way around.
+    ///
+    /// Returns the first and last half-edges of the chain.
+    fn add_boundary_chain(&mut self, from: VertexId, to: VertexId, face: FaceId) -> (HalfEdgeId, HalfEdgeId) {
+        let perimeter = self.corner_positions[self.boundary.len()];
+        let start = self.perimeter_position(self.vertices[from.0 as usize].coordinates);
+        let mut distance = self.perimeter_position(self.vertices[to.0 as usize].coordinates) - start;
+        if distance < T::zero() || from == to {
+            distance += perimeter;
+        }
+
+        let mut corners = self.boundary.iter().zip(&self.corner_positions).filter_map(|(&corner, &position)| {
+            let mut offset = position - start;
+            if offset < T::zero() {
+                offset += perimeter;
+            }
+
+            if offset > T::zero() && offset < distance {
+                Some((offset, corner))
+            } else {
+                None
+            }
+        }).collect::<Vec<_>>();
+        corners.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+
+        let first = self.add_boundary_halfedge(from, face);
+        let mut last = first;
+        for (_, corner) in corners {
+            let vertex = self.add_vertex(corner);
+            let halfedge = self.add_boundary_halfedge(vertex, face);
+            self.link_halfedges(last, halfedge);
+            last = halfedge;
+        }
+
+        (first, last)
+    }
+
+    fn clip_edges(&mut self) {
+        let input = self.input;
+        let mut halfedge_map = vec![None; input.halfedges.len()];
+
+        for (index, halfedge) in input.halfedges.iter().enumerate() {
+            let twin = halfedge.twin.unwrap();
+
+            // Each edge is clipped once, from its lower indexed half-edge
+            if twin.0 < index as u32 {
+                continue;
+            }
+
+            let twin_halfedge = &input.halfedges[twin.0 as usize];
+            let origin = halfedge.origin.map(|vertex| input.vertices[vertex.0 as usize].coordinates);
+            let destination = twin_halfedge.origin.map(|vertex| input.vertices[vertex.0 as usize].coordinates);
+
+            // Vertices inside the boundary are kept as they are. This is decided for
+            // each vertex rather than each edge so that all edges meeting at a vertex
+            // agree
+            let boundary = self.boundary;
+            let inside = |point: Point2<T>| inside_boundary(boundary, point);
+            let inside_origin = halfedge.origin.filter(|_| origin.is_some_and(inside));
+            let inside_destination = twin_halfedge.origin.filter(|_| destination.is_some_and(inside));
+
+            // The edge runs along the bisector of the two sites with the half-edge's
+            // site on its left. Nearly collinear sites can put vertices so far away
+            // that they are too imprecise to find where the edge crosses the
+            // boundary, so it is clipped along the bisector instead, with each
+            // vertex projected onto it
+            let site = self.sites[halfedge.incident_face.0 as usize];
+            let twin_site = self.sites[twin_halfedge.incident_face.0 as usize];
+            let direction = Vector2::new(site.y - twin_site.y, twin_site.x - site.x);
+
+            // Weighted sites are separated by the line where their power distances are
+            // equal instead, which is parallel to the bisector but shifted towards
+            // the site with the smaller weight
+            let mut start = site.midpoint(twin_site);
+            if let Some(weights) = self.weights {
+                let offset = twin_site - site;
+                let weight_difference = weights[halfedge.incident_face.0 as usize] - weights[twin_halfedge.incident_face.0 as usize];
+                start += offset * (weight_difference / (offset.magnitude2() + offset.magnitude2()));
+            }
+            let project = |point: Point2<T>| (point - start).dot(direction) / direction.magnitude2();
+
+            let t_min = origin.map_or(T::neg_infinity(), project);
+            let t_max = destination.map_or(T::infinity(), project);
+            let clipped = clip_segment(start, direction, t_min, t_max, boundary);
+
+            let (origin, destination) = match (inside_origin, inside_destination, clipped) {
+                (Some(origin), Some(destination), _) => {
+                    (self.map_vertex(origin), self.map_vertex(destination))
+                }
+                (Some(origin), None, clipped) => {
+                    let t = clipped.map_or(t_min, |range| range.1);
+                    (self.map_vertex(origin), self.add_vertex(start + direction * t))
+                }
+                (None, Some(destination), clipped) => {
+                    let t = clipped.map_or(t_max, |range| range.0);
+                    (self.add_vertex(start + direction * t), self.map_vertex(destination))
+                }
+                (None, None, Some((clipped_min, clipped_max))) => {
+                    (self.add_vertex(start + direction * clipped_min), self.add_vertex(start + direction * clipped_max))
+                }
+                (None, None, None) => continue,
+            };
+
+            let new_halfedge = HalfEdgeId(self.halfedges.len() as u32);
+            let new_twin = HalfEdgeId(new_halfedge.0 + 1);
+            for &(halfedge, twin, origin, face) in &[(new_halfedge, new_twin, origin, halfedge.incident_face), (new_twin, new_halfedge, destination, twin_halfedge.incident_face)] {
+                self.halfedges.push(HalfEdge {
+                    origin: Some(origin),
+                    twin: Some(twin),
+                    incident_face: face,
+                    next: halfedge,
+                    prev: halfedge,
+                });
+            }
+
+            halfedge_map[index] = Some(new_halfedge);
+            halfedge_map[twin.0 as usize] = Some(new_twin);
+        }
+
+        // If no edges cross the boundary, it lies entirely in one face. This is the
+        // face of the only site for an ordinary diagram, but in a power diagram it's
+        // the face closest to any point inside the boundary
+        let filled_face = if self.halfedges.is_empty() && !self.boundary.is_empty() {
+            let centre = Point2::centroid(self.boundary);
+            (0..input.faces.len() as u32).map(FaceId).fold(None, |closest: Option<FaceId>, face| {
+                match closest {
+                    Some(closest) if self.power_distance(closest, centre) <= self.power_distance(face, centre) => Some(closest),
+                    _ => Some(face),
+                }
+            })
+        } else {
+            None
+        };
+
+        // Relink the face cycles, closing the gaps left by clipped edges with
+        // half-edges along the boundary
+        for (face_index, face) in input.faces.iter().enumerate() {
+            let face_id = FaceId(face_index as u32);
+
+            let mut cycle = Vec::new();
+            if let Some(first_halfedge) = face.first_halfedge {
+                let mut halfedge = first_halfedge;
+                loop {
+                    if let Some(new_halfedge) = halfedge_map[halfedge.0 as usize] {
+                        cycle.push(new_halfedge);
+                    }
+
+                    halfedge = input.halfedges[halfedge.0 as usize].next;
+                    if halfedge == first_halfedge {
+                        break;
+                    }
+                }
+            }
+
+            if cycle.is_empty() {
+                if filled_face != Some(face_id) {
+                    // The face doesn't reach inside the boundary
+                    self.faces.push(Face {
+                        first_halfedge: None,
+                        site: face.site,
+                    });
+                    continue;
+                }
+
+                let corner = self.add_vertex(self.boundary[0]);
+                let (first, last) = self.add_boundary_chain(corner, corner, face_id);
+                self.link_halfedges(last, first);
+                self.faces.push(Face {
+                    first_halfedge: Some(first),
+                    site: face.site,
+                });
+                continue;
+            }
+
+            // Every half-edge made above starts at a vertex inside the boundary, and
+            // has the other half of its edge as its twin
+            for (index, &halfedge) in cycle.iter().enumerate() {
+                let next = cycle[(index + 1) % cycle.len()];
+                let destination = self.halfedges[self.halfedges[halfedge.0 as usize].twin.unwrap().0 as usize].origin.unwrap();
+                let next_origin = self.halfedges[next.0 as usize].origin.unwrap();
+
+                if destination == next_origin {
+                    self.link_halfedges(halfedge, next);
+                } else {
+                    let (first, last) = self.add_boundary_chain(destination, next_origin, face_id);
+                    self.link_halfedges(halfedge, first);
+                    self.link_halfedges(last, next);
+                }
+            }
+
+            self.faces.push(Face {
+                first_halfedge: Some(cycle[0]),
+                site: face.site,
+            });
+        }
+    }
+}
+
+/// Clips every face of a diagram to a boundary
+///
+/// The boundary is a convex polygon given by its corners in counter-clockwise
+/// order. `sites` holds the position of the site of each face, which gives the
+/// direction of edges that extend to infinity. For power diagrams, `weights` holds
+/// the weight of each site, scaled the same way as the positions.
+///
+/// Edges outside the boundary are removed, edges crossing it are cut short and the
+/// gaps this leaves in each face are closed with half-edges along the boundary.
+/// These boundary half-edges have no twin. Vertices outside the boundary are
+/// removed and the remaining ids are renumbered. Faces that don't reach inside the
+/// boundary are left without any half-edges.
+///
+/// The next pointers of the input don't need to join up outside the boundary, as
+/// the gaps between them are closed along it.
+pub fn clip_to_boundary<T: BaseFloat>(diagram: &Diagram<T>, sites: &[Point2<T>], weights: Option<&[T]>, boundary: &[Point2<T>]) -> Diagram<T> {
+    let mut output = Diagram::default();
+    clip_to_boundary_into(diagram, sites, weights, boundary, &mut output);
+    output
+}
+
+/// Same as `clip_to_boundary`, but replaces the contents of `output` so the memory
+/// it already has can be used again. Its site index is left empty
+pub fn clip_to_boundary_into<T: BaseFloat>(diagram: &Diagram<T>, sites: &[Point2<T>], weights: Option<&[T]>, boundary: &[Point2<T>], output: &mut Diagram<T>) {
+    let mut corner_positions = vec![T::zero()];
+    for (_, edge) in boundary_edges(boundary) {
+        let position = *corner_positions.last().unwrap() + edge.magnitude();
+        corner_positions.push(position);
+    }
+
+    let mut clipper = Clipper {
+        input: diagram,
+        sites,
+        weights,
+        boundary,
+        corner_positions,
+        vertices: mem::take(&mut output.vertices),
+        halfedges: mem::take(&mut output.halfedges),
+        faces: mem::take(&mut output.faces),
+        vertex_map: vec![None; diagram.vertices.len()],
+    };
+    clipper.vertices.clear();
+    clipper.halfedges.clear();
+    clipper.faces.clear();
+
+    clipper.clip_edges();
+
+    // Point each vertex at one of its outgoing half-edges, which all clipped
+    // half-edges have
+    for (index, halfedge) in clipper.halfedges.iter().enumerate() {
+        clipper.vertices[halfedge.origin.unwrap().0 as usize].incident_edge = HalfEdgeId(index as u32);
+    }
+
+    output.vertices = clipper.vertices;
+    output.halfedges = clipper.halfedges;
+    output.faces = clipper.faces;
+    output.site_index.rebuild(iter::empty(), false);
+}
+
+/// Sign of the side of the line from `a` to `b` that a point moved by (ε, ε²) for
+/// a tiny ε ends up on, if it started on the line
+fn perturbation_side(a: Point2<f64>, b: Point2<f64>) -> f64 {
+    let (dx, dy) = (b.x - a.x, b.y - a.y);
+    if dy != 0.0 { -dy } else { dx }
+}
+
+/// Which side of the edge of a face from `a` to `b` a corner of the polygon is on
+///
+/// The polygon is treated as if it were moved by a tiny amount, so that none of its
+/// corners lie exactly on an edge of the face and none of the face's vertices lie
+/// exactly on an edge of the polygon. This settles every touching case the same
+/// way for both faces next to an edge.
+fn polygon_corner_side(a: Point2<f64>, b: Point2<f64>, corner: Point2<f64>) -> f64 {
+    let side = orient2d(a, b, corner);
+    if side != 0.0 { side } else { perturbation_side(a, b) }
+}
+
+/// Which side of the edge of the polygon from `u` to `v` a vertex of a face is on,
+/// with the polygon moved the same way as in `polygon_corner_side`
+fn vertex_side(u: Point2<f64>, v: Point2<f64>, vertex: Point2<f64>) -> f64 {
+    let side = orient2d(u, v, vertex);
+    if side != 0.0 { side } else { -perturbation_side(u, v) }
+}
+
+/// A corner of a piece of a face clipped to a polygon
+///
+/// Corners are identified by where they came from rather than by their position, so
+/// that the pieces on either side of an edge agree on the corners they share.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum PieceCorner {
+    Vertex(VertexId),
+    PolygonCorner(usize),
+
+    /// Where an edge of the polygon crosses an edge of the diagram, which is given
+    /// by the lower id of its two half-edges
+    Crossing(usize, HalfEdgeId),
+}
+
+/// Where an edge of the polygon crosses the boundary of a face
+#[derive(Debug, Clone, Copy)]
+struct Crossing {
+    polygon_edge: usize,
+
+    /// Index of the crossed edge in the cycle of the face
+    face_edge: usize,
+
+    /// Whether the polygon enters the face here, rather than leaving it
+    entering: bool,
+
+    corner: PieceCorner,
+    position: Point2<f64>,
+}
+
+/// Intersects a face with a polygon, returning the corners of each piece
+///
+/// The face must be convex, apart from rounding, and the polygon must be simple
+/// with its corners in counter-clockwise order. Pieces are found with the
+/// Weiler-Atherton algorithm: starting where the polygon enters the face, follow
+/// the polygon until it leaves, then follow the boundary of the face until the
+/// polygon enters again, and repeat until back at the start.
+fn clip_face_to_polygon<T: BaseFloat>(diagram: &Diagram<T>, merged: &[VertexId], face_id: FaceId, polygon: &[Point2<f64>]) -> Vec<Vec<(PieceCorner, Point2<f64>)>> {
+    let mut halfedges = Vec::new();
+    let mut vertex_ids = Vec::new();
+    for halfedge in diagram.face_edges(face_id) {
+        match diagram.halfedges[halfedge.0 as usize].origin {
+            Some(vertex) => {
+                halfedges.push(halfedge);
+                vertex_ids.push(merged[vertex.0 as usize]);
+            }
+            None => return Vec::new(),
+        }
+    }
+
+    // Leave out edges between vertices that were merged
+    let mut index = 0;
+    while index < vertex_ids.len() && vertex_ids.len() > 1 {
+        if vertex_ids[index] == vertex_ids[(index + 1) % vertex_ids.len()] {
+            halfedges.remove(index);
+            vertex_ids.remove(index);
+        } else {
+            index += 1;
+        }
+    }
+    if vertex_ids.len() < 3 {
+        return Vec::new();
+    }
+
+    let corners = vertex_ids.iter()
+        .map(|vertex| diagram.vertices[vertex.0 as usize].coordinates.cast::<f64>())
+        .collect::<Vec<_>>();
+    let face_edge = |index: usize| (corners[index], corners[(index + 1) % corners.len()]);
+    let polygon_edge = |index: usize| (polygon[index], polygon[(index + 1) % polygon.len()]);
+
+    // Only edges of the polygon that overlap the face's bounding box can cross it
+    let min = corners.iter().fold(corners[0], |min, corner| Point2::new(min.x.min(corner.x), min.y.min(corner.y)));
+    let max = corners.iter().fold(corners[0], |max, corner| Point2::new(max.x.max(corner.x), max.y.max(corner.y)));
+
+    let mut crossings = Vec::new();
+    for polygon_index in 0..polygon.len() {
+        let (u, v) = polygon_edge(polygon_index);
+        if u.x.max(v.x) < min.x || u.x.min(v.x) > max.x || u.y.max(v.y) < min.y || u.y.min(v.y) > max.y {
+            continue;
+        }
+
+        for (face_index, &halfedge) in halfedges.iter().enumerate() {
+            let (a, b) = face_edge(face_index);
+            if a == b {
+                continue;
+            }
+
+            let u_side = polygon_corner_side(a, b, u);
+            let v_side = polygon_corner_side(a, b, v);
+            if (u_side > 0.0) == (v_side > 0.0) || (vertex_side(u, v, a) > 0.0) == (vertex_side(u, v, b) > 0.0) {
+                continue;
+            }
+
+            let (u_orientation, v_orientation) = (orient2d(a, b, u), orient2d(a, b, v));
+            let t = (u_orientation / (u_orientation - v_orientation)).clamp(0.0, 1.0);
+
+            let edge = diagram.halfedges[halfedge.0 as usize].twin.map_or(halfedge, |twin| HalfEdgeId(halfedge.0.min(twin.0)));
+            crossings.push(Crossing {
+                polygon_edge: polygon_index,
+                face_edge: face_index,
+                entering: v_side > 0.0,
+                corner: PieceCorner::Crossing(polygon_index, edge),
+                position: u + (v - u) * t,
+            });
+        }
+    }
+
+    if crossings.is_empty() {
+        // Either the polygon is inside the face, the face is inside the polygon, or
+        // they don't overlap at all. A corner of the polygon moved up by ε² is above
+        // any point at the same height, and a vertex of the face moved down by ε² is
+        // below any corner of the polygon at the same height
+        let polygon_corner = polygon[0];
+        let polygon_inside = winding_number(&corners, |point| polygon_corner.y >= point.y, |a, b| polygon_corner_side(a, b, polygon_corner));
+        if polygon_inside != 0 {
+            return vec![polygon.iter().enumerate().map(|(index, &corner)| (PieceCorner::PolygonCorner(index), corner)).collect()];
+        }
+
+        let vertex = corners[0];
+        let face_inside = winding_number(polygon, |point| vertex.y > point.y, |u, v| vertex_side(u, v, vertex));
+        if face_inside != 0 {
+            return vec![vertex_ids.iter().zip(&corners).map(|(&vertex, &corner)| (PieceCorner::Vertex(vertex), corner)).collect()];
+        }
+
+        return Vec::new();
+    }
+
+    // Order the crossings along the polygon. Where several edges of the face cross
+    // an edge of the polygon at the same point, the order they would cross the
+    // moved polygon in depends on their directions. Rounding can fold an edge of
+    // the face back on itself, and the polygon enters the fold before leaving it
+    let mut along_polygon = (0..crossings.len()).collect::<Vec<_>>();
+    along_polygon.sort_by(|&first, &second| {
+        let (first, second) = (crossings[first], crossings[second]);
+        first.polygon_edge.cmp(&second.polygon_edge).then_with(|| {
+            let (u, v) = polygon_edge(first.polygon_edge);
+            let (a, b) = face_edge(first.face_edge);
+            let (c, d) = face_edge(second.face_edge);
+            compare_crossings(u, v, [a, b], [c, d]).then_with(|| {
+                let shift = |edge: Vector2<f64>| {
+                    let cross = edge.perp_dot(v - u);
+                    (edge.y / cross, -edge.x / cross)
+                };
+                shift(b - a).partial_cmp(&shift(d - c)).unwrap_or(Ordering::Equal)
+            })
+        }).then(second.entering.cmp(&first.entering))
+    });
+
+    // Order the crossings along the face, in the same way
+    let mut along_face = (0..crossings.len()).collect::<Vec<_>>();
+    along_face.sort_by(|&first, &second| {
+        let (first, second) = (crossings[first], crossings[second]);
+        first.face_edge.cmp(&second.face_edge).then_with(|| {
+            let (a, b) = face_edge(first.face_edge);
+            let (u, v) = polygon_edge(first.polygon_edge);
+            let (w, x) = polygon_edge(second.polygon_edge);
+            compare_crossings(a, b, [u, v], [w, x]).then_with(|| {
+                let shift = |edge: Vector2<f64>| {
+                    let cross = edge.perp_dot(b - a);
+                    (-edge.y / cross, edge.x / cross)
+                };
+                shift(v - u).partial_cmp(&shift(x - w)).unwrap_or(Ordering::Equal)
+            })
+        })
+    });
+
+    let next_crossing = |order: &[usize]| {
+        let mut next = vec![(0, false); crossings.len()];
+        for (position, &crossing) in order.iter().enumerate() {
+            // Whether this is the last crossing, so going on to the next one wraps around
+            let wraps = position + 1 == order.len();
+            next[crossing] = (order[(position + 1) % order.len()], wraps);
+        }
+        next
+    };
+    let next_along_polygon = next_crossing(&along_polygon);
+    let next_along_face = next_crossing(&along_face);
+
+    // Corners passed between two crossings, given by the indices of the edges they
+    // are on. If both are on the same edge and the second comes first, the path goes
+    // all the way around
+    let corners_between = |from: usize, to: usize, wraps: bool, count: usize| {
+        let mut passed = (to + count - from) % count;
+        if passed == 0 && wraps {
+            passed = count;
+        }
+        (1..=passed).map(move |offset| (from + offset) % count)
+    };
+
+    let mut pieces = Vec::new();
+    let mut used = vec![false; crossings.len()];
+    for &start in &along_polygon {
+        if !crossings[start].entering || used[start] {
+            continue;
+        }
+
+        let mut piece = Vec::new();
+        let mut entry = start;
+        loop {
+            used[entry] = true;
+            piece.push((crossings[entry].corner, crossings[entry].position));
+
+            // Follow the polygon to where it leaves the face
+            let (exit, wraps) = next_along_polygon[entry];
+            for index in corners_between(crossings[entry].polygon_edge, crossings[exit].polygon_edge, wraps, polygon.len()) {
+                piece.push((PieceCorner::PolygonCorner(index), polygon[index]));
+            }
+
+            used[exit] = true;
+            piece.push((crossings[exit].corner, crossings[exit].position));
+
+            // Follow the face to where the polygon enters it again
+            let (next, wraps) = next_along_face[exit];
+            for index in corners_between(crossings[exit].face_edge, crossings[next].face_edge, wraps, corners.len()) {
+                piece.push((PieceCorner::Vertex(vertex_ids[index]), corners[index]));
+            }
+
+            if next == start || used[next] {
+                break;
+            }
+            entry = next;
+        }
+
+        pieces.push(piece);
+    }
+
+    pieces
+}
+
+/// Winding number of a polygon around a point that is never exactly on its edges
+///
+/// `above` says whether the point is above a corner, and `side` which side of an
+/// edge the point is on. Both must settle ties the same way for every edge.
+fn winding_number<A, S>(polygon: &[Point2<f64>], above: A, side: S) -> i32
+    where A: Fn(Point2<f64>) -> bool,
+          S: Fn(Point2<f64>, Point2<f64>) -> f64
+{
+    let mut winding = 0;
+    for (index, &a) in polygon.iter().enumerate() {
+        let b = polygon[(index + 1) % polygon.len()];
+        match (above(a), above(b)) {
+            (true, false) if side(a, b) > 0.0 => winding += 1,
+            (false, true) if side(a, b) < 0.0 => winding -= 1,
+            _ => {}
+        }
+    }
+    winding
+}
+
+/// Finds which vertices to treat as one, by merging the ends of edges so short
+/// they are only there because of rounding
+///
+/// Those edges can make faces fold back on themselves slightly, which would confuse
+/// the clipping. Each vertex is mapped to the lowest id of the ones merged with it.
+fn merge_short_edges<T: BaseFloat>(diagram: &Diagram<T>) -> Vec<VertexId> {
+    fn find(merged: &mut [VertexId], vertex: VertexId) -> VertexId {
+        let parent = merged[vertex.0 as usize];
+        if parent == vertex {
+            return vertex;
+        }
+
+        let root = find(merged, parent);
+        merged[vertex.0 as usize] = root;
+        root
+    }
+
+    let mut merged = (0..diagram.vertices.len()).map(|index| VertexId(index as u32)).collect::<Vec<_>>();
+    let extent = diagram.vertices.iter().fold(T::zero(), |extent, vertex| {
+        extent.max(vertex.coordinates.x.abs()).max(vertex.coordinates.y.abs())
+    });
+    let tolerance = extent * T::epsilon() * cast(256.0).unwrap();
+
+    for halfedge in &diagram.halfedges {
+        let next = &diagram.halfedges[halfedge.next.0 as usize];
+        if let (Some(from), Some(to)) = (halfedge.origin, next.origin) {
+            let length = (diagram.vertices[to.0 as usize].coordinates - diagram.vertices[from.0 as usize].coordinates).magnitude();
+            if length <= tolerance {
+                let (from, to) = (find(&mut merged, from), find(&mut merged, to));
+                if from.0 < to.0 {
+                    merged[to.0 as usize] = from;
+                } else {
+                    merged[from.0 as usize] = to;
+                }
+            }
+        }
+    }
+
+    for index in 0..merged.len() {
+        find(&mut merged, VertexId(index as u32));
+    }
+    merged
+}
+
+/// Clips every face of a diagram to a simple polygon, which may be concave
+///
+/// The polygon's corners can be in either order. A face can be split into several
+/// pieces. The first piece keeps the face and the others are added as new faces at
+/// the end, with the same site. Faces outside the polygon are left without any
+/// half-edges. Half-edges along the polygon have no twin.
+pub fn clip_to_polygon<T: BaseFloat>(diagram: &Diagram<T>, polygon: &[Point2<T>]) -> Diagram<T> {
+    let mut polygon = polygon.iter().map(|corner| corner.cast::<f64>()).collect::<Vec<_>>();
+    polygon.dedup();
+    while polygon.len() > 1 && polygon.first() == polygon.last() {
+        polygon.pop();
+    }
+
+    // Shoelace formula
+    let twice_area = polygon.iter().enumerate().fold(0.0, |area, (index, a)| {
+        let b = polygon[(index + 1) % polygon.len()];
+        area + a.x * b.y - b.x * a.y
+    });
+    if twice_area < 0.0 {
+        polygon.reverse();
+    }
+
+    let merged = merge_short_edges(diagram);
+    let mut pieces = Vec::new();
+    let mut extra_pieces = Vec::new();
+    if polygon.len() >= 3 {
+        for face_index in 0..diagram.faces.len() {
+            let face_id = FaceId(face_index as u32);
+            let mut face_pieces = clip_face_to_polygon(diagram, &merged, face_id, &polygon).into_iter();
+            if let Some(piece) = face_pieces.next() {
+                pieces.push((face_id, piece));
+            }
+            extra_pieces.extend(face_pieces.map(|piece| (face_id, piece)));
+        }
+    }
+
+    let mut output = Diagram {
+        faces: diagram.faces.iter().map(|face| {
+            Face {
+                first_halfedge: None,
+                site: face.site,
+            }
+        }).collect(),
+        ..Diagram::default()
+    };
+
+    let mut vertex_ids = FnvHashMap::default();
+    let mut edges = FnvHashMap::default();
+    for (face_id, piece) in pieces {
+        add_piece(&mut output, &mut vertex_ids, &mut edges, face_id, &piece);
+    }
+    for (face_id, piece) in extra_pieces {
+        let new_face_id = FaceId(output.faces.len() as u32);
+        output.faces.push(Face {
+            first_halfedge: None,
+            site: diagram.faces[face_id.0 as usize].site,
+        });
+        add_piece(&mut output, &mut vertex_ids, &mut edges, new_face_id, &piece);
+    }
+
+    // Pieces next to each other share the edge between them in opposite directions
+    for (&(from, to), &halfedge) in &edges {
+        output.halfedges[halfedge.0 as usize].twin = edges.get(&(to, from)).cloned();
+    }
+
+    output.metric = diagram.metric;
+    output.index_sites(diagram.site_index.is_weighted());
+    output
+}
+
+/// Adds a piece of a face to the diagram, reusing the vertices it shares with
+/// pieces added before
+fn add_piece<T: BaseFloat>(diagram: &mut Diagram<T>, vertex_ids: &mut FnvHashMap<PieceCorner, VertexId>, edges: &mut FnvHashMap<(PieceCorner, PieceCorner), HalfEdgeId>, face_id: FaceId, piece: &[(PieceCorner, Point2<f64>)]) {
+    let first_halfedge = HalfEdgeId(diagram.halfedges.len() as u32);
+    for (index, &(corner, position)) in piece.iter().enumerate() {
+        let halfedge = HalfEdgeId(first_halfedge.0 + index as u32);
+        let vertex = *vertex_ids.entry(corner).or_insert_with(|| {
+            diagram.vertices.push(Vertex {
+                coordinates: position.cast::<T>(),
+                incident_edge: halfedge,
+            });
+            VertexId(diagram.vertices.len() as u32 - 1)
+        });
+
+        let next = (index + 1) % piece.len();
+        let prev = (index + piece.len() - 1) % piece.len();
+        diagram.halfedges.push(HalfEdge {
+            origin: Some(vertex),
+            twin: None,
+            incident_face: face_id,
+            next: HalfEdgeId(first_halfedge.0 + next as u32),
+            prev: HalfEdgeId(first_halfedge.0 + prev as u32),
+        });
+        edges.insert((corner, piece[next].0), halfedge);
+    }
+
+    diagram.faces[face_id.0 as usize].first_halfedge = Some(first_halfedge);
+}
@@ -0,0 +1,115 @@
+//! Diagrams where every cell is known to be a closed polygon
+//!
+//! A `Diagram` allows for faces without any half-edges and for half-edges that
+//! haven't got an origin yet, so most of its accessors return `Option`s. Clipping
+//! closes every cell of an ordinary diagram, and `ClosedDiagram` holds on to that
+//! so code that turns cells into meshes doesn't have to deal with the other cases.
+
+use std::error::Error;
+use std::fmt;
+
+use cgmath::{Point2, BaseFloat};
+
+use diagram::{Diagram, HalfEdgeId, FaceId, ValidationError};
+
+/// A diagram where every face is a closed polygon with at least three corners and
+/// an area above zero
+///
+/// Every half-edge has an origin and the links pass `Diagram::validate`. Methods
+/// that take an id panic if it's out of range, like indexing.
+#[derive(Debug, Clone)]
+pub struct ClosedDiagram<T = f32> {
+    diagram: Diagram<T>,
+}
+
+/// Returned when a diagram has a face that isn't a closed polygon
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NotClosedError {
+    /// The links between the vertices, half-edges and faces are broken
+    Invalid(ValidationError),
+
+    /// A face has no half-edges
+    EmptyFace(FaceId),
+
+    /// A face has fewer than three half-edges, or no area
+    DegenerateFace(FaceId),
+}
+
+impl fmt::Display for NotClosedError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            NotClosedError::Invalid(error) => write!(f, "invalid diagram: {}", error),
+            NotClosedError::EmptyFace(face) => write!(f, "face {} has no half-edges", face.0),
+            NotClosedError::DegenerateFace(face) => write!(f, "face {} isn't a polygon with any area", face.0),
+        }
+    }
+}
+
+impl Error for NotClosedError {}
+
+impl From<ValidationError> for NotClosedError {
+    fn from(error: ValidationError) -> NotClosedError {
+        NotClosedError::Invalid(error)
+    }
+}
+
+impl<T: BaseFloat> ClosedDiagram<T> {
+    /// Checks that every face of a diagram is a closed polygon
+    ///
+    /// Power, farthest-point and Apollonius diagrams can have faces without
+    /// half-edges, so they only pass if every site got a cell.
+    pub fn new(diagram: Diagram<T>) -> Result<ClosedDiagram<T>, NotClosedError> {
+        diagram.validate()?;
+
+        for (index, face) in diagram.faces.iter().enumerate() {
+            let face_id = FaceId(index as u32);
+            if face.first_halfedge.is_none() {
+                return Err(NotClosedError::EmptyFace(face_id));
+            }
+            if diagram.face_edges(face_id).nth(2).is_none() || diagram.face_area(face_id) <= T::zero() {
+                return Err(NotClosedError::DegenerateFace(face_id));
+            }
+        }
+
+        Ok(ClosedDiagram {
+            diagram,
+        })
+    }
+
+    pub fn diagram(&self) -> &Diagram<T> {
+        &self.diagram
+    }
+
+    pub fn into_diagram(self) -> Diagram<T> {
+        self.diagram
+    }
+
+    pub fn first_halfedge(&self, face_id: FaceId) -> HalfEdgeId {
+        self.diagram.faces[face_id.0 as usize].first_halfedge.unwrap()
+    }
+
+    /// Where a half-edge starts
+    pub fn origin(&self, halfedge_id: HalfEdgeId) -> Point2<T> {
+        // `new` checked that every half-edge has an origin with `validate`
+        let origin = self.diagram.halfedges[halfedge_id.0 as usize].origin.unwrap();
+        self.diagram.vertices[origin.0 as usize].coordinates
+    }
+
+    /// Where a half-edge ends, which is where the next one around its face starts
+    pub fn destination(&self, halfedge_id: HalfEdgeId) -> Point2<T> {
+        self.origin(self.diagram.halfedges[halfedge_id.0 as usize].next)
+    }
+
+    /// Corners of a face's polygon in counter-clockwise order
+    pub fn polygon(&self, face_id: FaceId) -> Vec<Point2<T>> {
+        self.diagram.face_edges(face_id).map(|halfedge_id| self.origin(halfedge_id)).collect()
+    }
+
+    pub fn face_area(&self, face_id: FaceId) -> T {
+        self.diagram.face_area(face_id)
+    }
+
+    pub fn face_centroid(&self, face_id: FaceId) -> Point2<T> {
+        self.diagram.face_centroid(face_id).unwrap()
+    }
+}
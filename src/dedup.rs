@@ -0,0 +1,140 @@
+use std::f64::consts::PI;
+
+use cgmath::{Point2, Vector2, BaseFloat};
+use cgmath::num_traits::cast;
+use fnv::FnvHashMap;
+#[cfg(feature = "serde")]
+use serde::{Serialize, Deserialize};
+
+/// The positions of the sites placed so far, for finding sites that are too close
+/// to each other
+///
+/// Positions are bucketed into a grid with cells as wide as the tolerance, so only
+/// the neighbouring cells need checking. With a tolerance of zero, only identical
+/// positions count as duplicates and the grid is keyed on the exact coordinates.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(
+    from = "SiteSetData<T>",
+    bound(serialize = "T: Serialize", deserialize = "T: BaseFloat + Deserialize<'de>"),
+))]
+pub struct SiteSet<T> {
+    tolerance: T,
+    sites: Vec<(Point2<T>, u32)>,
+
+    /// Indices into `sites` of the sites in each cell, which is left out when
+    /// serializing and filled in again from the sites
+    #[cfg_attr(feature = "serde", serde(skip_serializing))]
+    cells: FnvHashMap<(i64, i64), Vec<usize>>,
+}
+
+#[cfg(feature = "serde")]
+#[derive(Deserialize)]
+struct SiteSetData<T> {
+    tolerance: T,
+    sites: Vec<(Point2<T>, u32)>,
+}
+
+#[cfg(feature = "serde")]
+impl<T: BaseFloat> From<SiteSetData<T>> for SiteSet<T> {
+    fn from(data: SiteSetData<T>) -> SiteSet<T> {
+        let mut set = SiteSet::new(data.tolerance);
+        for (position, id) in data.sites {
+            set.insert(position, id);
+        }
+        set
+    }
+}
+
+impl<T: BaseFloat> SiteSet<T> {
+    pub fn new(tolerance: T) -> SiteSet<T> {
+        SiteSet {
+            tolerance,
+            sites: Vec::new(),
+            cells: FnvHashMap::default(),
+        }
+    }
+
+    /// Removes all the sites and changes the tolerance, keeping the memory the set
+    /// already has
+    pub fn reset(&mut self, tolerance: T) {
+        self.tolerance = tolerance;
+        self.sites.clear();
+        self.cells.clear();
+    }
+
+    fn cell(&self, position: Point2<T>) -> (i64, i64) {
+        if self.tolerance == T::zero() {
+            let x = cast::<T, f64>(position.x).unwrap().to_bits() as i64;
+            let y = cast::<T, f64>(position.y).unwrap().to_bits() as i64;
+            return (x, y);
+        }
+
+        let x = cast((position.x / self.tolerance).floor()).unwrap_or(i64::MAX);
+        let y = cast((position.y / self.tolerance).floor()).unwrap_or(i64::MAX);
+        (x, y)
+    }
+
+    /// Finds the id of a site within the tolerance of the given position
+    pub fn find(&self, position: Point2<T>) -> Option<u32> {
+        let (x, y) = self.cell(position);
+        let range = if self.tolerance == T::zero() { 0 } else { 1 };
+        let tolerance2 = self.tolerance * self.tolerance;
+
+        for cell_x in x.saturating_sub(range)..=x.saturating_add(range) {
+            for cell_y in y.saturating_sub(range)..=y.saturating_add(range) {
+                let indices = match self.cells.get(&(cell_x, cell_y)) {
+                    Some(indices) => indices,
+                    None => continue,
+                };
+
+                for &index in indices {
+                    let (other, id) = self.sites[index];
+                    let dx = other.x - position.x;
+                    let dy = other.y - position.y;
+                    if dx * dx + dy * dy <= tolerance2 {
+                        return Some(id);
+                    }
+                }
+            }
+        }
+
+        None
+    }
+
+    pub fn insert(&mut self, position: Point2<T>, id: u32) {
+        let cell = self.cell(position);
+        self.cells.entry(cell).or_default().push(self.sites.len());
+        self.sites.push((position, id));
+    }
+}
+
+/// Pseudo-random number between zero and one, from the SplitMix64 generator
+pub fn next_random(state: &mut u64) -> f64 {
+    *state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^= z >> 31;
+    (z >> 11) as f64 / (1u64 << 53) as f64
+}
+
+/// Moves a position in a random direction until `accept` returns true for it
+///
+/// The first attempt is `radius` away and each later one is twice as far as the
+/// last. Gives up and returns None after a few attempts. The direction depends
+/// only on `seed`, so the result is the same every time.
+pub fn jitter<T: BaseFloat, F: Fn(Point2<T>) -> bool>(position: Point2<T>, mut radius: T, seed: u64, accept: F) -> Option<Point2<T>> {
+    let mut state = seed;
+    for _ in 0..16 {
+        let angle: T = cast(next_random(&mut state) * 2.0 * PI).unwrap();
+        let candidate = position + Vector2::new(angle.cos(), angle.sin()) * radius;
+        if accept(candidate) {
+            return Some(candidate);
+        }
+
+        radius += radius;
+    }
+
+    None
+}
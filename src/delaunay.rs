@@ -0,0 +1,645 @@
+use cgmath::{Point2, InnerSpace, BaseFloat};
+use cgmath::num_traits::cast;
+
+use beachline::circumcircle_of_points;
+use dedup::next_random;
+use diagram::{Diagram, FaceId};
+#[cfg(feature = "io")]
+use mesh_io;
+use predicates::{incircle, orient2d};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TriangleId(pub u32);
+
+#[derive(Debug, Clone)]
+pub struct Triangle {
+    /// Indices of the corners in `Triangulation::points`, in counter-clockwise order
+    pub points: [u32; 3],
+
+    /// The triangle on the other side of the edge opposite each corner
+    pub neighbours: [Option<TriangleId>; 3],
+}
+
+/// The Delaunay triangulation of the sites, which is the dual of the Voronoi diagram
+///
+/// Points are indexed the same as the faces of the diagram built alongside it,
+/// with any added by `insert_point` after them.
+#[derive(Debug, Clone)]
+pub struct Triangulation<T = f32> {
+    pub points: Vec<Point2<T>>,
+    pub triangles: Vec<Triangle>,
+}
+
+impl<T> Default for Triangulation<T> {
+    fn default() -> Triangulation<T> {
+        Triangulation {
+            points: Vec::new(),
+            triangles: Vec::new(),
+        }
+    }
+}
+
+/// Where a walk towards a point ended up
+enum Location {
+    /// In or on the edge of a triangle
+    Inside(usize),
+
+    /// Outside every triangle
+    Outside,
+}
+
+impl<T: BaseFloat> Triangulation<T> {
+    /// Builds the triangulation from an unclipped diagram
+    ///
+    /// Each vertex of the diagram is the circumcentre of a triangle, which is given
+    /// by the faces around it in `triangles`, indexed by vertex id. Two triangles are
+    /// neighbours if their vertices are joined by an edge.
+    pub(crate) fn from_diagram(diagram: &Diagram<T>, points: Vec<Point2<T>>, triangles: &[[FaceId; 3]]) -> Triangulation<T> {
+        let mut triangles = triangles.iter().map(|corners| {
+            Triangle {
+                points: [corners[0].0, corners[1].0, corners[2].0],
+                neighbours: [None; 3],
+            }
+        }).collect::<Vec<_>>();
+
+        for halfedge in &diagram.halfedges {
+            let twin = &diagram.halfedges[halfedge.twin.unwrap().0 as usize];
+            if let (Some(origin), Some(destination)) = (halfedge.origin, twin.origin) {
+                let triangle = &mut triangles[origin.0 as usize];
+                let faces = [halfedge.incident_face.0, twin.incident_face.0];
+
+                if let Some(corner) = triangle.points.iter().position(|point| !faces.contains(point)) {
+                    triangle.neighbours[corner] = Some(TriangleId(destination.0));
+                }
+            }
+        }
+
+        Triangulation {
+            points,
+            triangles,
+        }
+    }
+
+    pub fn get_triangle(&self, triangle_id: TriangleId) -> Option<&Triangle> {
+        self.triangles.get(triangle_id.0 as usize)
+    }
+
+    fn corner_points(&self, triangle: &Triangle) -> [Point2<T>; 3] {
+        triangle.points.map(|point| self.points[point as usize])
+    }
+
+    /// The centre of the circle through the corners of each triangle, indexed by
+    /// triangle id
+    ///
+    /// These are the vertices of the Voronoi diagram of the points. None for
+    /// triangles whose corners are in a line, which only happens after they've been
+    /// moved.
+    pub fn circumcenters(&self) -> Vec<Option<Point2<T>>> {
+        self.triangles.iter().map(|triangle| {
+            let [a, b, c] = self.corner_points(triangle);
+            circumcircle_of_points(a, b, c).map(|(centre, _)| centre)
+        }).collect()
+    }
+
+    /// The angle in radians at each corner of each triangle, in the same order as
+    /// the corners in `Triangle::points`, indexed by triangle id
+    pub fn angles(&self) -> Vec<[T; 3]> {
+        self.triangles.iter().map(|triangle| {
+            let corners = self.corner_points(triangle);
+            [0, 1, 2].map(|corner| {
+                let to_next = corners[(corner + 1) % 3] - corners[corner];
+                let to_previous = corners[(corner + 2) % 3] - corners[corner];
+                to_next.perp_dot(to_previous).abs().atan2(to_next.dot(to_previous))
+            })
+        }).collect()
+    }
+
+    /// The circumradius of each triangle over twice its inradius, indexed by
+    /// triangle id
+    ///
+    /// This is one for an equilateral triangle and grows as triangles get thinner,
+    /// so it's a measure of how badly shaped they are. Infinite for triangles whose
+    /// corners are in a line.
+    pub fn aspect_ratios(&self) -> Vec<T> {
+        self.triangles.iter().map(|triangle| {
+            let [a, b, c] = self.corner_points(triangle).map(|point| point.cast::<f64>());
+            let sides = [(b - c).magnitude(), (c - a).magnitude(), (a - b).magnitude()];
+            let double_area = (b - a).perp_dot(c - a).abs();
+            if double_area == 0.0 {
+                return T::infinity();
+            }
+
+            // The circumradius is abc / 4A and the inradius is 2A / (a + b + c)
+            let ratio = sides[0] * sides[1] * sides[2] * (sides[0] + sides[1] + sides[2]) / (4.0 * double_area * double_area);
+            T::from(ratio).unwrap_or(T::infinity())
+        }).collect()
+    }
+
+    fn corner(&self, triangle: usize, corner: usize) -> Point2<f64> {
+        self.points[self.triangles[triangle].points[corner % 3] as usize].cast::<f64>()
+    }
+
+    /// The triangle a point is in, found by walking across the triangulation from
+    /// `hint`
+    ///
+    /// The walk only looks at the triangles between the two, so passing the
+    /// triangle found for a point nearby, like the last one looked up, makes
+    /// lookups of points that move a little at a time quick. A point on an edge
+    /// may be given either triangle next to it. None if the point is outside the
+    /// triangulation.
+    pub fn locate_with_hint(&self, point: Point2<T>, hint: TriangleId) -> Option<TriangleId> {
+        let position = point.cast::<f64>();
+        if !(position.x.is_finite() && position.y.is_finite()) {
+            return None;
+        }
+        match self.locate_from(hint.0 as usize, position) {
+            Location::Inside(triangle) => Some(TriangleId(triangle as u32)),
+            Location::Outside => None,
+        }
+    }
+
+    /// The value at a point blended from the values at the corners of the triangle
+    /// it's in, with `values` indexed like `points`
+    ///
+    /// The values have the same type as the coordinates, and are blended in f64.
+    ///
+    /// Each corner is weighted by its barycentric coordinate, so the result is the
+    /// height of the flat triangle through the values at the corners, and is the
+    /// value at a corner where the point is on one. None if the point is outside
+    /// the triangulation or a corner has no value.
+    ///
+    /// The triangle is found by walking from the first one, see
+    /// `interpolate_with_hint` for sampling many points close together.
+    pub fn interpolate(&self, point: Point2<T>, values: &[T]) -> Option<T> {
+        self.interpolate_with_hint(point, values, &mut TriangleId(0))
+    }
+
+    /// Like `interpolate`, but walks to the triangle from `hint`, and sets `hint`
+    /// to the triangle the point is in
+    ///
+    /// Keeping one hint for a run of points near each other, like the pixels of an
+    /// image in order, means each lookup only crosses the few triangles between
+    /// one point and the next. The hint is left as it was if the point is outside
+    /// the triangulation.
+    pub fn interpolate_with_hint(&self, point: Point2<T>, values: &[T], hint: &mut TriangleId) -> Option<T> {
+        *hint = self.locate_with_hint(point, *hint)?;
+        let position = point.cast::<f64>();
+        let triangle = &self.triangles[hint.0 as usize];
+
+        let [a, b, c] = self.corner_points(triangle).map(|corner| corner.cast::<f64>());
+        let area = (b - a).perp_dot(c - a);
+        if area == 0.0 {
+            return None;
+        }
+        let weights = [(b - position).perp_dot(c - position), (c - position).perp_dot(a - position), (a - position).perp_dot(b - position)];
+        let mut value = 0.0;
+        for (&corner, weight) in triangle.points.iter().zip(weights) {
+            value += values.get(corner as usize)?.to_f64()? * weight / area;
+        }
+        cast(value)
+    }
+
+    /// Whether the edge opposite a corner of a triangle passes the incircle test,
+    /// so the far corner of the triangle on the other side isn't inside its
+    /// circumcircle
+    ///
+    /// Edges on the outside of the triangulation always pass.
+    pub fn is_locally_delaunay(&self, triangle_id: TriangleId, corner: usize) -> bool {
+        match self.triangles.get(triangle_id.0 as usize).filter(|_| corner < 3) {
+            Some(_) => !needs_flip(&self.points, &self.triangles, triangle_id.0 as usize, corner),
+            None => true,
+        }
+    }
+
+    /// Swaps the edge opposite a corner of a triangle for the other diagonal of the
+    /// quadrilateral it makes with the triangle on the other side
+    ///
+    /// The triangle p q r with the edge q r shared with s r q becomes p q s, and
+    /// the other one becomes p s r, keeping their ids. Returns false and leaves the
+    /// triangles as they are if there's no triangle on the other side, or the
+    /// quadrilateral isn't convex, so the other diagonal would be outside it.
+    pub fn flip(&mut self, triangle_id: TriangleId, corner: usize) -> bool {
+        let t = triangle_id.0 as usize;
+        let (u, s) = match self.triangles.get(t).filter(|_| corner < 3).and_then(|_| across(&self.triangles, t, corner)) {
+            Some(across) => across,
+            None => return false,
+        };
+
+        let (p, q, r) = (self.corner(t, corner), self.corner(t, corner + 1), self.corner(t, corner + 2));
+        let s = self.points[s as usize].cast::<f64>();
+        let convex = orient2d(p, q, s) > 0.0 && orient2d(p, s, r) > 0.0;
+        if convex {
+            flip_edge(&mut self.triangles, t, corner, u);
+        }
+        convex
+    }
+
+    /// Flips edges until every one passes the incircle test, and returns how many
+    /// were flipped
+    ///
+    /// This is Lawson's flip algorithm, which turns any triangulation of the points
+    /// into their Delaunay triangulation. Returns None if it takes more flips than
+    /// the square of the number of triangles, which only happens when the triangles
+    /// overlap or are inside out.
+    pub fn legalize(&mut self) -> Option<usize> {
+        let pending = (0..self.triangles.len()).flat_map(|triangle| (0..3).map(move |corner| (triangle, corner))).collect();
+        let limit = self.triangles.len().saturating_mul(self.triangles.len());
+        flip_to_delaunay(&self.points, &mut self.triangles, pending, limit)
+    }
+
+    /// Adds a point to the triangulation and flips the edges around it until it's
+    /// Delaunay again, returning the index of the point
+    ///
+    /// The triangle the point is in is split into three, or the two either side of
+    /// the edge it's on into four. A point outside the triangulation is joined to
+    /// the edges of the outside that it can see. A point already in the
+    /// triangulation isn't added again, and its index is returned. Returns None if
+    /// there are no triangles yet or the point isn't finite.
+    pub fn insert_point(&mut self, point: Point2<T>) -> Option<u32> {
+        let position = point.cast::<f64>();
+        if self.triangles.is_empty() || !(position.x.is_finite() && position.y.is_finite()) {
+            return None;
+        }
+
+        let new = self.points.len() as u32;
+        let pending = match self.locate_from(0, position) {
+            Location::Inside(t) => {
+                let sides = [0, 1, 2].map(|side| orient2d(self.corner(t, side + 1), self.corner(t, side + 2), position));
+                if let Some(corner) = (0..3).find(|&corner| self.corner(t, corner) == position) {
+                    return Some(self.triangles[t].points[corner]);
+                }
+                self.points.push(point);
+                match sides.iter().position(|&side| side == 0.0) {
+                    Some(side) => self.split_edge(t, side, new),
+                    None => self.split_triangle(t, new),
+                }
+            }
+            Location::Outside => {
+                self.points.push(point);
+                self.attach_outside(position, new)
+            }
+        };
+
+        let limit = self.triangles.len().saturating_mul(self.triangles.len());
+        flip_to_delaunay(&self.points, &mut self.triangles, pending, limit)?;
+        Some(new)
+    }
+
+    /// Moves a point and flips the edges around it until the triangulation is
+    /// Delaunay again
+    ///
+    /// The point can only move as far as it can without turning any of its
+    /// triangles inside out, or, for a point on the outside of the triangulation,
+    /// making the outside turn right anywhere. Returns false and leaves the point
+    /// where it was if it can't move that far, in which case the triangulation has
+    /// to be built again.
+    pub fn move_point(&mut self, index: u32, position: Point2<T>) -> bool {
+        let target = position.cast::<f64>();
+        let valid = (index as usize) < self.points.len() && target.x.is_finite() && target.y.is_finite();
+        if !valid {
+            return false;
+        }
+
+        // The triangles around the point, and the edges of the outside before and
+        // after it, if it's on the outside
+        let around = (0..self.triangles.len()).filter(|&t| self.triangles[t].points.contains(&index)).collect::<Vec<_>>();
+        let mut previous = None;
+        let mut next = None;
+        for &t in &around {
+            let triangle = &self.triangles[t];
+            let corner = triangle.points.iter().position(|&point| point == index).unwrap_or(0);
+            let (after, before) = (triangle.points[(corner + 1) % 3], triangle.points[(corner + 2) % 3]);
+            if orient2d(target, self.points[after as usize].cast::<f64>(), self.points[before as usize].cast::<f64>()) <= 0.0 {
+                return false;
+            }
+            if triangle.neighbours[(corner + 2) % 3].is_none() {
+                next = Some(after);
+            }
+            if triangle.neighbours[(corner + 1) % 3].is_none() {
+                previous = Some(before);
+            }
+        }
+
+        if let (Some(previous), Some(next)) = (previous, next) {
+            let hull = hull_neighbours(&self.triangles, [previous, next]);
+            let point = |index: u32| self.points[index as usize].cast::<f64>();
+            let (previous, next) = (point(previous), point(next));
+            let convex = orient2d(previous, target, next) >= 0.0
+                && hull[0].is_none_or(|(before, _)| orient2d(point(before), previous, target) >= 0.0)
+                && hull[1].is_none_or(|(_, after)| orient2d(target, next, point(after)) >= 0.0);
+            if !convex {
+                return false;
+            }
+        }
+
+        self.points[index as usize] = position;
+        let pending = around.iter().flat_map(|&triangle| (0..3).map(move |corner| (triangle, corner))).collect();
+        let limit = self.triangles.len().saturating_mul(self.triangles.len());
+        flip_to_delaunay(&self.points, &mut self.triangles, pending, limit).is_some()
+    }
+
+    /// Walks from a triangle towards a point, crossing an edge the point is beyond,
+    /// and falls back to looking at every triangle if that goes round in circles
+    ///
+    /// This is the remembering stochastic walk. The edges of each triangle are
+    /// tried starting from a random one, which stops the walk going round in
+    /// circles where the triangles aren't Delaunay, and the edge it came in
+    /// through isn't tried again.
+    fn locate_from(&self, start: usize, point: Point2<f64>) -> Location {
+        let mut triangle = start.min(self.triangles.len().saturating_sub(1));
+        let mut came_from = None;
+        let mut state = (start as u64) ^ point.x.to_bits() ^ point.y.to_bits().rotate_left(32);
+        'walk: for _ in 0..self.triangles.len() {
+            let first = (next_random(&mut state) * 3.0) as usize;
+            for side in (first..first + 3).map(|side| side % 3) {
+                let neighbour = self.triangles[triangle].neighbours[side];
+                if neighbour.is_some() && neighbour == came_from {
+                    continue;
+                }
+                if orient2d(self.corner(triangle, side + 1), self.corner(triangle, side + 2), point) < 0.0 {
+                    match neighbour {
+                        Some(TriangleId(neighbour)) => {
+                            came_from = Some(TriangleId(triangle as u32));
+                            triangle = neighbour as usize;
+                            continue 'walk;
+                        }
+                        None => return Location::Outside,
+                    }
+                }
+            }
+            return Location::Inside(triangle);
+        }
+
+        let inside = (0..self.triangles.len()).find(|&triangle| {
+            (0..3).all(|side| orient2d(self.corner(triangle, side + 1), self.corner(triangle, side + 2), point) >= 0.0)
+        });
+        match inside {
+            Some(triangle) => Location::Inside(triangle),
+            None => Location::Outside,
+        }
+    }
+
+    /// Splits a triangle into three around a point inside it, and returns the
+    /// edges opposite the point
+    fn split_triangle(&mut self, t: usize, new: u32) -> Vec<(usize, usize)> {
+        let [a, b, c] = self.triangles[t].points;
+        let [na, nb, nc] = self.triangles[t].neighbours;
+        let (t1, t2) = (self.triangles.len(), self.triangles.len() + 1);
+        let id = |index: usize| Some(TriangleId(index as u32));
+
+        self.triangles[t] = Triangle { points: [new, b, c], neighbours: [na, id(t1), id(t2)] };
+        self.triangles.push(Triangle { points: [a, new, c], neighbours: [id(t), nb, id(t2)] });
+        self.triangles.push(Triangle { points: [a, b, new], neighbours: [id(t), id(t1), nc] });
+        relink(&mut self.triangles, nb, TriangleId(t as u32), TriangleId(t1 as u32));
+        relink(&mut self.triangles, nc, TriangleId(t as u32), TriangleId(t2 as u32));
+        vec![(t, 0), (t1, 1), (t2, 2)]
+    }
+
+    /// Splits the triangles either side of an edge in two at a point on it, and
+    /// returns the edges opposite the point
+    fn split_edge(&mut self, t: usize, side: usize, new: u32) -> Vec<(usize, usize)> {
+        // The triangle is a b c with the point on b c, and the one across it is
+        // d c b
+        let points = self.triangles[t].points;
+        let neighbours = self.triangles[t].neighbours;
+        let (a, b, c) = (points[side], points[(side + 1) % 3], points[(side + 2) % 3]);
+        let (nb, nc) = (neighbours[(side + 1) % 3], neighbours[(side + 2) % 3]);
+        let across = across(&self.triangles, t, side);
+        let t1 = self.triangles.len();
+        let id = |index: usize| Some(TriangleId(index as u32));
+
+        let (u, u1) = match across {
+            Some((u, _)) => (id(u), id(t1 + 1)),
+            None => (None, None),
+        };
+        self.triangles[t] = Triangle { points: [a, b, new], neighbours: [u1, id(t1), nc] };
+        self.triangles.push(Triangle { points: [a, new, c], neighbours: [u, nb, id(t)] });
+        relink(&mut self.triangles, nb, TriangleId(t as u32), TriangleId(t1 as u32));
+        let mut pending = vec![(t, 2), (t1, 1)];
+
+        if let Some((u, d)) = across {
+            let corner = self.triangles[u].points.iter().position(|&point| point == d).unwrap_or(0);
+            let neighbours = self.triangles[u].neighbours;
+            let (ub, uc) = (neighbours[(corner + 1) % 3], neighbours[(corner + 2) % 3]);
+            self.triangles[u] = Triangle { points: [d, c, new], neighbours: [id(t1), id(t1 + 1), uc] };
+            self.triangles.push(Triangle { points: [d, new, b], neighbours: [id(t), ub, id(u)] });
+            relink(&mut self.triangles, ub, TriangleId(u as u32), TriangleId(t1 as u32 + 1));
+            pending.extend([(u, 2), (t1 + 1, 1)]);
+        }
+        pending
+    }
+
+    /// Joins a point outside the triangulation to every edge of the outside that
+    /// it's strictly beyond, and returns those edges
+    fn attach_outside(&mut self, point: Point2<f64>, new: u32) -> Vec<(usize, usize)> {
+        let visible = (0..self.triangles.len())
+            .flat_map(|triangle| (0..3).map(move |side| (triangle, side)))
+            .filter(|&(triangle, side)| self.triangles[triangle].neighbours[side].is_none() && orient2d(self.corner(triangle, side + 1), self.corner(triangle, side + 2), point) < 0.0)
+            .collect::<Vec<_>>();
+
+        // The new triangles next to each other share the edge from the point to the
+        // end of the outside they have in common
+        let mut pending = Vec::with_capacity(visible.len());
+        let mut from_start: Vec<(u32, usize)> = Vec::new();
+        let mut from_end: Vec<(u32, usize)> = Vec::new();
+        for (triangle, side) in visible {
+            let start = self.triangles[triangle].points[(side + 1) % 3];
+            let end = self.triangles[triangle].points[(side + 2) % 3];
+            let added = self.triangles.len();
+            self.triangles.push(Triangle { points: [end, start, new], neighbours: [None, None, Some(TriangleId(triangle as u32))] });
+            self.triangles[triangle].neighbours[side] = Some(TriangleId(added as u32));
+            from_start.push((start, added));
+            from_end.push((end, added));
+            pending.push((added, 2));
+        }
+        for &(point, before) in &from_end {
+            if let Some(&(_, after)) = from_start.iter().find(|&&(other, _)| other == point) {
+                self.triangles[before].neighbours[1] = Some(TriangleId(after as u32));
+                self.triangles[after].neighbours[0] = Some(TriangleId(before as u32));
+            }
+        }
+        pending
+    }
+
+    /// Writes the triangles to a Wavefront OBJ file
+    #[cfg(feature = "io")]
+    pub fn write_obj<W: ::std::io::Write>(&self, writer: W) -> ::std::io::Result<()> {
+        mesh_io::write_triangulation_obj(self, writer)
+    }
+
+    /// Writes the triangles to an ASCII PLY file
+    #[cfg(feature = "io")]
+    pub fn write_ply<W: ::std::io::Write>(&self, writer: W) -> ::std::io::Result<()> {
+        mesh_io::write_triangulation_ply(self, writer)
+    }
+
+    /// Outlines of the alpha shape of the points, which is made of the triangles
+    /// whose circumcircles have a radius of at most `alpha`
+    ///
+    /// Each outline is a list of indices into `points`. Outlines around the kept
+    /// triangles go counter-clockwise and outlines of holes go clockwise. A point
+    /// where two outlines touch is in both of them. An `alpha` at least as large as
+    /// every circumradius gives the convex hull, and smaller values carve out
+    /// concave bays and holes where the points are spread out.
+    pub fn alpha_shape(&self, alpha: T) -> Vec<Vec<u32>> {
+        let alpha = alpha.to_f64().unwrap();
+        let kept = self.triangles.iter().map(|triangle| {
+            let [a, b, c] = triangle.points.map(|point| self.points[point as usize].cast::<f64>());
+            let double_area = (b - a).perp_dot(c - a).abs();
+
+            // The circumradius is the product of the sides over four times the area
+            (b - a).magnitude() * (c - b).magnitude() * (a - c).magnitude() <= 2.0 * double_area * alpha
+        }).collect::<Vec<_>>();
+        let is_kept = |neighbour: Option<TriangleId>| neighbour.is_some_and(|TriangleId(index)| kept[index as usize]);
+
+        // The edge opposite each corner of a kept triangle is on an outline if the
+        // triangle across it isn't kept. Outlines follow these edges with the kept
+        // triangles on the left
+        let mut visited = vec![[false; 3]; self.triangles.len()];
+        let mut outlines = Vec::new();
+        for (index, triangle) in self.triangles.iter().enumerate() {
+            if !kept[index] {
+                continue;
+            }
+
+            for corner in 0..3 {
+                if visited[index][corner] || is_kept(triangle.neighbours[corner]) {
+                    continue;
+                }
+
+                let mut outline = Vec::new();
+                let (mut triangle_index, mut edge) = (index, corner);
+                while !visited[triangle_index][edge] {
+                    visited[triangle_index][edge] = true;
+                    let points = self.triangles[triangle_index].points;
+                    outline.push(points[(edge + 1) % 3]);
+
+                    // Turn around the end of the edge through kept triangles until
+                    // reaching the next edge on the outline
+                    let end = points[(edge + 2) % 3];
+                    loop {
+                        let triangle = &self.triangles[triangle_index];
+                        let end_corner = triangle.points.iter().position(|&point| point == end).unwrap();
+                        edge = (end_corner + 2) % 3;
+                        match triangle.neighbours[edge] {
+                            Some(TriangleId(neighbour)) if kept[neighbour as usize] => triangle_index = neighbour as usize,
+                            _ => break,
+                        }
+                    }
+                }
+                outlines.push(outline);
+            }
+        }
+
+        outlines
+    }
+}
+
+/// Points the neighbour of a triangle that was `old` at `new` instead
+pub(crate) fn relink(triangles: &mut [Triangle], triangle: Option<TriangleId>, old: TriangleId, new: TriangleId) {
+    if let Some(triangle) = triangle {
+        for neighbour in &mut triangles[triangle.0 as usize].neighbours {
+            if *neighbour == Some(old) {
+                *neighbour = Some(new);
+            }
+        }
+    }
+}
+
+/// The triangle across the edge opposite a corner, and its corner that isn't on
+/// that edge
+fn across(triangles: &[Triangle], t: usize, corner: usize) -> Option<(usize, u32)> {
+    let u = triangles[t].neighbours[corner]?.0 as usize;
+    let q = triangles[t].points[(corner + 1) % 3];
+    let r = triangles[t].points[(corner + 2) % 3];
+    let s = *triangles.get(u)?.points.iter().find(|&&point| point != q && point != r)?;
+    Some((u, s))
+}
+
+/// Whether the far corner of the triangle across the edge opposite a corner is
+/// inside the circumcircle of the triangle
+fn needs_flip<T: BaseFloat>(points: &[Point2<T>], triangles: &[Triangle], t: usize, corner: usize) -> bool {
+    let s = match across(triangles, t, corner) {
+        Some((_, s)) => s,
+        None => return false,
+    };
+    let point = |index: u32| points[index as usize].cast::<f64>();
+    let [p, q, r] = [corner, corner + 1, corner + 2].map(|corner| triangles[t].points[corner % 3]);
+    incircle(point(p), point(q), point(r), point(s)) > 0.0
+}
+
+/// Flips the edge opposite a corner of triangle `t`, which is shared with `u`,
+/// see `Triangulation::flip`
+fn flip_edge(triangles: &mut [Triangle], t: usize, corner: usize, u: usize) {
+    let p = triangles[t].points[corner];
+    let q = triangles[t].points[(corner + 1) % 3];
+    let r = triangles[t].points[(corner + 2) % 3];
+    let u_corner_of = |point: u32| triangles[u].points.iter().position(|&corner| corner == point).unwrap_or(0);
+    let s = triangles[u].points[(u_corner_of(q) + 1) % 3];
+
+    // The edges around the four points, named by the points they join
+    let r_p = triangles[t].neighbours[(corner + 1) % 3];
+    let p_q = triangles[t].neighbours[(corner + 2) % 3];
+    let q_s = triangles[u].neighbours[u_corner_of(r)];
+    let s_r = triangles[u].neighbours[u_corner_of(q)];
+
+    let (t_id, u_id) = (TriangleId(t as u32), TriangleId(u as u32));
+    triangles[t] = Triangle {
+        points: [p, q, s],
+        neighbours: [q_s, Some(u_id), p_q],
+    };
+    triangles[u] = Triangle {
+        points: [p, s, r],
+        neighbours: [s_r, r_p, Some(t_id)],
+    };
+    relink(triangles, q_s, u_id, t_id);
+    relink(triangles, r_p, t_id, u_id);
+}
+
+/// Lawson's flip algorithm, which flips edges that fail the incircle test,
+/// starting from the ones given by a triangle and the corner opposite them, until
+/// none do, or None if that takes more than `limit` flips
+pub(crate) fn flip_to_delaunay<T: BaseFloat>(points: &[Point2<T>], triangles: &mut [Triangle], mut pending: Vec<(usize, usize)>, limit: usize) -> Option<usize> {
+    let mut flips = 0;
+    while let Some((t, corner)) = pending.pop() {
+        if !needs_flip(points, triangles, t, corner) {
+            continue;
+        }
+
+        flips += 1;
+        if flips > limit {
+            return None;
+        }
+        let u = match triangles[t].neighbours[corner] {
+            Some(u) => u.0 as usize,
+            None => continue,
+        };
+        flip_edge(triangles, t, corner, u);
+        pending.extend([(t, 0), (t, 2), (u, 0), (u, 1)]);
+    }
+    Some(flips)
+}
+
+/// The points before and after each of two points on the outside of a
+/// triangulation, going counter-clockwise around it
+fn hull_neighbours(triangles: &[Triangle], points: [u32; 2]) -> [Option<(u32, u32)>; 2] {
+    let mut before = [None; 2];
+    let mut after = [None; 2];
+    for triangle in triangles {
+        for side in 0..3 {
+            if triangle.neighbours[side].is_some() {
+                continue;
+            }
+            let (start, end) = (triangle.points[(side + 1) % 3], triangle.points[(side + 2) % 3]);
+            for (index, &point) in points.iter().enumerate() {
+                if end == point {
+                    before[index] = Some(start);
+                }
+                if start == point {
+                    after[index] = Some(end);
+                }
+            }
+        }
+    }
+    [0, 1].map(|index| before[index].zip(after[index]))
+}
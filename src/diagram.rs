@@ -1,43 +1,256 @@
-use cgmath::Point2;
+use std::cmp::Ordering;
+use std::error::Error;
+use std::fmt;
+use std::ops::{Index, IndexMut};
+
+use cgmath::{Point2, Vector2, InnerSpace, BaseFloat};
+use cgmath::num_traits::cast;
+#[cfg(feature = "serde")]
+use serde::{Serialize, Serializer, Deserialize};
+
+use adjacency;
+use binary;
+use buffers::{self, FlatBuffers};
+use build::{Metric, Rect, Site};
+use canonical;
+use clip;
+use edit;
+#[cfg(feature = "geojson")]
+use export;
+use grid::FaceGrid;
+use hull;
+use hydrology;
+use insert;
+#[cfg(feature = "geo")]
+use interop;
+use kdtree::KdTree;
+use mesh::{self, Mesh};
+#[cfg(feature = "io")]
+use mesh_io::{self, DiagramGeometry};
+use noise;
+use offset;
+#[cfg(feature = "pathfinding")]
+use path::{self, Path, PathCost};
+use proximity;
+use range;
+use regions;
+#[cfg(feature = "rand")]
+use sample;
+use smooth;
+use soa::{self, DiagramSoa};
+use table;
+use validate;
+use walk::SegmentWalk;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
-pub struct VertexId(u32);
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct VertexId(pub u32);
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct HalfEdgeId(pub u32);
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
-pub struct FaceId(u32);
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct FaceId(pub u32);
+
+/// A group of faces, see `Diagram::group_faces`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct RegionId(pub u32);
 
 #[derive(Debug, Clone)]
-pub struct Vertex {
-    pub coordinates: Point2<f32>,
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Vertex<T = f32> {
+    pub coordinates: Point2<T>,
     pub incident_edge: HalfEdgeId,
 }
 
+/// One side of an edge in the diagram
+///
+/// Half-edges are oriented so that their incident face lies on their left, which
+/// makes every face cycle counter-clockwise.
+///
+/// Half-edges along the bounding rectangle have no twin. Every half-edge of a
+/// clipped diagram has an origin; it is only missing where the edge goes off to
+/// infinity, while the diagram is being built or if it was finished without
+/// clipping. `Diagram::origin` gives the direction in that case.
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct HalfEdge {
-    pub origin: VertexId,
-    pub twin: HalfEdgeId,
+    pub origin: Option<VertexId>,
+    pub twin: Option<HalfEdgeId>,
     pub incident_face: FaceId,
     pub next: HalfEdgeId,
     pub prev: HalfEdgeId,
 }
 
+/// Where a half-edge starts or ends
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Endpoint<T = f32> {
+    Vertex(VertexId),
+
+    /// The edge has no end this way and goes off to infinity in this direction,
+    /// which has length one. Only edges of unclipped diagrams do this
+    InfiniteDirection(Vector2<T>),
+}
+
+/// A cell of the diagram, clipped to the bounding rectangle
+///
+/// Each face holds the site it was built from, as it was passed to the builder.
+///
+/// In a power diagram, a site's cell can be empty or lie entirely outside the
+/// bounding rectangle. Its face is still there but has no half-edges.
 #[derive(Debug, Clone)]
-pub struct Face {
-    pub first_halfedge: HalfEdgeId,
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Face<T = f32> {
+    pub first_halfedge: Option<HalfEdgeId>,
+    pub site: Site<T>,
 }
 
-#[derive(Debug, Default, Clone)]
-pub struct Diagram {
-    pub vertices: Vec<Vertex>,
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(
+    from = "DiagramData<T>",
+    bound(serialize = "T: BaseFloat + Serialize", deserialize = "T: BaseFloat + Deserialize<'de>"),
+))]
+pub struct Diagram<T = f32> {
+    pub vertices: Vec<Vertex<T>>,
     pub halfedges: Vec<HalfEdge>,
-    pub faces: Vec<Face>,
+    pub faces: Vec<Face<T>>,
+
+    /// Index of the sites of the faces, for finding the face a point is in
+    ///
+    /// Only whether it is weighted gets serialized. It is built again when the
+    /// diagram is deserialized.
+    #[cfg_attr(feature = "serde", serde(rename = "weighted", serialize_with = "serialize_weighted"))]
+    pub(crate) site_index: KdTree<T>,
+
+    /// Metric the cells were built under. Only Euclidean cells can have sites
+    /// added to them, see `insert_site`
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub(crate) metric: Metric,
+
+    /// Grid over the faces from `build_accelerator`, if it has been built
+    pub(crate) accelerator: Option<FaceGrid<T>>,
+}
+
+#[cfg(feature = "serde")]
+fn serialize_weighted<T: BaseFloat, S: Serializer>(site_index: &KdTree<T>, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_bool(site_index.is_weighted())
+}
+
+/// A broken invariant found by `Diagram::validate`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValidationError {
+    /// A link points at a half-edge that doesn't exist
+    MissingHalfEdge(HalfEdgeId),
+
+    /// A half-edge starts at a vertex that doesn't exist
+    MissingVertex(VertexId),
+
+    /// A half-edge belongs to a face that doesn't exist
+    MissingFace(FaceId),
+
+    /// A vertex has a NaN coordinate
+    NanCoordinates(VertexId),
+
+    /// The site of a face has a NaN coordinate
+    NanSite(FaceId),
+
+    /// A half-edge has no origin
+    MissingOrigin(HalfEdgeId),
+
+    /// A half-edge isn't the previous half-edge of its next one, or the next
+    /// half-edge of its previous one
+    NextPrevMismatch(HalfEdgeId),
+
+    /// A half-edge is its own twin, or its twin has a different twin
+    TwinMismatch(HalfEdgeId),
+
+    /// The next half-edge doesn't start where the twin does
+    DestinationMismatch(HalfEdgeId),
+
+    /// A vertex's incident edge starts at another vertex
+    IncidentEdgeMismatch(VertexId),
+
+    /// A half-edge is in the cycle of a face other than its incident face
+    FaceMismatch(HalfEdgeId),
+
+    /// Following the next links from a face's first half-edge never gets back to it
+    OpenCycle(FaceId),
+
+    /// A half-edge isn't in the cycle of any face
+    UnreachableHalfEdge(HalfEdgeId),
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ValidationError::MissingHalfEdge(halfedge) => write!(f, "half-edge {} doesn't exist", halfedge.0),
+            ValidationError::MissingVertex(vertex) => write!(f, "vertex {} doesn't exist", vertex.0),
+            ValidationError::MissingFace(face) => write!(f, "face {} doesn't exist", face.0),
+            ValidationError::NanCoordinates(vertex) => write!(f, "vertex {} has NaN coordinates", vertex.0),
+            ValidationError::NanSite(face) => write!(f, "the site of face {} has NaN coordinates", face.0),
+            ValidationError::MissingOrigin(halfedge) => write!(f, "half-edge {} has no origin", halfedge.0),
+            ValidationError::NextPrevMismatch(halfedge) => write!(f, "next and prev links around half-edge {} don't match", halfedge.0),
+            ValidationError::TwinMismatch(halfedge) => write!(f, "twin of half-edge {} doesn't link back to it", halfedge.0),
+            ValidationError::DestinationMismatch(halfedge) => write!(f, "half-edge {} doesn't end where its twin starts", halfedge.0),
+            ValidationError::IncidentEdgeMismatch(vertex) => write!(f, "incident edge of vertex {} starts elsewhere", vertex.0),
+            ValidationError::FaceMismatch(halfedge) => write!(f, "half-edge {} is in the cycle of another face", halfedge.0),
+            ValidationError::OpenCycle(face) => write!(f, "half-edges of face {} don't form a cycle", face.0),
+            ValidationError::UnreachableHalfEdge(halfedge) => write!(f, "half-edge {} isn't in the cycle of any face", halfedge.0),
+        }
+    }
+}
+
+impl Error for ValidationError {}
+
+/// A serialized diagram, before its site index is built
+#[cfg(feature = "serde")]
+#[derive(Deserialize)]
+struct DiagramData<T> {
+    vertices: Vec<Vertex<T>>,
+    halfedges: Vec<HalfEdge>,
+    faces: Vec<Face<T>>,
+    weighted: bool,
+    #[serde(default)]
+    metric: Metric,
+    accelerator: Option<FaceGrid<T>>,
+}
+
+#[cfg(feature = "serde")]
+impl<T: BaseFloat> From<DiagramData<T>> for Diagram<T> {
+    fn from(data: DiagramData<T>) -> Diagram<T> {
+        let mut diagram = Diagram {
+            vertices: data.vertices,
+            halfedges: data.halfedges,
+            faces: data.faces,
+            site_index: KdTree::default(),
+            metric: data.metric,
+            accelerator: data.accelerator,
+        };
+        diagram.index_sites(data.weighted);
+        diagram
+    }
+}
+
+impl<T> Default for Diagram<T> {
+    fn default() -> Diagram<T> {
+        Diagram {
+            vertices: Vec::new(),
+            halfedges: Vec::new(),
+            faces: Vec::new(),
+            site_index: KdTree::default(),
+            metric: Metric::Euclidean,
+            accelerator: None,
+        }
+    }
 }
 
-impl Diagram {
-    pub fn get_vertex(&self, vertex_id: VertexId) -> Option<&Vertex> {
+impl<T: BaseFloat> Diagram<T> {
+    pub fn get_vertex(&self, vertex_id: VertexId) -> Option<&Vertex<T>> {
         self.vertices.get(vertex_id.0 as usize)
     }
 
@@ -45,7 +258,985 @@ impl Diagram {
         self.halfedges.get(halfedge_id.0 as usize)
     }
 
-    pub fn get_face(&self, face_id: FaceId) -> Option<&Face> {
+    pub fn get_face(&self, face_id: FaceId) -> Option<&Face<T>> {
         self.faces.get(face_id.0 as usize)
     }
+
+    pub fn get_vertex_mut(&mut self, vertex_id: VertexId) -> Option<&mut Vertex<T>> {
+        self.vertices.get_mut(vertex_id.0 as usize)
+    }
+
+    pub fn get_half_edge_mut(&mut self, halfedge_id: HalfEdgeId) -> Option<&mut HalfEdge> {
+        self.halfedges.get_mut(halfedge_id.0 as usize)
+    }
+
+    pub fn get_face_mut(&mut self, face_id: FaceId) -> Option<&mut Face<T>> {
+        self.faces.get_mut(face_id.0 as usize)
+    }
+
+    /// Adds a vertex and returns its id
+    ///
+    /// Nothing is linked up, so the vertex's incident edge has to be set to a
+    /// half-edge that starts at it before the diagram passes `validate`.
+    pub fn push_vertex(&mut self, vertex: Vertex<T>) -> VertexId {
+        self.vertices.push(vertex);
+        VertexId(self.vertices.len() as u32 - 1)
+    }
+
+    /// Adds a half-edge and returns its id
+    ///
+    /// Its twin, next and prev half-edges aren't changed to point back at it.
+    pub fn push_halfedge(&mut self, halfedge: HalfEdge) -> HalfEdgeId {
+        self.halfedges.push(halfedge);
+        HalfEdgeId(self.halfedges.len() as u32 - 1)
+    }
+
+    /// Adds a face and returns its id
+    ///
+    /// `locate` and the other lookups by position still find the new face, but
+    /// check every site to do it until the diagram is built again.
+    pub fn push_face(&mut self, face: Face<T>) -> FaceId {
+        self.faces.push(face);
+        FaceId(self.faces.len() as u32 - 1)
+    }
+
+    /// Finds the face that was built from the site with the given id
+    ///
+    /// Returns None for sites that were outside the bounding rect.
+    pub fn face_for_site(&self, site_id: u32) -> Option<FaceId> {
+        // Faces are in the same order as the sites that were given to the builder, so
+        // when site ids are indices into that list the face is usually at that index
+        if let Some(face) = self.get_face(FaceId(site_id)) {
+            if face.site.id == site_id {
+                return Some(FaceId(site_id));
+            }
+        }
+
+        self.faces.iter()
+            .position(|face| face.site.id == site_id)
+            .map(|index| FaceId(index as u32))
+    }
+
+    pub fn site_of_face(&self, face_id: FaceId) -> Option<&Site<T>> {
+        self.get_face(face_id).map(|face| &face.site)
+    }
+
+    /// Where a half-edge starts, which is a direction if it comes in from infinity
+    ///
+    /// Returns None if the half-edge doesn't exist, or if it has no origin and
+    /// no twin to work out the direction from.
+    pub fn origin(&self, halfedge_id: HalfEdgeId) -> Option<Endpoint<T>> {
+        let halfedge = self.get_half_edge(halfedge_id)?;
+        if let Some(origin) = halfedge.origin {
+            return Some(Endpoint::Vertex(origin));
+        }
+
+        // Edges run along the bisector of the sites on either side, with the
+        // half-edge's own site on its left
+        let twin = self.get_half_edge(halfedge.twin?)?;
+        let site = self.get_face(halfedge.incident_face)?.site.position;
+        let other = self.get_face(twin.incident_face)?.site.position;
+        let across = other - site;
+        Some(Endpoint::InfiniteDirection(Vector2::new(across.y, -across.x).normalize()))
+    }
+
+    /// Where a half-edge ends, which is a direction if it goes off to infinity
+    pub fn destination(&self, halfedge_id: HalfEdgeId) -> Option<Endpoint<T>> {
+        let halfedge = self.get_half_edge(halfedge_id)?;
+        match halfedge.twin {
+            Some(twin) => self.origin(twin),
+            None => self.get_half_edge(halfedge.next)?.origin.map(Endpoint::Vertex),
+        }
+    }
+
+    /// Iterates over the half-edges around a face in counter-clockwise order
+    pub fn face_edges(&self, face_id: FaceId) -> FaceEdges<'_, T> {
+        let first_halfedge = self.get_face(face_id).and_then(|face| face.first_halfedge);
+
+        FaceEdges {
+            diagram: self,
+            first_halfedge,
+            next_halfedge: first_halfedge,
+        }
+    }
+
+    /// Iterates over the corners of a face's polygon in counter-clockwise order
+    pub fn face_vertices(&self, face_id: FaceId) -> impl Iterator<Item = Point2<T>> + '_ {
+        self.face_edges(face_id).filter_map(move |halfedge_id| {
+            let origin = self.get_half_edge(halfedge_id)?.origin?;
+            self.get_vertex(origin).map(|vertex| vertex.coordinates)
+        })
+    }
+
+    /// Twice the signed area of a face and the sums used to find its centroid, from
+    /// the shoelace formula
+    ///
+    /// Coordinates are taken relative to the face's site to keep the numbers small.
+    fn face_moments(&self, face_id: FaceId) -> (T, T, T) {
+        let points = self.face_vertices(face_id).collect::<Vec<_>>();
+        let origin = match self.get_face(face_id) {
+            Some(face) => face.site.position,
+            None => return (T::zero(), T::zero(), T::zero()),
+        };
+
+        let mut area = T::zero();
+        let mut x = T::zero();
+        let mut y = T::zero();
+        for (index, a) in points.iter().enumerate() {
+            let b = points[(index + 1) % points.len()];
+            let (ax, ay) = (a.x - origin.x, a.y - origin.y);
+            let (bx, by) = (b.x - origin.x, b.y - origin.y);
+
+            let cross = ax * by - bx * ay;
+            area += cross;
+            x += (ax + bx) * cross;
+            y += (ay + by) * cross;
+        }
+
+        (area, x, y)
+    }
+
+    /// Area of a face's polygon
+    ///
+    /// This is zero for faces that don't exist or have no half-edges.
+    pub fn face_area(&self, face_id: FaceId) -> T {
+        let (area, _, _) = self.face_moments(face_id);
+        area / (T::one() + T::one())
+    }
+
+    /// Centroid of a face's polygon
+    ///
+    /// Returns None for faces with no area, including ones that don't exist.
+    pub fn face_centroid(&self, face_id: FaceId) -> Option<Point2<T>> {
+        let (area, x, y) = self.face_moments(face_id);
+        if area == T::zero() {
+            return None;
+        }
+
+        let origin = self.faces[face_id.0 as usize].site.position;
+        let three: T = cast(3.0).unwrap();
+        Some(Point2::new(origin.x + x / (three * area), origin.y + y / (three * area)))
+    }
+
+    /// Length of the edge a half-edge runs along
+    ///
+    /// Edges that go off to infinity, which only unclipped diagrams have, are
+    /// infinitely long. Returns None if the half-edge doesn't exist, or one of its
+    /// ends can't be found.
+    pub fn edge_length(&self, halfedge_id: HalfEdgeId) -> Option<T> {
+        match (self.origin(halfedge_id)?, self.destination(halfedge_id)?) {
+            (Endpoint::Vertex(a), Endpoint::Vertex(b)) => {
+                Some((self.get_vertex(b)?.coordinates - self.get_vertex(a)?.coordinates).magnitude())
+            }
+            _ => Some(T::infinity()),
+        }
+    }
+
+    /// Total length of the edges around a face
+    ///
+    /// This is zero for faces that don't exist or have no half-edges, and infinite
+    /// for cells of an unclipped diagram that aren't closed.
+    pub fn face_perimeter(&self, face_id: FaceId) -> T {
+        self.face_edges(face_id)
+            .filter_map(|halfedge_id| self.edge_length(halfedge_id))
+            .fold(T::zero(), |total, length| total + length)
+    }
+
+    /// Total length of every edge in the diagram, counting the two half-edges of
+    /// an edge once
+    pub fn total_edge_length(&self) -> T {
+        self.halfedges.iter().enumerate()
+            .filter(|&(index, halfedge)| halfedge.twin.is_none_or(|twin| index < twin.0 as usize))
+            .filter_map(|(index, _)| self.edge_length(HalfEdgeId(index as u32)))
+            .fold(T::zero(), |total, length| total + length)
+    }
+
+    /// Distance from a vertex to the nearest of the sites whose cells meet there
+    ///
+    /// Where three or more cells meet, this is the radius of the circle through
+    /// their sites. No site is inside that circle, so it's how much room there is
+    /// around the vertex. Vertices made by clipping are only measured against the
+    /// sites of the cells they're on, and the weights of a power diagram aren't
+    /// taken into account. Returns None if the vertex or its incident edge doesn't
+    /// exist.
+    pub fn vertex_clearance(&self, vertex_id: VertexId) -> Option<T> {
+        let vertex = self.get_vertex(vertex_id)?;
+        self.get_half_edge(vertex.incident_edge)?;
+        let coordinates = vertex.coordinates;
+        self.incident_edges(vertex_id)
+            .take(self.halfedges.len())
+            .filter_map(|halfedge_id| self.get_face(self.get_half_edge(halfedge_id)?.incident_face))
+            .map(|face| (face.site.position - coordinates).magnitude())
+            .fold(None, |nearest: Option<T>, distance| Some(nearest.map_or(distance, |nearest| nearest.min(distance))))
+    }
+
+    /// A random point inside a face, with every point in its cell equally likely
+    ///
+    /// Returns None if the face doesn't exist, has no area, or isn't closed.
+    #[cfg(feature = "rand")]
+    pub fn sample_point_in_face<R: ::rand::Rng + ?Sized>(&self, face_id: FaceId, rng: &mut R) -> Option<Point2<T>> {
+        sample::sample_point_in_face(self, face_id, rng)
+    }
+
+    /// Builds the index used by `locate` from the sites of the faces
+    ///
+    /// Weights are only taken into account for power diagrams.
+    pub(crate) fn index_sites(&mut self, weighted: bool) {
+        let sites = self.faces.iter().enumerate().map(|(index, face)| (FaceId(index as u32), face.site.position, face.site.weight));
+        self.site_index.rebuild(sites, weighted);
+    }
+
+    /// Finds the face whose cell contains a point
+    ///
+    /// This is the face of the nearest site, or of the site with the smallest power
+    /// distance in a power diagram. Points outside the bounding rect get the face
+    /// whose cell would contain them if it hadn't been clipped. Returns None if the
+    /// diagram has no faces.
+    pub fn locate(&self, point: Point2<T>) -> Option<FaceId> {
+        if let Some(face_id) = self.accelerator().and_then(|grid| grid.locate(self, point)) {
+            return Some(face_id);
+        }
+        if self.site_index.len() == self.faces.len() {
+            return self.site_index.nearest(point);
+        }
+
+        // The faces have changed since the index was built, so check every site
+        let weighted = self.site_index.is_weighted();
+        let power_distance = |site: &Site<T>| {
+            let weight = if weighted { site.weight } else { T::zero() };
+            let dx = point.x - site.position.x;
+            let dy = point.y - site.position.y;
+            dx * dx + dy * dy - weight
+        };
+
+        self.faces.iter().enumerate().fold(None, |closest: Option<(T, FaceId)>, (index, face)| {
+            let distance = power_distance(&face.site);
+            match closest {
+                Some((closest_distance, _)) if closest_distance <= distance => closest,
+                _ => Some((distance, FaceId(index as u32))),
+            }
+        }).map(|(_, face_id)| face_id)
+    }
+
+    /// Builds a grid over the faces, which `locate` looks in first
+    ///
+    /// Each square of the grid lists the few faces that overlap it, so a point only
+    /// has to be compared with those. Where the grid can't tell, such as outside
+    /// it, `locate` goes on as it would without one. The grid is kept with the
+    /// diagram, and serialized with it, until a method that changes the diagram
+    /// drops it. Changing the lists of the diagram directly doesn't, so build it
+    /// again after doing that.
+    ///
+    /// Inside the diagram, the grid gives the face of the nearest site out of the
+    /// ones it lists. That is the same face as without it as long as the cells are
+    /// the points nearest their sites, which they aren't under other metrics.
+    pub fn build_accelerator(&mut self) {
+        self.accelerator = Some(FaceGrid::new(self));
+    }
+
+    /// The grid from `build_accelerator`, if it has been built
+    pub fn accelerator(&self) -> Option<&FaceGrid<T>> {
+        self.accelerator.as_ref().filter(|grid| grid.fits(self))
+    }
+
+    /// Faces whose cells overlap a rect, such as the part of the diagram in view,
+    /// in order of their ids
+    ///
+    /// Cells that only touch the rect count. This uses the grid from
+    /// `build_accelerator` to find the faces near the rect if it has been built,
+    /// and looks at every face otherwise. Returns nothing if the size of the rect
+    /// is negative.
+    pub fn faces_in_rect(&self, rect: Rect<T>) -> Vec<FaceId> {
+        range::faces_in_rect(self, rect)
+    }
+
+    /// Faces whose cells overlap a circle, in order of their ids
+    ///
+    /// Like `faces_in_rect`, cells that only touch it count, and the faces near it
+    /// are found with the grid if there is one. Returns nothing if the radius is
+    /// negative.
+    pub fn faces_in_circle(&self, centre: Point2<T>, radius: T) -> Vec<FaceId> {
+        range::faces_in_circle(self, centre, radius)
+    }
+
+    /// Follows a line segment across the cells, giving each face it passes through
+    /// and the part of the segment inside it, in order from `start` to `end`
+    ///
+    /// The walk starts in the face `locate` gives for `start` and only looks at the
+    /// faces along the way. Parts of the segment outside a clipped diagram are
+    /// left out. Where it passes through a corner, faces that it only touches
+    /// there aren't given.
+    pub fn walk_segment(&self, start: Point2<T>, end: Point2<T>) -> SegmentWalk<'_, T> {
+        SegmentWalk::new(self, start, end)
+    }
+
+    /// Finds the cheapest way from one face to another, going from each face to
+    /// the next across an edge they share
+    ///
+    /// The path gives the faces in order along with the edges it crosses and a
+    /// point on each of them, so it can be followed like a navigation mesh. Use
+    /// `path::SiteDistance` for the shortest path between sites, or give the cost
+    /// of crossing each half-edge with a closure. Returns None if either face
+    /// doesn't exist or the goal can't be reached.
+    #[cfg(feature = "pathfinding")]
+    pub fn find_path<C: PathCost<T> + ?Sized>(&self, from: FaceId, to: FaceId, costs: &C) -> Option<Path<T>> {
+        path::find_path(self, from, to, costs)
+    }
+
+    /// Finds the face of the site nearest to a point
+    ///
+    /// Unlike `locate`, this ignores the weights of the sites of a power diagram.
+    /// Returns None if the diagram has no faces.
+    pub fn nearest_site(&self, point: Point2<T>) -> Option<FaceId> {
+        self.knn(point, 1).into_iter().next()
+    }
+
+    /// Finds the faces of the `k` sites nearest to a point, nearest first
+    ///
+    /// Returns fewer than `k` faces if the diagram doesn't have that many.
+    pub fn knn(&self, point: Point2<T>, k: usize) -> Vec<FaceId> {
+        if self.site_index.len() == self.faces.len() {
+            return self.site_index.nearest_unweighted(point, k);
+        }
+
+        // The faces have changed since the index was built, so check every site
+        let mut faces = self.faces.iter().enumerate().map(|(index, face)| {
+            let dx = point.x - face.site.position.x;
+            let dy = point.y - face.site.position.y;
+            (dx * dx + dy * dy, FaceId(index as u32))
+        }).collect::<Vec<_>>();
+        faces.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(Ordering::Equal));
+
+        faces.into_iter().take(k).map(|(_, face_id)| face_id).collect()
+    }
+
+    /// Converts the cells to a GeoJSON feature collection, with a polygon for each
+    /// face in the same order as the faces
+    ///
+    /// Each feature has the index of its face and the id and position of its site
+    /// as properties. Faces without any half-edges get a feature with no geometry.
+    #[cfg(feature = "geojson")]
+    pub fn to_geojson(&self) -> ::geojson::FeatureCollection {
+        export::to_geojson(self)
+    }
+
+    /// Converts the vertices, cells and edges to flat arrays, for renderers and
+    /// for passing to JavaScript without walking the half-edges from there
+    ///
+    /// Positions are converted to f32 and indices are vertex ids, so a vertex
+    /// keeps its id in the buffers. Faces without any half-edges get a cell with
+    /// no corners, and edges that go off to infinity are left out.
+    pub fn to_flat_buffers(&self) -> FlatBuffers {
+        buffers::to_flat_buffers(self)
+    }
+
+    /// Copies the diagram into a list for each field of its vertices, half-edges
+    /// and faces, which is faster to walk and can be uploaded or written out as it
+    /// is
+    ///
+    /// Nothing is lost, and `DiagramSoa::to_diagram` turns it back into the same
+    /// diagram.
+    pub fn to_soa(&self) -> DiagramSoa<T> {
+        soa::to_soa(self)
+    }
+
+    /// Writes the diagram in a compact binary format, which is much quicker to
+    /// write and read back than going through serde, see `read_from`
+    ///
+    /// Every field goes in a flat array of u32s or floats after a short header,
+    /// with coordinates at the precision of `T`. The grid from `build_accelerator`
+    /// isn't written. The writer is buffered, so a plain file can be passed.
+    pub fn write_to<W: ::std::io::Write>(&self, writer: W) -> ::std::io::Result<()> {
+        binary::write_to(self, writer)
+    }
+
+    /// Reads a diagram written by `write_to`
+    ///
+    /// Coordinates written at a different precision are converted to `T`. The data
+    /// is read in large pieces, so the reader doesn't need to be buffered, and it
+    /// has to end where the diagram does.
+    ///
+    /// Gives an error of kind `InvalidData` if the data isn't a diagram in a
+    /// version of the format this can read, if it's cut short or has anything
+    /// after the diagram, or if a link points outside the diagram or the links
+    /// don't pass the checks `validate` makes. The only exception is half-edges
+    /// coming from infinity, so unclipped diagrams can be read back.
+    pub fn read_from<R: ::std::io::Read>(reader: R) -> ::std::io::Result<Diagram<T>> {
+        binary::read_from(reader)
+    }
+
+    /// Writes a table with a row for each edge, giving the ids of the sites either
+    /// side of it and the coordinates of its ends
+    ///
+    /// The columns are `site_a`, `site_b`, `x1`, `y1`, `x2` and `y2`, split by
+    /// `separator`, which is `','` for CSV or `'\t'` for TSV. `site_a` is the site
+    /// of the face on the left going from the first end to the second. Edges along
+    /// the boundary have no `site_b`, and edges that go off to infinity are left
+    /// out.
+    pub fn write_edge_table<W: ::std::io::Write>(&self, writer: W, separator: char) -> ::std::io::Result<()> {
+        table::write_edge_table(self, writer, separator)
+    }
+
+    /// Writes a table with a row for each face, giving the id of its site, the
+    /// area and centroid of its cell, and its corners
+    ///
+    /// The columns are `face`, `site_id`, `area`, `centroid_x`, `centroid_y` and
+    /// `vertices`, split by `separator` like `write_edge_table`. The corners go
+    /// counter-clockwise in one field as x and y after x and y, split by spaces.
+    /// Faces without any area have an empty centroid.
+    pub fn write_cell_table<W: ::std::io::Write>(&self, writer: W, separator: char) -> ::std::io::Result<()> {
+        table::write_cell_table(self, writer, separator)
+    }
+
+    /// The polygon of each face shrunk by `distance`, so that cells drawn with them
+    /// have a gap of twice that between them
+    ///
+    /// Every edge moves inwards by the distance, in the same order as the faces and
+    /// counter-clockwise. Convex cells are cut down to the part that far from all
+    /// their edges, so short edges disappear and the corners stay sharp. Concave
+    /// cells have their corners moved to where the moved edges meet, which is only
+    /// right while the distance is small compared to their edges. Cells that would
+    /// disappear, faces without any half-edges and cells that go off to infinity
+    /// get no corners. A distance that isn't above zero gives the polygons as they
+    /// are.
+    pub fn inset_faces(&self, distance: T) -> Vec<Vec<Point2<T>>> {
+        offset::inset_faces(self, distance)
+    }
+
+    /// The polygon of each face with its corners rounded off, for drawing cells
+    /// that look less regular
+    ///
+    /// Each iteration of Chaikin's algorithm replaces every edge with the middle
+    /// half of it, which doubles the number of corners, and the polygons approach
+    /// a smooth curve inside the cell. The diagram itself isn't changed. Smoothed
+    /// cells no longer meet exactly, as each cell's corners are cut on its own.
+    /// Polygons are in the same order as the faces, and faces without any
+    /// half-edges or that go off to infinity get no corners.
+    pub fn smooth_cells(&self, iterations: u32) -> Vec<Vec<Point2<T>>> {
+        smooth::smooth_cells(self, iterations)
+    }
+
+    /// Cuts every cell into triangles, for drawing the diagram on a GPU
+    ///
+    /// Each cell gets its own copy of its corners, so the mesh has a face index for
+    /// every vertex that can be used to colour the cells. Positions are converted
+    /// to f32 and triangles are counter-clockwise. Cells can be concave, like ones
+    /// from `clip_to_polygon`. Faces without any half-edges, and cells of an
+    /// unclipped diagram that go off to infinity, get no triangles.
+    pub fn to_mesh(&self) -> Mesh {
+        mesh::to_mesh(self)
+    }
+
+    /// Writes the diagram to a Wavefront OBJ file, as lines along its edges or as
+    /// its cells cut into triangles
+    ///
+    /// Edges and cells that go off to infinity are left out.
+    #[cfg(feature = "io")]
+    pub fn write_obj<W: ::std::io::Write>(&self, writer: W, geometry: DiagramGeometry) -> ::std::io::Result<()> {
+        mesh_io::write_diagram_obj(self, geometry, writer)
+    }
+
+    /// Writes the diagram to an ASCII PLY file, as an edge element of its edges or
+    /// a face element of its cells cut into triangles
+    ///
+    /// Edges and cells that go off to infinity are left out.
+    #[cfg(feature = "io")]
+    pub fn write_ply<W: ::std::io::Write>(&self, writer: W, geometry: DiagramGeometry) -> ::std::io::Result<()> {
+        mesh_io::write_diagram_ply(self, geometry, writer)
+    }
+
+    /// The polygon of a face as a geo polygon, with its exterior ring in
+    /// counter-clockwise order
+    ///
+    /// Returns None if the face doesn't exist or has no half-edges.
+    #[cfg(feature = "geo")]
+    pub fn face_polygon(&self, face_id: FaceId) -> Option<::geo_types::Polygon<T>> where T: ::geo_types::CoordFloat {
+        interop::face_polygon(self, face_id)
+    }
+
+    /// Adds a site to the diagram without rebuilding it, and returns its face
+    ///
+    /// Only the cells around the new site are changed. Ids of existing faces stay
+    /// the same, but vertices and half-edges that are no longer needed are removed,
+    /// which moves the last ones into their places. The new site gets an id one
+    /// more than the largest so far, and no weight in a power diagram.
+    ///
+    /// Returns None without changing anything if the point is outside the diagram,
+    /// or is the same as an existing site in a diagram that isn't weighted. It
+    /// also does if the diagram was built under a metric other than the Euclidean
+    /// one, or if the cells around the point aren't closed convex cells with
+    /// their edges on the bisectors of the sites either side. That rules out the
+    /// open cells from `finish_unclipped`, and cells changed by edits like
+    /// `snap_to_grid` or `add_noisy_edges`. Those can only be built again with
+    /// the new site.
+    pub fn insert_site(&mut self, position: Point2<T>) -> Option<FaceId> {
+        self.accelerator = None;
+        insert::insert_site(self, position)
+    }
+
+    /// Builds a new diagram with every face cut down to the part inside a polygon
+    ///
+    /// The polygon must be simple, but can be concave and its corners can be in
+    /// either order. Faces keep their ids, and faces outside the polygon are left
+    /// without any half-edges. When the polygon splits a face into several pieces,
+    /// the first piece keeps the face and the others are added at the end as new
+    /// faces with the same site.
+    pub fn clip_to_polygon(&self, polygon: &[Point2<T>]) -> Diagram<T> {
+        clip::clip_to_polygon(self, polygon)
+    }
+
+    /// Splits an edge in two at a point, and returns the new vertex there
+    ///
+    /// The half-edge keeps the part of the edge before the point, and a new
+    /// half-edge after it takes the rest. Its twin is split in the same way. The
+    /// point isn't checked, so putting it anywhere other than on the edge bends
+    /// it. Returns None without changing anything if the half-edge doesn't exist
+    /// or either end of it goes off to infinity.
+    pub fn split_edge(&mut self, halfedge_id: HalfEdgeId, point: Point2<T>) -> Option<VertexId> {
+        self.accelerator = None;
+        edit::split_edge(self, halfedge_id, point)
+    }
+
+    /// Shrinks an edge down to a point halfway along it, and returns the vertex
+    /// that is left there
+    ///
+    /// The edges at the far end are moved over to the vertex at the start. The
+    /// half-edge, its twin and the vertex at the far end are removed, which moves
+    /// the last ones into their places. Returns None without changing anything
+    /// if the half-edge doesn't exist, either end goes off to infinity, a face on
+    /// either side would be left with fewer than three edges, or a face would be
+    /// left touching itself at the vertex. That happens when the edge runs between
+    /// two points on the boundary, or both ends are corners of some other face.
+    pub fn collapse_edge(&mut self, halfedge_id: HalfEdgeId) -> Option<VertexId> {
+        self.accelerator = None;
+        edit::collapse_edge(self, halfedge_id)
+    }
+
+    /// Merges a face into another one next to it, by removing the edges between
+    /// them
+    ///
+    /// The first face gets all of the half-edges that are left, and the other one
+    /// is left without any but keeps its id and site, so `locate` can still return
+    /// it. Corners that only joined the two faces are removed too.
+    ///
+    /// The edges between the faces have to follow on from each other, so that the
+    /// merged face has no hole. Returns false without changing anything otherwise,
+    /// or if the faces don't share an edge.
+    pub fn merge_faces(&mut self, face_id: FaceId, other_id: FaceId) -> bool {
+        self.accelerator = None;
+        edit::merge_faces(self, face_id, other_id)
+    }
+
+    /// Moves every vertex to the nearest point on a grid of squares `cell_size`
+    /// across, with a corner at the origin, and merges the ends of edges that end
+    /// up at the same point
+    ///
+    /// Faces that are left with only two edges between the same two points are
+    /// removed, and the faces either side of them become neighbours. Vertices that
+    /// end up at the same point without an edge between them are left separate,
+    /// and so are the ends of an edge that can't be collapsed without a face
+    /// touching itself, see `collapse_edge`. Nothing happens if the cell size isn't
+    /// above zero.
+    pub fn snap_to_grid(&mut self, cell_size: T) {
+        self.accelerator = None;
+        edit::snap_to_grid(self, cell_size)
+    }
+
+    /// Collapses every edge shorter than `epsilon`, to clean up the tiny edges and
+    /// almost coincident vertices that rounding leaves around sites that are
+    /// nearly on a circle
+    ///
+    /// Vertices joined by a chain of short edges all end up as one. It goes where
+    /// the outline of the diagram would stay the same: at a corner of the boundary
+    /// or on its edge if either end is there, and otherwise halfway along the edge.
+    /// Faces that shrink to two edges are removed, like in `snap_to_grid`, and edges
+    /// that can't be collapsed without a face touching itself are left. Nothing
+    /// happens if `epsilon` isn't above zero.
+    pub fn simplify(&mut self, epsilon: T) {
+        self.accelerator = None;
+        edit::simplify(self, epsilon)
+    }
+
+    /// Makes the edges between cells wiggly, by splitting each one into `2^depth`
+    /// pieces with their ends moved at random
+    ///
+    /// `amplitude` goes from zero, which leaves the edges straight, to one, which
+    /// lets the middle of an edge move all the way to one of the sites either side
+    /// of it. The edges are split with `split_edge`, so cells still share their
+    /// edges exactly, and the same seed always gives the same edges. Edges along
+    /// the boundary and edges that go off to infinity stay straight. In a diagram
+    /// with convex cells the edges never cross each other.
+    pub fn add_noisy_edges(&mut self, amplitude: T, depth: u32, seed: u64) {
+        self.accelerator = None;
+        noise::add_noisy_edges(self, amplitude, depth, seed)
+    }
+
+    /// Whether a face's cell reaches the outline of the diagram
+    ///
+    /// These are the cells with an edge along the clip region, which has no twin,
+    /// or with an edge going off to infinity in an unclipped diagram. Cells that
+    /// only touch the outline at a corner don't count, and nor do faces that don't
+    /// exist or have no half-edges. Unlike comparing vertices to the bounding
+    /// rect, this still works after the vertices have been moved.
+    pub fn touches_boundary(&self, face_id: FaceId) -> bool {
+        self.face_edges(face_id)
+            .filter_map(|halfedge_id| self.get_half_edge(halfedge_id))
+            .any(|halfedge| halfedge.twin.is_none() || halfedge.origin.is_none())
+    }
+
+    /// Faces whose cells reach the outline of the diagram, see `touches_boundary`,
+    /// in order of their ids
+    pub fn boundary_faces(&self) -> Vec<FaceId> {
+        (0..self.faces.len() as u32).map(FaceId).filter(|&face_id| self.touches_boundary(face_id)).collect()
+    }
+
+    /// Faces whose sites are the corners of the convex hull of all the sites, in
+    /// counter-clockwise order starting from the one furthest left
+    ///
+    /// These all have cells that were unbounded before clipping. Sites along an
+    /// edge of the hull are left out, and if every site is in a line this is just
+    /// the two at its ends.
+    pub fn convex_hull(&self) -> Vec<FaceId> {
+        hull::convex_hull(self)
+    }
+
+    /// Faces in each of the nested convex hulls of the sites, from the outside in
+    ///
+    /// The first layer is the hull of all the sites, the next is the hull of the
+    /// ones left after taking those away, and so on until there are none left.
+    /// This is also called onion peeling. Unlike `convex_hull`, sites along an edge
+    /// of a hull are in its layer, as are faces with their sites in the same place.
+    /// Each layer goes counter-clockwise from the site furthest left, or along the
+    /// line if its sites are all in one.
+    pub fn convex_layers(&self) -> Vec<Vec<FaceId>> {
+        hull::convex_layers(self)
+    }
+
+    /// Positions of the sites of the faces from `convex_hull`, in the same order
+    pub fn convex_hull_points(&self) -> Vec<Point2<T>> {
+        self.convex_hull().into_iter().map(|face_id| self.faces[face_id.0 as usize].site.position).collect()
+    }
+
+    /// Edges of the minimum spanning tree of the sites, as the faces at each end
+    /// and the distance between their sites, from shortest to longest
+    ///
+    /// Only pairs of faces that share an edge are tried, which is enough for
+    /// ordinary diagrams clipped to a convex region. Power diagrams join up
+    /// different pairs, and if the faces can't all be reached from each other this
+    /// gives a tree for each group of them.
+    pub fn euclidean_mst(&self) -> Vec<(FaceId, FaceId, T)> {
+        proximity::euclidean_mst(self)
+    }
+
+    /// Pairs of faces whose sites have no other site inside the circle that has
+    /// the two of them at opposite ends of a diameter, lower id first
+    ///
+    /// Sites on the circle don't count, but sites whose cells only meet at a
+    /// corner aren't paired. Like `euclidean_mst`, this only tries faces that share
+    /// an edge.
+    pub fn gabriel_graph(&self) -> Vec<(FaceId, FaceId)> {
+        proximity::gabriel_graph(self)
+    }
+
+    /// Pairs of faces whose sites have no other site nearer to both of them than
+    /// they are to each other, lower id first
+    ///
+    /// This is the relative neighbourhood graph. It's part of the Gabriel graph
+    /// and contains the minimum spanning tree.
+    pub fn rng_graph(&self) -> Vec<(FaceId, FaceId)> {
+        proximity::rng_graph(self)
+    }
+
+    /// The faces that share an edge with each face, indexed by face id
+    ///
+    /// Each list goes counter-clockwise around the face like `neighbours`, but
+    /// has each neighbour once even where they share more than one edge.
+    pub fn adjacency(&self) -> Vec<Vec<FaceId>> {
+        adjacency::adjacency(self)
+    }
+
+    /// A colour for every face, indexed by face id, such that faces sharing an
+    /// edge never have the same colour
+    ///
+    /// Colours are numbered from zero, and greedy colouring in smallest-last order
+    /// never needs more than six of them, and usually needs four or five. Faces
+    /// that only touch at a corner, or along the boundary of a clipped diagram,
+    /// may share a colour. None if this way of colouring them needs more than
+    /// `max_colors` colours.
+    pub fn color_faces(&self, max_colors: u32) -> Option<Vec<u32>> {
+        adjacency::color_faces(self, max_colors)
+    }
+
+    /// Groups the faces into regions of neighbouring faces, giving the region of
+    /// each face indexed by its id
+    ///
+    /// `same_region` is called once for each pair of faces that share an edge,
+    /// with the lower id first, and the two are put in the same region if it
+    /// returns true. Regions are numbered from zero in order of their faces with
+    /// the lowest ids.
+    pub fn group_faces<F: Fn(FaceId, FaceId) -> bool>(&self, same_region: F) -> Vec<RegionId> {
+        regions::group_faces(self, same_region)
+    }
+
+    /// The outline of each region from `group_faces`, as rings of points indexed
+    /// by region id
+    ///
+    /// The outline of a region is made of the edges between its faces and faces in
+    /// other regions, along with the edges on the outline of the diagram. Outer
+    /// rings go counter-clockwise and the rings around holes go clockwise. Where an
+    /// outline touches itself at a corner, it's split into separate rings there.
+    /// Edges going off to infinity in an unclipped diagram leave their far ends out.
+    pub fn region_outlines(&self, regions: &[RegionId]) -> Vec<Vec<Vec<Point2<T>>>> {
+        regions::region_outlines(self, regions)
+    }
+
+    /// The outline of the area covered by some faces, as rings of points
+    ///
+    /// Only the edges between a face in the list and one that isn't, or the
+    /// outline of the diagram, are followed. The rings go the same ways as in
+    /// `region_outlines`, counter-clockwise around the outside and clockwise
+    /// around holes, and faces that don't exist are ignored.
+    pub fn region_outline(&self, faces: &[FaceId]) -> Vec<Vec<Point2<T>>> {
+        regions::region_outline(self, faces)
+    }
+
+    /// The neighbour each face's water runs off to, given the elevation of each
+    /// face indexed by its id
+    ///
+    /// This is the lowest neighbour that's lower than the face, or None if there
+    /// isn't one, so the face is a sink. Ties go to the neighbour with the lowest
+    /// id. Faces without an elevation, past the end of `elevations` or NaN, aren't
+    /// neighbours of anything and don't run off anywhere.
+    pub fn downhill(&self, elevations: &[T]) -> Vec<Option<FaceId>> {
+        hydrology::downhill(self, elevations)
+    }
+
+    /// Groups the faces into the drainage basins of the sinks that their water
+    /// runs down to, following `downhill`
+    ///
+    /// Basins are numbered like the regions from `group_faces`, and can be
+    /// outlined with `region_outlines` the same way.
+    pub fn drainage_basins(&self, elevations: &[T]) -> Vec<RegionId> {
+        hydrology::drainage_basins(self, elevations)
+    }
+
+    /// Half-edges that rivers run along, given the elevation of each face, with
+    /// how much water flows down them
+    ///
+    /// Rivers follow the edges between cells. Each vertex is as high as the
+    /// average of the cells around it and sends its water down the edge to its
+    /// lowest neighbour, if that's lower. Every vertex adds one to the water it
+    /// passes on, so the flow down an edge is the number of vertices upstream of
+    /// it. Half-edges with a flow of at least `min_flow` are given in order of their
+    /// ids, pointing downhill.
+    pub fn rivers(&self, elevations: &[T], min_flow: u32) -> Vec<(HalfEdgeId, u32)> {
+        hydrology::rivers(self, elevations, min_flow)
+    }
+
+    /// The faces as the nodes of a petgraph graph, with an edge between each pair
+    /// that share an edge
+    ///
+    /// Node `i` is face `i` and has its id as its weight. Edges are weighted with
+    /// the length of the border between the two faces, which is infinite for cells
+    /// of an unclipped diagram that share a ray.
+    #[cfg(feature = "petgraph")]
+    pub fn to_petgraph(&self) -> ::petgraph::Graph<FaceId, T, ::petgraph::Undirected> {
+        adjacency::to_petgraph(self)
+    }
+
+    /// Renumbers the vertices, half-edges and faces into an order that only
+    /// depends on the sites and the coordinates of the corners
+    ///
+    /// Faces are sorted by the ids of their sites, then by position. Half-edges go
+    /// around each face in turn, starting from its lowest corner, which becomes its
+    /// first half-edge, and vertices are numbered in the order that half-edges
+    /// leave them. So building the same sites always gives exactly the same
+    /// diagram after this, even if they were given in a different order.
+    ///
+    /// Face ids no longer match the order the sites were given in, so a
+    /// `SiteMapping` from the builder doesn't apply afterwards. The diagram has to
+    /// pass `validate`, or this may panic.
+    pub fn canonicalize(&mut self) {
+        self.accelerator = None;
+        canonical::canonicalize(self)
+    }
+
+    /// Whether two diagrams have the same cells, joined up the same way, no matter
+    /// what order they were built in
+    ///
+    /// Faces are matched up by the ids of their sites, and each one has to have the
+    /// same neighbours in the same order around it. Coordinates aren't compared, so
+    /// this is also true after the sites have moved a little, as long as no edge
+    /// appeared or went away. Both diagrams have to pass `validate`.
+    pub fn eq_topology(&self, other: &Diagram<T>) -> bool {
+        canonical::eq_topology(self, other)
+    }
+
+    /// Checks that the links between vertices, half-edges and faces are consistent
+    ///
+    /// Every clipped diagram built by this crate passes, so this is mostly useful
+    /// after changing a diagram by hand or deserializing one. Faces without any
+    /// half-edges are allowed.
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        validate::validate(self)
+    }
+
+    /// Iterates over the half-edges leaving a vertex in counter-clockwise order
+    ///
+    /// For vertices on the bounding rect, this starts from the edge that runs
+    /// along the boundary so that every edge is visited.
+    pub fn incident_edges(&self, vertex_id: VertexId) -> VertexIncidentEdges<'_, T> {
+        let first_halfedge = self.get_vertex(vertex_id).map(|vertex| {
+            // Turn clockwise until we either get back to where we started or
+            // reach the boundary
+            let mut halfedge_id = vertex.incident_edge;
+            while let Some(twin) = self.halfedges[halfedge_id.0 as usize].twin {
+                halfedge_id = self.halfedges[twin.0 as usize].next;
+                if halfedge_id == vertex.incident_edge {
+                    break;
+                }
+            }
+            halfedge_id
+        });
+
+        VertexIncidentEdges {
+            diagram: self,
+            first_halfedge,
+            next_halfedge: first_halfedge,
+        }
+    }
+
+    /// Iterates over the faces that share an edge with a face
+    pub fn neighbours(&self, face_id: FaceId) -> FaceNeighbours<'_, T> {
+        FaceNeighbours {
+            edges: self.face_edges(face_id),
+        }
+    }
+
+    /// Same as `neighbours`
+    pub fn neighbors(&self, face_id: FaceId) -> FaceNeighbors<'_, T> {
+        self.neighbours(face_id)
+    }
+}
+
+/// Panics if the vertex doesn't exist, see `Diagram::get_vertex` for a version
+/// that doesn't
+impl<T> Index<VertexId> for Diagram<T> {
+    type Output = Vertex<T>;
+
+    fn index(&self, vertex_id: VertexId) -> &Vertex<T> {
+        &self.vertices[vertex_id.0 as usize]
+    }
+}
+
+impl<T> IndexMut<VertexId> for Diagram<T> {
+    fn index_mut(&mut self, vertex_id: VertexId) -> &mut Vertex<T> {
+        &mut self.vertices[vertex_id.0 as usize]
+    }
+}
+
+impl<T> Index<HalfEdgeId> for Diagram<T> {
+    type Output = HalfEdge;
+
+    fn index(&self, halfedge_id: HalfEdgeId) -> &HalfEdge {
+        &self.halfedges[halfedge_id.0 as usize]
+    }
+}
+
+impl<T> IndexMut<HalfEdgeId> for Diagram<T> {
+    fn index_mut(&mut self, halfedge_id: HalfEdgeId) -> &mut HalfEdge {
+        &mut self.halfedges[halfedge_id.0 as usize]
+    }
+}
+
+impl<T> Index<FaceId> for Diagram<T> {
+    type Output = Face<T>;
+
+    fn index(&self, face_id: FaceId) -> &Face<T> {
+        &self.faces[face_id.0 as usize]
+    }
+}
+
+impl<T> IndexMut<FaceId> for Diagram<T> {
+    fn index_mut(&mut self, face_id: FaceId) -> &mut Face<T> {
+        &mut self.faces[face_id.0 as usize]
+    }
+}
+
+/// Iterator over the half-edges around a face, see `Diagram::face_edges`
+#[derive(Debug, Clone)]
+pub struct FaceEdges<'a, T: 'a = f32> {
+    diagram: &'a Diagram<T>,
+    first_halfedge: Option<HalfEdgeId>,
+    next_halfedge: Option<HalfEdgeId>,
+}
+
+impl<'a, T: BaseFloat> Iterator for FaceEdges<'a, T> {
+    type Item = HalfEdgeId;
+
+    fn next(&mut self) -> Option<HalfEdgeId> {
+        let halfedge_id = self.next_halfedge?;
+        let next = self.diagram.get_half_edge(halfedge_id)?.next;
+
+        self.next_halfedge = if Some(next) == self.first_halfedge {
+            None
+        } else {
+            Some(next)
+        };
+
+        Some(halfedge_id)
+    }
+}
+
+/// Iterator over the half-edges leaving a vertex, see `Diagram::incident_edges`
+#[derive(Debug, Clone)]
+pub struct VertexIncidentEdges<'a, T: 'a = f32> {
+    diagram: &'a Diagram<T>,
+    first_halfedge: Option<HalfEdgeId>,
+    next_halfedge: Option<HalfEdgeId>,
+}
+
+impl<'a, T: BaseFloat> Iterator for VertexIncidentEdges<'a, T> {
+    type Item = HalfEdgeId;
+
+    fn next(&mut self) -> Option<HalfEdgeId> {
+        let halfedge_id = self.next_halfedge?;
+
+        // The next edge counter-clockwise is the twin of the edge coming into
+        // the vertex before this one
+        let prev = self.diagram.get_half_edge(halfedge_id)?.prev;
+        let next = self.diagram.get_half_edge(prev)?.twin;
+
+        self.next_halfedge = if next == self.first_halfedge {
+            None
+        } else {
+            next
+        };
+
+        Some(halfedge_id)
+    }
+}
+
+/// Iterator over the neighbours of a face, see `Diagram::neighbours`
+#[derive(Debug, Clone)]
+pub struct FaceNeighbours<'a, T: 'a = f32> {
+    edges: FaceEdges<'a, T>,
+}
+
+/// Same as `FaceNeighbours`, see `Diagram::neighbors`
+pub type FaceNeighbors<'a, T = f32> = FaceNeighbours<'a, T>;
+
+impl<'a, T: BaseFloat> Iterator for FaceNeighbours<'a, T> {
+    type Item = FaceId;
+
+    fn next(&mut self) -> Option<FaceId> {
+        let diagram = self.edges.diagram;
+
+        // Skip over edges along the boundary, which have no twin
+        self.edges.by_ref()
+            .filter_map(|halfedge_id| diagram.get_half_edge(halfedge_id)?.twin)
+            .filter_map(|twin| diagram.get_half_edge(twin))
+            .map(|twin| twin.incident_face)
+            .next()
+    }
 }
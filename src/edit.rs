@@ -0,0 +1,405 @@
+//! Changing the cells of a finished diagram while keeping its links consistent
+//!
+//! Vertices and half-edges are removed by moving the last one into their place,
+//! like `Vec::swap_remove`, so only the links to the moved one need updating.
+//! Faces are never removed, as other code refers to them by id; a face that is
+//! merged into another is left without any half-edges instead.
+
+use std::cmp::Ordering;
+
+use cgmath::{Point2, InnerSpace, BaseFloat};
+
+use diagram::{Diagram, Vertex, VertexId, HalfEdge, HalfEdgeId, FaceId};
+
+/// How far a point on the boundary can be off the line through the points either
+/// side of it and still not count as a corner, in multiples of the precision of
+/// the scalar type at the size of the diagram
+const CORNER_TOLERANCE: f64 = 64.0;
+
+/// See `Diagram::split_edge`
+pub fn split_edge<T: BaseFloat>(diagram: &mut Diagram<T>, halfedge_id: HalfEdgeId, point: Point2<T>) -> Option<VertexId> {
+    let halfedge = diagram.get_half_edge(halfedge_id)?.clone();
+    halfedge.origin?;
+    diagram.get_half_edge(halfedge.next)?.origin?;
+
+    // The half-edge keeps the first part of the edge and a new one after it takes
+    // the rest, and the same happens on the other side
+    let vertex_id = VertexId(diagram.vertices.len() as u32);
+    let after = diagram.push_halfedge(HalfEdge {
+        origin: Some(vertex_id),
+        twin: halfedge.twin,
+        incident_face: halfedge.incident_face,
+        next: halfedge.next,
+        prev: halfedge_id,
+    });
+    diagram.halfedges[halfedge.next.0 as usize].prev = after;
+    diagram.halfedges[halfedge_id.0 as usize].next = after;
+    diagram.push_vertex(Vertex {
+        coordinates: point,
+        incident_edge: after,
+    });
+
+    if let Some(twin_id) = halfedge.twin {
+        let twin = diagram.halfedges[twin_id.0 as usize].clone();
+        let twin_after = diagram.push_halfedge(HalfEdge {
+            origin: Some(vertex_id),
+            twin: Some(halfedge_id),
+            incident_face: twin.incident_face,
+            next: twin.next,
+            prev: twin_id,
+        });
+        diagram.halfedges[twin.next.0 as usize].prev = twin_after;
+        diagram.halfedges[twin_id.0 as usize].next = twin_after;
+        diagram.halfedges[twin_id.0 as usize].twin = Some(after);
+        diagram.halfedges[halfedge_id.0 as usize].twin = Some(twin_after);
+    }
+
+    Some(vertex_id)
+}
+
+/// Number of half-edges around the face of a half-edge
+fn cycle_length<T: BaseFloat>(diagram: &Diagram<T>, halfedge_id: HalfEdgeId) -> usize {
+    let face_id = diagram.halfedges[halfedge_id.0 as usize].incident_face;
+    diagram.face_edges(face_id).take(diagram.halfedges.len()).count()
+}
+
+fn on_boundary<T: BaseFloat>(diagram: &Diagram<T>, vertex_id: VertexId) -> bool {
+    diagram.incident_edges(vertex_id).any(|halfedge_id| diagram.halfedges[halfedge_id.0 as usize].twin.is_none())
+}
+
+/// Whether the boundary turns a corner at a vertex, rather than going straight on
+fn at_corner<T: BaseFloat>(diagram: &Diagram<T>, vertex_id: VertexId) -> bool {
+    let mut before = None;
+    let mut after = None;
+    for halfedge_id in diagram.incident_edges(vertex_id) {
+        let halfedge = &diagram.halfedges[halfedge_id.0 as usize];
+        if halfedge.twin.is_none() {
+            after = diagram.halfedges[halfedge.next.0 as usize].origin;
+        }
+        let prev = &diagram.halfedges[halfedge.prev.0 as usize];
+        if prev.twin.is_none() {
+            before = prev.origin;
+        }
+    }
+
+    match (before, after) {
+        (Some(before), Some(after)) => {
+            let (a, b, c) = (diagram.vertices[before.0 as usize].coordinates, diagram.vertices[vertex_id.0 as usize].coordinates, diagram.vertices[after.0 as usize].coordinates);
+            let turn = (b.x - a.x) * (c.y - b.y) - (b.y - a.y) * (c.x - b.x);
+
+            // Clipping rounds points on the boundary a little way off it, which
+            // changes the turn by up to that much times the lengths of the edges
+            let size = [a, b, c].iter().fold(T::zero(), |size, point| size.max(point.x.abs()).max(point.y.abs()));
+            let lengths = (b - a).magnitude() + (c - b).magnitude();
+            turn.abs() > size * T::epsilon() * T::from(CORNER_TOLERANCE).unwrap() * lengths
+        }
+        _ => false,
+    }
+}
+
+/// How much a vertex should stay where it is when an edge to it is collapsed:
+/// corners of the boundary most, then other points on the boundary
+fn rank<T: BaseFloat>(diagram: &Diagram<T>, vertex_id: VertexId) -> u8 {
+    if !on_boundary(diagram, vertex_id) {
+        0
+    } else if at_corner(diagram, vertex_id) {
+        2
+    } else {
+        1
+    }
+}
+
+fn midpoint<T: BaseFloat>(a: Point2<T>, b: Point2<T>) -> Point2<T> {
+    let two = T::one() + T::one();
+    Point2::new((a.x + b.x) / two, (a.y + b.y) / two)
+}
+
+fn faces_around<T: BaseFloat>(diagram: &Diagram<T>, vertex_id: VertexId) -> Vec<FaceId> {
+    diagram.incident_edges(vertex_id).map(|halfedge_id| diagram.halfedges[halfedge_id.0 as usize].incident_face).collect()
+}
+
+/// Whether an edge can shrink to a point without any face touching itself there
+///
+/// The only faces at both ends have to be the ones either side of the edge. An
+/// edge across the diagram from one side of the boundary to the other would leave
+/// the faces either side of it touching at a single corner, with the outside
+/// touching itself there.
+fn can_collapse<T: BaseFloat>(diagram: &Diagram<T>, halfedge_id: HalfEdgeId, from: VertexId, to: VertexId) -> bool {
+    let halfedge = &diagram.halfedges[halfedge_id.0 as usize];
+    let sides = Some(halfedge.incident_face).into_iter()
+        .chain(halfedge.twin.map(|twin| diagram.halfedges[twin.0 as usize].incident_face))
+        .collect::<Vec<_>>();
+    let across = halfedge.twin.is_some() && on_boundary(diagram, from) && on_boundary(diagram, to);
+    let to_faces = faces_around(diagram, to);
+    from != to && !across && faces_around(diagram, from).iter().all(|face| sides.contains(face) || !to_faces.contains(face))
+}
+
+/// See `Diagram::collapse_edge`
+pub fn collapse_edge<T: BaseFloat>(diagram: &mut Diagram<T>, halfedge_id: HalfEdgeId) -> Option<VertexId> {
+    let halfedge = diagram.get_half_edge(halfedge_id)?.clone();
+    let from = halfedge.origin?;
+    let to = diagram.get_half_edge(halfedge.next)?.origin?;
+
+    // Faces on either side have to stay polygons
+    let sides = Some(halfedge_id).into_iter().chain(halfedge.twin).collect::<Vec<_>>();
+    if !can_collapse(diagram, halfedge_id, from, to) || sides.iter().any(|&side| cycle_length(diagram, side) <= 3) {
+        return None;
+    }
+
+    let point = midpoint(diagram.vertices[from.0 as usize].coordinates, diagram.vertices[to.0 as usize].coordinates);
+    Some(contract(diagram, halfedge_id, from, to, point))
+}
+
+/// See `Diagram::snap_to_grid`
+pub fn snap_to_grid<T: BaseFloat>(diagram: &mut Diagram<T>, cell_size: T) {
+    if cell_size.partial_cmp(&T::zero()) != Some(Ordering::Greater) || !cell_size.is_finite() {
+        return;
+    }
+
+    for vertex in &mut diagram.vertices {
+        let coordinates = vertex.coordinates;
+        vertex.coordinates = Point2::new((coordinates.x / cell_size).round() * cell_size, (coordinates.y / cell_size).round() * cell_size);
+    }
+    collapse_edges(diagram, |a, b| a == b);
+}
+
+/// See `Diagram::simplify`
+pub fn simplify<T: BaseFloat>(diagram: &mut Diagram<T>, epsilon: T) {
+    if epsilon.partial_cmp(&T::zero()) != Some(Ordering::Greater) {
+        return;
+    }
+
+    collapse_edges(diagram, |a, b| (b - a).magnitude() < epsilon);
+}
+
+/// Collapses every edge whose ends are close enough together by `close`, and
+/// removes faces that are left with two edges
+///
+/// The vertex left where an edge was is at whichever end is on the boundary, or
+/// where the boundary turns a corner if both are, so the outline of the diagram
+/// doesn't move. Otherwise it's halfway between them. Edges that can't be
+/// collapsed without a face touching itself are left as they are.
+pub fn collapse_edges<T: BaseFloat, F: Fn(Point2<T>, Point2<T>) -> bool>(diagram: &mut Diagram<T>, close: F) {
+    // Collapsing an edge can move the last half-edge into a place that has
+    // already been looked at, so this goes round until nothing changes
+    let mut changed = true;
+    while changed {
+        changed = false;
+        let mut index = 0;
+        while index < diagram.halfedges.len() {
+            if collapse_if_close(diagram, HalfEdgeId(index as u32), &close) {
+                changed = true;
+            } else {
+                index += 1;
+            }
+        }
+    }
+}
+
+fn collapse_if_close<T: BaseFloat, F: Fn(Point2<T>, Point2<T>) -> bool>(diagram: &mut Diagram<T>, halfedge_id: HalfEdgeId, close: &F) -> bool {
+    let halfedge = diagram.halfedges[halfedge_id.0 as usize].clone();
+    let (from, to) = match (halfedge.origin, diagram.halfedges[halfedge.next.0 as usize].origin) {
+        (Some(from), Some(to)) => (from, to),
+        _ => return false,
+    };
+    let (a, b) = (diagram.vertices[from.0 as usize].coordinates, diagram.vertices[to.0 as usize].coordinates);
+
+    // A face with two edges would be left with one, which can't be removed
+    let sides = Some(halfedge_id).into_iter().chain(halfedge.twin).collect::<Vec<_>>();
+    if !close(a, b) || !can_collapse(diagram, halfedge_id, from, to) || sides.iter().any(|&side| cycle_length(diagram, side) <= 2) {
+        return false;
+    }
+
+    let faces = sides.iter().map(|&side| diagram.halfedges[side.0 as usize].incident_face).collect::<Vec<_>>();
+    let point = match rank(diagram, from).cmp(&rank(diagram, to)) {
+        Ordering::Greater => a,
+        Ordering::Less => b,
+        Ordering::Equal => midpoint(a, b),
+    };
+    contract(diagram, halfedge_id, from, to, point);
+    for face_id in faces {
+        if diagram.face_edges(face_id).take(3).count() == 2 {
+            dissolve_face(diagram, face_id);
+        }
+    }
+    true
+}
+
+/// Shrinks an edge to a point, see `collapse_edge`, and returns the vertex there
+fn contract<T: BaseFloat>(diagram: &mut Diagram<T>, halfedge_id: HalfEdgeId, from: VertexId, to: VertexId, point: Point2<T>) -> VertexId {
+    let halfedge = diagram.halfedges[halfedge_id.0 as usize].clone();
+    let sides = Some(halfedge_id).into_iter().chain(halfedge.twin).collect::<Vec<_>>();
+    let outgoing = diagram.incident_edges(to).collect::<Vec<_>>();
+    for &side in &sides {
+        let HalfEdge { incident_face, next, prev, .. } = diagram.halfedges[side.0 as usize];
+        diagram.halfedges[prev.0 as usize].next = next;
+        diagram.halfedges[next.0 as usize].prev = prev;
+
+        let face = &mut diagram.faces[incident_face.0 as usize];
+        if face.first_halfedge == Some(side) {
+            face.first_halfedge = Some(next);
+        }
+    }
+    for other in outgoing {
+        diagram.halfedges[other.0 as usize].origin = Some(from);
+    }
+
+    diagram.vertices[from.0 as usize] = Vertex {
+        coordinates: point,
+        incident_edge: halfedge.next,
+    };
+
+    remove_halfedges(diagram, sides);
+    let last = VertexId(diagram.vertices.len() as u32 - 1);
+    remove_vertices(diagram, vec![to]);
+    if from == last { to } else { from }
+}
+
+/// Removes a face with two edges, by making the edges on the other side of them
+/// twins of each other
+fn dissolve_face<T: BaseFloat>(diagram: &mut Diagram<T>, face_id: FaceId) {
+    let first = match diagram.faces[face_id.0 as usize].first_halfedge {
+        Some(first) => first,
+        None => return,
+    };
+    let second = diagram.halfedges[first.0 as usize].next;
+    let (first_twin, second_twin) = (diagram.halfedges[first.0 as usize].twin, diagram.halfedges[second.0 as usize].twin);
+    if let Some(twin) = first_twin {
+        diagram.halfedges[twin.0 as usize].twin = second_twin;
+    }
+    if let Some(twin) = second_twin {
+        diagram.halfedges[twin.0 as usize].twin = first_twin;
+    }
+
+    // Each end keeps an edge that leaves it on the other side, if there is one.
+    // An end at infinity in an unclipped diagram has no vertex to keep
+    let mut dead_vertices = Vec::new();
+    for &(halfedge_id, twin, other_twin) in &[(first, second_twin, first_twin), (second, first_twin, second_twin)] {
+        let vertex_id = match diagram.halfedges[halfedge_id.0 as usize].origin {
+            Some(vertex_id) => vertex_id,
+            None => continue,
+        };
+        let replacement = twin.or_else(|| other_twin.map(|other_twin| diagram.halfedges[other_twin.0 as usize].next));
+        match replacement {
+            Some(replacement) => diagram.vertices[vertex_id.0 as usize].incident_edge = replacement,
+            None => dead_vertices.push(vertex_id),
+        }
+    }
+
+    diagram.faces[face_id.0 as usize].first_halfedge = None;
+    remove_halfedges(diagram, vec![first, second]);
+    remove_vertices(diagram, dead_vertices);
+}
+
+/// See `Diagram::merge_faces`
+pub fn merge_faces<T: BaseFloat>(diagram: &mut Diagram<T>, face_id: FaceId, other_id: FaceId) -> bool {
+    if face_id == other_id || diagram.get_face(face_id).is_none() || diagram.get_face(other_id).is_none() {
+        return false;
+    }
+
+    let cycle = diagram.face_edges(face_id).take(diagram.halfedges.len()).collect::<Vec<_>>();
+    let other_cycle = diagram.face_edges(other_id).take(diagram.halfedges.len()).collect::<Vec<_>>();
+    let shared = cycle.iter()
+        .map(|&halfedge_id| {
+            let twin = diagram.halfedges[halfedge_id.0 as usize].twin;
+            twin.is_some_and(|twin| diagram.halfedges[twin.0 as usize].incident_face == other_id)
+        })
+        .collect::<Vec<_>>();
+
+    // The shared edges have to be in one run, which isn't the whole of either
+    // face, or the merged face would have a hole or nothing around it
+    let count = shared.iter().filter(|&&shared| shared).count();
+    let starts = (0..cycle.len()).filter(|&index| shared[index] && !shared[(index + cycle.len() - 1) % cycle.len()]).collect::<Vec<_>>();
+    if count == 0 || count >= other_cycle.len() || starts.len() != 1 {
+        return false;
+    }
+    let run = (0..count).map(|offset| cycle[(starts[0] + offset) % cycle.len()]).collect::<Vec<_>>();
+    let twins = run.iter().map(|&halfedge_id| diagram.halfedges[halfedge_id.0 as usize].twin.unwrap()).collect::<Vec<_>>();
+    if twins.windows(2).any(|pair| diagram.halfedges[pair[1].0 as usize].next != pair[0]) {
+        return false;
+    }
+
+    // Join the rest of the two cycles up where the run started and ended
+    let (first, last) = (run[0], run[count - 1]);
+    let (first_twin, last_twin) = (twins[0], twins[count - 1]);
+    let before = diagram.halfedges[first.0 as usize].prev;
+    let after = diagram.halfedges[last.0 as usize].next;
+    let other_after = diagram.halfedges[first_twin.0 as usize].next;
+    let other_before = diagram.halfedges[last_twin.0 as usize].prev;
+    diagram.halfedges[before.0 as usize].next = other_after;
+    diagram.halfedges[other_after.0 as usize].prev = before;
+    diagram.halfedges[other_before.0 as usize].next = after;
+    diagram.halfedges[after.0 as usize].prev = other_before;
+
+    for &halfedge_id in &other_cycle {
+        diagram.halfedges[halfedge_id.0 as usize].incident_face = face_id;
+    }
+    diagram.faces[face_id.0 as usize].first_halfedge = Some(before);
+    diagram.faces[other_id.0 as usize].first_halfedge = None;
+
+    // The ends of the run keep their other edges, but the corners inside it only
+    // joined the two faces
+    for &(vertex_id, halfedge_id) in &[(diagram.halfedges[first.0 as usize].origin, other_after), (diagram.halfedges[after.0 as usize].origin, after)] {
+        if let Some(vertex_id) = vertex_id {
+            diagram.vertices[vertex_id.0 as usize].incident_edge = halfedge_id;
+        }
+    }
+    let corners = run[1..].iter().filter_map(|&halfedge_id| diagram.halfedges[halfedge_id.0 as usize].origin).collect();
+
+    remove_halfedges(diagram, run.into_iter().chain(twins).collect());
+
+    remove_vertices(diagram, corners);
+    true
+}
+
+/// Removes half-edges that nothing links to any more
+///
+/// Each one is replaced by the last half-edge, so only the links to that need
+/// updating.
+pub fn remove_halfedges<T>(diagram: &mut Diagram<T>, mut dead: Vec<HalfEdgeId>) {
+    dead.sort_by_key(|halfedge_id| halfedge_id.0);
+    for halfedge_id in dead.into_iter().rev() {
+        let index = halfedge_id.0 as usize;
+        let last = HalfEdgeId(diagram.halfedges.len() as u32 - 1);
+        diagram.halfedges.swap_remove(index);
+        if index == last.0 as usize {
+            continue;
+        }
+
+        let moved = diagram.halfedges[index].clone();
+        diagram.halfedges[moved.next.0 as usize].prev = halfedge_id;
+        diagram.halfedges[moved.prev.0 as usize].next = halfedge_id;
+        if let Some(twin) = moved.twin {
+            diagram.halfedges[twin.0 as usize].twin = Some(halfedge_id);
+        }
+        if let Some(origin) = moved.origin {
+            let vertex = &mut diagram.vertices[origin.0 as usize];
+            if vertex.incident_edge == last {
+                vertex.incident_edge = halfedge_id;
+            }
+        }
+
+        let face = &mut diagram.faces[moved.incident_face.0 as usize];
+        if face.first_halfedge == Some(last) {
+            face.first_halfedge = Some(halfedge_id);
+        }
+    }
+}
+
+/// Removes vertices that no half-edge starts at any more, in the same way as
+/// `remove_halfedges`
+pub fn remove_vertices<T: BaseFloat>(diagram: &mut Diagram<T>, mut dead: Vec<VertexId>) {
+    dead.sort_by_key(|vertex_id| vertex_id.0);
+    for vertex_id in dead.into_iter().rev() {
+        let index = vertex_id.0 as usize;
+        diagram.vertices.swap_remove(index);
+        if index == diagram.vertices.len() {
+            continue;
+        }
+
+        let outgoing = diagram.incident_edges(vertex_id).collect::<Vec<_>>();
+        for halfedge in outgoing {
+            diagram.halfedges[halfedge.0 as usize].origin = Some(vertex_id);
+        }
+    }
+}
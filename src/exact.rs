@@ -0,0 +1,338 @@
+//! Exact comparisons for the sweep, used when the "exact" feature is enabled
+//!
+//! Otherwise the beachline is searched by working out where its breakpoints are,
+//! and events are ordered by the bottoms of their circles, both rounded. Sites
+//! that are nearly collinear or nearly cocircular can then be handled in the wrong
+//! order, which gives the wrong topology. Here both are decided from the sites
+//! themselves with the expansions from `predicates`, so the answer is always the
+//! one exact arithmetic would give. Each comparison is made in floating point
+//! first along with a bound on its error, and only worked out exactly when that is
+//! too close to call.
+//!
+//! Positions of vertices are still rounded, but only once the topology is decided.
+//! Sites are moved to the corner of the clip region before the sweep, which can
+//! round them, and the topology is exact for the sites as they are after that.
+//! The scale they are multiplied by is a power of two so it doesn't round them
+//! any more.
+//!
+//! Most comparisons never need the exact path, but events that really are at the
+//! same place always do, so sites on a lattice or a circle are much slower to
+//! build than sites at random.
+
+use std::borrow::Cow;
+use std::cell::OnceCell;
+use std::cmp::Ordering;
+
+use cgmath::{Point2, BaseFloat};
+#[cfg(feature = "serde")]
+use serde::{Serialize, Serializer, Deserialize, Deserializer};
+
+use predicates::{compress, difference, estimate, expansion_negate, expansion_product, expansion_sum, orient2d_expansion};
+
+/// Relative error bound of the floating point breakpoint tests, with room to spare
+const BREAKPOINT_BOUND: f64 = 16.0 * f64::EPSILON;
+
+/// Relative error bound of the bottom and centre of a circle in floating point,
+/// with room to spare
+const CIRCLE_BOUND: f64 = 64.0 * f64::EPSILON;
+
+fn sign(expansion: &[f64]) -> Ordering {
+    estimate(expansion).partial_cmp(&0.0).unwrap_or(Ordering::Equal)
+}
+
+/// Products and sums of expansions, compressed as they go so that nesting them
+/// doesn't blow up their length
+fn product(a: &[f64], b: &[f64]) -> Vec<f64> {
+    compress(&expansion_product(a, b))
+}
+
+fn sum(a: &[f64], b: &[f64]) -> Vec<f64> {
+    compress(&expansion_sum(a, b))
+}
+
+fn subtract(a: &[f64], b: &[f64]) -> Vec<f64> {
+    sum(a, &expansion_negate(b))
+}
+
+/// The sign of a floating point value if it's further from zero than its error
+fn trusted_sign(value: f64, error: f64) -> Option<Ordering> {
+    if value > error {
+        Some(Ordering::Greater)
+    } else if -value > error {
+        Some(Ordering::Less)
+    } else {
+        None
+    }
+}
+
+/// The largest power of two that isn't above a scale, so multiplying by it
+/// doesn't round. Scales that aren't finite and above zero are left as they are
+pub fn power_of_two<T: BaseFloat>(scale: T) -> T {
+    if scale.partial_cmp(&T::zero()) != Some(Ordering::Greater) || !scale.is_finite() {
+        return scale;
+    }
+
+    (T::one() + T::one()).powi(scale.log2().floor().to_i32().unwrap())
+}
+
+/// Where `x` is on the sweep line compared to the breakpoint between the arcs of
+/// `left` and `right`, when the sweep line is at `directrix`
+///
+/// Both sites have to be at or below the sweep line. Returns `Equal` if `x` is
+/// exactly on the breakpoint.
+pub fn compare_to_breakpoint(x: f64, directrix: f64, left: Point2<f64>, right: Point2<f64>) -> Ordering {
+    // Parabolas of sites at the same height cross halfway between them
+    if left.y == right.y {
+        return sign(&sum(&difference(x, left.x), &difference(x, right.x)));
+    }
+
+    // The arc of a site is the set of points as far from it as from the sweep
+    // line, and it's on the beachline where it's closer to the sweep line than the
+    // other arc. Scaled up so there are no divisions, how much closer the left arc
+    // is at x is
+    //
+    //   g = dl dr (left.y - right.y) - (x - left.x)^2 dr + (x - right.x)^2 dl
+    //
+    // where dl and dr are how far below the sweep line the sites are, and h has the
+    // sign of its slope. g is a quadratic with a root at each place the parabolas
+    // cross, and which root is the breakpoint depends on which parabola is wider
+    let (g, h) = breakpoint_terms(x, directrix, left, right);
+    if left.y < right.y {
+        // The left parabola is wider, so the breakpoint is the left root, where g
+        // is falling
+        match (g, h) {
+            (Ordering::Greater, Ordering::Less) => Ordering::Less,
+            (Ordering::Equal, Ordering::Less) | (Ordering::Equal, Ordering::Equal) => Ordering::Equal,
+            _ => Ordering::Greater,
+        }
+    } else {
+        // The right parabola is wider, so the breakpoint is the right root, where
+        // g is falling. Everywhere to the left of it, g is either above zero or
+        // rising
+        match (g, h) {
+            (Ordering::Greater, _) | (_, Ordering::Greater) => Ordering::Less,
+            (Ordering::Equal, _) => Ordering::Equal,
+            _ => Ordering::Greater,
+        }
+    }
+}
+
+/// The signs of g and h in `compare_to_breakpoint`
+fn breakpoint_terms(x: f64, directrix: f64, left: Point2<f64>, right: Point2<f64>) -> (Ordering, Ordering) {
+    let left_depth = directrix - left.y;
+    let right_depth = directrix - right.y;
+    let left_offset = x - left.x;
+    let right_offset = x - right.x;
+    let height = left.y - right.y;
+
+    let g_terms = [
+        left_depth * right_depth * height,
+        -left_offset * left_offset * right_depth,
+        right_offset * right_offset * left_depth,
+    ];
+    let g_permanent = g_terms.iter().map(|term| term.abs()).sum::<f64>();
+    let g = trusted_sign(g_terms.iter().sum(), BREAKPOINT_BOUND * g_permanent);
+
+    let h_terms = [right_offset * left_depth, -left_offset * right_depth];
+    let h_permanent = h_terms[0].abs() + h_terms[1].abs();
+    let h = trusted_sign(h_terms[0] + h_terms[1], BREAKPOINT_BOUND * h_permanent);
+
+    if let (Some(g), Some(h)) = (g, h) {
+        return (g, h);
+    }
+
+    let left_depth = difference(directrix, left.y);
+    let right_depth = difference(directrix, right.y);
+    let left_offset = difference(x, left.x);
+    let right_offset = difference(x, right.x);
+
+    let g = g.unwrap_or_else(|| {
+        let height = difference(left.y, right.y);
+        let depths = product(&product(&left_depth, &right_depth), &height);
+        let left_term = product(&product(&left_offset, &left_offset), &right_depth);
+        let right_term = product(&product(&right_offset, &right_offset), &left_depth);
+        sign(&sum(&depths, &subtract(&right_term, &left_term)))
+    });
+    let h = h.unwrap_or_else(|| {
+        sign(&subtract(&product(&right_offset, &left_depth), &product(&left_offset, &right_depth)))
+    });
+    (g, h)
+}
+
+/// The circle of a circle event, kept so it can be compared exactly
+#[derive(Debug, Clone)]
+pub struct Circle {
+    /// The sites it goes through
+    sites: [Point2<f64>; 3],
+
+    /// Its bottom and the x coordinate of its centre, in floating point
+    bottom: f64,
+    x: f64,
+
+    /// How far off either of those could be
+    error: f64,
+
+    /// Worked out the first time it's needed, as events at the same place are
+    /// compared many times over
+    exact: OnceCell<Box<Exact>>,
+}
+
+impl Circle {
+    /// The circle through three sites, which mustn't be collinear
+    pub fn new(sites: [Point2<f64>; 3]) -> Circle {
+        let [a, b, c] = sites;
+        let (bx, by) = (b.x - a.x, b.y - a.y);
+        let (cx, cy) = (c.x - a.x, c.y - a.y);
+        let b2 = bx * bx + by * by;
+        let c2 = cx * cx + cy * cy;
+
+        // The orientation is worked out exactly so its magnitude is accurate as
+        // well as its sign, however close the sites are to collinear
+        let d = 2.0 * estimate(&orient2d_expansion(a, b, c));
+        let x = (cy * b2 - by * c2) / d;
+        let y = (bx * c2 - cx * b2) / d;
+        let radius = x.hypot(y);
+
+        // Both parts of the centre are out by a small multiple of this at most
+        let magnitude = (cy.abs() * b2 + by.abs() * c2 + bx.abs() * c2 + cx.abs() * b2) / d.abs();
+        Circle {
+            sites,
+            bottom: a.y + y + radius,
+            x: a.x + x,
+            error: CIRCLE_BOUND * (magnitude + a.x.abs() + a.y.abs()),
+            exact: OnceCell::new(),
+        }
+    }
+
+    fn exact(&self) -> &Exact {
+        self.exact.get_or_init(|| {
+            let [a, b, c] = self.sites;
+
+            // As in `new`, relative to the first site
+            let (bx, by) = (difference(b.x, a.x), difference(b.y, a.y));
+            let (cx, cy) = (difference(c.x, a.x), difference(c.y, a.y));
+            let b2 = sum(&product(&bx, &bx), &product(&by, &by));
+            let c2 = sum(&product(&cx, &cx), &product(&cy, &cy));
+            let mut x = subtract(&product(&cy, &b2), &product(&by, &c2));
+            let mut y = subtract(&product(&bx, &c2), &product(&cx, &b2));
+            let mut d = orient2d_expansion(a, b, c).iter().map(|component| component * 2.0).collect::<Vec<_>>();
+            if sign(&d) == Ordering::Less {
+                x = expansion_negate(&x);
+                y = expansion_negate(&y);
+                d = expansion_negate(&d);
+            }
+
+            Box::new(Exact {
+                a: sum(&product(&[a.y], &d), &y),
+                s: sum(&product(&x, &x), &product(&y, &y)),
+                x: sum(&product(&[a.x], &d), &x),
+                d_squared: product(&d, &d),
+                d,
+            })
+        })
+    }
+}
+
+/// Only the sites are serialized, as everything else is worked out from them
+#[cfg(feature = "serde")]
+impl Serialize for Circle {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.sites.serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> Deserialize<'de> for Circle {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Circle, D::Error> {
+        <[Point2<f64>; 3]>::deserialize(deserializer).map(Circle::new)
+    }
+}
+
+/// Where an event happens
+#[derive(Debug, Clone, Copy)]
+pub enum Position<'a> {
+    Site(Point2<f64>),
+    Circle(&'a Circle),
+}
+
+/// A position as exact values. Its y is `(a + √s) / d` and its x is `x / d`,
+/// where `d` is above zero
+#[derive(Debug, Clone)]
+struct Exact {
+    a: Vec<f64>,
+    s: Vec<f64>,
+    d: Vec<f64>,
+    d_squared: Vec<f64>,
+    x: Vec<f64>,
+}
+
+impl<'a> Position<'a> {
+    /// The y and x coordinates, and how far off either could be
+    fn estimate(&self) -> (f64, f64, f64) {
+        match *self {
+            Position::Site(position) => (position.y, position.x, 0.0),
+            Position::Circle(circle) => (circle.bottom, circle.x, circle.error),
+        }
+    }
+
+    fn exact(&self) -> Cow<'a, Exact> {
+        match *self {
+            Position::Site(position) => Cow::Owned(Exact {
+                a: difference(position.y, 0.0),
+                s: Vec::new(),
+                d: vec![1.0],
+                d_squared: vec![1.0],
+                x: difference(position.x, 0.0),
+            }),
+            Position::Circle(circle) => Cow::Borrowed(circle.exact()),
+        }
+    }
+}
+
+/// The sign of `p + √r - √q`
+fn sign_with_roots(p: &[f64], r: &[f64], q: &[f64]) -> Ordering {
+    let p_sign = sign(p);
+    let roots_sign = sign(&subtract(r, q));
+    if p_sign == Ordering::Equal || p_sign == roots_sign {
+        return roots_sign;
+    }
+    if roots_sign == Ordering::Equal {
+        return p_sign;
+    }
+
+    // The signs differ, so the answer has the sign of whichever is bigger. p^2
+    // against (√r - √q)^2 is t + 2√(rq) against zero, where t = p^2 - r - q
+    let t = subtract(&product(p, p), &sum(r, q));
+    let larger = match sign(&t) {
+        Ordering::Less => sign(&subtract(&product(&[4.0], &product(r, q)), &product(&t, &t))),
+        Ordering::Equal => if sign(r) == Ordering::Equal || sign(q) == Ordering::Equal { Ordering::Equal } else { Ordering::Greater },
+        Ordering::Greater => Ordering::Greater,
+    };
+    match larger {
+        Ordering::Greater => p_sign,
+        Ordering::Equal => Ordering::Equal,
+        Ordering::Less => roots_sign,
+    }
+}
+
+/// Compares where two events happen, by y and then by x
+pub fn compare(a: &Position, b: &Position) -> Ordering {
+    let (ay, ax, a_error) = a.estimate();
+    let (by, bx, b_error) = b.estimate();
+    let error = a_error + b_error;
+    if let Some(order) = trusted_sign(ay - by, error) {
+        return order;
+    }
+
+    let (a, b) = (a.exact(), b.exact());
+
+    // Multiplied through by both denominators, which are above zero
+    let p = subtract(&product(&a.a, &b.d), &product(&b.a, &a.d));
+    let r = product(&a.s, &b.d_squared);
+    let q = product(&b.s, &a.d_squared);
+    sign_with_roots(&p, &r, &q).then_with(|| {
+        trusted_sign(ax - bx, error).unwrap_or_else(|| {
+            sign(&subtract(&product(&a.x, &b.d), &product(&b.x, &a.d)))
+        })
+    })
+}
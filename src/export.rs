@@ -0,0 +1,39 @@
+use cgmath::BaseFloat;
+use geojson::{Feature, FeatureCollection, Geometry, JsonObject, JsonValue, Value};
+
+use diagram::{Diagram, FaceId};
+
+/// Converts a diagram to GeoJSON, see `Diagram::to_geojson`
+pub fn to_geojson<T: BaseFloat>(diagram: &Diagram<T>) -> FeatureCollection {
+    let features = diagram.faces.iter().enumerate().map(|(index, face)| {
+        let mut ring = diagram.face_vertices(FaceId(index as u32))
+            .map(|vertex| vec![vertex.x.to_f64().unwrap(), vertex.y.to_f64().unwrap()])
+            .collect::<Vec<_>>();
+
+        // GeoJSON rings end where they start
+        let geometry = ring.first().cloned().map(|first| {
+            ring.push(first);
+            Geometry::new(Value::Polygon(vec![ring]))
+        });
+
+        let mut properties = JsonObject::new();
+        properties.insert("face".to_string(), JsonValue::from(index));
+        properties.insert("site_id".to_string(), JsonValue::from(face.site.id));
+        properties.insert("site_x".to_string(), JsonValue::from(face.site.position.x.to_f64().unwrap()));
+        properties.insert("site_y".to_string(), JsonValue::from(face.site.position.y.to_f64().unwrap()));
+
+        Feature {
+            bbox: None,
+            geometry,
+            id: None,
+            properties: Some(properties),
+            foreign_members: None,
+        }
+    }).collect();
+
+    FeatureCollection {
+        bbox: None,
+        features,
+        foreign_members: None,
+    }
+}
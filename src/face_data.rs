@@ -0,0 +1,114 @@
+//! Values attached to the cells of a diagram
+//!
+//! Face ids change whenever a diagram is built again, but the ids of sites are
+//! kept: relaxation moves sites without changing them, inserting a site leaves
+//! the others alone and the pieces of a face split by `clip_to_polygon` share its
+//! site. So `FaceData` keys its values by site id, and looks them up for a face
+//! through its site.
+
+use cgmath::BaseFloat;
+use fnv::{FnvHashMap, FnvHashSet};
+#[cfg(feature = "serde")]
+use serde::{Serialize, Deserialize};
+
+use build::Site;
+use diagram::{Diagram, FaceId};
+
+/// A value for each site, such as the biome or colour of its cell
+///
+/// This works with any diagram built from the same sites, so it doesn't have to
+/// be rebuilt along with the diagram. Sites don't need a value, and values for
+/// sites that aren't in a diagram are ignored.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct FaceData<V> {
+    values: FnvHashMap<u32, V>,
+}
+
+impl<V> Default for FaceData<V> {
+    fn default() -> FaceData<V> {
+        FaceData {
+            values: FnvHashMap::default(),
+        }
+    }
+}
+
+impl<V> FaceData<V> {
+    pub fn new() -> FaceData<V> {
+        FaceData::default()
+    }
+
+    /// Gives every face of a diagram a value
+    pub fn from_faces<T: BaseFloat, F: FnMut(FaceId, &Site<T>) -> V>(diagram: &Diagram<T>, mut value: F) -> FaceData<V> {
+        let mut data = FaceData::new();
+        for (index, face) in diagram.faces.iter().enumerate() {
+            data.insert(face.site.id, value(FaceId(index as u32), &face.site));
+        }
+        data
+    }
+
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// Sets the value of a site, and returns the value it had before
+    pub fn insert(&mut self, site_id: u32, value: V) -> Option<V> {
+        self.values.insert(site_id, value)
+    }
+
+    pub fn remove(&mut self, site_id: u32) -> Option<V> {
+        self.values.remove(&site_id)
+    }
+
+    pub fn get(&self, site_id: u32) -> Option<&V> {
+        self.values.get(&site_id)
+    }
+
+    pub fn get_mut(&mut self, site_id: u32) -> Option<&mut V> {
+        self.values.get_mut(&site_id)
+    }
+
+    /// Iterates over the site ids and their values, in no particular order
+    pub fn iter(&self) -> impl Iterator<Item = (u32, &V)> + '_ {
+        self.values.iter().map(|(&site_id, value)| (site_id, value))
+    }
+
+    /// Sets the value of the site of a face
+    ///
+    /// Returns the value it had before, or gives the value back as an error if
+    /// the face doesn't exist.
+    pub fn insert_face<T: BaseFloat>(&mut self, diagram: &Diagram<T>, face_id: FaceId, value: V) -> Result<Option<V>, V> {
+        match diagram.site_of_face(face_id) {
+            Some(site) => Ok(self.insert(site.id, value)),
+            None => Err(value),
+        }
+    }
+
+    /// The value of the site of a face
+    pub fn face<T: BaseFloat>(&self, diagram: &Diagram<T>, face_id: FaceId) -> Option<&V> {
+        diagram.site_of_face(face_id).and_then(|site| self.get(site.id))
+    }
+
+    pub fn face_mut<T: BaseFloat>(&mut self, diagram: &Diagram<T>, face_id: FaceId) -> Option<&mut V> {
+        match diagram.site_of_face(face_id) {
+            Some(site) => self.get_mut(site.id),
+            None => None,
+        }
+    }
+
+    /// The value of each face of a diagram, indexed by face id
+    pub fn faces<T: BaseFloat>(&self, diagram: &Diagram<T>) -> Vec<Option<&V>> {
+        diagram.faces.iter().map(|face| self.get(face.site.id)).collect()
+    }
+
+    /// Removes the values of sites that aren't in a diagram, such as ones that
+    /// were outside its bounds
+    pub fn retain_diagram<T: BaseFloat>(&mut self, diagram: &Diagram<T>) {
+        let sites = diagram.faces.iter().map(|face| face.site.id).collect::<FnvHashSet<_>>();
+        self.values.retain(|site_id, _| sites.contains(site_id));
+    }
+}
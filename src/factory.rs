@@ -0,0 +1,61 @@
+//! Building many diagrams one after the other without allocating for each
+//!
+//! A `DiagramBuilder` fills in an event queue, a beachline and a diagram of its
+//! own while it sweeps, and all of them are thrown away once the cells are
+//! clipped. For small diagrams rebuilt every frame, such as the cells of agents
+//! that move around, allocating those again each time can take longer than the
+//! sweep. A `DiagramFactory` keeps them between builds instead, and can build
+//! into a diagram that was returned before so its lists are reused as well.
+
+use std::mem;
+
+use cgmath::BaseFloat;
+
+use build::{ClipRegion, DiagramBuilder, DuplicatePolicy, NonFinitePolicy, OutOfBoundsPolicy, Site, SiteMapping, SweepBuffers};
+use diagram::Diagram;
+
+/// Builds diagrams in the same clip region, reusing memory from one build to the
+/// next
+#[derive(Debug)]
+pub struct DiagramFactory<T = f32> {
+    clip_region: ClipRegion<T>,
+    buffers: SweepBuffers<T>,
+}
+
+impl<T: BaseFloat> DiagramFactory<T> {
+    pub fn new<R: Into<ClipRegion<T>>>(clip_region: R) -> DiagramFactory<T> {
+        DiagramFactory {
+            clip_region: clip_region.into(),
+            buffers: SweepBuffers::default(),
+        }
+    }
+
+    pub fn clip_region(&self) -> &ClipRegion<T> {
+        &self.clip_region
+    }
+
+    /// Builds the diagram of the sites
+    ///
+    /// Sites are dealt with in the same way as by `DiagramBuilder::new`, and the
+    /// diagram is the same as it would build.
+    pub fn build(&mut self, sites: &[Site<T>]) -> Diagram<T> {
+        let mut diagram = Diagram::default();
+        self.build_into(&mut diagram, sites);
+        diagram
+    }
+
+    /// Builds the diagram of the sites into `diagram`, replacing what was there
+    /// and reusing its memory
+    pub fn build_into(&mut self, diagram: &mut Diagram<T>, sites: &[Site<T>]) {
+        let buffers = mem::take(&mut self.buffers);
+
+        // Merging and skipping never fail
+        let builder = DiagramBuilder::with_buffers(self.clip_region.clone(), sites, DuplicatePolicy::MergeFirstWins, T::zero(), OutOfBoundsPolicy::Skip, NonFinitePolicy::Skip, buffers).unwrap();
+        self.buffers = builder.finish_into(diagram);
+    }
+
+    /// How the sites given to the last build map to the faces of its diagram
+    pub fn site_mapping(&self) -> &SiteMapping {
+        self.buffers.site_mapping()
+    }
+}
@@ -0,0 +1,120 @@
+//! Farthest-point diagrams, where each cell holds the points farther from its site
+//! than from any other site
+//!
+//! Only sites at the corners of the convex hull have cells. The cells are all
+//! unbounded and the edges between them form a tree. Each vertex is the centre of
+//! a circle through three sites that has every other site inside it, so the
+//! smallest circle around all the sites is centred either on a vertex or halfway
+//! between the two sites farthest apart.
+//!
+//! Being far from a site is the same as being near its reflection through some
+//! point, if the reflection is given a weight that grows with its distance from
+//! that point. These diagrams are built as the power diagrams of the reflected
+//! sites, which hides every site inside the hull.
+
+use cgmath::{Point2, EuclideanSpace, InnerSpace, BaseFloat};
+use cgmath::num_traits::cast;
+
+use build::{ClipRegion, Metric, Site};
+use clip::strictly_inside_boundary;
+use diagram::{Diagram, Vertex, Face};
+use kdtree::KdTree;
+use power::build_scaled;
+
+/// Builds the farthest-point diagram of sites, clipped to a region
+///
+/// Sites outside the clip region are left out, and the diagram has a face for
+/// each of the others in the order they were given. Faces of sites that aren't
+/// corners of the convex hull have no half-edges. Of sites at the same position,
+/// the first one gets the cell.
+///
+/// `Diagram::locate` finds the face of the nearest site in these diagrams, not
+/// the cell a point is in.
+#[derive(Debug, Clone)]
+pub struct FarthestPointDiagramBuilder<T = f32> {
+    offset: Point2<f64>,
+    scale: f64,
+
+    /// Corners of the clip region after scaling, in counter-clockwise order
+    boundary: Vec<Point2<f64>>,
+
+    /// Centre of the clip region's bounding rect after scaling, which sites are
+    /// reflected through
+    centre: Point2<f64>,
+
+    faces: Vec<Face<T>>,
+
+    /// Scaled position of the site of each face
+    sites: Vec<Point2<f64>>,
+}
+
+impl<T: BaseFloat> FarthestPointDiagramBuilder<T> {
+    /// Creates a builder for the farthest-point diagram of the sites inside the clip
+    /// region
+    pub fn new<R: Into<ClipRegion<T>>>(clip_region: R, sites: Vec<Site<T>>) -> FarthestPointDiagramBuilder<T> {
+        let clip_region = clip_region.into();
+        let bounding_rect = clip_region.bounding_rect();
+        let offset = bounding_rect.position.cast::<f64>();
+        let scale = 1.0 / cast::<T, f64>(bounding_rect.size.x.max(bounding_rect.size.y)).unwrap();
+        let boundary = clip_region.boundary(T::one()).into_iter()
+            .map(|corner| corner.cast::<f64>() * scale)
+            .collect::<Vec<_>>();
+        let centre = Point2::from_vec(bounding_rect.size.cast::<f64>() * (scale / 2.0));
+
+        let mut faces = Vec::new();
+        let mut scaled_sites = Vec::new();
+        for site in sites {
+            let position = Point2::from_vec((site.position.cast::<f64>() - offset) * scale);
+
+            if strictly_inside_boundary(&boundary, position) {
+                faces.push(Face {
+                    first_halfedge: None,
+                    site,
+                });
+                scaled_sites.push(position);
+            }
+        }
+
+        FarthestPointDiagramBuilder {
+            offset,
+            scale,
+            boundary,
+            centre,
+            faces,
+            sites: scaled_sites,
+        }
+    }
+
+    pub fn finish(self) -> Diagram<T> {
+        // Reflected through the centre, the sites stay inside the bounding rect, and
+        // giving each one twice its squared distance from the centre as its weight
+        // makes the power distance to it the squared distance to the original site
+        // taken away from a term that's the same for every site
+        let centre = self.centre;
+        let reflected = self.sites.iter().map(|&site| centre + (centre - site)).collect::<Vec<_>>();
+        let weights = self.sites.iter().map(|&site| 2.0 * (site - centre).magnitude2()).collect::<Vec<_>>();
+        let diagram = build_scaled(&reflected, &weights, &self.boundary);
+
+        let mut diagram = Diagram {
+            vertices: diagram.vertices.into_iter().map(|vertex| {
+                Vertex {
+                    coordinates: (self.offset + vertex.coordinates.to_vec() / self.scale).cast::<T>(),
+                    incident_edge: vertex.incident_edge,
+                }
+            }).collect(),
+            halfedges: diagram.halfedges,
+            faces: diagram.faces.into_iter().zip(self.faces).map(|(clipped, face)| {
+                Face {
+                    first_halfedge: clipped.first_halfedge,
+                    site: face.site,
+                }
+            }).collect(),
+            site_index: KdTree::default(),
+            metric: Metric::Euclidean,
+            accelerator: None,
+        };
+
+        diagram.index_sites(false);
+        diagram
+    }
+}
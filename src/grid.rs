@@ -0,0 +1,279 @@
+//! A uniform grid over the cells of a diagram, see `Diagram::build_accelerator`
+//!
+//! The grid covers the bounding box of the diagram's vertices, with about two
+//! faces to a square. Each square lists the faces whose bounding boxes overlap
+//! it, so the face a point is in is one of the few listed for its square, and
+//! the faces near an area are the ones listed for the squares it covers.
+//!
+//! In a square that the outline of the diagram doesn't pass through, the face a
+//! point is in is always the one with the nearest site. Elsewhere that face is
+//! checked to contain the point, as it may be outside the diagram.
+
+use cgmath::{Point2, Vector2, BaseFloat};
+use cgmath::num_traits::cast;
+#[cfg(feature = "serde")]
+use serde::{Serialize, Deserialize};
+
+use diagram::{Diagram, FaceId};
+use insert::inside_face;
+
+/// Number of faces aimed for in each square
+const FACES_PER_SQUARE: usize = 2;
+
+/// Faces of a diagram bucketed into the squares of a grid
+///
+/// Faces with edges that go off to infinity, which only unclipped diagrams
+/// have, don't fit in any square and are kept in a list of their own.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct FaceGrid<T = f32> {
+    origin: Point2<T>,
+    square_size: Vector2<T>,
+    columns: u32,
+    rows: u32,
+
+    /// Where the faces of each square start in `faces`, row by row, with the end
+    /// of the last square at the end
+    starts: Vec<u32>,
+    faces: Vec<FaceId>,
+    unbounded: Vec<FaceId>,
+
+    /// Whether each square is inside the diagram and clear of its outline
+    interior: Vec<bool>,
+
+    /// Number of faces in the diagram it was built for
+    face_count: usize,
+}
+
+impl<T: BaseFloat> FaceGrid<T> {
+    pub(crate) fn new(diagram: &Diagram<T>) -> FaceGrid<T> {
+        let mut unbounded = Vec::new();
+        let bounds = (0..diagram.faces.len()).map(|index| {
+            let face_id = FaceId(index as u32);
+            let bounds = face_bounds(diagram, face_id);
+            if bounds.is_none() && diagram.faces[index].first_halfedge.is_some() {
+                unbounded.push(face_id);
+            }
+            bounds
+        }).collect::<Vec<_>>();
+
+        let mut grid = FaceGrid {
+            origin: Point2::new(T::zero(), T::zero()),
+            square_size: Vector2::new(T::zero(), T::zero()),
+            columns: 0,
+            rows: 0,
+            starts: vec![0],
+            faces: Vec::new(),
+            unbounded,
+            interior: Vec::new(),
+            face_count: diagram.faces.len(),
+        };
+        let (min, max) = match bounds.iter().flatten().fold(None, |total: Option<(Point2<T>, Point2<T>)>, &(min, max)| match total {
+            Some((total_min, total_max)) => Some((
+                Point2::new(total_min.x.min(min.x), total_min.y.min(min.y)),
+                Point2::new(total_max.x.max(max.x), total_max.y.max(max.y)),
+            )),
+            None => Some((min, max)),
+        }) {
+            Some(total) => total,
+            None => return grid,
+        };
+
+        // Squares are made about as square as the bounding box allows
+        let squares = (bounds.len() / FACES_PER_SQUARE).max(1);
+        let (width, height) = (cast::<T, f64>(max.x - min.x).unwrap(), cast::<T, f64>(max.y - min.y).unwrap());
+        let columns = if width > 0.0 && height > 0.0 {
+            (squares as f64 * width / height).sqrt().ceil().clamp(1.0, squares as f64) as u32
+        } else if width > 0.0 {
+            squares as u32
+        } else {
+            1
+        };
+        let rows = (squares as u32).div_ceil(columns);
+        grid.origin = min;
+        grid.square_size = Vector2::new((max.x - min.x) / cast(columns).unwrap(), (max.y - min.y) / cast(rows).unwrap());
+        grid.columns = columns;
+        grid.rows = rows;
+
+        // Counted first, so the faces of every square can go in one list
+        let mut starts = vec![0u32; (columns * rows) as usize + 1];
+        for &(min, max) in bounds.iter().flatten() {
+            grid.for_each_square(min, max, |square| starts[square + 1] += 1);
+        }
+        for index in 1..starts.len() {
+            starts[index] += starts[index - 1];
+        }
+
+        let mut next = starts.clone();
+        let mut faces = vec![FaceId(0); starts[starts.len() - 1] as usize];
+        for (index, bounds) in bounds.iter().enumerate() {
+            if let Some((min, max)) = *bounds {
+                grid.for_each_square(min, max, |square| {
+                    faces[next[square] as usize] = FaceId(index as u32);
+                    next[square] += 1;
+                });
+            }
+        }
+        grid.starts = starts;
+        grid.faces = faces;
+
+        // The outline is made of the half-edges without twins. Squares it misses are
+        // either all inside the diagram or all outside, which their centres tell.
+        // Unbounded faces aren't in the grid, so none of it can be trusted then
+        let mut interior = vec![grid.unbounded.is_empty(); (columns * rows) as usize];
+        if grid.unbounded.is_empty() {
+            for halfedge in diagram.halfedges.iter().filter(|halfedge| halfedge.twin.is_none()) {
+                let destination = diagram.halfedges[halfedge.next.0 as usize].origin;
+                if let (Some(origin), Some(destination)) = (halfedge.origin, destination) {
+                    let (a, b) = (diagram.vertices[origin.0 as usize].coordinates, diagram.vertices[destination.0 as usize].coordinates);
+                    grid.for_each_square(Point2::new(a.x.min(b.x), a.y.min(b.y)), Point2::new(a.x.max(b.x), a.y.max(b.y)), |square| interior[square] = false);
+                }
+            }
+        }
+        let half: T = cast(0.5).unwrap();
+        for (square, interior) in interior.iter_mut().enumerate().filter(|(_, interior)| **interior) {
+            let (column, row) = (square as u32 % columns, square as u32 / columns);
+            let centre = Point2::new(
+                grid.origin.x + grid.square_size.x * (cast::<u32, T>(column).unwrap() + half),
+                grid.origin.y + grid.square_size.y * (cast::<u32, T>(row).unwrap() + half),
+            );
+            *interior = nearest_site(diagram, grid.square(square), centre).is_some_and(|face_id| inside_face(diagram, face_id, centre.cast()));
+        }
+        grid.interior = interior;
+
+        grid
+    }
+
+    /// Number of squares across the grid
+    pub fn columns(&self) -> u32 {
+        self.columns
+    }
+
+    /// Number of squares down the grid
+    pub fn rows(&self) -> u32 {
+        self.rows
+    }
+
+    /// Faces that might contain a point, which are the faces listed for the
+    /// square it's in
+    ///
+    /// This is empty for points outside the grid.
+    pub fn faces_near(&self, point: Point2<T>) -> &[FaceId] {
+        self.square_at(point).map_or(&[], |square| self.square(square))
+    }
+
+    /// Faces with edges that go off to infinity, which aren't in any square
+    pub fn unbounded_faces(&self) -> &[FaceId] {
+        &self.unbounded
+    }
+
+    /// Whether the grid was built for a diagram with this many faces
+    pub(crate) fn fits(&self, diagram: &Diagram<T>) -> bool {
+        self.face_count == diagram.faces.len()
+    }
+
+    /// Finds the face a point is in, or None if the grid can't tell
+    pub(crate) fn locate(&self, diagram: &Diagram<T>, point: Point2<T>) -> Option<FaceId> {
+        let square = self.square_at(point)?;
+        let nearest = nearest_site(diagram, self.square(square), point)?;
+        if self.interior[square] || inside_face(diagram, nearest, point.cast()) {
+            Some(nearest)
+        } else {
+            None
+        }
+    }
+
+    /// Faces listed for the squares that overlap a box, along with the unbounded
+    /// ones, in order of their ids
+    pub(crate) fn faces_in_box(&self, min: Point2<T>, max: Point2<T>) -> Vec<FaceId> {
+        let mut faces = self.unbounded.clone();
+        self.for_each_square(min, max, |square| faces.extend_from_slice(self.square(square)));
+        faces.sort_unstable_by_key(|face_id| face_id.0);
+        faces.dedup();
+        faces
+    }
+
+    fn square(&self, square: usize) -> &[FaceId] {
+        &self.faces[self.starts[square] as usize..self.starts[square + 1] as usize]
+    }
+
+    fn square_at(&self, point: Point2<T>) -> Option<usize> {
+        let (column, row) = (self.column(point.x)?, self.row(point.y)?);
+        Some((row * self.columns + column) as usize)
+    }
+
+    fn column(&self, x: T) -> Option<u32> {
+        index_along(x - self.origin.x, self.square_size.x, self.columns)
+    }
+
+    fn row(&self, y: T) -> Option<u32> {
+        index_along(y - self.origin.y, self.square_size.y, self.rows)
+    }
+
+    /// Calls `f` with the index of every square that overlaps a box inside the grid
+    fn for_each_square<F: FnMut(usize)>(&self, min: Point2<T>, max: Point2<T>, mut f: F) {
+        if self.columns == 0 || self.rows == 0 {
+            return;
+        }
+        let clamp = |index: Option<u32>, offset: T, count: u32| index.unwrap_or(if offset < T::zero() { 0 } else { count - 1 });
+        let first_column = clamp(self.column(min.x), min.x - self.origin.x, self.columns);
+        let last_column = clamp(self.column(max.x), max.x - self.origin.x, self.columns);
+        let first_row = clamp(self.row(min.y), min.y - self.origin.y, self.rows);
+        let last_row = clamp(self.row(max.y), max.y - self.origin.y, self.rows);
+        for row in first_row..last_row + 1 {
+            for column in first_column..last_column + 1 {
+                f((row * self.columns + column) as usize);
+            }
+        }
+    }
+}
+
+/// Which of `count` squares `size` long an offset from the start of the grid is in
+///
+/// The far edge counts as being in the last square. A grid with no length along
+/// this axis only has one square.
+fn index_along<T: BaseFloat>(offset: T, size: T, count: u32) -> Option<u32> {
+    if count == 0 || offset.is_nan() || offset < T::zero() {
+        return None;
+    }
+    if size == T::zero() {
+        return if offset == T::zero() { Some(0) } else { None };
+    }
+
+    let index = (offset / size).floor().to_u32()?;
+    match index {
+        index if index < count => Some(index),
+        index if index == count && offset <= size * cast(count).unwrap() => Some(count - 1),
+        _ => None,
+    }
+}
+
+/// The face with the nearest site to a point out of some faces, by power
+/// distance if the diagram is weighted
+fn nearest_site<T: BaseFloat>(diagram: &Diagram<T>, faces: &[FaceId], point: Point2<T>) -> Option<FaceId> {
+    let weighted = diagram.site_index.is_weighted();
+    faces.iter().map(|&face_id| {
+        let site = &diagram.faces[face_id.0 as usize].site;
+        let weight = if weighted { site.weight } else { T::zero() };
+        let (dx, dy) = (point.x - site.position.x, point.y - site.position.y);
+        (dx * dx + dy * dy - weight, face_id)
+    }).fold(None, |nearest: Option<(T, FaceId)>, (distance, face_id)| match nearest {
+        Some((nearest_distance, _)) if nearest_distance <= distance => nearest,
+        _ => Some((distance, face_id)),
+    }).map(|(_, face_id)| face_id)
+}
+
+/// Corners of the box around a face, or None if it has no half-edges or some of
+/// them have no origin
+fn face_bounds<T: BaseFloat>(diagram: &Diagram<T>, face_id: FaceId) -> Option<(Point2<T>, Point2<T>)> {
+    let mut bounds: Option<(Point2<T>, Point2<T>)> = None;
+    for halfedge_id in diagram.face_edges(face_id) {
+        let origin = diagram.halfedges[halfedge_id.0 as usize].origin?;
+        let point = diagram.vertices[origin.0 as usize].coordinates;
+        bounds = Some(match bounds {
+            Some((min, max)) => (Point2::new(min.x.min(point.x), min.y.min(point.y)), Point2::new(max.x.max(point.x), max.y.max(point.y))),
+            None => (point, point),
+        });
+    }
+    bounds
+}
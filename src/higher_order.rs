@@ -0,0 +1,192 @@
+//! Higher order diagrams, where each cell holds the points that have the same set
+//! of nearest sites
+//!
+//! In the diagram of order k, points are in the same cell if the same k sites are
+//! nearest to them, in any order. Cells don't have to contain any of their sites,
+//! and a site is in the set of many cells.
+//!
+//! The sum of squared distances from a point to a set of sites is smallest for
+//! the set of its nearest sites. That sum is the squared distance to the centroid
+//! of the set plus a term that only depends on how spread out the set is, so these
+//! are power diagrams of the centroids. The nearest sites to any point are joined
+//! up in the Delaunay triangulation, so each set of k sites that has a cell is a
+//! set of k - 1 sites that has a cell plus a neighbour of one of them. Diagrams of
+//! each order up to k are built in turn to find which sets to try.
+
+use cgmath::{Point2, EuclideanSpace, InnerSpace, BaseFloat};
+use cgmath::num_traits::cast;
+use fnv::FnvHashMap;
+
+use build::{ClipRegion, DiagramBuilder, Site};
+use diagram::{Diagram, Face, FaceId};
+use power::PowerDiagramBuilder;
+
+/// Builds the diagram of some order of sites, clipped to a region
+///
+/// Sites outside the clip region and sites at the same position as an earlier one
+/// are left out.
+#[derive(Debug, Clone)]
+pub struct HigherOrderDiagramBuilder<T = f32> {
+    clip_region: ClipRegion<T>,
+    sites: Vec<Site<T>>,
+    order: usize,
+}
+
+/// A diagram built by `HigherOrderDiagramBuilder`
+///
+/// Each face has a set of sites that are nearest to every point in its cell. The
+/// site of a face has the index of the face as its id. It's at the centroid of the
+/// set and its weight is minus the mean squared distance from the centroid to the
+/// sites in the set, so `Diagram::locate` finds the cell a point is in.
+#[derive(Debug, Clone)]
+pub struct HigherOrderDiagram<T = f32> {
+    diagram: Diagram<T>,
+
+    /// Ids of the set of sites of each face
+    site_sets: Vec<Vec<u32>>,
+}
+
+impl<T: BaseFloat> HigherOrderDiagramBuilder<T> {
+    /// Creates a builder for the diagram where each cell has `order` nearest sites
+    pub fn new<R: Into<ClipRegion<T>>>(clip_region: R, sites: Vec<Site<T>>, order: usize) -> HigherOrderDiagramBuilder<T> {
+        HigherOrderDiagramBuilder {
+            clip_region: clip_region.into(),
+            sites,
+            order,
+        }
+    }
+
+    pub fn finish(self) -> HigherOrderDiagram<T> {
+        if self.order == 0 {
+            return HigherOrderDiagram {
+                diagram: Diagram::default(),
+                site_sets: Vec::new(),
+            };
+        }
+
+        // The ordinary diagram gives the sites that are kept and their neighbours,
+        // including ones whose shared edge is outside the clip region
+        let voronoi = DiagramBuilder::new(self.clip_region.clone(), self.sites).finish_unclipped();
+        let sites = voronoi.faces.iter().map(|face| face.site).collect::<Vec<_>>();
+        let mut neighbours = vec![Vec::new(); sites.len()];
+        for halfedge in &voronoi.halfedges {
+            if let Some(twin) = halfedge.twin {
+                let neighbour = voronoi.halfedges[twin.0 as usize].incident_face.0;
+                neighbours[halfedge.incident_face.0 as usize].push(neighbour);
+            }
+        }
+
+        // Sets are lists of indices into `sites`, kept sorted
+        let mut sets = (0..sites.len() as u32).map(|index| vec![index]).collect::<Vec<_>>();
+        let mut diagram = Diagram::default();
+        for order in 1..self.order + 1 {
+            if order > 1 {
+                sets = grow_sets(&sets, &neighbours);
+            }
+
+            // Each centroid has the index of its set as its id
+            let centroids = sets.iter().enumerate().map(|(index, set)| centroid_site(index as u32, &sites, set)).collect();
+            let power_diagram = PowerDiagramBuilder::new(self.clip_region.clone(), centroids).finish();
+
+            // Sets without a cell can't grow into sets that have one
+            let (kept_diagram, kept_sets) = remove_empty_faces(power_diagram, &sets);
+            diagram = kept_diagram;
+            sets = kept_sets;
+        }
+
+        let site_sets = sets.iter()
+            .map(|set| set.iter().map(|&index| sites[index as usize].id).collect())
+            .collect();
+
+        HigherOrderDiagram {
+            diagram,
+            site_sets,
+        }
+    }
+}
+
+impl<T: BaseFloat> HigherOrderDiagram<T> {
+    pub fn diagram(&self) -> &Diagram<T> {
+        &self.diagram
+    }
+
+    pub fn into_diagram(self) -> Diagram<T> {
+        self.diagram
+    }
+
+    /// The ids of the sites nearest to the points in a face, in the order the sites
+    /// were given
+    pub fn sites(&self, face_id: FaceId) -> Option<&[u32]> {
+        self.site_sets.get(face_id.0 as usize).map(|sites| &sites[..])
+    }
+}
+
+/// Finds every set made of a set and one neighbour of one of its sites that isn't
+/// already in it
+fn grow_sets(sets: &[Vec<u32>], neighbours: &[Vec<u32>]) -> Vec<Vec<u32>> {
+    let mut grown = Vec::new();
+    let mut seen = FnvHashMap::default();
+    for set in sets {
+        for &index in set {
+            for &neighbour in &neighbours[index as usize] {
+                if set.contains(&neighbour) {
+                    continue;
+                }
+
+                let mut candidate = set.clone();
+                let position = candidate.binary_search(&neighbour).unwrap_err();
+                candidate.insert(position, neighbour);
+                if seen.insert(candidate.clone(), ()).is_none() {
+                    grown.push(candidate);
+                }
+            }
+        }
+    }
+
+    grown
+}
+
+/// The weighted site at the centroid of a set, whose power distance from a point
+/// is the mean squared distance from the point to the sites in the set
+fn centroid_site<T: BaseFloat>(id: u32, sites: &[Site<T>], set: &[u32]) -> Site<T> {
+    let count = cast::<usize, T>(set.len()).unwrap();
+    let centroid = set.iter().fold(Point2::origin(), |sum, &index| sum + sites[index as usize].position.to_vec() / count);
+    let spread = set.iter().fold(T::zero(), |sum, &index| sum + (sites[index as usize].position - centroid).magnitude2() / count);
+    Site::with_weight(id, centroid, -spread)
+}
+
+/// Removes faces without any half-edges, and finds the sets of the faces that are
+/// left from the ids of their sites, which are then changed to the new face ids
+fn remove_empty_faces<T: BaseFloat>(diagram: Diagram<T>, sets: &[Vec<u32>]) -> (Diagram<T>, Vec<Vec<u32>>) {
+    let mut face_map = vec![FaceId(0); diagram.faces.len()];
+    let mut faces = Vec::new();
+    let mut kept_sets = Vec::new();
+    for (index, face) in diagram.faces.into_iter().enumerate() {
+        if face.first_halfedge.is_none() {
+            continue;
+        }
+
+        let face_id = FaceId(faces.len() as u32);
+        face_map[index] = face_id;
+        faces.push(Face {
+            first_halfedge: face.first_halfedge,
+            site: Site::with_weight(face_id.0, face.site.position, face.site.weight),
+        });
+        kept_sets.push(sets[face.site.id as usize].clone());
+    }
+
+    let mut halfedges = diagram.halfedges;
+    for halfedge in &mut halfedges {
+        halfedge.incident_face = face_map[halfedge.incident_face.0 as usize];
+    }
+
+    let mut kept = Diagram {
+        vertices: diagram.vertices,
+        halfedges,
+        faces,
+        ..Diagram::default()
+    };
+    kept.index_sites(true);
+
+    (kept, kept_sets)
+}
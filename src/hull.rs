@@ -0,0 +1,100 @@
+use std::cmp::Ordering;
+
+use cgmath::{Point2, BaseFloat};
+
+use diagram::{Diagram, FaceId};
+use predicates::orient2d;
+
+/// All the faces, sorted by the positions of their sites from left to right and
+/// then from bottom to top
+fn sorted_faces<T: BaseFloat>(diagram: &Diagram<T>) -> Vec<FaceId> {
+    let position = |face: FaceId| diagram.faces[face.0 as usize].site.position.cast::<f64>();
+    let mut faces = (0..diagram.faces.len() as u32).map(FaceId).collect::<Vec<_>>();
+    faces.sort_by(|&a, &b| {
+        let (a, b) = (position(a), position(b));
+        a.x.partial_cmp(&b.x).unwrap_or(Ordering::Equal).then(a.y.partial_cmp(&b.y).unwrap_or(Ordering::Equal))
+    });
+    faces
+}
+
+/// Faces whose sites are the corners of the convex hull, see `Diagram::convex_hull`
+pub fn convex_hull<T: BaseFloat>(diagram: &Diagram<T>) -> Vec<FaceId> {
+    let position = |face: FaceId| diagram.faces[face.0 as usize].site.position.cast::<f64>();
+    let mut faces = sorted_faces(diagram);
+    faces.dedup_by(|a, b| position(*a) == position(*b));
+    if faces.len() < 3 {
+        return faces;
+    }
+    chain(&faces, position, false)
+}
+
+/// The convex hull of some sorted points with no two the same, counter-clockwise
+/// from the first
+///
+/// Points along an edge of the hull are only kept if `keep_collinear` is set,
+/// which needs at least three points that aren't all in a line.
+fn chain<P: Copy, F: Fn(P) -> Point2<f64>>(points: &[P], position: F, keep_collinear: bool) -> Vec<P> {
+    // Andrew's monotone chain: the lower half of the hull from left to right, then
+    // the upper half back again. Each half ends where the other one starts
+    let mut hull: Vec<P> = Vec::with_capacity(points.len() + 1);
+    for half in 0..2 {
+        let start = hull.len();
+        let mut add = |point: P| {
+            while hull.len() >= start + 2 {
+                let turn = orient2d(position(hull[hull.len() - 2]), position(hull[hull.len() - 1]), position(point));
+                let keep = turn > 0.0 || (keep_collinear && turn == 0.0);
+                if keep {
+                    break;
+                }
+                hull.pop();
+            }
+            hull.push(point);
+        };
+
+        if half == 0 {
+            points.iter().for_each(|&point| add(point));
+        } else {
+            points.iter().rev().for_each(|&point| add(point));
+        }
+        hull.pop();
+    }
+
+    hull
+}
+
+/// Faces in each of the nested convex hulls of the sites, see
+/// `Diagram::convex_layers`
+pub fn convex_layers<T: BaseFloat>(diagram: &Diagram<T>) -> Vec<Vec<FaceId>> {
+    let position = |face: FaceId| diagram.faces[face.0 as usize].site.position.cast::<f64>();
+    let mut faces = sorted_faces(diagram);
+    let mut layers = Vec::new();
+    while !faces.is_empty() {
+        // Faces with their sites in the same place are peeled off together, so the
+        // hull is found from the first of each run of them
+        let mut runs = Vec::new();
+        for (index, &face) in faces.iter().enumerate() {
+            if index == 0 || position(faces[index - 1]) != position(face) {
+                runs.push(index);
+            }
+        }
+        let count = runs.len();
+        runs.push(faces.len());
+        let run_position = |run: usize| position(faces[runs[run]]);
+
+        // Every point is on the hull of points in a line
+        let (first, last) = (run_position(0), run_position(count - 1));
+        let all_runs = (0..count).collect::<Vec<_>>();
+        let collinear = all_runs.iter().all(|&run| orient2d(first, last, run_position(run)) == 0.0);
+        let hull = if collinear { all_runs } else { chain(&all_runs, run_position, true) };
+
+        let mut peeled = vec![false; count];
+        let mut layer = Vec::new();
+        for run in hull {
+            peeled[run] = true;
+            layer.extend_from_slice(&faces[runs[run]..runs[run + 1]]);
+        }
+        faces = (0..count).filter(|&run| !peeled[run]).flat_map(|run| faces[runs[run]..runs[run + 1]].iter().cloned()).collect();
+        layers.push(layer);
+    }
+    layers
+}
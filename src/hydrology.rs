@@ -0,0 +1,135 @@
+//! Water running downhill over cells with elevations
+//!
+//! Water leaves each cell for its lowest neighbour, so following those steps from
+//! any cell ends at a cell with no lower neighbours, and the cells that end at
+//! the same one make up a drainage basin. Rivers run along the edges between
+//! cells instead, from vertex to vertex, with each vertex as high as the average
+//! of the cells around it.
+
+use std::cmp::Ordering;
+
+use cgmath::BaseFloat;
+use cgmath::num_traits::cast;
+
+use build::compare;
+use diagram::{Diagram, FaceId, HalfEdgeId, RegionId};
+
+fn elevation<T: BaseFloat>(elevations: &[T], face_id: FaceId) -> Option<T> {
+    elevations.get(face_id.0 as usize).cloned().filter(|elevation| !elevation.is_nan())
+}
+
+/// The lowest neighbour of every face, see `Diagram::downhill`
+pub fn downhill<T: BaseFloat>(diagram: &Diagram<T>, elevations: &[T]) -> Vec<Option<FaceId>> {
+    (0..diagram.faces.len() as u32).map(|face_id| {
+        let height = elevation(elevations, FaceId(face_id))?;
+        diagram.neighbours(FaceId(face_id))
+            .filter_map(|neighbour| elevation(elevations, neighbour).map(|height| (height, neighbour)))
+            .filter(|&(neighbour_height, _)| neighbour_height < height)
+            .min_by(|&(a, a_id), &(b, b_id)| compare(a, b).then(a_id.0.cmp(&b_id.0)))
+            .map(|(_, neighbour)| neighbour)
+    }).collect()
+}
+
+/// The drainage basin of every face, see `Diagram::drainage_basins`
+pub fn drainage_basins<T: BaseFloat>(diagram: &Diagram<T>, elevations: &[T]) -> Vec<RegionId> {
+    let downhill = downhill(diagram, elevations);
+
+    // Each step goes strictly downhill, so no path comes back round to a face
+    // and the longest one has as many steps as there are faces
+    let mut sinks: Vec<Option<u32>> = vec![None; downhill.len()];
+    let mut path = Vec::new();
+    for face in 0..downhill.len() {
+        let mut current = face;
+        let sink = loop {
+            if let Some(sink) = sinks[current] {
+                break sink;
+            }
+            match downhill[current] {
+                Some(next) if path.len() < downhill.len() => {
+                    path.push(current);
+                    current = next.0 as usize;
+                }
+                _ => break current as u32,
+            }
+        };
+        sinks[current] = Some(sink);
+        for &face in &path {
+            sinks[face] = Some(sink);
+        }
+        path.clear();
+    }
+
+    // Basins are numbered in order of their faces with the lowest ids, like the
+    // regions from `group_faces`
+    let mut basins = vec![None; downhill.len()];
+    let mut regions = Vec::with_capacity(downhill.len());
+    let mut count = 0;
+    for sink in sinks {
+        let sink = sink.unwrap_or(0) as usize;
+        let region = *basins[sink].get_or_insert_with(|| {
+            count += 1;
+            RegionId(count - 1)
+        });
+        regions.push(region);
+    }
+    regions
+}
+
+/// The edges rivers run along, see `Diagram::rivers`
+pub fn rivers<T: BaseFloat>(diagram: &Diagram<T>, elevations: &[T], min_flow: u32) -> Vec<(HalfEdgeId, u32)> {
+    let halfedges = &diagram.halfedges;
+    let destination = |halfedge_id: usize| halfedges[halfedges[halfedge_id].next.0 as usize].origin;
+
+    // Every face around a vertex has one half-edge leaving it
+    let mut totals = vec![(T::zero(), 0u32); diagram.vertices.len()];
+    for halfedge in halfedges {
+        let height = elevation(elevations, halfedge.incident_face);
+        if let (Some(origin), Some(height)) = (halfedge.origin, height) {
+            let total = &mut totals[origin.0 as usize];
+            *total = (total.0 + height, total.1 + 1);
+        }
+    }
+    let heights = totals.into_iter()
+        .map(|(total, count)| if count > 0 { Some(total / cast(count).unwrap()) } else { None })
+        .collect::<Vec<_>>();
+
+    // Water leaves each vertex along the edge to its lowest neighbour, if that's
+    // lower than it
+    let mut outflow: Vec<Option<(T, usize)>> = vec![None; diagram.vertices.len()];
+    for (index, halfedge) in halfedges.iter().enumerate() {
+        let (origin, destination) = match (halfedge.origin, destination(index)) {
+            (Some(origin), Some(destination)) => (origin.0 as usize, destination.0 as usize),
+            _ => continue,
+        };
+        let (height, other) = match (heights[origin], heights[destination]) {
+            (Some(height), Some(other)) => (height, other),
+            _ => continue,
+        };
+        let lower = other < height && outflow[origin].is_none_or(|(lowest, _)| other < lowest);
+        if lower {
+            outflow[origin] = Some((other, index));
+        }
+    }
+
+    // Going from the highest vertex down, each passes on everything that's flowed
+    // into it along with its own
+    let mut order = (0..diagram.vertices.len()).filter(|&vertex| heights[vertex].is_some()).collect::<Vec<_>>();
+    order.sort_by(|&a, &b| match (heights[a], heights[b]) {
+        (Some(a), Some(b)) => compare(b, a),
+        _ => Ordering::Equal,
+    });
+    let mut flow = vec![1u32; diagram.vertices.len()];
+    let mut rivers = Vec::new();
+    for vertex in order {
+        if let Some((_, halfedge)) = outflow[vertex] {
+            if let Some(destination) = destination(halfedge) {
+                flow[destination.0 as usize] = flow[destination.0 as usize].saturating_add(flow[vertex]);
+            }
+            if flow[vertex] >= min_flow {
+                rivers.push((HalfEdgeId(halfedge as u32), flow[vertex]));
+            }
+        }
+    }
+    rivers.sort_by_key(|&(halfedge_id, _)| halfedge_id.0);
+    rivers
+}
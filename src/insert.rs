@@ -0,0 +1,448 @@
+use std::collections::VecDeque;
+
+use cgmath::{Point2, BaseFloat};
+use fnv::{FnvHashMap, FnvHashSet};
+
+use build::{Metric, Site};
+use diagram::{Diagram, Vertex, VertexId, HalfEdge, HalfEdgeId, Face, FaceId};
+use edit::{remove_halfedges, remove_vertices};
+use predicates::orient2d;
+
+/// Whether a point is inside a face
+///
+/// Points on an edge between two faces count as being in both, but points on the
+/// outside of the diagram count as being outside it, like in the builder.
+pub(crate) fn inside_face<T: BaseFloat>(diagram: &Diagram<T>, face_id: FaceId, point: Point2<f64>) -> bool {
+    let mut winding = 0;
+    for halfedge_id in diagram.face_edges(face_id) {
+        let halfedge = &diagram.halfedges[halfedge_id.0 as usize];
+        let (a, b) = match (halfedge.origin, destination(diagram, halfedge_id)) {
+            (Some(a), Some(b)) => (diagram.vertices[a.0 as usize].coordinates.cast::<f64>(), diagram.vertices[b.0 as usize].coordinates.cast::<f64>()),
+            _ => return false,
+        };
+
+        let side = match orient2d(a, b, point) {
+            side if side != 0.0 => side,
+            _ if halfedge.twin.is_some() => 1.0,
+            _ => -1.0,
+        };
+        match (point.y >= a.y, point.y >= b.y) {
+            (true, false) if side > 0.0 => winding += 1,
+            (false, true) if side < 0.0 => winding -= 1,
+            _ => {}
+        }
+    }
+    winding != 0
+}
+
+/// Destination of a half-edge
+fn destination<T: BaseFloat>(diagram: &Diagram<T>, halfedge_id: HalfEdgeId) -> Option<VertexId> {
+    let next = diagram.halfedges[halfedge_id.0 as usize].next;
+    diagram.halfedges[next.0 as usize].origin
+}
+
+/// The half-edge of an edge that its cut is kept under, the lower of the two
+fn cut_key<T>(diagram: &Diagram<T>, halfedge: HalfEdgeId) -> HalfEdgeId {
+    diagram.halfedges[halfedge.0 as usize].twin.map_or(halfedge, |twin| HalfEdgeId(halfedge.0.min(twin.0)))
+}
+
+/// Half-edge of a new cell, before it is linked into a cycle
+struct CellEdge {
+    halfedge: HalfEdgeId,
+    from: VertexId,
+    to: VertexId,
+}
+
+/// Whether a face is the convex cell of its site, with each edge it shares on
+/// the power bisector of the sites either side, within rounding
+///
+/// Edits like `snap_to_grid` and `add_noisy_edges` move vertices off the
+/// bisectors, and faces that bend inwards can't have a cell cut out of them in
+/// one piece.
+fn is_site_cell<T: BaseFloat>(diagram: &Diagram<T>, face_id: FaceId, halfedges: &[HalfEdgeId], ends: &[(VertexId, VertexId)], weighted: bool) -> bool {
+    let corners = ends.iter().map(|&(from, _)| diagram.vertices[from.0 as usize].coordinates.cast::<f64>()).collect::<Vec<_>>();
+    if corners.len() < 3 {
+        return false;
+    }
+
+    let site = |face_id: FaceId| {
+        let site = &diagram.faces[face_id.0 as usize].site;
+        (site.position.cast::<f64>(), if weighted { site.weight.to_f64().unwrap() } else { 0.0 })
+    };
+    let (position, weight) = site(face_id);
+
+    // Rounding errors grow with the size of the coordinates, and the power
+    // distances compared below are squares of them
+    let extent = corners.iter().chain(Some(&position)).fold(0.0f64, |extent, point| extent.max(point.x.abs()).max(point.y.abs()));
+    let tolerance = T::epsilon().to_f64().unwrap().sqrt() * (extent * extent + weight.abs());
+
+    (0..corners.len()).all(|index| {
+        let (a, b, c) = (corners[index], corners[(index + 1) % corners.len()], corners[(index + 2) % corners.len()]);
+        if orient2d(a, b, c) < -tolerance {
+            return false;
+        }
+
+        // The edge's line is where the two power distances are the same, and the
+        // face's site is on its side of it
+        diagram.halfedges[halfedges[index].0 as usize].twin.is_none_or(|twin| {
+            let (other, other_weight) = site(diagram.halfedges[twin.0 as usize].incident_face);
+            let difference = |point: Point2<f64>| {
+                let (dx, dy) = (point.x - position.x, point.y - position.y);
+                let (ox, oy) = (point.x - other.x, point.y - other.y);
+                (dx * dx + dy * dy - weight) - (ox * ox + oy * oy - other_weight)
+            };
+            let side = (b.x - a.x) * (position.y - other.y) - (b.y - a.y) * (position.x - other.x);
+            difference(a).abs() <= tolerance && difference(b).abs() <= tolerance && side > 0.0
+        })
+    })
+}
+
+/// A corner of the new cell, either a vertex already in it or the cut on an edge
+/// leaving it, keyed by the lower of the edge's half-edges
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+enum Corner {
+    Vertex(VertexId),
+    Cut(HalfEdgeId),
+}
+
+/// Checks that every face around the new cell keeps one piece, and that the new
+/// cell's edges join up into one cycle, before anything is changed
+///
+/// Returns the index of the edge where each face's boundary leaves the new cell.
+/// It is zero for faces the new cell covers, which have no such edge.
+fn plan_cell<T, F>(diagram: &Diagram<T>, affected: &[(FaceId, Vec<HalfEdgeId>)], affected_ends: &[Vec<(VertexId, VertexId)>], is_inside: F) -> Option<Vec<usize>>
+    where F: Fn(VertexId) -> bool
+{
+    let cut = |halfedge: HalfEdgeId| Corner::Cut(cut_key(diagram, halfedge));
+    let corner = |halfedge: HalfEdgeId, vertex: VertexId| if is_inside(vertex) { Corner::Vertex(vertex) } else { cut(halfedge) };
+
+    let mut exits = Vec::with_capacity(affected.len());
+    let mut cell_edges = FnvHashMap::default();
+    for ((_, halfedges), ends) in affected.iter().zip(affected_ends) {
+        let entries = (0..ends.len()).filter(|&index| !is_inside(ends[index].0) && is_inside(ends[index].1)).collect::<Vec<_>>();
+        let leaving = (0..ends.len()).filter(|&index| is_inside(ends[index].0) && !is_inside(ends[index].1)).collect::<Vec<_>>();
+        if entries.len() > 1 || entries.len() != leaving.len() {
+            return None;
+        }
+        exits.push(leaving.first().cloned().unwrap_or(0));
+
+        // The same edges as the ones `insert_site` makes for this face
+        let mut add = |from: Corner, to: Corner| cell_edges.insert(from, to).is_none();
+        for (index, &halfedge) in halfedges.iter().enumerate() {
+            let (from, to) = ends[index];
+            let boundary = diagram.halfedges[halfedge.0 as usize].twin.is_none();
+            let added = match (is_inside(from), is_inside(to)) {
+                (false, true) => {
+                    let exit = halfedges[leaving[0]];
+                    (!boundary || add(cut(halfedge), corner(halfedge, to))) && add(cut(exit), cut(halfedge))
+                }
+                (true, _) if boundary => add(corner(halfedge, from), corner(halfedge, to)),
+                _ => true,
+            };
+            if !added {
+                return None;
+            }
+        }
+    }
+
+    // Following the edges around from any one of them has to come back to it
+    // after all of them
+    let first = *cell_edges.keys().next()?;
+    let mut corner = first;
+    for length in 1..=cell_edges.len() {
+        corner = *cell_edges.get(&corner)?;
+        if corner == first {
+            return if length == cell_edges.len() { Some(exits) } else { None };
+        }
+    }
+    None
+}
+
+/// Adds a site to a finished diagram, carving its cell out of the cells around it
+///
+/// Only the cells next to the new one are changed. A vertex is in the new cell if
+/// the new site is closer to it than the sites it was equidistant to, which is the
+/// Bowyer-Watson test on the Delaunay triangle it is the centre of. Edges between
+/// two such vertices are removed, and edges with one are cut where they leave the
+/// new cell.
+pub fn insert_site<T: BaseFloat>(diagram: &mut Diagram<T>, position: Point2<T>) -> Option<FaceId> {
+    // Cells under other metrics aren't cut by straight bisectors
+    if diagram.metric != Metric::Euclidean {
+        return None;
+    }
+
+    let located = diagram.locate(position)?;
+    let point = position.cast::<f64>();
+    let weighted = diagram.site_index.is_weighted();
+
+    // A point on an edge is as close to the sites on both sides, and rounding can
+    // leave it just outside the face that was found, so also try the faces around
+    // that one. Points outside the diagram don't get a cell, like in the builder
+    let mut around = vec![located];
+    for vertex in diagram.face_edges(located).filter_map(|halfedge| diagram.halfedges[halfedge.0 as usize].origin).collect::<Vec<_>>() {
+        for halfedge in diagram.incident_edges(vertex) {
+            let face_id = diagram.halfedges[halfedge.0 as usize].incident_face;
+            if !around.contains(&face_id) {
+                around.push(face_id);
+            }
+        }
+    }
+    let starts = around.into_iter().filter(|&face_id| inside_face(diagram, face_id, point)).collect::<Vec<_>>();
+    if starts.is_empty() {
+        return None;
+    }
+
+    // Duplicate sites are merged into the first one
+    if !weighted && diagram.faces[located.0 as usize].site.position == position {
+        return None;
+    }
+
+    // How much closer the new site is to a point than the site of a face, by
+    // power distance. This is positive in the new cell
+    let closer = |diagram: &Diagram<T>, face_id: FaceId, other: Point2<f64>| {
+        let site = &diagram.faces[face_id.0 as usize].site;
+        let site_position = site.position.cast::<f64>();
+        let weight = if weighted { site.weight.to_f64().unwrap() } else { 0.0 };
+        let (dx, dy) = (other.x - site_position.x, other.y - site_position.y);
+        let (px, py) = (other.x - point.x, other.y - point.y);
+        (dx * dx + dy * dy - weight) - (px * px + py * py)
+    };
+
+    // Find the vertices in the new cell, and the faces around them. A vertex is
+    // tested once, against whichever face reaches it first, so that every face
+    // agrees on it
+    let mut inside = FnvHashMap::default();
+    let mut affected = Vec::new();
+    let mut visited = FnvHashSet::default();
+    let mut queue = VecDeque::new();
+    for &start in &starts {
+        visited.insert(start);
+        queue.push_back(start);
+    }
+
+    while let Some(face_id) = queue.pop_front() {
+        let halfedges = diagram.face_edges(face_id).collect::<Vec<_>>();
+        let mut any_inside = false;
+        for &halfedge in &halfedges {
+            if let Some(vertex) = diagram.halfedges[halfedge.0 as usize].origin {
+                let coordinates = diagram.vertices[vertex.0 as usize].coordinates.cast::<f64>();
+                let is_inside = *inside.entry(vertex).or_insert_with(|| closer(diagram, face_id, coordinates) > 0.0);
+                any_inside |= is_inside;
+            }
+        }
+        if !any_inside {
+            continue;
+        }
+
+        for &halfedge in &halfedges {
+            let from = diagram.halfedges[halfedge.0 as usize].origin;
+            let to = destination(diagram, halfedge);
+            let touches_cell = [from, to].iter().flatten().any(|vertex| inside.get(vertex) == Some(&true));
+            if let (true, Some(twin)) = (touches_cell, diagram.halfedges[halfedge.0 as usize].twin) {
+                let neighbour = diagram.halfedges[twin.0 as usize].incident_face;
+                if visited.insert(neighbour) {
+                    queue.push_back(neighbour);
+                }
+            }
+        }
+        affected.push((face_id, halfedges));
+    }
+
+    // The new cell is carved out of the cells around it, so they have to be
+    // closed cells of their sites, and the pieces have to fit together. Cells of
+    // a diagram from `finish_unclipped` can have edges going off to infinity
+    let affected_ends = affected.iter()
+        .map(|(_, halfedges)| halfedges.iter()
+            .map(|&halfedge| Some((diagram.halfedges[halfedge.0 as usize].origin?, destination(diagram, halfedge)?)))
+            .collect::<Option<Vec<_>>>())
+        .collect::<Option<Vec<_>>>()?;
+    let site_cells = affected.iter().zip(&affected_ends)
+        .all(|(&(face_id, ref halfedges), ends)| is_site_cell(diagram, face_id, halfedges, ends, weighted));
+    if !site_cells {
+        return None;
+    }
+    let is_inside = |vertex: VertexId| inside.get(&vertex) == Some(&true);
+    let exits = plan_cell(diagram, &affected, &affected_ends, is_inside)?;
+
+    let id = diagram.faces.iter().map(|face| face.site.id + 1).max().unwrap_or(0);
+    let new_face = FaceId(diagram.faces.len() as u32);
+    diagram.faces.push(Face {
+        first_halfedge: None,
+        site: Site::new(id, position),
+    });
+    diagram.site_index.insert(new_face, position, T::zero());
+
+    // Vertices in the new cell where an edge between two cells met the boundary.
+    // The edge goes away, so the boundary edges either side of one become a single
+    // edge, like they would be if the diagram had been built with the new site
+    let mut meets_edge = FnvHashSet::default();
+    let mut meets_boundary = FnvHashSet::default();
+    for ((_, halfedges), ends) in affected.iter().zip(&affected_ends) {
+        for (&halfedge, &(from, _)) in halfedges.iter().zip(ends) {
+            if is_inside(from) {
+                let meets = if diagram.halfedges[halfedge.0 as usize].twin.is_some() { &mut meets_edge } else { &mut meets_boundary };
+                meets.insert(from);
+            }
+        }
+    }
+
+    // Place a vertex where each edge leaves the new cell. Both halves of an edge
+    // share it, so it is only worked out once
+    let mut cuts = FnvHashMap::default();
+    for (&(face_id, ref halfedges), ends) in affected.iter().zip(&affected_ends) {
+        for (&halfedge, &(from, to)) in halfedges.iter().zip(ends) {
+            if is_inside(from) == is_inside(to) {
+                continue;
+            }
+
+            let key = cut_key(diagram, halfedge);
+            if cuts.contains_key(&key) {
+                continue;
+            }
+
+            let (outside_end, inside_end) = if is_inside(from) { (to, from) } else { (from, to) };
+            let outside_end = diagram.vertices[outside_end.0 as usize].coordinates.cast::<f64>();
+            let inside_end = diagram.vertices[inside_end.0 as usize].coordinates.cast::<f64>();
+            let (outside_closer, inside_closer) = (closer(diagram, face_id, outside_end), closer(diagram, face_id, inside_end));
+            let t = (outside_closer / (outside_closer - inside_closer)).clamp(0.0, 1.0);
+
+            diagram.vertices.push(Vertex {
+                coordinates: (outside_end + (inside_end - outside_end) * t).cast::<T>(),
+                incident_edge: halfedge,
+            });
+            cuts.insert(key, VertexId(diagram.vertices.len() as u32 - 1));
+        }
+    }
+
+    let cut = |diagram: &Diagram<T>, halfedge: HalfEdgeId| cuts[&cut_key(diagram, halfedge)];
+
+    // Work out the new cycle of each face, and collect the edges of the new cell
+    let mut cell_edges = Vec::new();
+    let mut dead_halfedges = Vec::new();
+    let new_halfedge = |diagram: &mut Diagram<T>, origin: VertexId, twin: Option<HalfEdgeId>, face_id: FaceId| {
+        let halfedge = HalfEdgeId(diagram.halfedges.len() as u32);
+        diagram.halfedges.push(HalfEdge {
+            origin: Some(origin),
+            twin,
+            incident_face: face_id,
+            next: halfedge,
+            prev: halfedge,
+        });
+        halfedge
+    };
+
+    for ((&(face_id, ref halfedges), ends), &exit_index) in affected.iter().zip(&affected_ends).zip(&exits) {
+        let mut cycle = Vec::new();
+        for (index, &halfedge) in halfedges.iter().enumerate() {
+            let (from, to) = ends[index];
+            let boundary = diagram.halfedges[halfedge.0 as usize].twin.is_none();
+
+            match (is_inside(from), is_inside(to)) {
+                (false, false) => cycle.push(halfedge),
+                (false, true) => {
+                    // Keep the part of the edge outside the new cell, then follow
+                    // the new cell to where the face's boundary comes out of it
+                    let entry = cut(diagram, halfedge);
+                    cycle.push(halfedge);
+                    if boundary {
+                        let outer = new_halfedge(diagram, entry, None, new_face);
+                        cell_edges.push(CellEdge { halfedge: outer, from: entry, to });
+                    }
+
+                    let exit = cut(diagram, halfedges[exit_index]);
+
+                    let across = new_halfedge(diagram, entry, None, face_id);
+                    let back = new_halfedge(diagram, exit, Some(across), new_face);
+                    diagram.halfedges[across.0 as usize].twin = Some(back);
+                    cycle.push(across);
+                    cell_edges.push(CellEdge { halfedge: back, from: exit, to: entry });
+                }
+                (true, true) => {
+                    if boundary {
+                        diagram.halfedges[halfedge.0 as usize].incident_face = new_face;
+                        cell_edges.push(CellEdge { halfedge, from, to });
+                    } else {
+                        dead_halfedges.push(halfedge);
+                    }
+                }
+                (true, false) => {
+                    let exit = cut(diagram, halfedge);
+                    if boundary {
+                        let outer = new_halfedge(diagram, from, None, new_face);
+                        cell_edges.push(CellEdge { halfedge: outer, from, to: exit });
+                    }
+
+                    diagram.halfedges[halfedge.0 as usize].origin = Some(exit);
+                    cycle.push(halfedge);
+                }
+            }
+        }
+
+        link_cycle(diagram, &cycle);
+        diagram.faces[face_id.0 as usize].first_halfedge = cycle.first().cloned();
+    }
+
+    // The edges of the new cell all meet end to end
+    let starting_at = cell_edges.iter()
+        .map(|edge| (edge.from, edge))
+        .collect::<FnvHashMap<_, _>>();
+    if let Some(first) = cell_edges.first() {
+        let mut cycle = vec![first];
+        while let Some(&edge) = starting_at.get(&cycle[cycle.len() - 1].to) {
+            if edge.halfedge == first.halfedge || cycle.len() > cell_edges.len() {
+                break;
+            }
+            cycle.push(edge);
+        }
+
+        let boundary = |diagram: &Diagram<T>, edge: &CellEdge| diagram.halfedges[edge.halfedge.0 as usize].twin.is_none();
+        let merged = (0..cycle.len())
+            .filter(|&index| {
+                let (previous, edge) = (cycle[(index + cycle.len() - 1) % cycle.len()], cycle[index]);
+                boundary(diagram, previous) && boundary(diagram, edge) && meets_edge.contains(&edge.from) && meets_boundary.contains(&edge.from)
+            })
+            .collect::<FnvHashSet<_>>();
+        let cycle = cycle.into_iter().enumerate()
+            .filter_map(|(index, edge)| if merged.contains(&index) {
+                dead_halfedges.push(edge.halfedge);
+                None
+            } else {
+                Some(edge.halfedge)
+            })
+            .collect::<Vec<_>>();
+
+        link_cycle(diagram, &cycle);
+        diagram.faces[new_face.0 as usize].first_halfedge = cycle.first().cloned();
+    }
+
+    // Point every vertex left on a changed edge at one of its half-edges
+    let changed = affected.iter()
+        .map(|&(face_id, _)| face_id)
+        .chain(Some(new_face))
+        .flat_map(|face_id| diagram.face_edges(face_id))
+        .collect::<Vec<_>>();
+    let mut alive = FnvHashSet::default();
+    for halfedge in changed {
+        if let Some(origin) = diagram.halfedges[halfedge.0 as usize].origin {
+            diagram.vertices[origin.0 as usize].incident_edge = halfedge;
+            alive.insert(origin);
+        }
+    }
+
+    let dead_vertices = inside.iter()
+        .filter(|&(vertex, &is_inside)| is_inside && !alive.contains(vertex))
+        .map(|(&vertex, _)| vertex)
+        .collect::<Vec<_>>();
+    remove_halfedges(diagram, dead_halfedges);
+    remove_vertices(diagram, dead_vertices);
+
+    Some(new_face)
+}
+
+fn link_cycle<T>(diagram: &mut Diagram<T>, cycle: &[HalfEdgeId]) {
+    for (index, &halfedge) in cycle.iter().enumerate() {
+        let next = cycle[(index + 1) % cycle.len()];
+        diagram.halfedges[halfedge.0 as usize].next = next;
+        diagram.halfedges[next.0 as usize].prev = halfedge;
+    }
+}
@@ -0,0 +1,122 @@
+//! Diagrams of sites with integer coordinates, like the ones in CAD drawings and
+//! circuit layouts
+//!
+//! Integers up to `MAX_COORDINATE` either way convert to f64 exactly, and so does
+//! the gap between two of them, so moving the sites to the corner of the clip rect
+//! and scaling them by a power of two doesn't round them. The sweep then decides
+//! everything with the exact comparisons of the "exact" feature, so the topology of
+//! the diagram is always the one exact arithmetic would give. Only the positions of
+//! vertices are rounded, to the nearest f64.
+//!
+//! This module is behind the "integer" feature, which turns on "exact" as well.
+
+use std::error::Error;
+use std::fmt;
+
+use cgmath::{Point2, Vector2};
+
+use build::{BuildError, DiagramBuilder, Rect, Site};
+use diagram::Diagram;
+
+/// Largest coordinate, either way from zero, that a site or a corner of the clip
+/// rect can have
+pub const MAX_COORDINATE: i64 = 1 << 52;
+
+/// A site at a point with integer coordinates
+///
+/// Coordinates can be any integer type that converts to i64, which is everything
+/// up to i64 except u64 and usize.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IntegerSite<I = i32> {
+    pub id: u32,
+    pub position: Point2<I>,
+}
+
+impl<I> IntegerSite<I> {
+    pub fn new(id: u32, position: Point2<I>) -> IntegerSite<I> {
+        IntegerSite {
+            id,
+            position,
+        }
+    }
+}
+
+/// Why `build` couldn't build a diagram
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IntegerError {
+    /// A site has a coordinate further than `MAX_COORDINATE` from zero
+    SiteOutOfRange {
+        site_id: u32,
+
+        /// Position of the site in the list that was given
+        index: usize,
+    },
+
+    /// A corner of the clip rect is further than `MAX_COORDINATE` from zero
+    RectOutOfRange,
+
+    /// The builder refused the sites, see `DiagramBuilder::try_finish`
+    Build(BuildError),
+}
+
+impl fmt::Display for IntegerError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            IntegerError::SiteOutOfRange { site_id, index } => {
+                write!(f, "site {} (index {}) has a coordinate too large to convert exactly", site_id, index)
+            }
+            IntegerError::RectOutOfRange => write!(f, "clip rect has a corner too large to convert exactly"),
+            IntegerError::Build(error) => write!(f, "build failed: {}", error),
+        }
+    }
+}
+
+impl Error for IntegerError {}
+
+impl From<BuildError> for IntegerError {
+    fn from(error: BuildError) -> IntegerError {
+        IntegerError::Build(error)
+    }
+}
+
+/// A coordinate as an f64, if it converts exactly
+fn convert(coordinate: i64) -> Option<f64> {
+    if coordinate.unsigned_abs() <= MAX_COORDINATE as u64 {
+        Some(coordinate as f64)
+    } else {
+        None
+    }
+}
+
+fn convert_point<I: Into<i64> + Copy>(point: Point2<I>) -> Option<Point2<f64>> {
+    Some(Point2::new(convert(point.x.into())?, convert(point.y.into())?))
+}
+
+/// Builds the diagram of sites with integer coordinates, clipped to a rect
+///
+/// Sites are treated like they are by `DiagramBuilder::try_finish`: ones on the
+/// edge of the rect or outside it are left out, and of sites at the same point
+/// the first one gets the cell. Fails if any coordinate, or the far corner of
+/// the rect, is too large to convert to f64 exactly.
+pub fn build<I: Into<i64> + Copy>(rect: Rect<I>, sites: &[IntegerSite<I>]) -> Result<Diagram<f64>, IntegerError> {
+    let position = convert_point(rect.position).ok_or(IntegerError::RectOutOfRange)?;
+    let (width, height) = (rect.size.x.into(), rect.size.y.into());
+    let far_corner = rect.position.x.into().checked_add(width).and_then(convert)
+        .zip(rect.position.y.into().checked_add(height).and_then(convert));
+    if far_corner.is_none() {
+        return Err(IntegerError::RectOutOfRange);
+    }
+
+    let sites = sites.iter().enumerate()
+        .map(|(index, site)| match convert_point(site.position) {
+            Some(position) => Ok(Site::new(site.id, position)),
+            None => Err(IntegerError::SiteOutOfRange { site_id: site.id, index }),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let rect = Rect {
+        position,
+        size: Vector2::new(width as f64, height as f64),
+    };
+    Ok(DiagramBuilder::new(rect, sites).try_finish()?)
+}
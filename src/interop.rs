@@ -0,0 +1,86 @@
+use cgmath::{Point2, Vector2, BaseFloat};
+use geo_types::{Coord, CoordFloat, LineString, MultiPolygon, Polygon};
+
+use build::{ClipRegion, IntoSite, Rect, Site};
+use diagram::{Diagram, FaceId};
+
+fn to_coord<T: BaseFloat + CoordFloat>(point: Point2<T>) -> Coord<T> {
+    Coord { x: point.x, y: point.y }
+}
+
+fn to_point<T: BaseFloat + CoordFloat>(coord: Coord<T>) -> Point2<T> {
+    Point2::new(coord.x, coord.y)
+}
+
+/// The polygon of a face, see `Diagram::face_polygon`
+pub fn face_polygon<T: BaseFloat + CoordFloat>(diagram: &Diagram<T>, face_id: FaceId) -> Option<Polygon<T>> {
+    let coords = diagram.face_vertices(face_id).map(to_coord).collect::<Vec<_>>();
+    if coords.is_empty() {
+        return None;
+    }
+
+    // Polygon::new closes the ring
+    Some(Polygon::new(LineString::new(coords), Vec::new()))
+}
+
+/// Creates sites from `geo` points or coordinates, using their indices as ids
+pub fn sites_from_geo<T, P, I>(points: I) -> Vec<Site<T>>
+    where T: BaseFloat + CoordFloat, P: Into<Coord<T>>, I: IntoIterator<Item = P>
+{
+    points.into_iter().enumerate()
+        .map(|(index, point)| Site::new(index as u32, to_point(point.into())))
+        .collect()
+}
+
+impl<T: BaseFloat + CoordFloat> IntoSite<T> for Coord<T> {
+    fn into_site(self, id: u32) -> Site<T> {
+        Site::new(id, to_point(self))
+    }
+}
+
+impl<T: BaseFloat + CoordFloat> IntoSite<T> for geo_types::Point<T> {
+    fn into_site(self, id: u32) -> Site<T> {
+        Site::new(id, to_point(self.0))
+    }
+}
+
+/// All the cells of a diagram, leaving out faces without any half-edges
+impl<'a, T: BaseFloat + CoordFloat> From<&'a Diagram<T>> for MultiPolygon<T> {
+    fn from(diagram: &'a Diagram<T>) -> MultiPolygon<T> {
+        let polygons = (0..diagram.faces.len())
+            .filter_map(|index| face_polygon(diagram, FaceId(index as u32)))
+            .collect::<Vec<_>>();
+
+        MultiPolygon::new(polygons)
+    }
+}
+
+impl<T: BaseFloat + CoordFloat> From<geo_types::Rect<T>> for Rect<T> {
+    fn from(rect: geo_types::Rect<T>) -> Rect<T> {
+        Rect {
+            position: to_point(rect.min()),
+            size: Vector2::new(rect.width(), rect.height()),
+        }
+    }
+}
+
+impl<T: BaseFloat + CoordFloat> From<Rect<T>> for geo_types::Rect<T> {
+    fn from(rect: Rect<T>) -> geo_types::Rect<T> {
+        geo_types::Rect::new(to_coord(rect.position), to_coord(rect.position + rect.size))
+    }
+}
+
+impl<T: BaseFloat + CoordFloat> From<geo_types::Rect<T>> for ClipRegion<T> {
+    fn from(rect: geo_types::Rect<T>) -> ClipRegion<T> {
+        ClipRegion::Rect(rect.into())
+    }
+}
+
+/// Clips to the exterior of a polygon, which must be convex
+///
+/// Holes are ignored. Use `Diagram::clip_to_polygon` for concave polygons.
+impl<T: BaseFloat + CoordFloat> From<Polygon<T>> for ClipRegion<T> {
+    fn from(polygon: Polygon<T>) -> ClipRegion<T> {
+        ClipRegion::ConvexPolygon(polygon.exterior().coords().map(|&coord| to_point(coord)).collect())
+    }
+}
@@ -0,0 +1,134 @@
+//! Checks that a diagram built from any points is correct, for fuzzing
+//!
+//! `build_and_validate` is meant to be called from a fuzz target or a property
+//! test with whatever points it generates. Input the builder refuses comes back
+//! as `InvariantError::Build`, which a fuzzer should skip over; every other error
+//! is a bug in the crate.
+
+use std::error::Error;
+use std::fmt;
+
+use cgmath::{Point2, BaseFloat};
+
+use build::{BuildError, DiagramBuilder, Site, SiteOutcome};
+use diagram::{Diagram, FaceId, ValidationError};
+use predicates::orient2d;
+
+/// How far outside its cell a site can be before it counts as a failure, in
+/// multiples of the precision of the scalar type at the size of the diagram.
+/// Vertices are rounded to the scalar type, which can move an edge past a site
+/// that is very close to it
+const OUTSIDE_TOLERANCE: f64 = 64.0;
+
+/// A way the diagram built by `build_and_validate` is wrong
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InvariantError {
+    /// The builder refused the points, see `DiagramBuilder::try_finish`
+    Build(BuildError),
+
+    /// The links between the vertices, half-edges and faces are broken, see
+    /// `Diagram::validate`
+    Invalid(ValidationError),
+
+    /// A point inside the clip region didn't get a face, and wasn't a duplicate
+    SiteDropped {
+        /// Position of the point in the list that was given
+        index: usize,
+    },
+
+    /// A face has no half-edges
+    EmptyFace(FaceId),
+
+    /// The edges don't make a single connected planar graph, as V − E + F isn't
+    /// two. F counts the outside of the diagram as a face
+    EulerCharacteristic {
+        vertices: usize,
+        edges: usize,
+        faces: usize,
+    },
+
+    /// The site of a face is outside its cell
+    SiteOutsideCell(FaceId),
+}
+
+impl fmt::Display for InvariantError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            InvariantError::Build(error) => write!(f, "build failed: {}", error),
+            InvariantError::Invalid(error) => write!(f, "invalid diagram: {}", error),
+            InvariantError::SiteDropped { index } => write!(f, "point at index {} didn't get a face", index),
+            InvariantError::EmptyFace(face) => write!(f, "face {} has no half-edges", face.0),
+            InvariantError::EulerCharacteristic { vertices, edges, faces } => {
+                write!(f, "{} vertices, {} edges and {} faces don't make a connected planar graph", vertices, edges, faces)
+            }
+            InvariantError::SiteOutsideCell(face) => write!(f, "site of face {} is outside its cell", face.0),
+        }
+    }
+}
+
+impl Error for InvariantError {}
+
+impl From<BuildError> for InvariantError {
+    fn from(error: BuildError) -> InvariantError {
+        InvariantError::Build(error)
+    }
+}
+
+/// Builds the diagram of some points, clipped to the rect around them, and checks
+/// everything that should be true of it
+///
+/// As well as `Diagram::validate`, this checks that every point got a face unless
+/// it was a duplicate, that no face is empty, that V − E + F = 2, and that every
+/// site is inside its own cell. No points at all give an empty diagram, which
+/// passes. Points get their index as their site id.
+pub fn build_and_validate<T: BaseFloat>(points: &[Point2<T>]) -> Result<Diagram<T>, InvariantError> {
+    if points.is_empty() {
+        return Ok(Diagram::default());
+    }
+
+    let sites = points.iter().enumerate().map(|(index, &point)| Site::new(index as u32, point)).collect();
+    let builder = DiagramBuilder::with_auto_bounds(sites, T::zero());
+    let site_mapping = builder.site_mapping().clone();
+    let diagram = builder.try_finish()?;
+    diagram.validate().map_err(InvariantError::Invalid)?;
+
+    // The rect is around every point, so only duplicates should be dropped
+    if let Some(index) = site_mapping.outcomes().iter().position(|outcome| outcome.face().is_none() && !matches!(*outcome, SiteOutcome::Merged(_))) {
+        return Err(InvariantError::SiteDropped { index });
+    }
+
+    if let Some(index) = diagram.faces.iter().position(|face| face.first_halfedge.is_none()) {
+        return Err(InvariantError::EmptyFace(FaceId(index as u32)));
+    }
+
+    // Twins are one edge, and half-edges along the boundary are an edge each
+    let vertices = diagram.vertices.len();
+    let edges = diagram.halfedges.iter().enumerate()
+        .filter(|&(index, halfedge)| halfedge.twin.is_none_or(|twin| index < twin.0 as usize))
+        .count();
+    let faces = diagram.faces.len() + 1;
+    if vertices + faces != edges + 2 {
+        return Err(InvariantError::EulerCharacteristic { vertices, edges, faces });
+    }
+
+    // Cells are convex and counter-clockwise, so the site has to be on the left of
+    // every edge. Moving the ends of an edge changes the orientation by up to how
+    // far they are from the site, which matters more than the direction of edges
+    // so short that rounding decides it
+    let bounds = points.iter().fold((T::zero(), T::zero()), |(x, y), point| (x.max(point.x.abs()), y.max(point.y.abs())));
+    let tolerance = bounds.0.max(bounds.1).to_f64().unwrap() * T::epsilon().to_f64().unwrap() * OUTSIDE_TOLERANCE;
+    for (index, face) in diagram.faces.iter().enumerate() {
+        let face_id = FaceId(index as u32);
+        let site = face.site.position.cast::<f64>();
+        let corners = diagram.face_vertices(face_id).map(|corner| corner.cast::<f64>()).collect::<Vec<_>>();
+        for (corner_index, &a) in corners.iter().enumerate() {
+            let b = corners[(corner_index + 1) % corners.len()];
+            let reach = (a.x - site.x).hypot(a.y - site.y) + (b.x - site.x).hypot(b.y - site.y);
+            if orient2d(a, b, site) < -tolerance * reach {
+                return Err(InvariantError::SiteOutsideCell(face_id));
+            }
+        }
+    }
+
+    Ok(diagram)
+}
@@ -0,0 +1,271 @@
+use std::cmp::Ordering;
+
+use cgmath::{Point2, BaseFloat};
+#[cfg(feature = "simd")]
+use cgmath::num_traits::cast;
+
+use diagram::FaceId;
+#[cfg(feature = "simd")]
+use simd;
+
+/// Number of sites in a subtree below which they're all checked instead of
+/// searched
+#[cfg(feature = "simd")]
+const LEAF_SIZE: usize = 8;
+
+#[derive(Debug, Clone, Copy)]
+struct Node<T> {
+    position: Point2<T>,
+    weight: T,
+    face: FaceId,
+
+    /// Largest weight of this node and the ones below it
+    max_weight: T,
+}
+
+/// k-d tree over the sites of a diagram, for finding the site closest to a point
+///
+/// The tree is stored in a flat list. The middle node of any range splits the
+/// others in it, along x and y in turn, and the nodes before and after it are
+/// subtrees laid out the same way.
+///
+/// Distances are power distances, so this works for power diagrams too. When the
+/// tree isn't weighted, weights are taken as zero and it finds the nearest site.
+#[derive(Debug, Clone)]
+pub struct KdTree<T> {
+    nodes: Vec<Node<T>>,
+    weighted: bool,
+
+    /// Sites inserted since the tree was built, which are checked one by one
+    pending: Vec<Node<T>>,
+}
+
+impl<T> Default for KdTree<T> {
+    fn default() -> KdTree<T> {
+        KdTree {
+            nodes: Vec::new(),
+            weighted: false,
+            pending: Vec::new(),
+        }
+    }
+}
+
+fn coordinate<T: BaseFloat>(point: Point2<T>, depth: usize) -> T {
+    match depth % 2 {
+        0 => point.x,
+        _ => point.y,
+    }
+}
+
+fn build<T: BaseFloat>(nodes: &mut [Node<T>], depth: usize) {
+    if nodes.is_empty() {
+        return;
+    }
+
+    let middle = nodes.len() / 2;
+    nodes.select_nth_unstable_by(middle, |a, b| {
+        coordinate(a.position, depth).partial_cmp(&coordinate(b.position, depth)).unwrap_or(Ordering::Equal)
+    });
+
+    let (before, rest) = nodes.split_at_mut(middle);
+    let (node, after) = rest.split_first_mut().unwrap();
+    build(before, depth + 1);
+    build(after, depth + 1);
+
+    for subtree in &[&*before, &*after] {
+        if !subtree.is_empty() {
+            node.max_weight = node.max_weight.max(subtree[subtree.len() / 2].max_weight);
+        }
+    }
+}
+
+impl<T: BaseFloat> KdTree<T> {
+    pub fn new<I: Iterator<Item = (FaceId, Point2<T>, T)>>(sites: I, weighted: bool) -> KdTree<T> {
+        let mut tree = KdTree::default();
+        tree.rebuild(sites, weighted);
+        tree
+    }
+
+    /// Replaces the sites in the tree, keeping the memory it already has
+    pub fn rebuild<I: Iterator<Item = (FaceId, Point2<T>, T)>>(&mut self, sites: I, weighted: bool) {
+        self.nodes.clear();
+        self.nodes.extend(sites.map(|(face, position, weight)| {
+            let weight = if weighted { weight } else { T::zero() };
+
+            Node {
+                position,
+                weight,
+                face,
+                max_weight: weight,
+            }
+        }));
+        build(&mut self.nodes, 0);
+
+        self.weighted = weighted;
+        self.pending.clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len() + self.pending.len()
+    }
+
+    /// Adds a site to the tree
+    ///
+    /// New sites are kept aside until there are enough of them that checking them
+    /// all costs more than a search, and then the tree is rebuilt.
+    pub fn insert(&mut self, face: FaceId, position: Point2<T>, weight: T) {
+        let weight = if self.weighted { weight } else { T::zero() };
+        self.pending.push(Node {
+            position,
+            weight,
+            face,
+            max_weight: weight,
+        });
+
+        if self.pending.len() * self.pending.len() > self.nodes.len() {
+            self.nodes.append(&mut self.pending);
+            for node in &mut self.nodes {
+                node.max_weight = node.weight;
+            }
+            build(&mut self.nodes, 0);
+        }
+    }
+
+    pub fn is_weighted(&self) -> bool {
+        self.weighted
+    }
+
+    /// Finds the face of the site with the smallest power distance to a point
+    ///
+    /// Ties go to the face with the lowest id.
+    pub fn nearest(&self, point: Point2<T>) -> Option<FaceId> {
+        let mut neighbours = Neighbours::new(1);
+        search(&self.nodes, 0, point, self.weighted, &mut neighbours);
+        self.search_pending(point, self.weighted, &mut neighbours);
+        neighbours.found.first().map(|&(_, face)| face)
+    }
+
+    /// Finds the faces of the `count` sites closest to a point, nearest first
+    ///
+    /// This ignores weights, even if the tree is weighted.
+    pub fn nearest_unweighted(&self, point: Point2<T>, count: usize) -> Vec<FaceId> {
+        let mut neighbours = Neighbours::new(count);
+        search(&self.nodes, 0, point, false, &mut neighbours);
+        self.search_pending(point, false, &mut neighbours);
+        neighbours.found.into_iter().map(|(_, face)| face).collect()
+    }
+
+    fn search_pending(&self, point: Point2<T>, weighted: bool, neighbours: &mut Neighbours<T>) {
+        offer_all(&self.pending, point, weighted, neighbours);
+    }
+}
+
+/// Offers every node in a list, one at a time
+#[cfg(not(feature = "simd"))]
+fn offer_all<T: BaseFloat>(nodes: &[Node<T>], point: Point2<T>, weighted: bool, neighbours: &mut Neighbours<T>) {
+    for node in nodes {
+        let dx = point.x - node.position.x;
+        let dy = point.y - node.position.y;
+        let weight = if weighted { node.weight } else { T::zero() };
+        neighbours.offer(dx * dx + dy * dy - weight, node.face);
+    }
+}
+
+/// Offers every node in a list, working out the distances to four at a time
+///
+/// Distances are worked out in f64, so for f32 sites they can round differently
+/// to the scalar version, and sites at almost the same distance can swap places.
+#[cfg(feature = "simd")]
+fn offer_all<T: BaseFloat>(nodes: &[Node<T>], point: Point2<T>, weighted: bool, neighbours: &mut Neighbours<T>) {
+    let point = point.cast::<f64>();
+    for chunk in nodes.chunks(4) {
+        let (mut x, mut y, mut weights) = ([0.0; 4], [0.0; 4], [0.0; 4]);
+        for (lane, node) in chunk.iter().enumerate() {
+            x[lane] = node.position.x.to_f64().unwrap();
+            y[lane] = node.position.y.to_f64().unwrap();
+            if weighted {
+                weights[lane] = node.weight.to_f64().unwrap();
+            }
+        }
+
+        let distances = simd::power_distances(point, x, y, weights);
+        for (node, &distance) in chunk.iter().zip(&distances) {
+            neighbours.offer(cast(distance).unwrap(), node.face);
+        }
+    }
+}
+
+/// The closest sites found so far in a search
+struct Neighbours<T> {
+    count: usize,
+
+    /// Distances and faces, ordered by distance and then by face id
+    found: Vec<(T, FaceId)>,
+}
+
+impl<T: BaseFloat> Neighbours<T> {
+    fn new(count: usize) -> Neighbours<T> {
+        Neighbours {
+            count,
+            found: Vec::with_capacity(count + 1),
+        }
+    }
+
+    fn offer(&mut self, distance: T, face: FaceId) {
+        let index = self.found.iter()
+            .position(|&(other_distance, other_face)| distance < other_distance || (distance == other_distance && face.0 < other_face.0))
+            .unwrap_or(self.found.len());
+
+        if index < self.count {
+            self.found.insert(index, (distance, face));
+            self.found.truncate(self.count);
+        }
+    }
+
+    /// Distance that a site has to beat to be one of the closest, if enough have
+    /// been found already
+    fn bound(&self) -> Option<T> {
+        if self.found.len() == self.count {
+            self.found.last().map(|&(distance, _)| distance)
+        } else {
+            None
+        }
+    }
+}
+
+fn search<T: BaseFloat>(nodes: &[Node<T>], depth: usize, point: Point2<T>, weighted: bool, neighbours: &mut Neighbours<T>) {
+    if nodes.is_empty() || neighbours.count == 0 {
+        return;
+    }
+
+    // Small subtrees are cheaper to check all at once than to search
+    #[cfg(feature = "simd")]
+    if nodes.len() <= LEAF_SIZE {
+        offer_all(nodes, point, weighted, neighbours);
+        return;
+    }
+
+    let middle = nodes.len() / 2;
+    let node = nodes[middle];
+
+    let dx = point.x - node.position.x;
+    let dy = point.y - node.position.y;
+    let weight = if weighted { node.weight } else { T::zero() };
+    neighbours.offer(dx * dx + dy * dy - weight, node.face);
+
+    let (before, after) = (&nodes[..middle], &nodes[middle + 1..]);
+    let offset = coordinate(point, depth) - coordinate(node.position, depth);
+    let (near, far) = if offset < T::zero() { (before, after) } else { (after, before) };
+
+    search(near, depth + 1, point, weighted, neighbours);
+
+    // Every site on the far side is at least as far away as the splitting line, so
+    // it can only be closer if its weight makes up the difference
+    if !far.is_empty() {
+        let max_weight = if weighted { far[far.len() / 2].max_weight } else { T::zero() };
+        match neighbours.bound() {
+            Some(bound) if offset * offset - max_weight > bound => {}
+            _ => search(far, depth + 1, point, weighted, neighbours),
+        }
+    }
+}
@@ -0,0 +1,744 @@
+//! Diagrams of sites that move a little at a time, kept up to date without
+//! sweeping again
+//!
+//! The Delaunay triangulation of the sites is kept alongside the diagram. When
+//! the sites move, the triangulation usually stays a valid triangulation of them,
+//! and only a few of its edges stop being Delaunay. Those are flipped until every
+//! edge passes the incircle test again, and the diagram is read back off the
+//! triangulation, since its vertices are the circumcentres of the triangles.
+//!
+//! A site that moves far enough to turn one of its triangles inside out is taken
+//! out of the triangulation, and put back in where it ended up. Sites on the edge
+//! of the triangulation have the triangles that turned inside out taken off
+//! instead where they can, and dents in the edge are filled in. When that isn't
+//! enough to repair the triangulation, the diagram is built from scratch.
+
+use cgmath::{Point2, Vector2, EuclideanSpace, BaseFloat};
+
+use beachline::circumcircle_of_points;
+use build::{ClipRegion, DiagramBuilder, Site, SiteMapping};
+use clip::{clip_to_boundary_into, strictly_inside_boundary};
+use delaunay::{Triangle, TriangleId, Triangulation, flip_to_delaunay, relink};
+use diagram::{Diagram, Vertex, VertexId, HalfEdge, HalfEdgeId, Face, FaceId};
+use predicates::orient2d;
+
+/// How a `KineticDiagram` was brought up to date
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KineticUpdate {
+    /// The triangulation was repaired
+    Repaired {
+        /// Number of edges that were flipped
+        flips: usize,
+
+        /// Number of sites that moved out of the triangles around them, and were
+        /// taken out of the triangulation and put back in where they ended up
+        reinserted: usize,
+    },
+
+    /// The diagram was built again from scratch
+    Rebuilt,
+}
+
+/// The diagram of a set of sites that move, see the module docs
+#[derive(Debug, Clone)]
+pub struct KineticDiagram<T = f32> {
+    clip_region: ClipRegion<T>,
+    sites: Vec<Site<T>>,
+    site_mapping: SiteMapping,
+    diagram: Diagram<T>,
+    triangulation: Triangulation<T>,
+}
+
+impl<T: BaseFloat> KineticDiagram<T> {
+    /// Builds the diagram of the sites, which are dealt with like
+    /// `DiagramBuilder::new` does
+    pub fn new<R: Into<ClipRegion<T>>>(clip_region: R, sites: Vec<Site<T>>) -> KineticDiagram<T> {
+        let mut kinetic = KineticDiagram {
+            clip_region: clip_region.into(),
+            sites,
+            site_mapping: SiteMapping::default(),
+            diagram: Diagram::default(),
+            triangulation: Triangulation::default(),
+        };
+        kinetic.rebuild();
+        kinetic
+    }
+
+    pub fn diagram(&self) -> &Diagram<T> {
+        &self.diagram
+    }
+
+    /// The Delaunay triangulation of the sites that have a face, indexed like the
+    /// faces
+    pub fn triangulation(&self) -> &Triangulation<T> {
+        &self.triangulation
+    }
+
+    pub fn sites(&self) -> &[Site<T>] {
+        &self.sites
+    }
+
+    /// How the sites map to the faces of the diagram
+    pub fn site_mapping(&self) -> &SiteMapping {
+        &self.site_mapping
+    }
+
+    pub fn into_diagram(self) -> Diagram<T> {
+        self.diagram
+    }
+
+    /// Moves the sites to new positions, given in the same order as the sites, and
+    /// brings the diagram up to date
+    ///
+    /// Sites without a position in the list stay where they are. The diagram is
+    /// the same as `DiagramBuilder::new` would build for the sites where they are
+    /// now, apart from rounding and the order of edges that are equally good.
+    ///
+    /// The triangulation is repaired as long as every site has a face and is still
+    /// inside the clip region. The diagram is built again if it can't be, or if
+    /// repairing it would take more flips than there are triangles, which is what
+    /// happens when the sites moved a long way.
+    pub fn move_sites(&mut self, positions: &[Point2<T>]) -> KineticUpdate {
+        for (site, &position) in self.sites.iter_mut().zip(positions) {
+            site.position = position;
+        }
+
+        match self.repair() {
+            Some(update) => update,
+            None => {
+                self.rebuild();
+                KineticUpdate::Rebuilt
+            }
+        }
+    }
+
+    /// Moves each site by its velocity times `time`, and brings the diagram up to
+    /// date like `move_sites`
+    pub fn advance(&mut self, velocities: &[Vector2<T>], time: T) -> KineticUpdate {
+        let positions = self.sites.iter().zip(velocities)
+            .map(|(site, &velocity)| site.position + velocity * time)
+            .collect::<Vec<_>>();
+        self.move_sites(&positions)
+    }
+
+    fn rebuild(&mut self) {
+        let builder = DiagramBuilder::new(self.clip_region.clone(), self.sites.clone());
+        self.site_mapping = builder.site_mapping().clone();
+        let (diagram, triangulation) = builder.finish_with_delaunay();
+        self.diagram = diagram;
+        self.triangulation = triangulation;
+    }
+
+    /// Moves the triangulation along with the sites and flips it back to Delaunay,
+    /// then builds the diagram from it. Returns the update, or None if the
+    /// diagram has to be built again instead
+    fn repair(&mut self) -> Option<KineticUpdate> {
+        if !self.site_mapping.is_identity() || self.triangulation.triangles.is_empty() {
+            return None;
+        }
+
+        // Sites are scaled like the builder does, so the diagram is clipped with
+        // the same precision
+        let bounding_rect = self.clip_region.bounding_rect();
+        let scale = T::one() / bounding_rect.size.x.max(bounding_rect.size.y);
+        let boundary = self.clip_region.boundary(scale);
+        let to_scaled = |position: Point2<T>| Point2::from_vec((position - bounding_rect.position) * scale);
+        let scaled = self.sites.iter().map(|site| to_scaled(site.position)).collect::<Vec<_>>();
+        if !scaled.iter().all(|&position| strictly_inside_boundary(&boundary, position)) {
+            return None;
+        }
+
+        let points = self.triangulation.points.iter().map(|&point| to_scaled(point).cast::<f64>()).collect();
+        let mut mesh = Mesh::new(points, &mut self.triangulation.triangles);
+        let mut reinserted = 0;
+        for (index, position) in scaled.iter().enumerate() {
+            let position = position.cast::<f64>();
+            if mesh.points[index] != position && mesh.move_point(index, position)? {
+                reinserted += 1;
+            }
+        }
+        mesh.fill_pockets(None)?;
+        mesh.compact();
+        if !triangulation_is_valid(&mesh.points, mesh.triangles) {
+            return None;
+        }
+        let count = mesh.triangles.len();
+        let pending = (0..count).flat_map(|triangle| (0..3).map(move |corner| (triangle, corner))).collect();
+        let flips = flip_to_delaunay(&mesh.points, mesh.triangles, pending, count)?;
+
+        for (point, site) in self.triangulation.points.iter_mut().zip(&self.sites) {
+            *point = site.position;
+        }
+
+        let unclipped = voronoi_of(&self.triangulation.triangles, &scaled, &self.sites)?;
+        clip_to_boundary_into(&unclipped, &scaled, None, &boundary, &mut self.diagram);
+        for vertex in &mut self.diagram.vertices {
+            vertex.coordinates = bounding_rect.position + vertex.coordinates.to_vec() / scale;
+        }
+        self.diagram.index_sites(false);
+
+        Some(KineticUpdate::Repaired { flips, reinserted })
+    }
+}
+
+fn corners(points: &[Point2<f64>], triangle: &Triangle) -> [Point2<f64>; 3] {
+    let [a, b, c] = triangle.points;
+    [points[a as usize], points[b as usize], points[c as usize]]
+}
+
+/// Whether the triangles still cover the convex hull of the points without
+/// overlapping
+///
+/// That holds when none of them are inside out, and the edge around them goes
+/// once around without turning right anywhere. Going around more than once
+/// would pass the lowest point more than once.
+fn triangulation_is_valid(points: &[Point2<f64>], triangles: &[Triangle]) -> bool {
+    if !triangles.iter().all(|triangle| {
+        let [a, b, c] = corners(points, triangle);
+        orient2d(a, b, c) > 0.0
+    }) {
+        return false;
+    }
+
+    let next_on_hull = match hull_edges(triangles, points.len(), &[]) {
+        Some(next_on_hull) => next_on_hull,
+        None => return false,
+    };
+    let start = match next_on_hull.iter().position(Option::is_some) {
+        Some(start) => start,
+        None => return false,
+    };
+    let count = next_on_hull.iter().filter(|edge| edge.is_some()).count();
+    let mut hull = vec![start];
+    while let Some((next, ..)) = next_on_hull[hull[hull.len() - 1]] {
+        if next == start || hull.len() == count {
+            break;
+        }
+        hull.push(next);
+    }
+    if hull.len() != count || next_on_hull[hull[count - 1]].map(|(next, ..)| next) != Some(start) {
+        return false;
+    }
+
+    let lower = |a: usize, b: usize| (points[a].y, points[a].x) < (points[b].y, points[b].x);
+    let mut lowest = 0;
+    for (index, &point) in hull.iter().enumerate() {
+        let (before, after) = (hull[(index + count - 1) % count], hull[(index + 1) % count]);
+        if orient2d(points[before], points[point], points[after]) < 0.0 {
+            return false;
+        }
+        if lower(point, before) && lower(point, after) {
+            lowest += 1;
+        }
+    }
+    lowest == 1
+}
+
+/// For each point on the edge of the triangulation, the next point along it
+/// counter-clockwise, and the triangle and corner opposite the edge between them
+///
+/// Triangles in `free` are skipped. Returns None if the edge passes through a
+/// point more than once.
+fn hull_edges(triangles: &[Triangle], count: usize, free: &[usize]) -> Option<Vec<Option<(usize, usize, usize)>>> {
+    let mut next_on_hull = vec![None; count];
+    for (t, triangle) in triangles.iter().enumerate() {
+        for corner in 0..3 {
+            if triangle.neighbours[corner].is_none() && !free.contains(&t) {
+                let from = triangle.points[(corner + 1) % 3] as usize;
+                let to = triangle.points[(corner + 2) % 3] as usize;
+                if next_on_hull[from].replace((to, t, corner)).is_some() {
+                    return None;
+                }
+            }
+        }
+    }
+    Some(next_on_hull)
+}
+
+/// A side of a triangle that a new triangle is about to be put next to, given
+/// by the triangle and the corner opposite the side
+type Side = Option<(usize, usize)>;
+
+/// Where a walk towards a position ended up
+enum Location {
+    /// Strictly inside a triangle
+    Inside(usize),
+
+    /// Outside the triangulation, past a side on its edge
+    Outside(usize, usize),
+}
+
+/// A triangulation whose points are being moved one at a time
+struct Mesh<'a> {
+    points: Vec<Point2<f64>>,
+    triangles: &'a mut Vec<Triangle>,
+
+    /// A triangle that each point is a corner of
+    corner_of: Vec<usize>,
+
+    /// Triangles that were taken out and not yet used again
+    free: Vec<usize>,
+}
+
+impl<'a> Mesh<'a> {
+    fn new(points: Vec<Point2<f64>>, triangles: &'a mut Vec<Triangle>) -> Mesh<'a> {
+        let mut corner_of = vec![0; points.len()];
+        for (t, triangle) in triangles.iter().enumerate() {
+            for &point in &triangle.points {
+                corner_of[point as usize] = t;
+            }
+        }
+
+        Mesh {
+            points,
+            triangles,
+            corner_of,
+            free: Vec::new(),
+        }
+    }
+
+    /// Puts a triangle in a free slot, or a new one at the end
+    fn add(&mut self, triangle: Triangle) -> usize {
+        let t = match self.free.pop() {
+            Some(t) => t,
+            None => {
+                self.triangles.push(triangle.clone());
+                self.triangles.len() - 1
+            }
+        };
+        for &point in &triangle.points {
+            self.corner_of[point as usize] = t;
+        }
+        self.triangles[t] = triangle;
+        t
+    }
+
+    /// Moves the last triangles into the free slots, so there are none left
+    fn compact(&mut self) {
+        self.free.sort_unstable();
+        while let Some(slot) = self.free.pop() {
+            let last = self.triangles.len() - 1;
+            if slot != last {
+                let triangle = self.triangles[last].clone();
+                for &neighbour in &triangle.neighbours {
+                    relink(self.triangles, neighbour, TriangleId(last as u32), TriangleId(slot as u32));
+                }
+                for &point in &triangle.points {
+                    self.corner_of[point as usize] = slot;
+                }
+                self.triangles[slot] = triangle;
+            }
+            self.triangles.pop();
+        }
+    }
+
+    /// Points the side of a triangle at the new triangle across it
+    fn attach(&mut self, side: Side, t: usize) {
+        if let Some((other, corner)) = side {
+            self.triangles[other].neighbours[corner] = Some(TriangleId(t as u32));
+        }
+    }
+
+    fn neighbour(&self, side: Side) -> Option<TriangleId> {
+        side.map(|(other, _)| TriangleId(other as u32))
+    }
+
+    fn corner(&self, t: usize, point: usize) -> usize {
+        self.triangles[t].points.iter().position(|&corner| corner as usize == point).unwrap_or(0)
+    }
+
+    /// The side of the triangle across from a corner, seen from the triangle on
+    /// the other side of it
+    fn outside(&self, t: usize, corner: usize) -> Side {
+        self.triangles[t].neighbours[corner].map(|other| {
+            let other = other.0 as usize;
+            let back = self.triangles[other].neighbours.iter().position(|&id| id == Some(TriangleId(t as u32))).unwrap_or(0);
+            (other, back)
+        })
+    }
+
+    /// The triangles around a point in counter-clockwise order, with the corner
+    /// the point is at in each, and whether they go all the way round it
+    ///
+    /// If they don't, the point is on the edge of the triangulation, and they
+    /// start from the one after it along the edge.
+    fn star(&self, point: usize) -> (Vec<(usize, usize)>, bool) {
+        let mut first = self.corner_of[point];
+        while let Some(previous) = self.triangles[first].neighbours[(self.corner(first, point) + 2) % 3] {
+            first = previous.0 as usize;
+            if first == self.corner_of[point] {
+                break;
+            }
+        }
+
+        let mut star = Vec::new();
+        let mut t = first;
+        loop {
+            let corner = self.corner(t, point);
+            star.push((t, corner));
+            match self.triangles[t].neighbours[(corner + 1) % 3] {
+                Some(next) if next.0 as usize == first => return (star, true),
+                Some(next) => t = next.0 as usize,
+                None => return (star, false),
+            }
+        }
+    }
+
+    /// Whether a triangle stays the right way round when the point at one of its
+    /// corners moves to a position
+    fn stays_ccw(&self, t: usize, corner: usize, position: Point2<f64>) -> bool {
+        let triangle = &self.triangles[t];
+        let a = self.points[triangle.points[(corner + 1) % 3] as usize];
+        let b = self.points[triangle.points[(corner + 2) % 3] as usize];
+        orient2d(position, a, b) > 0.0
+    }
+
+    /// Moves a point, returning whether it had to be taken out and put back in,
+    /// or None if the triangulation can't be repaired
+    ///
+    /// If the point would turn one of the triangles around it inside out, a point
+    /// inside the triangulation is taken out and put back in wherever it ends up.
+    /// A point on the edge has triangles taken off instead, until the rest are
+    /// fine, and is only put back in if that takes all of them.
+    fn move_point(&mut self, point: usize, position: Point2<f64>) -> Option<bool> {
+        let (star, closed) = self.star(point);
+        if star.iter().all(|&(t, corner)| self.stays_ccw(t, corner, position)) {
+            self.points[point] = position;
+            return Some(false);
+        }
+
+        let start = if closed {
+            self.remove(&star)
+        } else {
+            match self.peel(star, position)? {
+                Some(start) => {
+                    self.fill_pockets(Some(point))?;
+                    start
+                }
+                None => {
+                    self.points[point] = position;
+                    return Some(false);
+                }
+            }
+        };
+        self.points[point] = position;
+        match self.locate(start, position)? {
+            Location::Inside(t) => self.split(t, point),
+            Location::Outside(..) => {
+                // The walk can stop at a dent in the edge before it reaches the
+                // triangle, and the new triangle can only go on the outside of an
+                // edge that's convex
+                self.fill_pockets(Some(point))?;
+                match self.locate(start, position)? {
+                    Location::Inside(t) => self.split(t, point),
+                    Location::Outside(t, corner) => self.extend(t, corner, point),
+                }
+            }
+        }
+        Some(true)
+    }
+
+    /// Takes out the triangles around a point inside the triangulation, and fills
+    /// the hole with triangles between the points around it, by cutting off ears
+    ///
+    /// Returns one of the new triangles.
+    fn remove(&mut self, star: &[(usize, usize)]) -> usize {
+        // Each point around the hole, with the outside of the side from it to the
+        // next point
+        let mut hole = star.iter()
+            .map(|&(t, corner)| (self.triangles[t].points[(corner + 1) % 3], self.outside(t, corner)))
+            .collect::<Vec<(u32, Side)>>();
+        self.free.extend(star.iter().map(|&(t, _)| t));
+
+        let mut last = 0;
+        while hole.len() > 3 {
+            let count = hole.len();
+            let is_ear = |index: usize| {
+                let (a, b, c) = (hole[(index + count - 1) % count].0, hole[index].0, hole[(index + 1) % count].0);
+                let (pa, pb, pc) = (self.points[a as usize], self.points[b as usize], self.points[c as usize]);
+                orient2d(pa, pb, pc) > 0.0 && hole.iter().all(|&(other, _)| {
+                    let po = self.points[other as usize];
+                    other == a || other == b || other == c || orient2d(pa, pb, po) < 0.0 || orient2d(pb, pc, po) < 0.0 || orient2d(pc, pa, po) < 0.0
+                })
+            };
+            // A hole around a point always has an ear, and anything else means
+            // the points have already moved into a tangle
+            let index = (0..count).find(|&index| is_ear(index)).unwrap_or(0);
+            let before = (index + count - 1) % count;
+            let (a, b, c) = (hole[before].0, hole[index].0, hole[(index + 1) % count].0);
+            let t = self.add(Triangle {
+                points: [a, b, c],
+                neighbours: [self.neighbour(hole[index].1), None, self.neighbour(hole[before].1)],
+            });
+            self.attach(hole[index].1, t);
+            self.attach(hole[before].1, t);
+            hole[before].1 = Some((t, 1));
+            hole.remove(index);
+            last = t;
+        }
+
+        if hole.len() == 3 {
+            let t = self.add(Triangle {
+                points: [hole[0].0, hole[1].0, hole[2].0],
+                neighbours: [self.neighbour(hole[1].1), self.neighbour(hole[2].1), self.neighbour(hole[0].1)],
+            });
+            for &(_, outside) in &hole {
+                self.attach(outside, t);
+            }
+            last = t;
+        }
+        last
+    }
+
+    /// Takes triangles off either end of the triangles around a point on the edge
+    /// of the triangulation, until the point can move to a position without
+    /// turning any of the rest inside out
+    ///
+    /// Returns a triangle next to the last one taken off if there are none left,
+    /// so the point has to be put back in. Returns None if taking one off would
+    /// leave the edge passing through a point twice, or a point without any
+    /// triangles.
+    fn peel(&mut self, mut star: Vec<(usize, usize)>, position: Point2<f64>) -> Option<Option<usize>> {
+        let mut next_to = None;
+        while let (Some(&(first, first_corner)), Some(&(last, last_corner))) = (star.first(), star.last()) {
+            if star.iter().all(|&(t, corner)| self.stays_ccw(t, corner, position)) {
+                return Some(None);
+            }
+
+            // The corner of the triangle that's along the edge from the point, and
+            // the one that'll be after the triangle is gone
+            let (t, corner, along, exposed) = if self.stays_ccw(last, last_corner, position) && !self.stays_ccw(first, first_corner, position) {
+                star.remove(0);
+                (first, first_corner, (first_corner + 1) % 3, (first_corner + 2) % 3)
+            } else {
+                star.pop();
+                (last, last_corner, (last_corner + 2) % 3, (last_corner + 1) % 3)
+            };
+            let exposed_point = self.triangles[t].points[exposed] as usize;
+            if !self.star(exposed_point).1 {
+                return None;
+            }
+            let (beyond, between) = (self.outside(t, corner)?, self.outside(t, along));
+            self.triangles[beyond.0].neighbours[beyond.1] = None;
+            if let Some((other, back)) = between {
+                self.triangles[other].neighbours[back] = None;
+                self.corner_of[self.triangles[t].points[corner] as usize] = other;
+            }
+            self.corner_of[self.triangles[t].points[along] as usize] = beyond.0;
+            self.corner_of[exposed_point] = beyond.0;
+            self.free.push(t);
+            next_to = Some(beyond.0);
+        }
+        Some(next_to)
+    }
+
+    /// Finds the triangle a position is strictly inside by walking towards it, or
+    /// a side on the edge of the triangulation that it's past
+    ///
+    /// Returns None if the position is on an edge, or the walk goes round in
+    /// circles.
+    fn locate(&self, start: usize, position: Point2<f64>) -> Option<Location> {
+        let mut t = start;
+        for step in 0..self.triangles.len() {
+            let triangle = &self.triangles[t];
+            let corners = corners(&self.points, triangle);
+
+            // Starting from a different side each time stops the walk going round
+            // in circles
+            let side = (0..3).map(|offset| (step + offset) % 3).find(|&corner| {
+                orient2d(corners[(corner + 1) % 3], corners[(corner + 2) % 3], position) < 0.0
+            });
+            match side {
+                Some(corner) => match triangle.neighbours[corner] {
+                    Some(next) => t = next.0 as usize,
+                    None => return Some(Location::Outside(t, corner)),
+                },
+                None => {
+                    let on_edge = (0..3).any(|corner| orient2d(corners[(corner + 1) % 3], corners[(corner + 2) % 3], position) == 0.0);
+                    return if on_edge { None } else { Some(Location::Inside(t)) };
+                }
+            }
+        }
+        None
+    }
+
+    /// Splits a triangle into three around a point inside it
+    fn split(&mut self, t: usize, point: usize) {
+        let [a, b, c] = self.triangles[t].points;
+        let neighbours = self.triangles[t].neighbours;
+        let (across_a, across_b, across_c) = (self.outside(t, 0), self.outside(t, 1), self.outside(t, 2));
+        let point = point as u32;
+
+        // The old triangle becomes one of the new ones
+        self.free.push(t);
+        let opposite_a = self.add(Triangle { points: [point, b, c], neighbours: [neighbours[0], None, None] });
+        let opposite_b = self.add(Triangle { points: [point, c, a], neighbours: [neighbours[1], None, None] });
+        let opposite_c = self.add(Triangle { points: [point, a, b], neighbours: [neighbours[2], None, None] });
+        let id = |t: usize| Some(TriangleId(t as u32));
+        self.triangles[opposite_a].neighbours[1..].copy_from_slice(&[id(opposite_b), id(opposite_c)]);
+        self.triangles[opposite_b].neighbours[1..].copy_from_slice(&[id(opposite_c), id(opposite_a)]);
+        self.triangles[opposite_c].neighbours[1..].copy_from_slice(&[id(opposite_a), id(opposite_b)]);
+        self.attach(across_a, opposite_a);
+        self.attach(across_b, opposite_b);
+        self.attach(across_c, opposite_c);
+    }
+
+    /// Adds a triangle between a point outside the triangulation and a side on
+    /// its edge that faces the point
+    fn extend(&mut self, t: usize, corner: usize, point: usize) {
+        let from = self.triangles[t].points[(corner + 1) % 3];
+        let to = self.triangles[t].points[(corner + 2) % 3];
+        let new = self.add(Triangle {
+            points: [point as u32, to, from],
+            neighbours: [Some(TriangleId(t as u32)), None, None],
+        });
+        self.triangles[t].neighbours[corner] = Some(TriangleId(new as u32));
+    }
+
+    /// Fills in dents in the edge of the triangulation, or returns None if the edge
+    /// passes through a point more than once
+    ///
+    /// Dents are left by points on the edge that moved inwards or were taken out.
+    /// A point that's been taken out and not put back yet is ignored. Dents that
+    /// can't be filled are left, and make the triangulation fail the check at the
+    /// end.
+    fn fill_pockets(&mut self, taken_out: Option<usize>) -> Option<()> {
+        let mut next_on_hull = hull_edges(self.triangles, self.points.len(), &self.free)?;
+        let mut previous = vec![None; self.points.len()];
+        for (from, edge) in next_on_hull.iter().enumerate() {
+            if let Some((to, ..)) = *edge {
+                previous[to] = Some(from);
+            }
+        }
+
+        let mut pending = (0..self.points.len()).filter(|&point| next_on_hull[point].is_some()).collect::<Vec<_>>();
+        while let Some(b) = pending.pop() {
+            let (a, (c, after_t, after_corner)) = match (previous[b], next_on_hull[b]) {
+                (Some(a), Some(next)) => (a, next),
+                _ => continue,
+            };
+            let (_, before_t, before_corner) = next_on_hull[a]?;
+            let (pa, pb, pc) = (self.points[a], self.points[b], self.points[c]);
+            if a == c || orient2d(pa, pb, pc) >= 0.0 {
+                continue;
+            }
+
+            // The triangle a c b can only be added if there's no other point in or
+            // on it. If there is, filling in the dents next to it gets to it later
+            let empty = self.points.iter().enumerate().all(|(index, &point)| {
+                index == a || index == b || index == c || Some(index) == taken_out || orient2d(pa, pc, point) < 0.0 || orient2d(pc, pb, point) < 0.0 || orient2d(pb, pa, point) < 0.0
+            });
+            if !empty {
+                continue;
+            }
+
+            let t = self.add(Triangle {
+                points: [a as u32, c as u32, b as u32],
+                neighbours: [Some(TriangleId(after_t as u32)), Some(TriangleId(before_t as u32)), None],
+            });
+            self.attach(Some((after_t, after_corner)), t);
+            self.attach(Some((before_t, before_corner)), t);
+
+            next_on_hull[a] = Some((c, t, 2));
+            next_on_hull[b] = None;
+            previous[b] = None;
+            previous[c] = Some(a);
+            pending.extend([a, c]);
+        }
+        Some(())
+    }
+}
+
+/// The unclipped diagram whose vertices are the circumcentres of the triangles,
+/// laid out like the one the sweep makes so it can be clipped the same way
+///
+/// The edge dual to a triangle edge from `a` to `b` runs from the triangle on the
+/// right to the one on the left for the face of `a`, and the other way for `b`.
+/// Edges of the hull have no triangle on the right, so that end goes off to
+/// infinity. Returns None if a triangle is too thin to have a circumcentre at
+/// the precision of `T`.
+fn voronoi_of<T: BaseFloat>(triangles: &[Triangle], scaled: &[Point2<T>], sites: &[Site<T>]) -> Option<Diagram<T>> {
+    let mut diagram = Diagram::default();
+    for triangle in triangles {
+        let [a, b, c] = triangle.points;
+        let (centre, _) = circumcircle_of_points(scaled[a as usize], scaled[b as usize], scaled[c as usize])?;
+        diagram.vertices.push(Vertex {
+            coordinates: centre,
+            incident_edge: HalfEdgeId(0),
+        });
+    }
+
+    // Half-edges of each triangle edge that start and end at the triangle's vertex
+    let mut starting = vec![[HalfEdgeId(0); 3]; triangles.len()];
+    let mut ending = vec![[HalfEdgeId(0); 3]; triangles.len()];
+    let mut hull_in = vec![None; sites.len()];
+    let mut hull_out = vec![None; sites.len()];
+    for (t, triangle) in triangles.iter().enumerate() {
+        for corner in 0..3 {
+            let neighbour = triangle.neighbours[corner];
+            if neighbour.is_some_and(|neighbour| (neighbour.0 as usize) < t) {
+                continue;
+            }
+
+            let a = triangle.points[(corner + 1) % 3];
+            let b = triangle.points[(corner + 2) % 3];
+            let (a_halfedge, b_halfedge) = (HalfEdgeId(diagram.halfedges.len() as u32), HalfEdgeId(diagram.halfedges.len() as u32 + 1));
+            let right = neighbour.map(|neighbour| VertexId(neighbour.0));
+            for &(halfedge, twin, origin, face) in &[(a_halfedge, b_halfedge, right, a), (b_halfedge, a_halfedge, Some(VertexId(t as u32)), b)] {
+                diagram.halfedges.push(HalfEdge {
+                    origin,
+                    twin: Some(twin),
+                    incident_face: FaceId(face),
+                    next: halfedge,
+                    prev: halfedge,
+                });
+            }
+
+            ending[t][corner] = a_halfedge;
+            starting[t][corner] = b_halfedge;
+            match neighbour {
+                Some(neighbour) => {
+                    let (n, other) = (neighbour.0 as usize, &triangles[neighbour.0 as usize]);
+                    let other_corner = other.neighbours.iter().position(|&id| id == Some(TriangleId(t as u32)))?;
+                    starting[n][other_corner] = a_halfedge;
+                    ending[n][other_corner] = b_halfedge;
+                }
+                None => {
+                    hull_out[a as usize] = Some(a_halfedge);
+                    hull_in[b as usize] = Some(b_halfedge);
+                }
+            }
+        }
+    }
+
+    // Around a corner of a triangle, the edge opposite the next corner comes
+    // after the edge opposite this one
+    let mut link = |halfedge: HalfEdgeId, next: HalfEdgeId| {
+        diagram.halfedges[halfedge.0 as usize].next = next;
+        diagram.halfedges[next.0 as usize].prev = halfedge;
+    };
+    for t in 0..triangles.len() {
+        for corner in 0..3 {
+            link(ending[t][corner], starting[t][(corner + 2) % 3]);
+        }
+    }
+    for (&incoming, &outgoing) in hull_in.iter().zip(&hull_out) {
+        if let (Some(incoming), Some(outgoing)) = (incoming, outgoing) {
+            link(incoming, outgoing);
+        }
+    }
+
+    for (index, halfedge) in diagram.halfedges.iter().enumerate() {
+        if let Some(origin) = halfedge.origin {
+            diagram.vertices[origin.0 as usize].incident_edge = HalfEdgeId(index as u32);
+        }
+    }
+    diagram.faces = sites.iter().map(|&site| Face {
+        first_halfedge: None,
+        site,
+    }).collect();
+    for (index, halfedge) in diagram.halfedges.iter().enumerate() {
+        diagram.faces[halfedge.incident_face.0 as usize].first_halfedge = Some(HalfEdgeId(index as u32));
+    }
+    Some(diagram)
+}
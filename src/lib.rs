@@ -1,5 +1,104 @@
 extern crate cgmath;
 extern crate fnv;
+#[cfg(feature = "serde")]
+extern crate serde;
+#[cfg(feature = "geojson")]
+extern crate geojson;
+#[cfg(feature = "geo")]
+extern crate geo_types;
+#[cfg(feature = "rayon")]
+extern crate rayon;
+#[cfg(feature = "rand")]
+extern crate rand;
+#[cfg(feature = "simd")]
+extern crate wide;
+#[cfg(feature = "petgraph")]
+extern crate petgraph;
 
 pub mod diagram;
 pub mod build;
+pub mod buffers;
+pub mod delaunay;
+pub mod relax;
+pub mod refine;
+pub mod predicates;
+pub mod power;
+pub mod observer;
+pub mod periodic;
+pub mod segment;
+pub mod medial;
+pub mod farthest;
+pub mod higher_order;
+pub mod apollonius;
+pub mod closed;
+pub mod face_data;
+pub mod invariants;
+pub mod mesh;
+pub mod mesh3d;
+pub mod raster;
+pub mod sites;
+pub mod test_inputs;
+pub mod worley;
+pub mod factory;
+pub mod kinetic;
+pub mod soa;
+pub mod grid;
+pub mod walk;
+#[cfg(feature = "pathfinding")]
+pub mod path;
+#[cfg(feature = "io")]
+pub mod mesh_io;
+#[cfg(feature = "integer")]
+pub mod integer;
+#[cfg(feature = "rayon")]
+pub mod parallel;
+mod adjacency;
+mod beachline;
+mod binary;
+mod canonical;
+mod cells;
+mod clip;
+mod dedup;
+mod edit;
+#[cfg(feature = "exact")]
+mod exact;
+#[cfg(feature = "geojson")]
+mod export;
+mod hull;
+mod hydrology;
+mod insert;
+#[cfg(feature = "geo")]
+mod interop;
+mod kdtree;
+mod metric;
+mod noise;
+mod offset;
+mod options;
+mod proximity;
+mod range;
+mod regions;
+#[cfg(feature = "rand")]
+mod sample;
+#[cfg(feature = "simd")]
+mod simd;
+mod smooth;
+mod table;
+mod validate;
+
+use cgmath::BaseFloat;
+
+use build::{DiagramBuilder, IntoSite, Rect};
+use diagram::Diagram;
+
+/// Builds the diagram of some points, clipped to a rect
+///
+/// Points can be given as `Point2`s, `(x, y)` tuples or `[x, y]` arrays, and each
+/// one's site gets its index in the slice as its id. Use `DiagramBuilder` for more
+/// control over how the diagram is built.
+pub fn voronoi<T: BaseFloat, P: IntoSite<T> + Copy>(points: &[P], bounds: Rect<T>) -> Diagram<T> {
+    let sites = points.iter().enumerate()
+        .map(|(index, &point)| point.into_site(index as u32))
+        .collect();
+
+    DiagramBuilder::new(bounds, sites).finish()
+}
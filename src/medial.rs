@@ -0,0 +1,175 @@
+//! The medial axis of a polygon
+//!
+//! The medial axis is the set of points inside the polygon with more than one
+//! nearest point on its boundary, which forms a tree down the middle of the shape.
+//! It's made of the edges of the diagram of the polygon's sides that are inside the
+//! polygon, apart from those that come out of its reflex corners at right angles
+//! to a side, which only separate the points nearest to the corner from those
+//! nearest to the side next to it.
+
+use cgmath::{Point2, Vector2, InnerSpace, BaseFloat};
+use fnv::FnvHashMap;
+
+use build::Rect;
+use diagram::{HalfEdgeId, VertexId};
+use segment::{EdgeCurve, SegmentDiagramBuilder, SegmentSite};
+
+/// An edge of a `MedialAxis`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MedialEdge<T = f32> {
+    /// Index of the point at the start of the edge
+    pub start: u32,
+
+    /// Index of the point at the end of the edge
+    pub end: u32,
+
+    pub curve: EdgeCurve<T>,
+}
+
+/// The medial axis of a polygon, as a graph of points joined by curved edges
+#[derive(Debug, Clone)]
+pub struct MedialAxis<T = f32> {
+    pub points: Vec<Point2<T>>,
+
+    /// The distance from each point to the boundary of the polygon, which is the
+    /// radius of the largest circle inside the polygon around it
+    pub radii: Vec<T>,
+
+    pub edges: Vec<MedialEdge<T>>,
+}
+
+impl<T> Default for MedialAxis<T> {
+    fn default() -> MedialAxis<T> {
+        MedialAxis {
+            points: Vec::new(),
+            radii: Vec::new(),
+            edges: Vec::new(),
+        }
+    }
+}
+
+impl<T: BaseFloat> MedialAxis<T> {
+    /// Finds points along an edge from its start to its end, splitting it into the
+    /// given number of pieces
+    pub fn edge_points(&self, edge: usize, pieces: usize) -> Vec<Point2<T>> {
+        match self.edges.get(edge) {
+            Some(edge) => edge.curve.points(self.points[edge.start as usize], self.points[edge.end as usize], pieces),
+            None => Vec::new(),
+        }
+    }
+}
+
+/// Finds the medial axis of a simple polygon, whose corners can be in either order
+///
+/// The axis reaches each convex corner of the polygon, where the circle around it
+/// has no radius.
+pub fn medial_axis<T: BaseFloat>(polygon: &[Point2<T>]) -> MedialAxis<T> {
+    let sites = polygon.iter().enumerate()
+        .map(|(index, &start)| (start, polygon[(index + 1) % polygon.len()]))
+        .filter(|&(start, end)| start != end)
+        .enumerate()
+        .map(|(index, (start, end))| SegmentSite::Segment { id: index as u32, start, end })
+        .collect::<Vec<_>>();
+    if sites.len() < 3 {
+        return MedialAxis::default();
+    }
+
+    // The rect leaves some room around the polygon, so that none of its sides are
+    // on the edge of the diagram
+    let min = polygon.iter().fold(Point2::new(T::infinity(), T::infinity()), |min, corner| {
+        Point2::new(min.x.min(corner.x), min.y.min(corner.y))
+    });
+    let max = polygon.iter().fold(Point2::new(T::neg_infinity(), T::neg_infinity()), |max, corner| {
+        Point2::new(max.x.max(corner.x), max.y.max(corner.y))
+    });
+    let margin = (max.x - min.x).max(max.y - min.y) / T::from(10.0).unwrap();
+    if !margin.is_finite() || margin <= T::zero() {
+        return MedialAxis::default();
+    }
+    let rect = Rect {
+        position: Point2::new(min.x - margin, min.y - margin),
+        size: Vector2::new(max.x - min.x + margin * (T::one() + T::one()), max.y - min.y + margin * (T::one() + T::one())),
+    };
+    let segment_diagram = SegmentDiagramBuilder::new(rect, sites).finish();
+    let diagram = segment_diagram.diagram();
+
+    let mut axis = MedialAxis::default();
+    let mut point_ids = FnvHashMap::default();
+    for (index, halfedge) in diagram.halfedges.iter().enumerate() {
+        let twin = match halfedge.twin {
+            Some(twin) if index < twin.0 as usize => twin,
+            _ => continue,
+        };
+        let (origin, destination) = match (halfedge.origin, diagram.halfedges[halfedge.next.0 as usize].origin) {
+            (Some(origin), Some(destination)) => (origin, destination),
+            _ => continue,
+        };
+        let halfedge_id = HalfEdgeId(index as u32);
+        let side = segment_diagram.site(halfedge.incident_face).unwrap();
+        let other_side = segment_diagram.site(diagram.halfedges[twin.0 as usize].incident_face).unwrap();
+        let middle = segment_diagram.edge_points(halfedge_id, 2)[1];
+        if !inside(polygon, middle) || meets_at_corner(side, other_side, middle) {
+            continue;
+        }
+
+        let mut point_id = |vertex: VertexId| *point_ids.entry(vertex).or_insert_with(|| {
+            let point = diagram.vertices[vertex.0 as usize].coordinates;
+            axis.points.push(point);
+            axis.radii.push(side.distance(point));
+            axis.points.len() as u32 - 1
+        });
+        let start = point_id(origin);
+        let end = point_id(destination);
+
+        axis.edges.push(MedialEdge {
+            start,
+            end,
+            curve: *segment_diagram.curve(halfedge_id).unwrap(),
+        });
+    }
+
+    axis
+}
+
+/// Whether the part of one side nearest a point is a corner it shares with the
+/// other side, in which case the edge between them is a line at right angles to
+/// the other side
+fn meets_at_corner<T: BaseFloat>(side: &SegmentSite<T>, other_side: &SegmentSite<T>, point: Point2<T>) -> bool {
+    let ends = |site: &SegmentSite<T>| match *site {
+        SegmentSite::Segment { start, end, .. } => (start, end),
+        SegmentSite::Point { position, .. } => (position, position),
+    };
+    let nearest_end = |site: &SegmentSite<T>| {
+        let (start, end) = ends(site);
+        let t = (point - start).dot(end - start);
+        if t <= T::zero() {
+            Some(start)
+        } else if t >= (end - start).magnitude2() {
+            Some(end)
+        } else {
+            None
+        }
+    };
+    let is_end_of = |corner: Point2<T>, site: &SegmentSite<T>| {
+        let (start, end) = ends(site);
+        corner == start || corner == end
+    };
+
+    nearest_end(side).is_some_and(|corner| is_end_of(corner, other_side)) ||
+        nearest_end(other_side).is_some_and(|corner| is_end_of(corner, side))
+}
+
+/// Whether a point is inside a polygon, by counting the sides a ray from it crosses
+fn inside<T: BaseFloat>(polygon: &[Point2<T>], point: Point2<T>) -> bool {
+    let mut inside = false;
+    for (index, &a) in polygon.iter().enumerate() {
+        let b = polygon[(index + 1) % polygon.len()];
+        if (a.y > point.y) != (b.y > point.y) {
+            let x = a.x + (point.y - a.y) / (b.y - a.y) * (b.x - a.x);
+            if point.x < x {
+                inside = !inside;
+            }
+        }
+    }
+    inside
+}
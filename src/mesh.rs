@@ -0,0 +1,133 @@
+//! Triangle meshes of the cells, for uploading to a GPU
+//!
+//! Cells of a diagram clipped to a rect or a convex polygon are convex, and are
+//! split into a fan of triangles from one corner. Cells that have been clipped to
+//! a concave polygon, or merged with their neighbours, can be concave, and those
+//! are cut into triangles by clipping ears off them instead.
+
+use cgmath::{Point2, BaseFloat};
+
+use diagram::{Diagram, FaceId};
+use predicates::orient2d;
+
+/// The cells of a diagram as triangles, see `Diagram::to_mesh`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Mesh {
+    /// x and y of each vertex of the mesh, one after the other
+    pub positions: Vec<f32>,
+
+    /// Index of the face each vertex of the mesh belongs to
+    pub cells: Vec<u32>,
+
+    /// Indices of the three vertices of each triangle, in counter-clockwise order
+    pub indices: Vec<u32>,
+}
+
+impl Mesh {
+    /// Number of vertices in the mesh
+    pub fn vertex_count(&self) -> usize {
+        self.cells.len()
+    }
+
+    /// Number of triangles in the mesh
+    pub fn triangle_count(&self) -> usize {
+        self.indices.len() / 3
+    }
+}
+
+/// Whether a point is inside a counter-clockwise triangle or on its edge
+fn in_triangle(a: Point2<f64>, b: Point2<f64>, c: Point2<f64>, point: Point2<f64>) -> bool {
+    orient2d(a, b, point) >= 0.0 && orient2d(b, c, point) >= 0.0 && orient2d(c, a, point) >= 0.0
+}
+
+/// Cuts a simple counter-clockwise polygon into counter-clockwise triangles, given
+/// as indices of its corners
+///
+/// Corners where the polygon goes straight on are left out of the triangles
+/// where they can be, so there are no triangles without any area.
+pub(crate) fn triangulate(polygon: &[Point2<f64>]) -> Vec<[usize; 3]> {
+    let count = polygon.len();
+    if count < 3 {
+        return Vec::new();
+    }
+
+    let turn = |a: usize, b: usize, c: usize| orient2d(polygon[a], polygon[b], polygon[c]);
+    let convex = (0..count).all(|index| turn(index, (index + 1) % count, (index + 2) % count) >= 0.0);
+    if convex {
+        // The fan is from a corner that isn't on a straight stretch, so that only
+        // triangles on the far side of it can be flat
+        let apex = (0..count).find(|&index| turn((index + count - 1) % count, index, (index + 1) % count) > 0.0).unwrap_or(0);
+        return (1..count - 1)
+            .map(|offset| [apex, (apex + offset) % count, (apex + offset + 1) % count])
+            .filter(|&[a, b, c]| turn(a, b, c) > 0.0)
+            .collect();
+    }
+
+    let mut remaining = (0..count).collect::<Vec<_>>();
+    let mut triangles = Vec::with_capacity(count - 2);
+    while remaining.len() > 3 {
+        let length = remaining.len();
+        let corners = |position: usize| (remaining[(position + length - 1) % length], remaining[position], remaining[(position + 1) % length]);
+
+        // An ear is a corner that turns left with nothing else inside its triangle.
+        // Corners on a straight stretch are cut off without a triangle
+        let ear = (0..length).find(|&position| {
+            let (a, b, c) = corners(position);
+            let turn = turn(a, b, c);
+            turn == 0.0 || (turn > 0.0 && remaining.iter().all(|&other| {
+                other == a || other == b || other == c
+                    || polygon[other] == polygon[a] || polygon[other] == polygon[b] || polygon[other] == polygon[c]
+                    || !in_triangle(polygon[a], polygon[b], polygon[c], polygon[other])
+            }))
+        });
+
+        // Only a polygon that isn't simple has no ears, and the rest of it is
+        // given up on
+        let position = match ear {
+            Some(position) => position,
+            None => break,
+        };
+        let (a, b, c) = corners(position);
+        if turn(a, b, c) > 0.0 {
+            triangles.push([a, b, c]);
+        }
+        remaining.remove(position);
+    }
+
+    if remaining.len() == 3 && turn(remaining[0], remaining[1], remaining[2]) > 0.0 {
+        triangles.push([remaining[0], remaining[1], remaining[2]]);
+    }
+    triangles
+}
+
+/// The corners of a face, or None if it isn't closed
+pub(crate) fn closed_polygon<T: BaseFloat>(diagram: &Diagram<T>, face_id: FaceId) -> Option<Vec<Point2<T>>> {
+    diagram.face_edges(face_id)
+        .take(diagram.halfedges.len())
+        .map(|halfedge_id| diagram.halfedges[halfedge_id.0 as usize].origin.map(|origin| diagram.vertices[origin.0 as usize].coordinates))
+        .collect()
+}
+
+/// See `Diagram::to_mesh`
+pub fn to_mesh<T: BaseFloat>(diagram: &Diagram<T>) -> Mesh {
+    let mut mesh = Mesh::default();
+    for index in 0..diagram.faces.len() {
+        let polygon = match closed_polygon(diagram, FaceId(index as u32)) {
+            Some(polygon) => polygon,
+            None => continue,
+        };
+
+        let start = mesh.cells.len() as u32;
+        for &corner in &polygon {
+            mesh.positions.push(corner.x.to_f32().unwrap());
+            mesh.positions.push(corner.y.to_f32().unwrap());
+            mesh.cells.push(index as u32);
+        }
+
+        let polygon = polygon.iter().map(|corner| corner.cast::<f64>()).collect::<Vec<_>>();
+        for triangle in triangulate(&polygon) {
+            mesh.indices.extend(triangle.iter().map(|&corner| start + corner as u32));
+        }
+    }
+    mesh
+}
@@ -0,0 +1,104 @@
+//! Cells raised into 3D prisms, for low-poly terrain and the like
+//!
+//! Each cell becomes a closed prism standing on the plane z = `base`, with a top
+//! at the height given for its face, and walls all the way round. Prisms don't
+//! share vertices with each other, so neighbouring cells at different heights
+//! each have their own wall, and every prism is watertight on its own.
+
+use cgmath::{Point2, BaseFloat};
+
+use build::Site;
+use diagram::{Diagram, FaceId};
+use mesh::{closed_polygon, triangulate};
+use predicates::orient2d;
+
+/// Prisms for the cells of a diagram, see `extrude`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Mesh3d {
+    /// x, y and z of each vertex of the mesh, one after the other
+    pub positions: Vec<f32>,
+
+    /// Index of the face each vertex of the mesh belongs to
+    pub cells: Vec<u32>,
+
+    /// Indices of the three vertices of each triangle, counter-clockwise seen from
+    /// outside the prism
+    pub indices: Vec<u32>,
+}
+
+impl Mesh3d {
+    /// Number of vertices in the mesh
+    pub fn vertex_count(&self) -> usize {
+        self.cells.len()
+    }
+
+    /// Number of triangles in the mesh
+    pub fn triangle_count(&self) -> usize {
+        self.indices.len() / 3
+    }
+}
+
+/// The corners of a polygon except the ones where it doesn't turn
+fn without_straight_corners<T: BaseFloat>(polygon: &[Point2<T>]) -> Vec<Point2<T>> {
+    let count = polygon.len();
+    let corners = polygon.iter().map(|corner| corner.cast::<f64>()).collect::<Vec<_>>();
+    (0..count)
+        .filter(|&index| orient2d(corners[(index + count - 1) % count], corners[index], corners[(index + 1) % count]) != 0.0)
+        .map(|index| polygon[index])
+        .collect()
+}
+
+/// Raises every cell into a prism from z = `base` up to its height
+///
+/// `height` is called once for each face, with its site, so a heightmap can be
+/// looked up at the site's position. A height below the base makes a prism that
+/// goes down from it instead. Each corner of a prism is one vertex, shared by the
+/// top or bottom and the walls, so renderers that want flat shading need to work
+/// out normals per triangle. Faces whose height is the same as the base, faces
+/// without any half-edges and cells that go off to infinity get no prism.
+pub fn extrude<T: BaseFloat, F: FnMut(FaceId, &Site<T>) -> T>(diagram: &Diagram<T>, base: T, mut height: F) -> Mesh3d {
+    let mut mesh = Mesh3d::default();
+    for (index, face) in diagram.faces.iter().enumerate() {
+        let face_id = FaceId(index as u32);
+        let polygon = match closed_polygon(diagram, face_id) {
+            Some(polygon) => polygon,
+            None => continue,
+        };
+        let top = height(face_id, &face.site);
+
+        // The top and bottom wouldn't have edges at corners where the cell goes
+        // straight on, which would leave gaps next to the walls
+        let polygon = without_straight_corners(&polygon);
+        if polygon.len() < 3 || top == base {
+            continue;
+        }
+        let (low, high) = if top > base { (base, top) } else { (top, base) };
+
+        // The bottom ring of corners, then the top ring
+        let start = mesh.cells.len() as u32;
+        for &z in &[low, high] {
+            for corner in &polygon {
+                mesh.positions.extend([corner.x.to_f32().unwrap(), corner.y.to_f32().unwrap(), z.to_f32().unwrap()]);
+                mesh.cells.push(index as u32);
+            }
+        }
+
+        let count = polygon.len() as u32;
+        let (bottom, top) = (start, start + count);
+        let polygon = polygon.iter().map(|corner| corner.cast::<f64>()).collect::<Vec<Point2<f64>>>();
+        for [a, b, c] in triangulate(&polygon) {
+            let (a, b, c) = (a as u32, b as u32, c as u32);
+            mesh.indices.extend([top + a, top + b, top + c]);
+            mesh.indices.extend([bottom + a, bottom + c, bottom + b]);
+        }
+
+        // Cells are counter-clockwise, so the outside of each wall is on the right
+        // of its edge
+        for corner in 0..count {
+            let next = (corner + 1) % count;
+            mesh.indices.extend([bottom + corner, bottom + next, top + next]);
+            mesh.indices.extend([bottom + corner, top + next, top + corner]);
+        }
+    }
+    mesh
+}
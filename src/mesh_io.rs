@@ -0,0 +1,158 @@
+//! Writing diagrams and triangulations to Wavefront OBJ and PLY files, for
+//! looking at them in programs like Blender and MeshLab
+//!
+//! Both formats are written as text, with every point at a height of zero. OBJ
+//! counts vertices from one, and PLY counts them from zero.
+
+use std::fmt::Display;
+use std::io::{self, BufWriter, Write};
+
+use cgmath::{Point2, BaseFloat};
+
+use delaunay::Triangulation;
+use diagram::{Diagram, Endpoint, HalfEdgeId};
+
+/// What to write for a diagram
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiagramGeometry {
+    /// The vertices of the diagram, and a line for each edge between two of them
+    Edges,
+
+    /// The cells cut into triangles like `Diagram::to_mesh` does, with each cell
+    /// having its own copy of its corners
+    Cells,
+}
+
+/// Points and what joins them, numbered from zero
+///
+/// Points are kept in the type they were made with, so that f32 coordinates
+/// aren't written with the extra digits they'd get as f64s.
+struct Shapes<P> {
+    points: Vec<[P; 2]>,
+    lines: Vec<[u32; 2]>,
+    triangles: Vec<[u32; 3]>,
+}
+
+/// Coordinates that can be written, along with their type in a PLY header
+trait Coordinate: Display {
+    const PLY_TYPE: &'static str;
+}
+
+impl Coordinate for f32 {
+    const PLY_TYPE: &'static str = "float";
+}
+
+impl Coordinate for f64 {
+    const PLY_TYPE: &'static str = "double";
+}
+
+fn point<T: BaseFloat>(point: Point2<T>) -> [f64; 2] {
+    [point.x.to_f64().unwrap(), point.y.to_f64().unwrap()]
+}
+
+/// The vertices of a diagram and the edges between them, leaving out edges that
+/// go off to infinity
+fn edge_shapes<T: BaseFloat>(diagram: &Diagram<T>) -> Shapes<f64> {
+    // Each edge is written once, from the half-edge with the lower id
+    let lines = diagram.halfedges.iter().enumerate()
+        .filter(|&(index, halfedge)| halfedge.twin.is_none_or(|twin| twin.0 as usize > index))
+        .filter_map(|(index, _)| match (diagram.origin(HalfEdgeId(index as u32))?, diagram.destination(HalfEdgeId(index as u32))?) {
+            (Endpoint::Vertex(origin), Endpoint::Vertex(destination)) => Some([origin.0, destination.0]),
+            _ => None,
+        })
+        .collect();
+    Shapes {
+        points: diagram.vertices.iter().map(|vertex| point(vertex.coordinates)).collect(),
+        lines,
+        triangles: Vec::new(),
+    }
+}
+
+/// The cells of a diagram as triangles, from `Diagram::to_mesh`
+fn cell_shapes<T: BaseFloat>(diagram: &Diagram<T>) -> Shapes<f32> {
+    let mesh = diagram.to_mesh();
+    Shapes {
+        points: mesh.positions.chunks(2).map(|position| [position[0], position[1]]).collect(),
+        lines: Vec::new(),
+        triangles: mesh.indices.chunks(3).map(|corners| [corners[0], corners[1], corners[2]]).collect(),
+    }
+}
+
+fn triangulation_shapes<T: BaseFloat>(triangulation: &Triangulation<T>) -> Shapes<f64> {
+    Shapes {
+        points: triangulation.points.iter().map(|&position| point(position)).collect(),
+        lines: Vec::new(),
+        triangles: triangulation.triangles.iter().map(|triangle| triangle.points).collect(),
+    }
+}
+
+fn write_obj<P: Coordinate, W: Write>(shapes: &Shapes<P>, writer: W) -> io::Result<()> {
+    let mut writer = BufWriter::new(writer);
+    for [x, y] in &shapes.points {
+        writeln!(writer, "v {} {} 0", x, y)?;
+    }
+    for &[a, b] in &shapes.lines {
+        writeln!(writer, "l {} {}", a + 1, b + 1)?;
+    }
+    for &[a, b, c] in &shapes.triangles {
+        writeln!(writer, "f {} {} {}", a + 1, b + 1, c + 1)?;
+    }
+    writer.flush()
+}
+
+fn write_ply<P: Coordinate, W: Write>(shapes: &Shapes<P>, writer: W) -> io::Result<()> {
+    let mut writer = BufWriter::new(writer);
+    writeln!(writer, "ply")?;
+    writeln!(writer, "format ascii 1.0")?;
+    writeln!(writer, "element vertex {}", shapes.points.len())?;
+    for axis in &["x", "y", "z"] {
+        writeln!(writer, "property {} {}", P::PLY_TYPE, axis)?;
+    }
+    if !shapes.lines.is_empty() {
+        writeln!(writer, "element edge {}", shapes.lines.len())?;
+        writeln!(writer, "property int vertex1")?;
+        writeln!(writer, "property int vertex2")?;
+    }
+    if !shapes.triangles.is_empty() {
+        writeln!(writer, "element face {}", shapes.triangles.len())?;
+        writeln!(writer, "property list uchar int vertex_indices")?;
+    }
+    writeln!(writer, "end_header")?;
+
+    for [x, y] in &shapes.points {
+        writeln!(writer, "{} {} 0", x, y)?;
+    }
+    for &[a, b] in &shapes.lines {
+        writeln!(writer, "{} {}", a, b)?;
+    }
+    for &[a, b, c] in &shapes.triangles {
+        writeln!(writer, "3 {} {} {}", a, b, c)?;
+    }
+    writer.flush()
+}
+
+/// See `Diagram::write_obj`
+pub(crate) fn write_diagram_obj<T: BaseFloat, W: Write>(diagram: &Diagram<T>, geometry: DiagramGeometry, writer: W) -> io::Result<()> {
+    match geometry {
+        DiagramGeometry::Edges => write_obj(&edge_shapes(diagram), writer),
+        DiagramGeometry::Cells => write_obj(&cell_shapes(diagram), writer),
+    }
+}
+
+/// See `Diagram::write_ply`
+pub(crate) fn write_diagram_ply<T: BaseFloat, W: Write>(diagram: &Diagram<T>, geometry: DiagramGeometry, writer: W) -> io::Result<()> {
+    match geometry {
+        DiagramGeometry::Edges => write_ply(&edge_shapes(diagram), writer),
+        DiagramGeometry::Cells => write_ply(&cell_shapes(diagram), writer),
+    }
+}
+
+/// See `Triangulation::write_obj`
+pub(crate) fn write_triangulation_obj<T: BaseFloat, W: Write>(triangulation: &Triangulation<T>, writer: W) -> io::Result<()> {
+    write_obj(&triangulation_shapes(triangulation), writer)
+}
+
+/// See `Triangulation::write_ply`
+pub(crate) fn write_triangulation_ply<T: BaseFloat, W: Write>(triangulation: &Triangulation<T>, writer: W) -> io::Result<()> {
+    write_ply(&triangulation_shapes(triangulation), writer)
+}
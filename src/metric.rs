@@ -0,0 +1,323 @@
+//! Diagrams under the Manhattan (L1) and Chebyshev (L∞) metrics
+//!
+//! The bisector of two sites under the L1 metric is made of up to three straight
+//! pieces: a 45° piece across the box the two sites span, and two axis-aligned
+//! pieces going off from its ends. Turning the plane by 45° and shrinking it by √2
+//! takes L∞ distances to L1 distances, so both are built as L1 diagrams.
+//!
+//! Each cell starts as the clip region and is cut down by the bisector with each
+//! site that comes near enough to reach it, nearest first.
+
+use cgmath::{Point2, InnerSpace, BaseFloat};
+
+#[cfg(feature = "serde")]
+use serde::{Serialize, Deserialize};
+
+use cells::{Bound, CellAssembler};
+use diagram::{Diagram, Face, FaceId};
+use kdtree::KdTree;
+
+/// Number of the sites nearest to each cell that are looked for first
+const FIRST_CANDIDATES: usize = 16;
+
+/// Vertices closer together than this, relative to the size of the clip region,
+/// are the same vertex
+const VERTEX_TOLERANCE: f64 = 1e-9;
+
+/// Points closer than this to a bisector count as being on the first site's side,
+/// so that corners that are meant to be on it aren't cut off by rounding errors
+const SIDE_TOLERANCE: f64 = 1e-12;
+
+/// How distances between points are measured
+///
+/// Under the non-Euclidean metrics, points at the same distance from two sites
+/// can cover an area rather than a line, when the sites are diagonal from each
+/// other. Such an area goes to whichever site is nearer along the x axis, or
+/// along the diagonal x = y for `LInf`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Metric {
+    /// The straight line distance, `√(dx² + dy²)`
+    #[default]
+    Euclidean,
+
+    /// The Manhattan or taxicab distance, `|dx| + |dy|`. Edges are horizontal,
+    /// vertical or at 45°.
+    L1,
+
+    /// The Chebyshev or chessboard distance, `max(|dx|, |dy|)`. Edges are at 45°,
+    /// horizontal or vertical.
+    LInf,
+}
+
+impl Metric {
+    /// The distance between two points under this metric
+    pub fn distance<T: BaseFloat>(&self, a: Point2<T>, b: Point2<T>) -> T {
+        let dx = (a.x - b.x).abs();
+        let dy = (a.y - b.y).abs();
+        match *self {
+            Metric::Euclidean => dx.hypot(dy),
+            Metric::L1 => dx + dy,
+            Metric::LInf => dx.max(dy),
+        }
+    }
+
+    /// Takes a point to where L1 distances from it are distances under this metric
+    fn to_l1(self, point: Point2<f64>) -> Point2<f64> {
+        match self {
+            Metric::LInf => Point2::new((point.x + point.y) / 2.0, (point.y - point.x) / 2.0),
+            _ => point,
+        }
+    }
+
+    fn back_from_l1(self, point: Point2<f64>) -> Point2<f64> {
+        match self {
+            Metric::LInf => Point2::new(point.x - point.y, point.x + point.y),
+            _ => point,
+        }
+    }
+}
+
+/// The L1 bisector of two sites, and which side of it is nearer the first
+///
+/// It's given as a function from the coordinate it runs along to the one it
+/// runs across. Sites further apart in x than in y have a bisector that runs
+/// along y.
+struct Bisector {
+    along_y: bool,
+
+    /// Where the diagonal piece starts and ends along the bisector
+    low: f64,
+    high: f64,
+
+    midpoint: Point2<f64>,
+
+    /// Change across the diagonal piece per unit along it
+    slope: f64,
+
+    /// Sign of the first site's side, going across
+    side: f64,
+}
+
+impl Bisector {
+    fn new(site: Point2<f64>, other: Point2<f64>) -> Bisector {
+        let (dx, dy) = (other.x - site.x, other.y - site.y);
+        let along_y = dx.abs() >= dy.abs();
+        let (along, across) = if along_y { (dy, dx) } else { (dx, dy) };
+        let start = if along_y { site.y } else { site.x };
+
+        Bisector {
+            along_y,
+            low: start.min(start + along),
+            high: start.max(start + along),
+            midpoint: Point2::new((site.x + other.x) / 2.0, (site.y + other.y) / 2.0),
+            slope: if dx * dy > 0.0 { -1.0 } else if dx * dy < 0.0 { 1.0 } else { 0.0 },
+            side: -across.signum(),
+        }
+    }
+
+    fn along(&self, point: Point2<f64>) -> f64 {
+        if self.along_y { point.y } else { point.x }
+    }
+
+    fn across(&self, point: Point2<f64>) -> f64 {
+        if self.along_y { point.x } else { point.y }
+    }
+
+    /// The point on the bisector at some distance along it
+    fn at(&self, along: f64) -> Point2<f64> {
+        let across = self.across(self.midpoint) + self.slope * (along.max(self.low).min(self.high) - self.along(self.midpoint));
+        if self.along_y { Point2::new(across, along) } else { Point2::new(along, across) }
+    }
+
+    /// Positive on the first site's side of the bisector, negative on the other
+    fn distance(&self, point: Point2<f64>) -> f64 {
+        (self.across(point) - self.across(self.at(self.along(point)))) * self.side
+    }
+
+    fn keeps(&self, point: Point2<f64>) -> bool {
+        self.distance(point) > -SIDE_TOLERANCE
+    }
+
+    /// Cuts a cell down to the first site's side of the bisector with another site
+    ///
+    /// The cell must be star-shaped around the first site, so what's left is a
+    /// single piece. Parts of edges between cells that lie along the bisector
+    /// stay with whichever of the two neighbours is nearer there.
+    fn clip(&self, cell: &[(Point2<f64>, Bound)], sites: &[Point2<f64>], other_index: u32) -> Vec<(Point2<f64>, Bound)> {
+        let bound = Bound::Site(other_index);
+        let mut clipped = Vec::new();
+        let mut exit = None;
+
+        for (index, &(start, start_bound)) in cell.iter().enumerate() {
+            let end = cell[(index + 1) % cell.len()].0;
+
+            // The bisector bends where the edge crosses the ends of the diagonal
+            // piece, and is straight in between
+            let (start_along, end_along) = (self.along(start), self.along(end));
+            let mut breaks = vec![0.0, 1.0];
+            for &bend in &[self.low, self.high] {
+                let t = (bend - start_along) / (end_along - start_along);
+                if t > 0.0 && t < 1.0 {
+                    breaks.push(t);
+                }
+            }
+            breaks.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+            for pair in breaks.windows(2) {
+                let from = start + (end - start) * pair[0];
+                let to = start + (end - start) * pair[1];
+                let kept = self.keeps(from);
+                if kept {
+                    let piece_bound = match start_bound {
+                        Bound::Site(neighbour) if self.runs_along(from, to) => {
+                            let middle = from + (to - from) * 0.5;
+                            if Bisector::new(sites[neighbour as usize], sites[other_index as usize]).keeps(middle) {
+                                start_bound
+                            } else {
+                                bound
+                            }
+                        }
+                        _ => start_bound,
+                    };
+                    clipped.push((from, piece_bound));
+                }
+                if kept == self.keeps(to) {
+                    continue;
+                }
+
+                let (from_distance, to_distance) = (self.distance(from), self.distance(to));
+                let t = (from_distance / (from_distance - to_distance)).clamp(0.0, 1.0);
+                let crossing = self.at(self.along(from + (to - from) * t));
+                if kept {
+                    clipped.push((crossing, bound));
+                    exit = Some(self.along(crossing));
+                } else {
+                    // Follow the bisector from where the cell left it. The first
+                    // corner is outside if the cell enters before it has left
+                    if let Some(exit_along) = exit.take() {
+                        self.push_bends(&mut clipped, exit_along, self.along(crossing), bound);
+                    }
+                    clipped.push((crossing, start_bound));
+                }
+            }
+        }
+
+        // The cell left the bisector for the last time after its first corner, so
+        // the bisector goes back round to where the cell first joined it
+        if let Some(exit_along) = exit {
+            let entry_along = self.along(clipped[0].0);
+            self.push_bends(&mut clipped, exit_along, entry_along, bound);
+        }
+
+        remove_straight_corners(&mut clipped);
+        clipped
+    }
+
+    /// Whether a line lies along the bisector
+    fn runs_along(&self, from: Point2<f64>, to: Point2<f64>) -> bool {
+        self.distance(from).abs() < SIDE_TOLERANCE && self.distance(to).abs() < SIDE_TOLERANCE
+    }
+
+    /// Adds the bends of the bisector that are strictly between two points on it, in
+    /// order from the first
+    fn push_bends(&self, corners: &mut Vec<(Point2<f64>, Bound)>, from: f64, to: f64, bound: Bound) {
+        // Bisectors of sites in line with an axis don't bend
+        if self.slope == 0.0 {
+            return;
+        }
+
+        let bends = if from < to { [self.low, self.high] } else { [self.high, self.low] };
+        for &bend in &bends {
+            if (bend - from) * (to - bend) > 0.0 {
+                corners.push((self.at(bend), bound));
+            }
+        }
+    }
+}
+
+/// Removes corners in the middle of straight edges, which come from the bends of
+/// bisectors and the places edges were split, and corners at the start of edges
+/// with no length
+///
+/// The cells on both sides of an edge have to agree on its vertices, so every
+/// corner where an edge doesn't turn or change what it runs along is removed.
+fn remove_straight_corners(cell: &mut Vec<(Point2<f64>, Bound)>) {
+    // Removing a corner can straighten the one before it, so this goes round again
+    // until nothing changes
+    let mut removed = true;
+    while removed && cell.len() > 3 {
+        removed = false;
+        let mut index = 0;
+        while index < cell.len() && cell.len() > 3 {
+            let previous = cell[(index + cell.len() - 1) % cell.len()];
+            let (corner, bound) = cell[index];
+            let next = cell[(index + 1) % cell.len()].0;
+            let (incoming, outgoing) = (corner - previous.0, next - corner);
+
+            // Whether the corner is on the line that would replace it
+            let line = next - previous.0;
+            let straight = line.magnitude2() == 0.0 || (line.x * incoming.y - line.y * incoming.x).abs() <= SIDE_TOLERANCE * line.magnitude();
+
+            // Edges too short to have two different vertices are removed too, leaving
+            // the edge before to run on to the next corner
+            if outgoing.magnitude() < VERTEX_TOLERANCE || (previous.1 == bound && straight && incoming.dot(outgoing) >= 0.0) {
+                cell.remove(index);
+                removed = true;
+            } else {
+                index += 1;
+            }
+        }
+    }
+}
+
+/// Builds the cells of sites under the L1 or L∞ metric, in the same scaled
+/// coordinates as the sites and the boundary
+///
+/// There's a face for each of `faces`, with `sites` giving the scaled positions of
+/// their sites. The sites of the diagram are left for the caller to index.
+pub(crate) fn build_cells<T: BaseFloat>(metric: Metric, faces: &[Face<T>], sites: &[Point2<T>], boundary: &[Point2<T>]) -> Diagram<T> {
+    let sites = sites.iter().map(|&site| metric.to_l1(site.cast::<f64>())).collect::<Vec<_>>();
+    let boundary = boundary.iter()
+        .enumerate()
+        .map(|(index, &corner)| (metric.to_l1(corner.cast::<f64>()), Bound::Side(index)))
+        .collect::<Vec<_>>();
+    let l1_distance = |a: Point2<f64>, b: Point2<f64>| (a.x - b.x).abs() + (a.y - b.y).abs();
+    let site_index = KdTree::new(sites.iter().enumerate().map(|(index, &site)| (FaceId(index as u32), site, 0.0)), false);
+
+    let mut assembler = CellAssembler::new(VERTEX_TOLERANCE);
+    for (index, face) in faces.iter().enumerate() {
+        let site = sites[index];
+        let mut cell = boundary.clone();
+
+        // No point of a cell is nearer to another site than it is to its own one
+        // unless that site is less than twice as far from it as the cell reaches.
+        // L1 distances are never less than straight line distances, so once the
+        // nearest sites have been used up to twice the reach, no others can cut
+        // the cell down. The search includes the cell's own site
+        let mut used = 0;
+        let mut count = FIRST_CANDIDATES + 1;
+        loop {
+            let reach = cell.iter().map(|&(corner, _)| l1_distance(site, corner)).fold(0.0, f64::max);
+            let nearest = site_index.nearest_unweighted(site, count);
+            for &FaceId(other_index) in &nearest[used..] {
+                let other = sites[other_index as usize];
+                if other_index as usize != index && l1_distance(site, other) < 2.0 * reach {
+                    cell = Bisector::new(site, other).clip(&cell, &sites, other_index);
+                }
+            }
+
+            // The search always finds the cell's own site
+            let FaceId(furthest) = nearest[nearest.len() - 1];
+            if nearest.len() < count || (sites[furthest as usize] - site).magnitude() >= 2.0 * reach {
+                break;
+            }
+            used = nearest.len();
+            count *= 2;
+        }
+
+        assembler.add_cell(face.site, &cell);
+    }
+
+    assembler.finish(|position| metric.back_from_l1(position).cast::<T>())
+}
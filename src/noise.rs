@@ -0,0 +1,117 @@
+//! Wiggly edges between cells, by midpoint displacement
+//!
+//! This is the "noisy edges" technique from Amit Patel's map generators. Each
+//! edge is split in the middle, the middle is moved a random amount towards the
+//! site on one side or the other, and the two halves are split again in the same
+//! way. The edges are split with `Diagram::split_edge`, so both cells still share
+//! every part of the edge between them.
+//!
+//! The middle of an edge is kept inside the triangle between the edge and one of
+//! the sites, and the halves get smaller triangles inside that one. The triangle
+//! between an edge and its site is inside the site's cell when the cell is convex,
+//! so a noisy edge never crosses any other edge, or itself.
+
+use cgmath::{Point2, BaseFloat};
+use cgmath::num_traits::cast;
+
+use dedup::next_random;
+use diagram::{Diagram, Endpoint, HalfEdgeId};
+use predicates::orient2d;
+
+/// A stretch of an edge that still has to be split, with the corners of the
+/// triangles either side of it that the points it is split at have to stay in
+#[derive(Clone, Copy)]
+struct Span<T> {
+    start: Point2<T>,
+    end: Point2<T>,
+    left: Point2<T>,
+    right: Point2<T>,
+}
+
+fn midpoint<T: BaseFloat>(a: Point2<T>, b: Point2<T>) -> Point2<T> {
+    let two = T::one() + T::one();
+    Point2::new((a.x + b.x) / two, (a.y + b.y) / two)
+}
+
+/// Splits a span into `2^depth` pieces, and adds the points between them in order
+/// from its start
+fn subdivide<T: BaseFloat>(span: Span<T>, amplitude: T, depth: u32, state: &mut u64, points: &mut Vec<Point2<T>>) {
+    if depth == 0 {
+        return;
+    }
+
+    let Span { start, end, left, right } = span;
+    let middle = midpoint(start, end);
+    let amount = cast::<f64, T>(next_random(state) * 2.0 - 1.0).unwrap() * amplitude;
+    let (point, towards_left) = if amount > T::zero() {
+        (middle + (left - middle) * amount, true)
+    } else {
+        (middle + (right - middle) * -amount, false)
+    };
+
+    // The half on the side the point moved to keeps half the triangle it moved
+    // into, and the other side gets the part of the triangle it moved out of
+    let (first, second) = if towards_left {
+        (Span { start, end: point, left: midpoint(start, left), right: middle }, Span { start: point, end, left: midpoint(end, left), right: middle })
+    } else {
+        (Span { start, end: point, left: middle, right: midpoint(start, right) }, Span { start: point, end, left: middle, right: midpoint(end, right) })
+    };
+    subdivide(first, amplitude, depth - 1, state, points);
+    points.push(point);
+    subdivide(second, amplitude, depth - 1, state, points);
+}
+
+/// The site of a cell if it's on the correct side of an edge, and otherwise the
+/// middle of the edge so the edge can't move that way
+fn apex<T: BaseFloat>(start: Point2<T>, end: Point2<T>, site: Point2<T>, side: f64) -> Point2<T> {
+    if orient2d(start.cast::<f64>(), end.cast::<f64>(), site.cast::<f64>()) * side > 0.0 {
+        site
+    } else {
+        midpoint(start, end)
+    }
+}
+
+/// See `Diagram::add_noisy_edges`
+pub fn add_noisy_edges<T: BaseFloat>(diagram: &mut Diagram<T>, amplitude: T, depth: u32, seed: u64) {
+    let amplitude = amplitude.max(T::zero()).min(T::one());
+    if depth == 0 || amplitude.is_nan() {
+        return;
+    }
+
+    // Edges are looked up first, as splitting them adds more
+    let edges = diagram.halfedges.iter().enumerate()
+        .filter(|&(index, halfedge)| halfedge.twin.is_some_and(|twin| index < twin.0 as usize))
+        .map(|(index, _)| HalfEdgeId(index as u32))
+        .collect::<Vec<_>>();
+
+    let mut points = Vec::new();
+    for halfedge_id in edges {
+        let (start, end) = match (diagram.origin(halfedge_id), diagram.destination(halfedge_id)) {
+            (Some(Endpoint::Vertex(start)), Some(Endpoint::Vertex(end))) => (diagram.vertices[start.0 as usize].coordinates, diagram.vertices[end.0 as usize].coordinates),
+            _ => continue,
+        };
+        let halfedge = &diagram.halfedges[halfedge_id.0 as usize];
+        let twin = &diagram.halfedges[halfedge.twin.unwrap().0 as usize];
+        let (left, right) = (&diagram.faces[halfedge.incident_face.0 as usize].site, &diagram.faces[twin.incident_face.0 as usize].site);
+
+        // Each edge gets its own sequence from the sites either side of it, so it
+        // comes out the same whatever order the edges are in
+        let mut state = seed ^ (u64::from(left.id.min(right.id)) << 32 | u64::from(left.id.max(right.id))).wrapping_mul(0x9e37_79b9_7f4a_7c15);
+        let span = Span {
+            start,
+            end,
+            left: apex(start, end, left.position, 1.0),
+            right: apex(start, end, right.position, -1.0),
+        };
+        points.clear();
+        subdivide(span, amplitude, depth, &mut state, &mut points);
+
+        // Splitting keeps the first part of the half-edge, so the rest of the edge
+        // is always the next one
+        let mut current = halfedge_id;
+        for &point in &points {
+            diagram.split_edge(current, point);
+            current = diagram.halfedges[current.0 as usize].next;
+        }
+    }
+}
@@ -0,0 +1,140 @@
+//! Following the sweep while a diagram is built
+//!
+//! A `BuildObserver` gets callbacks as events are processed, and
+//! `DiagramBuilder::sweep_state` gives the whole state of the sweep between steps.
+
+use std::fmt::Debug;
+
+use cgmath::Point2;
+
+use build::Site;
+use diagram::FaceId;
+
+/// An arc on the beachline, in the coordinates of the input sites
+#[derive(Debug, Clone, Copy)]
+pub struct ArcSnapshot<T = f32> {
+    /// The site the arc belongs to
+    pub site: Site<T>,
+
+    /// x coordinate of the breakpoint with the arc to the left, or negative
+    /// infinity for the leftmost arc
+    pub left_breakpoint: T,
+
+    /// x coordinate of the breakpoint with the arc to the right, or infinity for
+    /// the rightmost arc
+    pub right_breakpoint: T,
+}
+
+/// An event waiting in the queue
+#[derive(Debug, Clone, Copy)]
+pub enum PendingEvent<T = f32> {
+    Site(Site<T>),
+
+    /// An arc that will disappear when the sweep line reaches `directrix`, leaving
+    /// a vertex behind
+    Circle {
+        vertex: Point2<T>,
+        directrix: T,
+    },
+}
+
+/// An edge found by the sweep so far
+///
+/// Each end of the edge is either a vertex or a breakpoint of the beachline, which
+/// moves along the edge as the sweep continues.
+#[derive(Debug, Clone, Copy)]
+pub struct SweepEdge<T = f32> {
+    /// The face to the left when going from start to end
+    pub left_face: FaceId,
+
+    /// The face to the right when going from start to end
+    pub right_face: FaceId,
+
+    pub start: Point2<T>,
+    pub end: Point2<T>,
+
+    /// Whether the start is a vertex rather than a breakpoint
+    pub start_fixed: bool,
+
+    /// Whether the end is a vertex rather than a breakpoint
+    pub end_fixed: bool,
+}
+
+/// The state of a sweep between two steps, in the coordinates of the input sites
+#[derive(Debug, Clone)]
+pub struct SweepState<T = f32> {
+    /// y coordinate of the sweep line, or None before the first step
+    pub directrix: Option<T>,
+
+    /// Arcs of the beachline from left to right
+    pub arcs: Vec<ArcSnapshot<T>>,
+
+    /// Events that haven't been processed yet in the order they will be, leaving
+    /// out cancelled circle events
+    pub pending_events: Vec<PendingEvent<T>>,
+
+    pub edges: Vec<SweepEdge<T>>,
+}
+
+/// Receives the events of a sweep as they happen, see
+/// `DiagramBuilder::step_with_observer`
+///
+/// Every method does nothing by default, so implementations only need the ones
+/// they care about. Positions are in the coordinates of the input sites and the
+/// directrix is the y coordinate of the sweep line.
+pub trait BuildObserver<T> {
+    /// Called after a site has been added to the beachline
+    fn site_event(&mut self, _site: &Site<T>) {}
+
+    /// Called after an arc has disappeared from the beachline, leaving a vertex at
+    /// the centre of the circle through it and its neighbours
+    fn circle_event(&mut self, _vertex: Point2<T>, _directrix: T) {}
+
+    /// Called for each arc whose pending circle event was cancelled during the
+    /// step, because one of its neighbours changed
+    fn circle_event_cancelled(&mut self, _site: &Site<T>) {}
+
+    /// Whether `beachline` should be called after every step
+    ///
+    /// Collecting the arcs takes time proportional to the length of the beachline,
+    /// so this is off by default.
+    fn wants_beachline(&self) -> bool {
+        false
+    }
+
+    /// Called after each step with the arcs of the beachline from left to right
+    fn beachline(&mut self, _directrix: T, _arcs: &[ArcSnapshot<T>]) {}
+}
+
+/// Ignores every event
+impl<T> BuildObserver<T> for () {}
+
+/// Prints every event to stdout
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PrintObserver;
+
+impl<T: Debug> BuildObserver<T> for PrintObserver {
+    fn site_event(&mut self, site: &Site<T>) {
+        println!("site event: id={} x={:?}, y={:?}", site.id, site.position.x, site.position.y);
+    }
+
+    fn circle_event(&mut self, vertex: Point2<T>, directrix: T) {
+        println!("circle event: cx={:?}, cy={:?}, directrix={:?}", vertex.x, vertex.y, directrix);
+    }
+
+    fn circle_event_cancelled(&mut self, site: &Site<T>) {
+        println!("cancelled circle event of arc of site {}", site.id);
+    }
+
+    fn wants_beachline(&self) -> bool {
+        true
+    }
+
+    fn beachline(&mut self, directrix: T, arcs: &[ArcSnapshot<T>]) {
+        println!("directrix={:?}", directrix);
+        for arc in arcs {
+            println!("arc: xl={:?}, xr={:?}, site={{id: {}, x:{:?}, y:{:?}}}", arc.left_breakpoint, arc.right_breakpoint, arc.site.id, arc.site.position.x, arc.site.position.y);
+        }
+        println!();
+    }
+}
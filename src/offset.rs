@@ -0,0 +1,108 @@
+//! Shrinking the polygons of cells, so they can be drawn with gaps between them
+
+use cgmath::{Point2, Vector2, InnerSpace, BaseFloat};
+
+use diagram::{Diagram, FaceId};
+use mesh::closed_polygon;
+use predicates::orient2d;
+
+fn cross<T: BaseFloat>(a: Vector2<T>, b: Vector2<T>) -> T {
+    a.x * b.y - a.y * b.x
+}
+
+/// How far inside the line through `start` and `end` a point is, times the length
+/// of the line
+fn depth<T: BaseFloat>(start: Point2<T>, end: Point2<T>, point: Point2<T>) -> T {
+    cross(end - start, point - start)
+}
+
+/// Cuts a polygon down to the part at least `distance` inside the line from
+/// `start` to `end`, with the inside on the left
+fn clip_to_line<T: BaseFloat>(polygon: &[Point2<T>], start: Point2<T>, end: Point2<T>, distance: T) -> Vec<Point2<T>> {
+    let limit = distance * (end - start).magnitude();
+    let mut clipped = Vec::with_capacity(polygon.len() + 1);
+    for (index, &point) in polygon.iter().enumerate() {
+        let next = polygon[(index + 1) % polygon.len()];
+        let (here, there) = (depth(start, end, point) - limit, depth(start, end, next) - limit);
+        if here >= T::zero() {
+            clipped.push(point);
+        }
+        if (here >= T::zero()) != (there >= T::zero()) {
+            clipped.push(point + (next - point) * (here / (here - there)));
+        }
+    }
+    clipped
+}
+
+/// Moves each corner of a polygon to where the lines through its edges meet once
+/// they have been moved inwards
+fn miter<T: BaseFloat>(polygon: &[Point2<T>], distance: T) -> Vec<Point2<T>> {
+    let count = polygon.len();
+    let normal = |index: usize| {
+        let edge = polygon[(index + 1) % count] - polygon[index];
+        Vector2::new(-edge.y, edge.x).normalize()
+    };
+
+    (0..count).map(|index| {
+        let (before, after) = (normal((index + count - 1) % count), normal(index));
+        let corner = polygon[index];
+
+        // Where the two lines meet, or straight in if they are parallel
+        let sine = cross(before, after);
+        if sine.abs() <= T::epsilon() {
+            return corner + after * distance;
+        }
+        let edge = polygon[(index + 1) % count] - corner;
+        let along = (T::one() - before.dot(after)) * distance / sine;
+        corner + after * distance + edge.normalize() * along
+    }).collect()
+}
+
+fn twice_area<T: BaseFloat>(polygon: &[Point2<T>]) -> T {
+    polygon.iter().enumerate().fold(T::zero(), |area, (index, a)| {
+        let b = polygon[(index + 1) % polygon.len()];
+        area + a.x * b.y - b.x * a.y
+    })
+}
+
+/// See `Diagram::inset_faces`
+pub fn inset_faces<T: BaseFloat>(diagram: &Diagram<T>, distance: T) -> Vec<Vec<Point2<T>>> {
+    (0..diagram.faces.len()).map(|index| {
+        let polygon = match closed_polygon(diagram, FaceId(index as u32)) {
+            Some(polygon) => polygon,
+            None => return Vec::new(),
+        };
+        if distance <= T::zero() || polygon.len() < 3 {
+            return polygon;
+        }
+
+        let corners = polygon.iter().map(|corner| corner.cast::<f64>()).collect::<Vec<_>>();
+        let count = corners.len();
+        let convex = (0..count).all(|index| orient2d(corners[index], corners[(index + 1) % count], corners[(index + 2) % count]) >= 0.0);
+        let inset = if convex {
+            // Each edge cuts off what's too close to it, which drops edges that are
+            // too short to survive
+            (0..count).fold(polygon.clone(), |inset, index| {
+                let (start, end) = (polygon[index], polygon[(index + 1) % count]);
+                if inset.is_empty() || start == end {
+                    inset
+                } else {
+                    clip_to_line(&inset, start, end, distance)
+                }
+            })
+        } else {
+            let mut corners = polygon.clone();
+            corners.dedup();
+            while corners.len() > 1 && corners.first() == corners.last() {
+                corners.pop();
+            }
+            miter(&corners, distance)
+        };
+
+        if inset.len() < 3 || twice_area(&inset) <= T::zero() {
+            Vec::new()
+        } else {
+            inset
+        }
+    }).collect()
+}
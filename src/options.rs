@@ -0,0 +1,142 @@
+use cgmath::BaseFloat;
+
+use build::{ClipRegion, DiagramBuilder, DuplicatePolicy, Metric, NonFinitePolicy, OutOfBoundsPolicy, Rect, Site, SiteError};
+
+/// Options for building a diagram, set one at a time
+///
+/// Start from `DiagramBuilder::options`, chain the options that differ from the
+/// defaults, such as `.clip(true).dedup_epsilon(1e-6)`, then call `build` with
+/// the sites. The precision is the type of the coordinates of the sites.
+///
+/// The defaults match `DiagramBuilder::new`, except that the clip region has to
+/// be set there, and here it is the rect around the sites unless it is set.
+///
+/// There's no option for watching the build. A `BuildObserver` is borrowed for
+/// as long as the sweep runs rather than kept, so it's passed to
+/// `DiagramBuilder::finish_with_observer` along with the builder from `build`.
+#[derive(Debug, Clone)]
+pub struct DiagramBuilderOptions<T = f32> {
+    clip_region: Option<ClipRegion<T>>,
+    padding: T,
+    duplicate_policy: DuplicatePolicy,
+    tolerance: T,
+    out_of_bounds: OutOfBoundsPolicy,
+    non_finite: NonFinitePolicy,
+    metric: Metric,
+    clipped: bool,
+    relaxation: u32,
+}
+
+impl<T: BaseFloat> Default for DiagramBuilderOptions<T> {
+    fn default() -> DiagramBuilderOptions<T> {
+        DiagramBuilderOptions {
+            clip_region: None,
+            padding: T::zero(),
+            duplicate_policy: DuplicatePolicy::MergeFirstWins,
+            tolerance: T::zero(),
+            out_of_bounds: OutOfBoundsPolicy::Skip,
+            non_finite: NonFinitePolicy::Skip,
+            metric: Metric::Euclidean,
+            clipped: true,
+            relaxation: 0,
+        }
+    }
+}
+
+impl<T: BaseFloat> DiagramBuilderOptions<T> {
+    /// Clips the diagram to a region
+    pub fn clip_region<R: Into<ClipRegion<T>>>(mut self, clip_region: R) -> DiagramBuilderOptions<T> {
+        self.clip_region = Some(clip_region.into());
+        self
+    }
+
+    /// Clips the diagram to the rect around the sites, with `padding` added on
+    /// each side, see `Rect::around`
+    pub fn auto_bounds(mut self, padding: T) -> DiagramBuilderOptions<T> {
+        self.clip_region = None;
+        self.padding = padding;
+        self
+    }
+
+    /// Deals with sites closer than `tolerance` to an earlier one according to
+    /// `policy`, see `DiagramBuilder::with_duplicate_policy`
+    pub fn duplicate_policy(mut self, policy: DuplicatePolicy, tolerance: T) -> DiagramBuilderOptions<T> {
+        self.duplicate_policy = policy;
+        self.tolerance = tolerance;
+        self
+    }
+
+    /// Sets how close sites have to be to count as duplicates, keeping the policy
+    pub fn dedup_epsilon(mut self, tolerance: T) -> DiagramBuilderOptions<T> {
+        self.tolerance = tolerance;
+        self
+    }
+
+    pub fn out_of_bounds_policy(mut self, policy: OutOfBoundsPolicy) -> DiagramBuilderOptions<T> {
+        self.out_of_bounds = policy;
+        self
+    }
+
+    pub fn non_finite_policy(mut self, policy: NonFinitePolicy) -> DiagramBuilderOptions<T> {
+        self.non_finite = policy;
+        self
+    }
+
+    /// See `DiagramBuilder::set_metric`
+    pub fn metric(mut self, metric: Metric) -> DiagramBuilderOptions<T> {
+        self.metric = metric;
+        self
+    }
+
+    /// Whether to clip the diagram to the clip region, see
+    /// `DiagramBuilder::set_clipped`
+    ///
+    /// Relaxation still uses the clipped cells, as sites on the outside have no
+    /// centroid without them.
+    pub fn clip(mut self, clipped: bool) -> DiagramBuilderOptions<T> {
+        self.clipped = clipped;
+        self
+    }
+
+    /// Moves the sites by some iterations of Lloyd relaxation before building
+    ///
+    /// Each iteration builds the diagram with the other options and moves every
+    /// site with a face to the centroid of its cell. Sites without a face are
+    /// left where they are, so the site mapping of the builder still matches the
+    /// sites that were given.
+    pub fn relaxation(mut self, iterations: u32) -> DiagramBuilderOptions<T> {
+        self.relaxation = iterations;
+        self
+    }
+
+    /// Creates a builder for the diagram of the sites with these options
+    pub fn build(self, mut sites: Vec<Site<T>>) -> Result<DiagramBuilder<T>, SiteError> {
+        // The rect around the sites is found before relaxing, which would
+        // otherwise pull it in a little more each time
+        let clip_region = match self.clip_region {
+            Some(ref clip_region) => clip_region.clone(),
+            None => Rect::around(sites.iter().map(|site| site.position), self.padding).into(),
+        };
+
+        for _ in 0..self.relaxation {
+            let mut builder = self.builder(clip_region.clone(), sites.clone())?;
+            builder.set_clipped(true);
+            let site_mapping = builder.site_mapping().clone();
+            let diagram = builder.finish();
+            for (index, site) in sites.iter_mut().enumerate() {
+                if let Some(centroid) = site_mapping.face(index).and_then(|face_id| diagram.face_centroid(face_id)) {
+                    site.position = centroid;
+                }
+            }
+        }
+
+        self.builder(clip_region, sites)
+    }
+
+    fn builder(&self, clip_region: ClipRegion<T>, sites: Vec<Site<T>>) -> Result<DiagramBuilder<T>, SiteError> {
+        let mut builder = DiagramBuilder::with_policies(clip_region, sites, self.duplicate_policy, self.tolerance, self.out_of_bounds, self.non_finite)?;
+        builder.set_metric(self.metric);
+        builder.set_clipped(self.clipped);
+        Ok(builder)
+    }
+}
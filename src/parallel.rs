@@ -0,0 +1,467 @@
+//! Building large diagrams on several threads
+//!
+//! The clip region is cut into vertical strips, and the part of the diagram in
+//! each strip is built on its own thread from the sites in the strip and a margin
+//! of sites around it. The margin starts small and is widened until it's known to
+//! contain every site that affects the cells in the strip: a piece of a cell is
+//! right if the circle around each of its corners through its site doesn't reach
+//! past the margin, as any missing site closer to some point of the piece than its
+//! own site would be inside one of those circles.
+//!
+//! The strips are then glued together along the lines between them. Rounding can
+//! make two strips disagree about a line when a vertex of the diagram is almost on
+//! it, in which case the two strips are built again as one.
+//!
+//! Strips are built with f64 coordinates whatever the scalar type of the diagram,
+//! so that gluing them doesn't lose any more precision.
+
+use std::cmp::Ordering;
+
+use cgmath::{Point2, Vector2, EuclideanSpace, InnerSpace, BaseFloat};
+use fnv::FnvHashMap;
+use rayon::prelude::*;
+
+use build::{ClipRegion, DiagramBuilder, Rect, Site};
+use clip::strictly_inside_boundary;
+use diagram::{Diagram, Vertex, VertexId, HalfEdge, HalfEdgeId, Face, FaceId};
+
+/// Fewest sites worth giving a strip of their own
+const MIN_SITES_PER_STRIP: usize = 1024;
+
+/// Builds a diagram by splitting it into strips that are built in parallel
+///
+/// The cells are the same as from `DiagramBuilder::new` apart from rounding, but
+/// where more than three cells meet there is a single vertex rather than several
+/// joined by edges of almost no length.
+pub struct ParallelDiagramBuilder<T = f32> {
+    clip_region: ClipRegion<T>,
+    sites: Vec<Site<T>>,
+    strips: usize,
+}
+
+/// The part of a diagram in one strip
+///
+/// The sites of its faces have their index in the list of sites as their id.
+struct Strip {
+    x_min: f64,
+    x_max: f64,
+    diagram: Diagram<f64>,
+}
+
+/// The sites that make it into the diagram and where they are
+struct Sites {
+    sites: Vec<Site<f64>>,
+
+    /// Indices into `sites` ordered by x coordinate
+    by_x: Vec<usize>,
+
+    /// Corners of the clip region in counter-clockwise order
+    boundary: Vec<Point2<f64>>,
+
+    bounding_rect: Rect<f64>,
+
+    /// Typical distance between neighbouring sites
+    spacing: f64,
+
+    /// Distances smaller than this are lost in rounding
+    tolerance: f64,
+}
+
+impl<T: BaseFloat> ParallelDiagramBuilder<T> {
+    /// Creates a builder for the diagram of the sites inside the clip region, using
+    /// as many strips as rayon has threads
+    pub fn new<R: Into<ClipRegion<T>>>(clip_region: R, sites: Vec<Site<T>>) -> ParallelDiagramBuilder<T> {
+        ParallelDiagramBuilder {
+            clip_region: clip_region.into(),
+            sites,
+            strips: ::rayon::current_num_threads(),
+        }
+    }
+
+    /// Sets the number of strips to split the diagram into
+    ///
+    /// Fewer strips are used when there aren't enough sites to make them worthwhile.
+    pub fn set_strips(&mut self, strips: usize) {
+        self.strips = strips;
+    }
+
+    pub fn finish(self) -> Diagram<T> {
+        let bounding_rect = self.clip_region.bounding_rect();
+        let boundary = self.clip_region.boundary(T::one()).into_iter()
+            .map(|corner| corner + bounding_rect.position.to_vec())
+            .collect::<Vec<_>>();
+
+        // Leave out the same sites as DiagramBuilder, which are the ones outside the
+        // region and ones at the same position as an earlier site
+        let mut positions = FnvHashMap::default();
+        let kept = self.sites.iter().filter(|site| {
+            let key = (site.position.x.to_f64().unwrap() + 0.0, site.position.y.to_f64().unwrap() + 0.0);
+            strictly_inside_boundary(&boundary, site.position) && positions.insert((key.0.to_bits(), key.1.to_bits()), ()).is_none()
+        }).cloned().collect::<Vec<_>>();
+
+        let count = self.strips.min(kept.len() / MIN_SITES_PER_STRIP);
+        if count <= 1 {
+            return DiagramBuilder::new(self.clip_region, self.sites).finish();
+        }
+
+        let mut by_x = (0..kept.len()).collect::<Vec<_>>();
+        by_x.sort_by(|&a, &b| kept[a].position.x.partial_cmp(&kept[b].position.x).unwrap_or(Ordering::Equal));
+
+        let bounding_rect: Rect<f64> = Rect {
+            position: bounding_rect.position.cast(),
+            size: bounding_rect.size.cast(),
+        };
+        let area = bounding_rect.size.x * bounding_rect.size.y;
+        let extent = bounding_rect.size.x.max(bounding_rect.size.y);
+        let sites = Sites {
+            sites: kept.iter().map(|site| Site::new(site.id, site.position.cast())).collect(),
+            by_x,
+            boundary: boundary.into_iter().map(|corner| corner.cast()).collect(),
+            bounding_rect,
+            spacing: (area / kept.len() as f64).sqrt(),
+            tolerance: extent * f64::EPSILON * 64.0,
+        };
+
+        let mut lines = Vec::new();
+        for strip in 1..count {
+            if let Some(x) = sites.dividing_line(strip * kept.len() / count) {
+                if lines.last().is_none_or(|&last| x > last) {
+                    lines.push(x);
+                }
+            }
+        }
+
+        let x_min = bounding_rect.position.x;
+        let x_max = x_min + bounding_rect.size.x;
+        let ranges = Some(x_min).into_iter().chain(lines.iter().cloned())
+            .zip(lines.iter().cloned().chain(Some(x_max)))
+            .collect::<Vec<_>>();
+        let mut strips = ranges.into_par_iter()
+            .map(|(x_min, x_max)| sites.build_strip(x_min, x_max))
+            .collect::<Vec<_>>();
+
+        loop {
+            match glue(&strips, &sites, &kept) {
+                Ok(diagram) => return diagram,
+                Err(line) => {
+                    let merged = sites.build_strip(strips[line].x_min, strips[line + 1].x_max);
+                    strips[line] = merged;
+                    strips.remove(line + 1);
+                }
+            }
+        }
+    }
+}
+
+impl Sites {
+    fn x(&self, index: usize) -> f64 {
+        self.sites[self.by_x[index]].position.x
+    }
+
+    /// Finds a vertical line between the sites near the given position in x order
+    fn dividing_line(&self, index: usize) -> Option<f64> {
+        let index = (index.max(1)..self.by_x.len()).find(|&index| self.x(index - 1) < self.x(index))?;
+        let (left, right) = (self.x(index - 1), self.x(index));
+
+        // The middle of the gap is where the edges between evenly spaced sites are,
+        // so the line goes a little to the left of it
+        let x = left + (right - left) * 0.4;
+        if left < x && x < right {
+            Some(x)
+        } else {
+            None
+        }
+    }
+
+    /// Builds the part of the diagram between two vertical lines
+    fn build_strip(&self, x_min: f64, x_max: f64) -> Strip {
+        let polygon = strip_polygon(&self.boundary, x_min, x_max);
+        let region_min = self.bounding_rect.position.x;
+        let region_max = region_min + self.bounding_rect.size.x;
+
+        let mut margin_min = x_min - self.spacing * 2.0;
+        let mut margin_max = x_max + self.spacing * 2.0;
+        loop {
+            let all_left = margin_min <= region_min;
+            let all_right = margin_max >= region_max;
+            let halo_min = margin_min.max(region_min - self.spacing);
+            let halo_max = margin_max.min(region_max + self.spacing);
+
+            let start = self.by_x.partition_point(|&index| self.sites[index].position.x <= halo_min);
+            let end = self.by_x.partition_point(|&index| self.sites[index].position.x < halo_max);
+            let mut indices = self.by_x[start..end].to_vec();
+            indices.sort();
+            let sites = indices.iter()
+                .map(|&index| Site { id: index as u32, ..self.sites[index] })
+                .collect::<Vec<_>>();
+
+            // The rect is a little bigger than the region, as clipping to a polygon
+            // that touches the edge of the diagram leaves edges of no length
+            let rect = Rect {
+                position: Point2::new(halo_min, self.bounding_rect.position.y - self.spacing),
+                size: Vector2::new(halo_max - halo_min, self.bounding_rect.size.y + self.spacing * 2.0),
+            };
+            let diagram = DiagramBuilder::new(rect, sites).finish();
+            let everything = all_left && all_right;
+
+            // Sites that are almost at the same position can be merged after scaling,
+            // which can't be told apart from sites that were left out
+            if diagram.faces.len() != indices.len() && !everything {
+                margin_min -= self.spacing * 2.0;
+                margin_max += self.spacing * 2.0;
+                continue;
+            }
+
+            let diagram = diagram.clip_to_polygon(&polygon);
+            if everything {
+                return Strip { x_min, x_max, diagram };
+            }
+
+            // Find how far the circles around the corners of each piece reach
+            let mut reach_min = f64::INFINITY;
+            let mut reach_max = f64::NEG_INFINITY;
+            for (index, face) in diagram.faces.iter().enumerate() {
+                let site = face.site.position;
+                for corner in diagram.face_vertices(FaceId(index as u32)) {
+                    let radius = (corner - site).magnitude() + self.tolerance;
+                    reach_min = reach_min.min(corner.x - radius);
+                    reach_max = reach_max.max(corner.x + radius);
+                }
+            }
+
+            let left_ok = all_left || reach_min > halo_min;
+            let right_ok = all_right || reach_max < halo_max;
+            if left_ok && right_ok {
+                return Strip { x_min, x_max, diagram };
+            }
+            if !left_ok {
+                margin_min = reach_min.min(margin_min) - self.spacing * 2.0;
+            }
+            if !right_ok {
+                margin_max = reach_max.max(margin_max) + self.spacing * 2.0;
+            }
+        }
+    }
+}
+
+/// Cuts the clip region down to the part between two vertical lines
+fn strip_polygon(boundary: &[Point2<f64>], x_min: f64, x_max: f64) -> Vec<Point2<f64>> {
+    // Sutherland-Hodgman, clipping against one line and then the other
+    let clip = |polygon: Vec<Point2<f64>>, x: f64, keep_left: bool| {
+        let inside = |point: Point2<f64>| if keep_left { point.x <= x } else { point.x >= x };
+        let mut clipped = Vec::new();
+        for (index, &a) in polygon.iter().enumerate() {
+            let b = polygon[(index + 1) % polygon.len()];
+            if inside(a) {
+                clipped.push(a);
+            }
+            if inside(a) != inside(b) && a.x != x && b.x != x {
+                let t = (x - a.x) / (b.x - a.x);
+                clipped.push(Point2::new(x, a.y + (b.y - a.y) * t));
+            }
+        }
+        clipped
+    };
+
+    clip(clip(boundary.to_vec(), x_min, false), x_max, true)
+}
+
+/// Finds the twin-less half-edges of a strip that lie on a vertical line, going up
+/// the line if `up` is set and down it otherwise
+fn halfedges_on_line(diagram: &Diagram<f64>, x: f64, up: bool, tolerance: f64) -> Vec<HalfEdgeId> {
+    let position = |halfedge: &HalfEdge| halfedge.origin.map(|origin| diagram.vertices[origin.0 as usize].coordinates);
+
+    diagram.halfedges.iter().enumerate().filter_map(|(index, halfedge)| {
+        if halfedge.twin.is_some() {
+            return None;
+        }
+        let start = position(halfedge)?;
+        let end = position(&diagram.halfedges[halfedge.next.0 as usize])?;
+        let on_line = (start.x - x).abs() <= tolerance && (end.x - x).abs() <= tolerance;
+        let going_up = end.y > start.y;
+
+        if on_line && going_up == up && start.y != end.y {
+            Some(HalfEdgeId(index as u32))
+        } else {
+            None
+        }
+    }).collect()
+}
+
+/// Joins the strips into one diagram, or returns the index of the line the strips
+/// on either side of it don't agree about
+fn glue<T: BaseFloat>(strips: &[Strip], sites: &Sites, kept: &[Site<T>]) -> Result<Diagram<T>, usize> {
+    let mut vertices = Vec::new();
+    let mut halfedges = Vec::new();
+    let mut halfedge_offsets = Vec::new();
+    for strip in strips {
+        let vertex_offset = vertices.len() as u32;
+        let halfedge_offset = halfedges.len() as u32;
+        halfedge_offsets.push(halfedge_offset);
+
+        vertices.extend(strip.diagram.vertices.iter().map(|vertex| Vertex {
+            coordinates: vertex.coordinates,
+            incident_edge: HalfEdgeId(vertex.incident_edge.0 + halfedge_offset),
+        }));
+        halfedges.extend(strip.diagram.halfedges.iter().map(|halfedge| HalfEdge {
+            origin: halfedge.origin.map(|origin| VertexId(origin.0 + vertex_offset)),
+            twin: halfedge.twin.map(|twin| HalfEdgeId(twin.0 + halfedge_offset)),
+            incident_face: FaceId(strip.diagram.faces[halfedge.incident_face.0 as usize].site.id),
+            next: HalfEdgeId(halfedge.next.0 + halfedge_offset),
+            prev: HalfEdgeId(halfedge.prev.0 + halfedge_offset),
+        }));
+    }
+
+    let mut removed = vec![false; halfedges.len()];
+    for line in 0..strips.len() - 1 {
+        let (left, right) = (&strips[line], &strips[line + 1]);
+        let x = left.x_max;
+        let offset = |strip: usize, halfedge: HalfEdgeId| HalfEdgeId(halfedge.0 + halfedge_offsets[strip]);
+
+        // The left strip goes up the line and the right one goes down it, so both
+        // are sorted from the bottom
+        let mut ups = halfedges_on_line(&left.diagram, x, true, sites.tolerance).into_iter()
+            .map(|halfedge| offset(line, halfedge))
+            .collect::<Vec<_>>();
+        let mut downs = halfedges_on_line(&right.diagram, x, false, sites.tolerance).into_iter()
+            .map(|halfedge| offset(line + 1, halfedge))
+            .collect::<Vec<_>>();
+        // `halfedges_on_line` only finds half-edges with a vertex at both ends
+        let start_y = |halfedge: HalfEdgeId| vertices[halfedges[halfedge.0 as usize].origin.unwrap().0 as usize].coordinates.y;
+        let end_y = |halfedge: HalfEdgeId| start_y(halfedges[halfedge.0 as usize].next);
+        ups.sort_by(|&a, &b| start_y(a).partial_cmp(&start_y(b)).unwrap_or(Ordering::Equal));
+        downs.sort_by(|&a, &b| end_y(a).partial_cmp(&end_y(b)).unwrap_or(Ordering::Equal));
+
+        if ups.is_empty() || ups.len() != downs.len() {
+            return Err(line);
+        }
+        for (index, (&up, &down)) in ups.iter().zip(&downs).enumerate() {
+            let up_edge = &halfedges[up.0 as usize];
+            let down_edge = &halfedges[down.0 as usize];
+            if up_edge.incident_face != down_edge.incident_face {
+                return Err(line);
+            }
+            if let (Some(&next_up), Some(&next_down)) = (ups.get(index + 1), downs.get(index + 1)) {
+                if halfedges[up_edge.next.0 as usize].origin != halfedges[next_up.0 as usize].origin ||
+                    down_edge.origin != halfedges[halfedges[next_down.0 as usize].next.0 as usize].origin {
+                    return Err(line);
+                }
+            }
+        }
+
+        // Each face on the line has a piece on either side of it. Dropping the
+        // half-edges along the line joins the pieces into one cycle, with the
+        // half-edges that reach the line from either side meeting on it.
+        let mut meetings = Vec::new();
+        for (&up, &down) in ups.iter().zip(&downs) {
+            let (up_prev, up_next) = (halfedges[up.0 as usize].prev, halfedges[up.0 as usize].next);
+            let (down_prev, down_next) = (halfedges[down.0 as usize].prev, halfedges[down.0 as usize].next);
+            link(&mut halfedges, up_prev, down_next);
+            link(&mut halfedges, down_prev, up_next);
+            removed[up.0 as usize] = true;
+            removed[down.0 as usize] = true;
+            meetings.push(((up_prev, down_next), (down_prev, up_next)));
+        }
+
+        // Where the line crosses an edge, the two halves of the edge on either side
+        // become one again
+        for index in 0..meetings.len() - 1 {
+            let (right_half, left_half) = meetings[index].1;
+            let (left_twin, right_twin) = meetings[index + 1].0;
+            if halfedges[right_half.0 as usize].twin != Some(right_twin) || halfedges[left_half.0 as usize].twin != Some(left_twin) {
+                return Err(line);
+            }
+            join(&mut halfedges, &mut removed, right_half, left_half);
+            join(&mut halfedges, &mut removed, left_twin, right_twin);
+            halfedges[right_half.0 as usize].twin = Some(left_twin);
+            halfedges[left_twin.0 as usize].twin = Some(right_half);
+        }
+
+        // Where the line meets the edge of the clip region, the half-edges along the
+        // region on either side are joined unless the region has a corner there. The
+        // copy of the vertex on the line from the other strip is no longer used.
+        let ends = [meetings[0].0, meetings[meetings.len() - 1].1];
+        for &(before, after) in &ends {
+            if halfedges[before.0 as usize].twin.is_some() || halfedges[after.0 as usize].twin.is_some() {
+                return Err(line);
+            }
+
+            let corner = match halfedges[after.0 as usize].origin {
+                Some(corner) => vertices[corner.0 as usize].coordinates,
+                None => return Err(line),
+            };
+            let is_corner = sites.boundary.iter().any(|boundary_corner| {
+                boundary_corner.x == x && (boundary_corner.y - corner.y).abs() <= sites.tolerance
+            });
+            if !is_corner {
+                join(&mut halfedges, &mut removed, before, after);
+            }
+        }
+    }
+
+    Ok(compact(vertices, halfedges, &removed, kept))
+}
+
+fn link(halfedges: &mut [HalfEdge], halfedge: HalfEdgeId, next: HalfEdgeId) {
+    halfedges[halfedge.0 as usize].next = next;
+    halfedges[next.0 as usize].prev = halfedge;
+}
+
+/// Extends a half-edge over the one after it, which is removed
+fn join(halfedges: &mut [HalfEdge], removed: &mut [bool], halfedge: HalfEdgeId, next: HalfEdgeId) {
+    let after = halfedges[next.0 as usize].next;
+    link(halfedges, halfedge, after);
+    removed[next.0 as usize] = true;
+}
+
+/// Builds the diagram from the half-edges that weren't removed, dropping vertices
+/// that are no longer used and renumbering what's left
+fn compact<T: BaseFloat>(vertices: Vec<Vertex<f64>>, halfedges: Vec<HalfEdge>, removed: &[bool], sites: &[Site<T>]) -> Diagram<T> {
+    let mut halfedge_ids = vec![None; halfedges.len()];
+    let mut vertex_ids = vec![None; vertices.len()];
+    let mut diagram = Diagram::default();
+    for (index, halfedge) in halfedges.iter().enumerate() {
+        if removed[index] {
+            continue;
+        }
+        halfedge_ids[index] = Some(HalfEdgeId(diagram.halfedges.len() as u32));
+        diagram.halfedges.push(halfedge.clone());
+
+        let origin = match halfedge.origin {
+            Some(origin) => origin.0 as usize,
+            None => continue,
+        };
+        if vertex_ids[origin].is_none() {
+            vertex_ids[origin] = Some(VertexId(diagram.vertices.len() as u32));
+            diagram.vertices.push(Vertex {
+                coordinates: vertices[origin].coordinates.cast(),
+                incident_edge: HalfEdgeId(index as u32),
+            });
+        }
+    }
+
+    let halfedge_id = |halfedge: HalfEdgeId| halfedge_ids[halfedge.0 as usize].unwrap();
+    for halfedge in &mut diagram.halfedges {
+        halfedge.origin = halfedge.origin.and_then(|origin| vertex_ids[origin.0 as usize]);
+        halfedge.twin = halfedge.twin.map(halfedge_id);
+        halfedge.next = halfedge_id(halfedge.next);
+        halfedge.prev = halfedge_id(halfedge.prev);
+    }
+    for vertex in &mut diagram.vertices {
+        vertex.incident_edge = halfedge_id(vertex.incident_edge);
+    }
+
+    diagram.faces = sites.iter().map(|&site| Face {
+        first_halfedge: None,
+        site,
+    }).collect();
+    for (index, halfedge) in diagram.halfedges.iter().enumerate() {
+        let face = &mut diagram.faces[halfedge.incident_face.0 as usize];
+        if face.first_halfedge.is_none() {
+            face.first_halfedge = Some(HalfEdgeId(index as u32));
+        }
+    }
+
+    diagram.index_sites(false);
+    diagram
+}
@@ -0,0 +1,209 @@
+//! Shortest paths between cells, going from each cell to the next across the
+//! edge they share
+//!
+//! This is A* over the graph of faces that share an edge, with the costs of
+//! entering faces and crossing edges given by a `PathCost`. Its estimate of the
+//! cost left to the goal is zero unless it says otherwise, which makes it
+//! Dijkstra's algorithm.
+
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+
+use cgmath::{Point2, InnerSpace, BaseFloat};
+use cgmath::num_traits::cast;
+#[cfg(feature = "serde")]
+use serde::{Serialize, Deserialize};
+
+use build::compare;
+use diagram::{Diagram, FaceId, HalfEdgeId};
+use range::halfedge_piece;
+
+/// Costs of moving between the cells of a diagram, see `Diagram::find_path`
+///
+/// Costs that are None, negative or NaN mean the face can't be entered or the
+/// edge can't be crossed.
+pub trait PathCost<T: BaseFloat> {
+    /// Cost of crossing from the face of a half-edge into the face of its twin
+    fn edge_cost(&self, diagram: &Diagram<T>, halfedge_id: HalfEdgeId) -> Option<T>;
+
+    /// Cost of going into a face, which isn't paid for the face the path starts in
+    fn face_cost(&self, _diagram: &Diagram<T>, _face_id: FaceId) -> Option<T> {
+        Some(T::zero())
+    }
+
+    /// Lower bound on the cost of getting from a face to the goal
+    ///
+    /// The search finds the cheapest path as long as this is never more than the
+    /// real cost, and never drops by more than the cost of the step between two
+    /// neighbouring faces. Zero, the default, always works but looks at more faces.
+    fn estimate(&self, _diagram: &Diagram<T>, _face_id: FaceId, _goal: FaceId) -> T {
+        T::zero()
+    }
+}
+
+/// Costs a step by the distance between the sites on either side of the edge,
+/// with the straight line between sites as the estimate
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SiteDistance;
+
+impl<T: BaseFloat> PathCost<T> for SiteDistance {
+    fn edge_cost(&self, diagram: &Diagram<T>, halfedge_id: HalfEdgeId) -> Option<T> {
+        let halfedge = diagram.get_half_edge(halfedge_id)?;
+        let twin = diagram.get_half_edge(halfedge.twin?)?;
+        Some(site_distance(diagram, halfedge.incident_face, twin.incident_face))
+    }
+
+    fn estimate(&self, diagram: &Diagram<T>, face_id: FaceId, goal: FaceId) -> T {
+        site_distance(diagram, face_id, goal)
+    }
+}
+
+/// Costs each step with a function of the half-edge crossed
+impl<T: BaseFloat, F: Fn(&Diagram<T>, HalfEdgeId) -> Option<T>> PathCost<T> for F {
+    fn edge_cost(&self, diagram: &Diagram<T>, halfedge_id: HalfEdgeId) -> Option<T> {
+        self(diagram, halfedge_id)
+    }
+}
+
+fn site_distance<T: BaseFloat>(diagram: &Diagram<T>, a: FaceId, b: FaceId) -> T {
+    (diagram.faces[a.0 as usize].site.position - diagram.faces[b.0 as usize].site.position).magnitude()
+}
+
+/// A path from one face to another, see `Diagram::find_path`
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Path<T = f32> {
+    /// The faces along the path, starting with the first and ending with the goal
+    pub faces: Vec<FaceId>,
+
+    /// The half-edge crossed to leave each face but the last, on the side of the
+    /// face being left
+    pub halfedges: Vec<HalfEdgeId>,
+
+    /// Where the path crosses each of those edges, as the point on the edge
+    /// nearest to halfway between the sites either side of it
+    pub crossings: Vec<Point2<T>>,
+
+    /// Total cost of the path
+    pub cost: T,
+}
+
+/// A face waiting to be looked at, with the cost of getting there
+#[derive(Debug, Clone, Copy)]
+struct Entry<T> {
+    priority: T,
+    cost: T,
+    face_id: FaceId,
+}
+
+impl<T: BaseFloat> PartialEq for Entry<T> {
+    fn eq(&self, other: &Entry<T>) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl<T: BaseFloat> Eq for Entry<T> {}
+
+impl<T: BaseFloat> PartialOrd for Entry<T> {
+    fn partial_cmp(&self, other: &Entry<T>) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T: BaseFloat> Ord for Entry<T> {
+    fn cmp(&self, other: &Entry<T>) -> Ordering {
+        // BinaryHeap pops the greatest first, so the lowest priority has to compare
+        // as the greatest
+        compare(other.priority, self.priority).then(other.face_id.0.cmp(&self.face_id.0))
+    }
+}
+
+/// The cost, if it's one that can be paid
+fn usable<T: BaseFloat>(cost: Option<T>) -> Option<T> {
+    cost.filter(|&cost| cost >= T::zero())
+}
+
+/// See `Diagram::find_path`
+pub fn find_path<T: BaseFloat, C: PathCost<T> + ?Sized>(diagram: &Diagram<T>, from: FaceId, to: FaceId, costs: &C) -> Option<Path<T>> {
+    let count = diagram.faces.len();
+    if from.0 as usize >= count || to.0 as usize >= count {
+        return None;
+    }
+
+    let mut best = vec![T::infinity(); count];
+    let mut reached_by: Vec<Option<HalfEdgeId>> = vec![None; count];
+    let mut done = vec![false; count];
+    let mut queue = BinaryHeap::new();
+    best[from.0 as usize] = T::zero();
+    queue.push(Entry { priority: costs.estimate(diagram, from, to), cost: T::zero(), face_id: from });
+
+    while let Some(Entry { cost, face_id, .. }) = queue.pop() {
+        if done[face_id.0 as usize] {
+            continue;
+        }
+        done[face_id.0 as usize] = true;
+        if face_id == to {
+            return Some(trace(diagram, from, to, cost, &reached_by));
+        }
+
+        for halfedge_id in diagram.face_edges(face_id) {
+            let next = match diagram.halfedges[halfedge_id.0 as usize].twin {
+                Some(twin) => diagram.halfedges[twin.0 as usize].incident_face,
+                None => continue,
+            };
+            if done[next.0 as usize] {
+                continue;
+            }
+
+            let step = match (usable(costs.edge_cost(diagram, halfedge_id)), usable(costs.face_cost(diagram, next))) {
+                (Some(edge), Some(face)) => edge + face,
+                _ => continue,
+            };
+            let next_cost = cost + step;
+            if next_cost < best[next.0 as usize] {
+                best[next.0 as usize] = next_cost;
+                reached_by[next.0 as usize] = Some(halfedge_id);
+                queue.push(Entry { priority: next_cost + costs.estimate(diagram, next, to), cost: next_cost, face_id: next });
+            }
+        }
+    }
+    None
+}
+
+/// Follows the half-edges each face was reached by back from the goal
+fn trace<T: BaseFloat>(diagram: &Diagram<T>, from: FaceId, to: FaceId, cost: T, reached_by: &[Option<HalfEdgeId>]) -> Path<T> {
+    let mut faces = vec![to];
+    let mut halfedges = Vec::new();
+    let mut face_id = to;
+    while face_id != from {
+        let halfedge_id = match reached_by[face_id.0 as usize] {
+            Some(halfedge_id) => halfedge_id,
+            None => break,
+        };
+        face_id = diagram.halfedges[halfedge_id.0 as usize].incident_face;
+        faces.push(face_id);
+        halfedges.push(halfedge_id);
+    }
+    faces.reverse();
+    halfedges.reverse();
+
+    let crossings = halfedges.iter().map(|&halfedge_id| crossing(diagram, halfedge_id)).collect();
+    Path { faces, halfedges, crossings, cost }
+}
+
+/// The point on an edge nearest to halfway between the sites either side of it
+fn crossing<T: BaseFloat>(diagram: &Diagram<T>, halfedge_id: HalfEdgeId) -> Point2<T> {
+    let halfedge = &diagram.halfedges[halfedge_id.0 as usize];
+    let site = diagram.faces[halfedge.incident_face.0 as usize].site.position;
+    let other = halfedge.twin.map_or(site, |twin| diagram.faces[diagram.halfedges[twin.0 as usize].incident_face.0 as usize].site.position);
+    let half: T = cast(0.5).unwrap();
+    let midpoint = site + (other - site) * half;
+
+    match halfedge_piece(diagram, halfedge_id) {
+        Some(piece) if piece.direction.magnitude2() > T::zero() => {
+            let along = (midpoint - piece.start).dot(piece.direction) / piece.direction.magnitude2();
+            piece.start + piece.direction * along.max(piece.from).min(piece.to)
+        }
+        _ => midpoint,
+    }
+}
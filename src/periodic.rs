@@ -0,0 +1,448 @@
+//! Periodic diagrams, where the bounding rect wraps around like a torus
+//!
+//! A point near the right edge of the rect is also near the left edge, so a site
+//! there can own cells on both sides. The diagram is built from the sites and
+//! copies of them shifted by the width and height of the rect, then clipped to the
+//! rect. A cell that crosses an edge of the rect ends up split into a piece for
+//! each copy of its site, and the pieces are stitched together along the seams.
+//!
+//! Only copies within a margin of the rect are used. As in `ParallelDiagramBuilder`
+//! the margin is widened until the circle around each corner of a piece through
+//! its site stays inside it, and at worst all eight copies of every site are used.
+
+use std::cmp::Ordering;
+
+use cgmath::{Point2, Vector2, InnerSpace, BaseFloat};
+use fnv::FnvHashMap;
+
+use build::{DiagramBuilder, Rect, Site};
+use diagram::{Diagram, Vertex, VertexId, HalfEdge, HalfEdgeId, Face, FaceId};
+
+/// Builds the diagram of sites in a rect whose opposite edges are joined
+///
+/// Sites outside the rect are wrapped into it, and sites at the same position
+/// after wrapping as an earlier one are left out.
+pub struct PeriodicDiagramBuilder<T = f32> {
+    rect: Rect<T>,
+    sites: Vec<Site<T>>,
+}
+
+/// A diagram built by `PeriodicDiagramBuilder`
+///
+/// The first faces are the cells of the sites in the order they were given, each
+/// the piece of the cell around its site. They are followed by the pieces of cells
+/// that wrapped around an edge of the rect, whose sites have the id of the original
+/// site but are at the position of the copy the piece belongs to.
+#[derive(Debug, Clone)]
+pub struct PeriodicDiagram<T = f32> {
+    diagram: Diagram<T>,
+    rect: Rect<T>,
+
+    /// Index of the first face of the site of each face
+    original: Vec<u32>,
+
+    /// The faces of each site, indexed by its first face
+    copies: Vec<Vec<FaceId>>,
+
+    /// The half-edge on the opposite edge of the rect each boundary half-edge is
+    /// joined to
+    seams: Vec<Option<HalfEdgeId>>,
+}
+
+impl<T: BaseFloat> PeriodicDiagramBuilder<T> {
+    pub fn new(rect: Rect<T>, sites: Vec<Site<T>>) -> PeriodicDiagramBuilder<T> {
+        PeriodicDiagramBuilder {
+            rect,
+            sites,
+        }
+    }
+
+    pub fn finish(self) -> PeriodicDiagram<T> {
+        let rect: Rect<f64> = Rect {
+            position: self.rect.position.cast(),
+            size: self.rect.size.cast(),
+        };
+        if !(rect.size.x > 0.0 && rect.size.y > 0.0) {
+            return PeriodicDiagram::empty(self.rect);
+        }
+
+        let mut positions = FnvHashMap::default();
+        let mut sites = self.sites.iter().filter_map(|site| {
+            let position = wrap(&rect, site.position.cast());
+            let key = (position.x.to_bits(), position.y.to_bits());
+            if position.x.is_nan() || position.y.is_nan() || positions.insert(key, ()).is_some() {
+                return None;
+            }
+            Some(Site::with_weight(site.id, position.cast(), site.weight))
+        }).collect::<Vec<_>>();
+
+        loop {
+            let (pieces, built) = build_pieces(&rect, &sites);
+
+            // Sites that are almost at the same position can be merged after scaling,
+            // so those that didn't get a face are left out and the rest built again
+            if built.len() == sites.len() {
+                return stitch(self.rect, &rect, &sites, &drop_flat_pieces(pieces, &rect));
+            }
+            let mut has_face = vec![false; sites.len()];
+            for index in built {
+                has_face[index as usize] = true;
+            }
+            let mut index = 0;
+            sites.retain(|_| {
+                index += 1;
+                has_face[index - 1]
+            });
+        }
+    }
+}
+
+/// Moves a point into a rect by whole multiples of its size
+fn wrap(rect: &Rect<f64>, point: Point2<f64>) -> Point2<f64> {
+    let wrap_axis = |value: f64, min: f64, size: f64| {
+        let wrapped = min + (value - min).rem_euclid(size);
+        if wrapped < min + size { wrapped } else { min }
+    };
+
+    Point2::new(
+        wrap_axis(point.x, rect.position.x, rect.size.x),
+        wrap_axis(point.y, rect.position.y, rect.size.y),
+    )
+}
+
+/// The eight shifts that move a site into the copies of the rect around it
+fn shifts(rect: &Rect<f64>) -> Vec<Vector2<f64>> {
+    let mut shifts = Vec::new();
+    for &dx in &[-rect.size.x, 0.0, rect.size.x] {
+        for &dy in &[-rect.size.y, 0.0, rect.size.y] {
+            if dx != 0.0 || dy != 0.0 {
+                shifts.push(Vector2::new(dx, dy));
+            }
+        }
+    }
+    shifts
+}
+
+/// Builds the diagram of the sites and enough of their copies, clipped to the rect
+///
+/// The sites of the faces have the index of their site in `sites` as their id, and
+/// the position of the copy. Also returns the indices of the sites that got a face
+/// of their own.
+fn build_pieces<T: BaseFloat>(rect: &Rect<f64>, sites: &[Site<T>]) -> (Diagram<f64>, Vec<u32>) {
+    let size = rect.size;
+    let min = rect.position;
+    let max = min + size;
+    let spacing = (size.x * size.y / sites.len().max(1) as f64).sqrt();
+    let extent = size.x.max(size.y);
+    let tolerance = extent * f64::EPSILON * 64.0;
+    let shifts = shifts(rect);
+    let boundary = [min, Point2::new(max.x, min.y), max, Point2::new(min.x, max.y)];
+
+    let mut margin = (spacing * 2.0).min(extent);
+    loop {
+        let everything = margin >= extent;
+        let within_margin = |point: Point2<f64>| {
+            everything || point.x > min.x - margin && point.x < max.x + margin &&
+                point.y > min.y - margin && point.y < max.y + margin
+        };
+
+        // The sites come first, followed by the copies of them that are needed, each
+        // paired with the index of the site it was made from
+        let mut copies = sites.iter().enumerate()
+            .map(|(index, site)| (index, site.position.cast::<f64>()))
+            .collect::<Vec<_>>();
+        for (index, site) in sites.iter().enumerate() {
+            let position = site.position.cast::<f64>();
+            copies.extend(shifts.iter()
+                .map(|&shift| position + shift)
+                .filter(|&copy| within_margin(copy))
+                .map(|copy| (index, copy)));
+        }
+        let copy_sites = copies.iter().enumerate()
+            .map(|(id, &(_, position))| Site::new(id as u32, position))
+            .collect::<Vec<_>>();
+
+        // The rect is a little bigger than the margin, so that the copies on its
+        // edge are inside it and the edges of the rect are away from its edges
+        let padding = spacing.min(extent);
+        let halo = Rect {
+            position: Point2::new(min.x - margin - padding, min.y - margin - padding),
+            size: Vector2::new(size.x + (margin + padding) * 2.0, size.y + (margin + padding) * 2.0),
+        };
+        let diagram = DiagramBuilder::new(halo, copy_sites).finish();
+        let built = diagram.faces.iter()
+            .map(|face| face.site.id)
+            .filter(|&id| (id as usize) < sites.len())
+            .collect::<Vec<_>>();
+        if built.len() != sites.len() {
+            return (Diagram::default(), built);
+        }
+
+        let mut diagram = diagram.clip_to_polygon(&boundary);
+        for face in &mut diagram.faces {
+            face.site.id = copies[face.site.id as usize].0 as u32;
+        }
+        if everything {
+            return (diagram, built);
+        }
+
+        let reach = diagram.faces.iter().enumerate().all(|(index, face)| {
+            let site = face.site.position;
+            diagram.face_vertices(FaceId(index as u32)).all(|corner| {
+                let radius = (corner - site).magnitude() + tolerance;
+                within_margin(Point2::new(corner.x - radius, corner.y - radius)) &&
+                    within_margin(Point2::new(corner.x + radius, corner.y + radius))
+            })
+        });
+        if reach {
+            return (diagram, built);
+        }
+        margin = (margin * 2.0).min(extent);
+    }
+}
+
+/// Removes the pieces of cells that only touch the rect along its edge
+///
+/// Clipping leaves these behind as faces with no area when an edge between two
+/// cells lies along an edge of the rect. The half-edges next to them become edges
+/// of the rect, with no twin.
+fn drop_flat_pieces(diagram: Diagram<f64>, rect: &Rect<f64>) -> Diagram<f64> {
+    let min = rect.position;
+    let max = min + rect.size;
+    let tolerance = rect.size.x.max(rect.size.y) * f64::EPSILON * 64.0;
+    let on_edge = |point: Point2<f64>| {
+        (point.x - min.x).abs() <= tolerance || (point.x - max.x).abs() <= tolerance ||
+            (point.y - min.y).abs() <= tolerance || (point.y - max.y).abs() <= tolerance
+    };
+    let flat = (0..diagram.faces.len()).map(|index| {
+        let face_id = FaceId(index as u32);
+        diagram.face_vertices(face_id).all(on_edge) && diagram.face_area(face_id).abs() <= tolerance * rect.size.x.max(rect.size.y)
+    }).collect::<Vec<_>>();
+    if !flat.contains(&true) {
+        return diagram;
+    }
+
+    let kept = diagram.halfedges.iter()
+        .map(|halfedge| !flat[halfedge.incident_face.0 as usize])
+        .collect::<Vec<_>>();
+    let mut halfedge_ids = vec![None; diagram.halfedges.len()];
+    let mut vertex_ids = vec![None; diagram.vertices.len()];
+    let mut output = Diagram::default();
+    for (index, halfedge) in diagram.halfedges.iter().enumerate() {
+        if !kept[index] {
+            continue;
+        }
+        let halfedge_id = HalfEdgeId(output.halfedges.len() as u32);
+        halfedge_ids[index] = Some(halfedge_id);
+        output.halfedges.push(halfedge.clone());
+
+        let origin = match halfedge.origin {
+            Some(origin) => origin.0 as usize,
+            None => continue,
+        };
+        if vertex_ids[origin].is_none() {
+            vertex_ids[origin] = Some(VertexId(output.vertices.len() as u32));
+            output.vertices.push(Vertex {
+                coordinates: diagram.vertices[origin].coordinates,
+                incident_edge: halfedge_id,
+            });
+        }
+    }
+
+    for halfedge in &mut output.halfedges {
+        halfedge.origin = halfedge.origin.and_then(|origin| vertex_ids[origin.0 as usize]);
+        halfedge.twin = halfedge.twin.and_then(|twin| halfedge_ids[twin.0 as usize]);
+        halfedge.next = halfedge_ids[halfedge.next.0 as usize].unwrap();
+        halfedge.prev = halfedge_ids[halfedge.prev.0 as usize].unwrap();
+    }
+    output.faces = diagram.faces.iter().zip(&flat).map(|(face, &flat)| Face {
+        first_halfedge: if flat { None } else { face.first_halfedge.and_then(|halfedge| halfedge_ids[halfedge.0 as usize]) },
+        site: face.site,
+    }).collect();
+    output
+}
+
+/// Puts the faces of each site first, followed by the wrapped pieces, and joins the
+/// half-edges along opposite edges of the rect
+fn stitch<T: BaseFloat>(output_rect: Rect<T>, rect: &Rect<f64>, sites: &[Site<T>], pieces: &Diagram<f64>) -> PeriodicDiagram<T> {
+    // The piece with the site itself in it is the one whose site wasn't moved
+    let mut first_faces = vec![None; sites.len()];
+    let mut wrapped = Vec::new();
+    for (index, face) in pieces.faces.iter().enumerate() {
+        if face.first_halfedge.is_none() {
+            continue;
+        }
+        let site = face.site.id as usize;
+        if face.site.position == sites[site].position.cast() && first_faces[site].is_none() {
+            first_faces[site] = Some(index);
+        } else {
+            wrapped.push(index);
+        }
+    }
+    wrapped.sort_by_key(|&index| pieces.faces[index].site.id);
+
+    let mut face_ids = vec![None; pieces.faces.len()];
+    let mut order = Vec::new();
+    for index in first_faces.iter().filter_map(|&index| index).chain(wrapped) {
+        face_ids[index] = Some(FaceId(order.len() as u32));
+        order.push(index);
+    }
+
+    let mut diagram = Diagram {
+        vertices: pieces.vertices.iter().map(|vertex| Vertex {
+            coordinates: vertex.coordinates.cast(),
+            incident_edge: vertex.incident_edge,
+        }).collect(),
+        halfedges: pieces.halfedges.iter().map(|halfedge| HalfEdge {
+            incident_face: face_ids[halfedge.incident_face.0 as usize].unwrap(),
+            ..halfedge.clone()
+        }).collect(),
+        faces: order.iter().map(|&index| {
+            let face = &pieces.faces[index];
+            let site = &sites[face.site.id as usize];
+            Face {
+                first_halfedge: face.first_halfedge,
+                site: Site::with_weight(site.id, face.site.position.cast(), site.weight),
+            }
+        }).collect(),
+        ..Diagram::default()
+    };
+    diagram.index_sites(false);
+
+    let mut original = Vec::with_capacity(order.len());
+    let mut copies = vec![Vec::new(); order.len()];
+    for (index, &piece) in order.iter().enumerate() {
+        let site = pieces.faces[piece].site.id as usize;
+        let first = face_ids[first_faces[site].unwrap()].unwrap();
+        original.push(first.0);
+        copies[first.0 as usize].push(FaceId(index as u32));
+    }
+
+    // Each boundary half-edge on the right or top edge is joined to the one on the
+    // left or bottom edge over the same stretch of it
+    let mut seams = vec![None; pieces.halfedges.len()];
+    let min = rect.position;
+    let max = min + rect.size;
+    let tolerance = rect.size.x.max(rect.size.y) * f64::EPSILON * 64.0;
+    for &(axis, near, far) in &[(0, max.x, min.x), (1, max.y, min.y)] {
+        let far_halfedges = boundary_halfedges(pieces, axis, far, tolerance);
+        for (halfedge, low, high) in boundary_halfedges(pieces, axis, near, tolerance) {
+            let matching = far_halfedges.iter()
+                .filter(|&&(other, _, _)| seams[other.0 as usize].is_none())
+                .map(|&(other, other_low, other_high)| (other, high.min(other_high) - low.max(other_low)))
+                .filter(|&(_, overlap)| overlap > 0.0)
+                .max_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(Ordering::Equal));
+            if let Some((other, _)) = matching {
+                seams[halfedge.0 as usize] = Some(other);
+                seams[other.0 as usize] = Some(halfedge);
+            }
+        }
+    }
+
+    PeriodicDiagram {
+        diagram,
+        rect: output_rect,
+        original,
+        copies,
+        seams,
+    }
+}
+
+/// Finds the twin-less half-edges along one edge of the rect, where coordinate
+/// `axis` of the points is `position`, with the range of the other coordinate
+/// they cover
+fn boundary_halfedges(diagram: &Diagram<f64>, axis: usize, position: f64, tolerance: f64) -> Vec<(HalfEdgeId, f64, f64)> {
+    let vertex = |halfedge: &HalfEdge| halfedge.origin.map(|origin| diagram.vertices[origin.0 as usize].coordinates);
+
+    diagram.halfedges.iter().enumerate().filter_map(|(index, halfedge)| {
+        if halfedge.twin.is_some() {
+            return None;
+        }
+        let start = vertex(halfedge)?;
+        let end = vertex(&diagram.halfedges[halfedge.next.0 as usize])?;
+        let on_edge = (start[axis] - position).abs() <= tolerance && (end[axis] - position).abs() <= tolerance;
+        let (low, high) = (start[1 - axis].min(end[1 - axis]), start[1 - axis].max(end[1 - axis]));
+
+        if on_edge && low < high {
+            Some((HalfEdgeId(index as u32), low, high))
+        } else {
+            None
+        }
+    }).collect()
+}
+
+impl<T: BaseFloat> PeriodicDiagram<T> {
+    fn empty(rect: Rect<T>) -> PeriodicDiagram<T> {
+        PeriodicDiagram {
+            diagram: Diagram::default(),
+            rect,
+            original: Vec::new(),
+            copies: Vec::new(),
+            seams: Vec::new(),
+        }
+    }
+
+    /// The faces, clipped to the rect
+    pub fn diagram(&self) -> &Diagram<T> {
+        &self.diagram
+    }
+
+    pub fn into_diagram(self) -> Diagram<T> {
+        self.diagram
+    }
+
+    pub fn rect(&self) -> Rect<T> {
+        self.rect
+    }
+
+    /// Finds every face that is part of the same cell as a face, starting with the
+    /// one around its site
+    ///
+    /// This is a single face for cells that don't cross an edge of the rect.
+    pub fn copies(&self, face_id: FaceId) -> &[FaceId] {
+        match self.original.get(face_id.0 as usize) {
+            Some(&original) => &self.copies[original as usize],
+            None => &[],
+        }
+    }
+
+    /// The face around the site of a face's cell
+    pub fn original(&self, face_id: FaceId) -> Option<FaceId> {
+        self.original.get(face_id.0 as usize).map(|&original| FaceId(original))
+    }
+
+    /// How far the site of a face was moved to make the copy the face belongs to
+    ///
+    /// Adding this to the corners of the face around the site gives where that cell
+    /// continues into the face. It is zero for the face around the site.
+    pub fn offset(&self, face_id: FaceId) -> Option<Vector2<T>> {
+        let original = self.original(face_id)?;
+        let site = self.diagram.faces[face_id.0 as usize].site.position;
+        let offset = site - self.diagram.faces[original.0 as usize].site.position;
+
+        // Snap to whole multiples of the rect, as the difference can be rounded
+        let round = |value: T, size: T| (value / size).round() * size;
+        Some(Vector2::new(round(offset.x, self.rect.size.x), round(offset.y, self.rect.size.y)))
+    }
+
+    /// Finds the half-edge a half-edge along an edge of the rect is joined to on the
+    /// opposite edge
+    ///
+    /// These usually belong to pieces of the same cell, which continues across the
+    /// seam, but when an edge between two cells lies along the seam they belong to
+    /// those cells. Returns None for half-edges that aren't on the edge of the rect,
+    /// which have a twin instead.
+    pub fn seam_twin(&self, halfedge_id: HalfEdgeId) -> Option<HalfEdgeId> {
+        self.seams.get(halfedge_id.0 as usize).cloned().and_then(|seam| seam)
+    }
+
+    /// Finds the face that contains a point, after wrapping it into the rect
+    pub fn locate(&self, point: Point2<T>) -> Option<FaceId> {
+        let rect = Rect {
+            position: self.rect.position.cast(),
+            size: self.rect.size.cast(),
+        };
+        let point = wrap(&rect, point.cast());
+        self.diagram.locate(point.cast())
+    }
+}
@@ -0,0 +1,509 @@
+//! Power diagrams, where each site has a weight
+//!
+//! The power distance from a point to a site is the squared distance between them
+//! minus the site's weight, and each cell holds the points whose power distance to
+//! its site is smallest. Cell boundaries are still straight, but they move towards
+//! the site with the smaller weight. Sites with a small weight next to ones with
+//! large weights can end up with empty cells, and a site doesn't have to lie in
+//! its own cell.
+//!
+//! Fortune's algorithm doesn't work with power distances, so these are built from
+//! their dual instead, the regular triangulation. That is built by inserting one
+//! site at a time into a large triangle that contains them all.
+
+use std::f64::consts::{PI, FRAC_PI_2};
+use std::mem;
+
+use cgmath::{Point2, Vector2, EuclideanSpace, BaseFloat};
+use cgmath::num_traits::cast;
+use fnv::FnvHashMap;
+
+use build::{ClipRegion, Metric, Site};
+use clip::{clip_to_boundary, strictly_inside_boundary};
+use diagram::{Diagram, Vertex, VertexId, HalfEdge, HalfEdgeId, Face, FaceId};
+use kdtree::KdTree;
+use predicates::{orient2d, power_test};
+
+/// Number of corners of the enclosing triangle, which come before the sites in the
+/// list of points
+const SUPER_POINTS: usize = 3;
+
+#[derive(Debug, Clone)]
+struct Triangle {
+    /// Corners in counter-clockwise order, as indices into the list of points
+    points: [usize; 3],
+
+    /// The triangle across the edge opposite each corner
+    neighbours: [Option<usize>; 3],
+}
+
+/// Regular triangulation of weighted points, worked out in f64
+#[derive(Debug)]
+struct RegularTriangulation {
+    points: Vec<Point2<f64>>,
+    weights: Vec<f64>,
+    triangles: Vec<Option<Triangle>>,
+
+    /// Slots in `triangles` that have been freed and can be reused
+    free: Vec<usize>,
+
+    /// The triangle most recently added, where point location starts from
+    last: usize,
+
+    /// Marks triangles visited by the current insertion
+    visited: Vec<u32>,
+    insertion: u32,
+}
+
+impl RegularTriangulation {
+    /// Creates a triangulation made of a single triangle
+    ///
+    /// The corners must be in counter-clockwise order and every point inserted later
+    /// must lie strictly inside it.
+    fn new(corners: [Point2<f64>; 3]) -> RegularTriangulation {
+        RegularTriangulation {
+            points: corners.to_vec(),
+            weights: vec![0.0; SUPER_POINTS],
+            triangles: vec![Some(Triangle {
+                points: [0, 1, 2],
+                neighbours: [None; 3],
+            })],
+            free: Vec::new(),
+            last: 0,
+            visited: vec![0],
+            insertion: 0,
+        }
+    }
+
+    fn triangle(&self, triangle: usize) -> &Triangle {
+        self.triangles[triangle].as_ref().unwrap()
+    }
+
+    fn add_triangle(&mut self, triangle: Triangle) -> usize {
+        match self.free.pop() {
+            Some(index) => {
+                self.triangles[index] = Some(triangle);
+                index
+            }
+            None => {
+                self.triangles.push(Some(triangle));
+                self.visited.push(0);
+                self.triangles.len() - 1
+            }
+        }
+    }
+
+    /// Positive if the point would replace the triangle, and zero if the triangle's
+    /// corners and the point all have the same power distance from some point
+    fn power_test(&self, triangle: usize, point: usize) -> f64 {
+        let [a, b, c] = self.triangle(triangle).points;
+        let weights = [self.weights[a], self.weights[b], self.weights[c], self.weights[point]];
+        power_test(self.points[a], self.points[b], self.points[c], self.points[point], weights)
+    }
+
+    /// Finds the triangle that contains a point by walking towards it
+    ///
+    /// The walk always terminates in a regular triangulation, as the triangles can't
+    /// be in front of each other in a cycle.
+    fn locate(&self, point: Point2<f64>) -> usize {
+        let mut current = self.last;
+        let mut step = 0;
+
+        'walk: loop {
+            let triangle = self.triangle(current);
+
+            // Vary the first edge checked so the walk doesn't always veer the same way
+            step += 1;
+            for offset in 0..3 {
+                let corner = (step + offset) % 3;
+                let a = self.points[triangle.points[(corner + 1) % 3]];
+                let b = self.points[triangle.points[(corner + 2) % 3]];
+
+                if orient2d(a, b, point) < 0.0 {
+                    if let Some(neighbour) = triangle.neighbours[corner] {
+                        current = neighbour;
+                        continue 'walk;
+                    }
+                }
+            }
+
+            return current;
+        }
+    }
+
+    /// Inserts a weighted point, returning false if it is hidden by the others
+    ///
+    /// The triangles that the point conflicts with form a region around it, which is
+    /// replaced with a fan of triangles from its boundary to the new point. Any
+    /// points inside the region are hidden by the new one and drop out.
+    ///
+    /// Ties are part of the region too. Otherwise a point whose cell is squeezed down
+    /// to a line by the new one would stay, and rounding could turn its cell inside
+    /// out when the diagram is clipped.
+    fn insert(&mut self, position: Point2<f64>, weight: f64) -> bool {
+        let point = self.points.len();
+        self.points.push(position);
+        self.weights.push(weight);
+
+        let start = self.locate(position);
+        if self.power_test(start, point) <= 0.0 {
+            return false;
+        }
+
+        self.insertion += 1;
+        self.visited[start] = self.insertion;
+
+        // Find the conflicting triangles and the edges around them, each given as the
+        // two points in counter-clockwise order and the triangle beyond it
+        let mut stack = vec![start];
+        let mut removed = Vec::new();
+        let mut boundary = Vec::new();
+        while let Some(current) = stack.pop() {
+            removed.push(current);
+
+            let triangle = self.triangle(current).clone();
+            for corner in 0..3 {
+                let a = triangle.points[(corner + 1) % 3];
+                let b = triangle.points[(corner + 2) % 3];
+
+                match triangle.neighbours[corner] {
+                    Some(neighbour) if self.visited[neighbour] == self.insertion => {}
+                    Some(neighbour) if self.power_test(neighbour, point) >= 0.0 => {
+                        self.visited[neighbour] = self.insertion;
+                        stack.push(neighbour);
+                    }
+                    neighbour => boundary.push((a, b, neighbour)),
+                }
+            }
+        }
+
+        for &triangle in &removed {
+            self.triangles[triangle] = None;
+            self.free.push(triangle);
+        }
+
+        // Connect each boundary edge to the new point. The new triangles are linked
+        // to each other through the point they start from
+        let mut starting_at = FnvHashMap::default();
+        let mut added = Vec::with_capacity(boundary.len());
+        for &(a, b, outside) in &boundary {
+            let triangle = self.add_triangle(Triangle {
+                points: [a, b, point],
+                neighbours: [None, None, outside],
+            });
+
+            if let Some(outside) = outside {
+                let outside = self.triangles[outside].as_mut().unwrap();
+                let corner = (0..3).find(|&corner| outside.points[corner] != a && outside.points[corner] != b).unwrap();
+                outside.neighbours[corner] = Some(triangle);
+            }
+
+            starting_at.insert(a, triangle);
+            added.push(triangle);
+        }
+
+        for &triangle in &added {
+            let b = self.triangle(triangle).points[1];
+            let next = starting_at[&b];
+            self.triangles[triangle].as_mut().unwrap().neighbours[0] = Some(next);
+            self.triangles[next].as_mut().unwrap().neighbours[1] = Some(triangle);
+        }
+
+        self.last = added[0];
+        true
+    }
+
+    /// Centre of the circle orthogonal to the weighted corners of a triangle, which is
+    /// where their cells in the power diagram meet
+    fn orthocentre(&self, triangle: usize) -> Point2<f64> {
+        let [a, b, c] = self.triangle(triangle).points;
+        let (origin, weight) = (self.points[a], self.weights[a]);
+        let b_offset = self.points[b] - origin;
+        let c_offset = self.points[c] - origin;
+        let b_lift = b_offset.x * b_offset.x + b_offset.y * b_offset.y - (self.weights[b] - weight);
+        let c_lift = c_offset.x * c_offset.x + c_offset.y * c_offset.y - (self.weights[c] - weight);
+
+        let d = 2.0 * orient2d(origin, self.points[b], self.points[c]);
+        let x = (c_offset.y * b_lift - b_offset.y * c_lift) / d;
+        let y = (b_offset.x * c_lift - c_offset.x * b_lift) / d;
+
+        Point2::new(origin.x + x, origin.y + y)
+    }
+
+    /// Builds the power diagram, with a vertex for each triangle and an edge for each
+    /// edge of the triangulation
+    ///
+    /// `point_faces` gives the face of each point, which is None for the corners of
+    /// the enclosing triangle. Edges to those are left out, leaving gaps in the cycles
+    /// of the faces around them. The gaps lie outside the bounding rect, so they are
+    /// closed when the diagram is clipped.
+    fn dual<T: BaseFloat>(&self, point_faces: &[Option<FaceId>], faces: Vec<Face<T>>) -> Diagram<T> {
+        let mut diagram = Diagram {
+            vertices: Vec::new(),
+            halfedges: Vec::new(),
+            faces,
+            site_index: KdTree::default(),
+            metric: Metric::Euclidean,
+            accelerator: None,
+        };
+
+        // Number the half-edges first, one for each side of each edge between two
+        // sites. The half-edge of a triangle's edge belongs to the face at its end
+        let mut triangle_halfedges = vec![[None; 3]; self.triangles.len()];
+        let mut halfedge_count = 0;
+        for (index, triangle) in self.triangles.iter().enumerate() {
+            let triangle = match *triangle {
+                Some(ref triangle) => triangle,
+                None => continue,
+            };
+
+            for (corner, halfedge) in triangle_halfedges[index].iter_mut().enumerate() {
+                let start = triangle.points[(corner + 1) % 3];
+                let end = triangle.points[(corner + 2) % 3];
+                if point_faces[start].is_some() && point_faces[end].is_some() {
+                    *halfedge = Some(HalfEdgeId(halfedge_count));
+                    halfedge_count += 1;
+                }
+            }
+        }
+
+        let mut triangle_vertices = vec![None; self.triangles.len()];
+        for (index, halfedges) in triangle_halfedges.iter().enumerate() {
+            if let Some(&Some(halfedge_id)) = halfedges.iter().find(|halfedge| halfedge.is_some()) {
+                triangle_vertices[index] = Some(VertexId(diagram.vertices.len() as u32));
+                diagram.vertices.push(Vertex {
+                    coordinates: self.orthocentre(index).cast::<T>(),
+                    incident_edge: halfedge_id,
+                });
+            }
+        }
+
+        diagram.halfedges = (0..halfedge_count).map(|_| HalfEdge {
+            origin: None,
+            twin: None,
+            incident_face: FaceId(0),
+            next: HalfEdgeId(0),
+            prev: HalfEdgeId(0),
+        }).collect();
+
+        for (index, halfedges) in triangle_halfedges.iter().enumerate() {
+            for (corner, &halfedge_id) in halfedges.iter().enumerate() {
+                let halfedge_id = match halfedge_id {
+                    Some(halfedge_id) => halfedge_id,
+                    None => continue,
+                };
+
+                let triangle = self.triangle(index);
+                let end = triangle.points[(corner + 2) % 3];
+                let face_id = point_faces[end].unwrap();
+
+                // Edges between two sites are never on the enclosing triangle, so
+                // there's always a triangle on the other side
+                let neighbour = triangle.neighbours[corner].unwrap();
+                let twin_corner = (0..3).find(|&twin_corner| self.triangle(neighbour).neighbours[twin_corner] == Some(index)).unwrap();
+
+                // Turn counter-clockwise around the end point to the next edge that
+                // leads into it, skipping edges to the enclosing triangle
+                let mut next_triangle = neighbour;
+                let next = loop {
+                    let points = self.triangle(next_triangle).points;
+                    let next_corner = (points.iter().position(|&point| point == end).unwrap() + 1) % 3;
+                    if let Some(next) = triangle_halfedges[next_triangle][next_corner] {
+                        break next;
+                    }
+                    next_triangle = self.triangle(next_triangle).neighbours[next_corner].unwrap();
+                };
+
+                {
+                    let halfedge = &mut diagram.halfedges[halfedge_id.0 as usize];
+                    halfedge.origin = triangle_vertices[index];
+                    halfedge.twin = triangle_halfedges[neighbour][twin_corner];
+                    halfedge.incident_face = face_id;
+                    halfedge.next = next;
+                }
+                diagram.halfedges[next.0 as usize].prev = halfedge_id;
+                diagram.faces[face_id.0 as usize].first_halfedge = Some(halfedge_id);
+            }
+        }
+
+        diagram
+    }
+}
+
+/// Interleaves the bits of the coordinates along a Hilbert curve, so that sorting by
+/// the result puts points that are close together next to each other
+fn hilbert_index(mut x: u32, mut y: u32, order: u32) -> u64 {
+    let mut index = 0;
+    let mut size = 1 << (order - 1);
+    while size > 0 {
+        let rx = (x & size > 0) as u32;
+        let ry = (y & size > 0) as u32;
+        index += size as u64 * size as u64 * ((3 * rx) ^ ry) as u64;
+
+        // Rotate the quadrant
+        if ry == 0 {
+            if rx == 1 {
+                x = size - 1 - (x & (size - 1));
+                y = size - 1 - (y & (size - 1));
+            }
+            mem::swap(&mut x, &mut y);
+        }
+
+        x &= size - 1;
+        y &= size - 1;
+        size >>= 1;
+    }
+    index
+}
+
+/// Builds the power diagram of a set of weighted sites
+///
+/// Faces are created for the sites inside the bounding rect, in the order they
+/// were given like `DiagramBuilder`. Faces whose cell is empty or lies outside the
+/// clip region have no half-edges. Of sites at the same position, the one with
+/// the largest weight (or the first of those) gets the cell.
+///
+/// Cells can be arbitrarily thin, so everything is worked out in f64 and only the
+/// finished vertices are converted back.
+#[derive(Debug, Clone)]
+pub struct PowerDiagramBuilder<T = f32> {
+    offset: Point2<f64>,
+    scale: f64,
+
+    /// Corners of the clip region after scaling, in counter-clockwise order
+    boundary: Vec<Point2<f64>>,
+
+    faces: Vec<Face<T>>,
+
+    /// Scaled position of the site of each face
+    sites: Vec<Point2<f64>>,
+
+    /// Scaled weight of the site of each face
+    weights: Vec<f64>,
+}
+
+impl<T: BaseFloat> PowerDiagramBuilder<T> {
+    /// Creates a builder for the power diagram of the sites inside the clip region
+    pub fn new<R: Into<ClipRegion<T>>>(clip_region: R, sites: Vec<Site<T>>) -> PowerDiagramBuilder<T> {
+        // Scale the same way as DiagramBuilder. Weights are squared distances so they
+        // are scaled twice
+        let clip_region = clip_region.into();
+        let bounding_rect = clip_region.bounding_rect();
+        let offset = bounding_rect.position.cast::<f64>();
+        let scale = 1.0 / cast::<T, f64>(bounding_rect.size.x.max(bounding_rect.size.y)).unwrap();
+        let boundary = clip_region.boundary(T::one()).into_iter()
+            .map(|corner| corner.cast::<f64>() * scale)
+            .collect::<Vec<_>>();
+
+        let mut faces = Vec::new();
+        let mut scaled_sites = Vec::new();
+        let mut weights = Vec::new();
+        for site in sites {
+            let position = Point2::from_vec((site.position.cast::<f64>() - offset) * scale);
+
+            if strictly_inside_boundary(&boundary, position) {
+                faces.push(Face {
+                    first_halfedge: None,
+                    site,
+                });
+                scaled_sites.push(position);
+                weights.push(cast::<T, f64>(site.weight).unwrap() * scale * scale);
+            }
+        }
+
+        PowerDiagramBuilder {
+            offset,
+            scale,
+            boundary,
+            faces,
+            sites: scaled_sites,
+            weights,
+        }
+    }
+
+    pub fn finish(self) -> Diagram<T> {
+        let diagram = build_scaled(&self.sites, &self.weights, &self.boundary);
+
+        // Scale vertices back to the coordinates of the bounding rect, and give the
+        // faces back their original sites
+        let mut diagram = Diagram {
+            vertices: diagram.vertices.into_iter().map(|vertex| {
+                Vertex {
+                    coordinates: (self.offset + vertex.coordinates.to_vec() / self.scale).cast::<T>(),
+                    incident_edge: vertex.incident_edge,
+                }
+            }).collect(),
+            halfedges: diagram.halfedges,
+            faces: diagram.faces.into_iter().zip(self.faces).map(|(clipped, face)| {
+                Face {
+                    first_halfedge: clipped.first_halfedge,
+                    site: face.site,
+                }
+            }).collect(),
+            site_index: KdTree::default(),
+            metric: Metric::Euclidean,
+            accelerator: None,
+        };
+
+        diagram.index_sites(true);
+        diagram
+    }
+}
+
+/// Builds the regular triangulation of scaled sites, along with the face of each
+/// of its points
+fn triangulate(sites: &[Point2<f64>], weights: &[f64], boundary: &[Point2<f64>]) -> (RegularTriangulation, Vec<Option<FaceId>>) {
+    // The cells of the corners of the enclosing triangle must not reach into the
+    // clip region, so they go far enough out that some site is always closer.
+    // The region fits in the unit square, so every point in it is within a
+    // squared distance of 2 of every site and the power distance to the
+    // heaviest site is at most 2 minus its weight
+    let max_weight = weights.iter().cloned().fold(0.0, f64::max);
+    let radius = 4.0 * (1.0 + (2.0 - max_weight).max(0.0).sqrt());
+    let centre = Point2::centroid(boundary);
+    let corner = |angle: f64| centre + Vector2::new(angle.cos(), angle.sin()) * radius;
+    let third = 2.0 * PI / 3.0;
+    let mut triangulation = RegularTriangulation::new([
+        corner(FRAC_PI_2),
+        corner(FRAC_PI_2 + third),
+        corner(FRAC_PI_2 + third * 2.0),
+    ]);
+
+    // Insert nearby sites one after the other so point location only has to walk
+    // a short way. The sort is stable, so the first of any sites at the same
+    // position is inserted first and wins ties
+    let order = 16;
+    let cells = ((1 << order) - 1) as f64;
+    let mut insertion_order = (0..sites.len()).collect::<Vec<_>>();
+    insertion_order.sort_by_key(|&index| {
+        let x = (sites[index].x * cells) as u32;
+        let y = (sites[index].y * cells) as u32;
+        hilbert_index(x, y, order)
+    });
+
+    // Points are numbered in the order they are inserted, hidden ones included
+    let mut point_faces = vec![None; SUPER_POINTS + sites.len()];
+    for (point, &index) in insertion_order.iter().enumerate() {
+        point_faces[SUPER_POINTS + point] = Some(FaceId(index as u32));
+        triangulation.insert(sites[index], weights[index]);
+    }
+
+    (triangulation, point_faces)
+}
+
+/// Builds the power diagram of sites scaled to fit in the unit square, clipped to
+/// a boundary in the same coordinates
+///
+/// There's a face for each site in the order they're given, holding its scaled
+/// position and weight.
+pub(crate) fn build_scaled(sites: &[Point2<f64>], weights: &[f64], boundary: &[Point2<f64>]) -> Diagram<f64> {
+    let (triangulation, point_faces) = triangulate(sites, weights, boundary);
+    let faces = sites.iter().zip(weights).enumerate().map(|(index, (&site, &weight))| {
+        Face {
+            first_halfedge: None,
+            site: Site::with_weight(index as u32, site, weight),
+        }
+    }).collect();
+    let diagram = triangulation.dual(&point_faces, faces);
+    clip_to_boundary(&diagram, sites, Some(weights), boundary)
+}
@@ -0,0 +1,355 @@
+//! Robust geometric predicates
+//!
+//! These follow Shewchuk's "Adaptive Precision Floating-Point Arithmetic and Fast
+//! Robust Geometric Predicates". Each predicate is first evaluated with ordinary
+//! floating point arithmetic along with a bound on its error. Only when the result
+//! is too close to zero to trust is it evaluated again exactly, by representing
+//! values as expansions (sums of non-overlapping floats) that never round.
+//!
+//! The sign of the result is always correct, but its magnitude is only approximate.
+
+use std::cmp::Ordering;
+
+use cgmath::Point2;
+
+/// Half the machine epsilon of f64, as used by Shewchuk
+const EPSILON: f64 = f64::EPSILON / 2.0;
+
+/// Relative error bound of the fast path of `orient2d`
+const ORIENT2D_BOUND: f64 = (3.0 + 16.0 * EPSILON) * EPSILON;
+
+/// Relative error bound of the fast path of `incircle`
+const INCIRCLE_BOUND: f64 = (10.0 + 96.0 * EPSILON) * EPSILON;
+
+/// Relative error bound of the fast path of `power_test`
+const POWER_TEST_BOUND: f64 = (16.0 + 224.0 * EPSILON) * EPSILON;
+
+/// Sum of two floats as the rounded sum and its rounding error
+fn two_sum(a: f64, b: f64) -> (f64, f64) {
+    let x = a + b;
+    let b_virtual = x - a;
+    let a_virtual = x - b_virtual;
+    let error = (a - a_virtual) + (b - b_virtual);
+    (x, error)
+}
+
+/// Product of two floats as the rounded product and its rounding error
+fn two_product(a: f64, b: f64) -> (f64, f64) {
+    let x = a * b;
+    (x, a.mul_add(b, -x))
+}
+
+/// Adds a float to an expansion
+///
+/// Expansions are stored in increasing order of magnitude with zeroes removed.
+fn grow_expansion(expansion: &[f64], b: f64) -> Vec<f64> {
+    let mut result = Vec::with_capacity(expansion.len() + 1);
+    result.extend_from_slice(expansion);
+    grow_expansion_in_place(&mut result, b);
+    result
+}
+
+/// Adds a float to an expansion without allocating a new one, which matters as
+/// sums and products add one component at a time
+fn grow_expansion_in_place(expansion: &mut Vec<f64>, b: f64) {
+    let mut q = b;
+    let mut length = 0;
+    for index in 0..expansion.len() {
+        let (sum, error) = two_sum(q, expansion[index]);
+        if error != 0.0 {
+            expansion[length] = error;
+            length += 1;
+        }
+        q = sum;
+    }
+    expansion.truncate(length);
+    if q != 0.0 {
+        expansion.push(q);
+    }
+}
+
+pub(crate) fn expansion_sum(a: &[f64], b: &[f64]) -> Vec<f64> {
+    let mut result = Vec::with_capacity(a.len() + b.len());
+    result.extend_from_slice(a);
+    for &component in b {
+        grow_expansion_in_place(&mut result, component);
+    }
+    result
+}
+
+pub(crate) fn expansion_negate(a: &[f64]) -> Vec<f64> {
+    a.iter().map(|component| -component).collect()
+}
+
+pub(crate) fn expansion_product(a: &[f64], b: &[f64]) -> Vec<f64> {
+    let mut result = Vec::with_capacity(2 * a.len() * b.len());
+    for &x in a {
+        for &y in b {
+            let (product, error) = two_product(x, y);
+            grow_expansion_in_place(&mut result, error);
+            grow_expansion_in_place(&mut result, product);
+        }
+    }
+    result
+}
+
+/// The same expansion with as few components as possible
+///
+/// Sums and products only drop components that are zero, so nesting them makes
+/// expansions much longer than they need to be. This is Shewchuk's Compress.
+#[cfg(feature = "exact")]
+pub(crate) fn compress(expansion: &[f64]) -> Vec<f64> {
+    let mut large = Vec::with_capacity(expansion.len());
+    let mut q = match expansion.last() {
+        Some(&q) => q,
+        None => return Vec::new(),
+    };
+    for &e in expansion.iter().rev().skip(1) {
+        let (sum, error) = two_sum(q, e);
+        if error != 0.0 {
+            large.push(sum);
+            q = error;
+        } else {
+            q = sum;
+        }
+    }
+    large.push(q);
+
+    let mut result = Vec::with_capacity(large.len());
+    let mut q = large.pop().unwrap();
+    while let Some(g) = large.pop() {
+        let (sum, error) = two_sum(g, q);
+        if error != 0.0 {
+            result.push(error);
+        }
+        q = sum;
+    }
+    if q != 0.0 {
+        result.push(q);
+    }
+    result
+}
+
+/// The exact difference of two floats as an expansion
+pub(crate) fn difference(a: f64, b: f64) -> Vec<f64> {
+    grow_expansion(&[-b], a)
+}
+
+/// The approximate value of an expansion
+///
+/// The largest component is last and the others are too small to change its sign.
+pub(crate) fn estimate(expansion: &[f64]) -> f64 {
+    expansion.last().cloned().unwrap_or(0.0)
+}
+
+pub(crate) fn orient2d_expansion(a: Point2<f64>, b: Point2<f64>, c: Point2<f64>) -> Vec<f64> {
+    let acx = difference(a.x, c.x);
+    let acy = difference(a.y, c.y);
+    let bcx = difference(b.x, c.x);
+    let bcy = difference(b.y, c.y);
+
+    let left = expansion_product(&acx, &bcy);
+    let right = expansion_product(&acy, &bcx);
+    expansion_sum(&left, &expansion_negate(&right))
+}
+
+fn orient2d_exact(a: Point2<f64>, b: Point2<f64>, c: Point2<f64>) -> f64 {
+    estimate(&orient2d_expansion(a, b, c))
+}
+
+/// Orientation of three points
+///
+/// Returns a positive value if `a`, `b` and `c` are in counter-clockwise order, a
+/// negative value if they are clockwise and zero if they are collinear. The value
+/// is approximately twice the signed area of the triangle.
+pub fn orient2d(a: Point2<f64>, b: Point2<f64>, c: Point2<f64>) -> f64 {
+    let left = (a.x - c.x) * (b.y - c.y);
+    let right = (a.y - c.y) * (b.x - c.x);
+    let determinant = left - right;
+
+    // If the two products have different signs there is no cancellation and the
+    // result can be trusted
+    let sum = if left > 0.0 {
+        if right <= 0.0 {
+            return determinant;
+        }
+        left + right
+    } else if left < 0.0 {
+        if right >= 0.0 {
+            return determinant;
+        }
+        -left - right
+    } else {
+        return determinant;
+    };
+
+    let bound = ORIENT2D_BOUND * sum;
+    if determinant >= bound || -determinant >= bound {
+        return determinant;
+    }
+
+    orient2d_exact(a, b, c)
+}
+
+fn incircle_exact(a: Point2<f64>, b: Point2<f64>, c: Point2<f64>, d: Point2<f64>) -> f64 {
+    let adx = difference(a.x, d.x);
+    let ady = difference(a.y, d.y);
+    let bdx = difference(b.x, d.x);
+    let bdy = difference(b.y, d.y);
+    let cdx = difference(c.x, d.x);
+    let cdy = difference(c.y, d.y);
+
+    let lift = |x: &[f64], y: &[f64]| expansion_sum(&expansion_product(x, x), &expansion_product(y, y));
+    let cross = |ax: &[f64], ay: &[f64], bx: &[f64], by: &[f64]| {
+        expansion_sum(&expansion_product(ax, by), &expansion_negate(&expansion_product(ay, bx)))
+    };
+
+    let a_term = expansion_product(&lift(&adx, &ady), &cross(&bdx, &bdy, &cdx, &cdy));
+    let b_term = expansion_product(&lift(&bdx, &bdy), &cross(&cdx, &cdy, &adx, &ady));
+    let c_term = expansion_product(&lift(&cdx, &cdy), &cross(&adx, &ady, &bdx, &bdy));
+    let determinant = expansion_sum(&expansion_sum(&a_term, &b_term), &c_term);
+
+    estimate(&determinant)
+}
+
+/// Whether `d` lies inside the circle through `a`, `b` and `c`
+///
+/// Returns a positive value if `d` is inside the circle, a negative value if it is
+/// outside and zero if the four points are cocircular. `a`, `b` and `c` must be in
+/// counter-clockwise order, otherwise the sign is reversed.
+pub fn incircle(a: Point2<f64>, b: Point2<f64>, c: Point2<f64>, d: Point2<f64>) -> f64 {
+    let adx = a.x - d.x;
+    let ady = a.y - d.y;
+    let bdx = b.x - d.x;
+    let bdy = b.y - d.y;
+    let cdx = c.x - d.x;
+    let cdy = c.y - d.y;
+
+    let bdxcdy = bdx * cdy;
+    let cdxbdy = cdx * bdy;
+    let a_lift = adx * adx + ady * ady;
+
+    let cdxady = cdx * ady;
+    let adxcdy = adx * cdy;
+    let b_lift = bdx * bdx + bdy * bdy;
+
+    let adxbdy = adx * bdy;
+    let bdxady = bdx * ady;
+    let c_lift = cdx * cdx + cdy * cdy;
+
+    let determinant = a_lift * (bdxcdy - cdxbdy)
+                    + b_lift * (cdxady - adxcdy)
+                    + c_lift * (adxbdy - bdxady);
+
+    let permanent = (bdxcdy.abs() + cdxbdy.abs()) * a_lift
+                  + (cdxady.abs() + adxcdy.abs()) * b_lift
+                  + (adxbdy.abs() + bdxady.abs()) * c_lift;
+
+    let bound = INCIRCLE_BOUND * permanent;
+    if determinant > bound || -determinant > bound {
+        return determinant;
+    }
+
+    incircle_exact(a, b, c, d)
+}
+
+fn power_test_exact(a: Point2<f64>, b: Point2<f64>, c: Point2<f64>, d: Point2<f64>, weights: [f64; 4]) -> f64 {
+    let adx = difference(a.x, d.x);
+    let ady = difference(a.y, d.y);
+    let bdx = difference(b.x, d.x);
+    let bdy = difference(b.y, d.y);
+    let cdx = difference(c.x, d.x);
+    let cdy = difference(c.y, d.y);
+
+    let lift = |x: &[f64], y: &[f64], weight: f64| {
+        let squared = expansion_sum(&expansion_product(x, x), &expansion_product(y, y));
+        expansion_sum(&squared, &expansion_negate(&difference(weight, weights[3])))
+    };
+    let cross = |ax: &[f64], ay: &[f64], bx: &[f64], by: &[f64]| {
+        expansion_sum(&expansion_product(ax, by), &expansion_negate(&expansion_product(ay, bx)))
+    };
+
+    let a_term = expansion_product(&lift(&adx, &ady, weights[0]), &cross(&bdx, &bdy, &cdx, &cdy));
+    let b_term = expansion_product(&lift(&bdx, &bdy, weights[1]), &cross(&cdx, &cdy, &adx, &ady));
+    let c_term = expansion_product(&lift(&cdx, &cdy, weights[2]), &cross(&adx, &ady, &bdx, &bdy));
+    let determinant = expansion_sum(&expansion_sum(&a_term, &b_term), &c_term);
+
+    estimate(&determinant)
+}
+
+/// Whether the weighted point `d` conflicts with the circle orthogonal to the
+/// weighted points `a`, `b` and `c`
+///
+/// This is `incircle` for weighted points, where the power distance from a point
+/// `p` to a weighted point `q` is `|p - q|^2 - weight`, and `weights` holds the
+/// weights of `a`, `b`, `c` and `d` in that order. Returns a positive value if
+/// `d` would replace the triangle in the regular triangulation (the weighted
+/// Delaunay triangulation), a negative value if not and zero if the four points
+/// have a common orthogonal circle. With all weights zero this gives the same sign
+/// as `incircle`. `a`, `b` and `c` must be in counter-clockwise order.
+pub fn power_test(a: Point2<f64>, b: Point2<f64>, c: Point2<f64>, d: Point2<f64>, weights: [f64; 4]) -> f64 {
+    let adx = a.x - d.x;
+    let ady = a.y - d.y;
+    let bdx = b.x - d.x;
+    let bdy = b.y - d.y;
+    let cdx = c.x - d.x;
+    let cdy = c.y - d.y;
+    let adw = weights[0] - weights[3];
+    let bdw = weights[1] - weights[3];
+    let cdw = weights[2] - weights[3];
+
+    let bdxcdy = bdx * cdy;
+    let cdxbdy = cdx * bdy;
+    let a_lift = adx * adx + ady * ady - adw;
+
+    let cdxady = cdx * ady;
+    let adxcdy = adx * cdy;
+    let b_lift = bdx * bdx + bdy * bdy - bdw;
+
+    let adxbdy = adx * bdy;
+    let bdxady = bdx * ady;
+    let c_lift = cdx * cdx + cdy * cdy - cdw;
+
+    let determinant = a_lift * (bdxcdy - cdxbdy)
+                    + b_lift * (cdxady - adxcdy)
+                    + c_lift * (adxbdy - bdxady);
+
+    // As for incircle, but the lifts have an extra term each so both they and the
+    // bound are a little larger
+    let permanent = (bdxcdy.abs() + cdxbdy.abs()) * (adx * adx + ady * ady + adw.abs())
+                  + (cdxady.abs() + adxcdy.abs()) * (bdx * bdx + bdy * bdy + bdw.abs())
+                  + (adxbdy.abs() + bdxady.abs()) * (cdx * cdx + cdy * cdy + cdw.abs());
+
+    let bound = POWER_TEST_BOUND * permanent;
+    if determinant > bound || -determinant > bound {
+        return determinant;
+    }
+
+    power_test_exact(a, b, c, d, weights)
+}
+
+/// Compares where two lines cross the line through `a` and `b`
+///
+/// Each line is given by two points on it and must not be parallel to the line
+/// through `a` and `b`. Returns `Less` if the first line crosses closer to `a` in
+/// the direction of `b`. This is always exact, as it's only needed when the
+/// crossings are too close together to tell apart otherwise.
+pub fn compare_crossings(a: Point2<f64>, b: Point2<f64>, first: [Point2<f64>; 2], second: [Point2<f64>; 2]) -> Ordering {
+    // Line i crosses at a + (b - a) * s_i, where s_i = A_i / (A_i - B_i) and A_i and
+    // B_i are the orientations of a and b relative to the line. The difference of
+    // the two has the sign of (A_2 * B_1 - A_1 * B_2) / ((A_1 - B_1) * (A_2 - B_2))
+    let first_a = orient2d_expansion(first[0], first[1], a);
+    let first_b = orient2d_expansion(first[0], first[1], b);
+    let second_a = orient2d_expansion(second[0], second[1], a);
+    let second_b = orient2d_expansion(second[0], second[1], b);
+
+    let numerator = expansion_sum(
+        &expansion_product(&second_a, &first_b),
+        &expansion_negate(&expansion_product(&first_a, &second_b))
+    );
+    let first_denominator = estimate(&expansion_sum(&first_a, &expansion_negate(&first_b)));
+    let second_denominator = estimate(&expansion_sum(&second_a, &expansion_negate(&second_b)));
+
+    let difference = estimate(&numerator) * first_denominator.signum() * second_denominator.signum();
+    difference.partial_cmp(&0.0).unwrap_or(Ordering::Equal)
+}
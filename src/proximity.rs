@@ -0,0 +1,122 @@
+//! Graphs over the sites that only join up sites whose cells are neighbours
+//!
+//! The edges between cells are the dual of the Delaunay triangulation. Clipping
+//! can remove some of them, but the circle through two sites with their midpoint
+//! as its centre is empty for every edge these graphs keep, so that midpoint is on
+//! the edge between their cells. It's also inside a convex clip region whenever
+//! both sites are.
+//!
+//! If that circle isn't empty, going from one of the sites towards the midpoint
+//! leaves its cell for the cell of a site inside the circle. So the Gabriel test
+//! only needs the neighbours of one end. The lune of the relative neighbourhood
+//! graph can hold sites that aren't neighbours of either end, so those are found
+//! with a search around one end instead.
+
+use std::cmp::Ordering;
+
+use cgmath::{InnerSpace, BaseFloat};
+
+use diagram::{Diagram, FaceId};
+use kdtree::KdTree;
+
+/// How many sites the first search for sites in a lune asks for. It includes the
+/// site the search is around
+const FIRST_CANDIDATES: usize = 8;
+
+/// Each pair of faces that share an edge, once, with the lower id first
+pub(crate) fn neighbour_pairs<T: BaseFloat>(diagram: &Diagram<T>) -> Vec<(FaceId, FaceId)> {
+    let mut pairs = diagram.halfedges.iter()
+        .filter_map(|halfedge| {
+            let twin = halfedge.twin?;
+            let (face, other) = (halfedge.incident_face, diagram.halfedges[twin.0 as usize].incident_face);
+            if face.0 < other.0 { Some((face, other)) } else { None }
+        })
+        .collect::<Vec<_>>();
+    pairs.sort_by_key(|&(a, b)| (a.0, b.0));
+    pairs.dedup();
+    pairs
+}
+
+fn distance<T: BaseFloat>(diagram: &Diagram<T>, a: FaceId, b: FaceId) -> T {
+    (diagram.faces[b.0 as usize].site.position - diagram.faces[a.0 as usize].site.position).magnitude()
+}
+
+/// Edges of the minimum spanning tree, see `Diagram::euclidean_mst`
+pub fn euclidean_mst<T: BaseFloat>(diagram: &Diagram<T>) -> Vec<(FaceId, FaceId, T)> {
+    fn find(trees: &mut [u32], face: u32) -> u32 {
+        let mut face = face;
+        while trees[face as usize] != face {
+            let parent = trees[face as usize];
+            trees[face as usize] = trees[parent as usize];
+            face = parent;
+        }
+        face
+    }
+
+    let mut edges = neighbour_pairs(diagram).into_iter()
+        .map(|(a, b)| (a, b, distance(diagram, a, b)))
+        .collect::<Vec<_>>();
+    edges.sort_by(|a, b| a.2.partial_cmp(&b.2).unwrap_or(Ordering::Equal));
+
+    // Kruskal's algorithm: take the shortest edges that join two separate trees
+    let mut trees = (0..diagram.faces.len() as u32).collect::<Vec<_>>();
+    edges.retain(|&(a, b, _)| {
+        let (a, b) = (find(&mut trees, a.0), find(&mut trees, b.0));
+        trees[a.max(b) as usize] = a.min(b);
+        a != b
+    });
+
+    edges
+}
+
+/// Pairs of faces whose sites have no other site inside the circle between them,
+/// see `Diagram::gabriel_graph`
+pub fn gabriel_graph<T: BaseFloat>(diagram: &Diagram<T>) -> Vec<(FaceId, FaceId)> {
+    let pairs = neighbour_pairs(diagram);
+    let mut neighbours = vec![Vec::new(); diagram.faces.len()];
+    for &(a, b) in &pairs {
+        neighbours[a.0 as usize].push(b);
+        neighbours[b.0 as usize].push(a);
+    }
+
+    // A site is inside the circle if the angle it sees the other two at is obtuse
+    let position = |face: FaceId| diagram.faces[face.0 as usize].site.position;
+    pairs.into_iter()
+        .filter(|&(a, b)| {
+            neighbours[a.0 as usize].iter().all(|&other| {
+                other == b || (position(a) - position(other)).dot(position(b) - position(other)) >= T::zero()
+            })
+        })
+        .collect()
+}
+
+/// Pairs of faces whose sites have no other site nearer to both of them than they
+/// are to each other, see `Diagram::rng_graph`
+pub fn rng_graph<T: BaseFloat>(diagram: &Diagram<T>) -> Vec<(FaceId, FaceId)> {
+    let position = |face: FaceId| diagram.faces[face.0 as usize].site.position;
+    let site_index = KdTree::new(diagram.faces.iter().enumerate().map(|(index, face)| {
+        (FaceId(index as u32), face.site.position, T::zero())
+    }), false);
+
+    // Every edge of the graph is also in the Gabriel graph, whose circle is inside
+    // the lune
+    gabriel_graph(diagram).into_iter()
+        .filter(|&(a, b)| {
+            let length = (position(b) - position(a)).magnitude2();
+            let mut count = FIRST_CANDIDATES;
+            loop {
+                let nearest = site_index.nearest_unweighted(position(a), count);
+                let mut in_range = nearest.iter().take_while(|&&other| (position(other) - position(a)).magnitude2() < length);
+                if in_range.any(|&other| (position(other) - position(b)).magnitude2() < length) {
+                    return false;
+                }
+
+                let furthest = nearest[nearest.len() - 1];
+                if nearest.len() < count || (position(furthest) - position(a)).magnitude2() >= length {
+                    return true;
+                }
+                count *= 2;
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,187 @@
+use cgmath::{Point2, Vector2, InnerSpace, BaseFloat};
+use cgmath::num_traits::cast;
+
+use build::Rect;
+use diagram::{Diagram, Endpoint, FaceId, HalfEdgeId, VertexId};
+use insert::inside_face;
+
+/// An area to find the faces in
+#[derive(Debug, Clone, Copy)]
+enum Area<T> {
+    Rect(Point2<T>, Point2<T>),
+    Circle(Point2<T>, T),
+}
+
+/// The part of a line along a half-edge, from `start + direction * from` to
+/// `start + direction * to`, where the ends can be infinite
+///
+/// The direction is the way the half-edge goes, so its face is on the left.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Piece<T> {
+    pub halfedge: HalfEdgeId,
+    pub start: Point2<T>,
+    pub direction: Vector2<T>,
+    pub from: T,
+    pub to: T,
+}
+
+impl<T: BaseFloat> Area<T> {
+    fn bounds(&self) -> (Point2<T>, Point2<T>) {
+        match *self {
+            Area::Rect(min, max) => (min, max),
+            Area::Circle(centre, radius) => (centre + Vector2::new(-radius, -radius), centre + Vector2::new(radius, radius)),
+        }
+    }
+
+    fn centre(&self) -> Point2<T> {
+        match *self {
+            Area::Rect(min, max) => {
+                let half: T = cast(0.5).unwrap();
+                Point2::new((min.x + max.x) * half, (min.y + max.y) * half)
+            }
+            Area::Circle(centre, _) => centre,
+        }
+    }
+
+    fn touches(&self, piece: &Piece<T>) -> bool {
+        match *self {
+            Area::Rect(min, max) => {
+                // Cut the piece down to the slab between each pair of sides in turn
+                let (mut from, mut to) = (piece.from, piece.to);
+                for &(start, direction, low, high) in &[(piece.start.x, piece.direction.x, min.x, max.x), (piece.start.y, piece.direction.y, min.y, max.y)] {
+                    if direction == T::zero() {
+                        if start < low || start > high {
+                            return false;
+                        }
+                        continue;
+                    }
+
+                    let (a, b) = ((low - start) / direction, (high - start) / direction);
+                    from = from.max(a.min(b));
+                    to = to.min(a.max(b));
+                }
+                from <= to
+            }
+            Area::Circle(centre, radius) => {
+                let length2 = piece.direction.magnitude2();
+                let along = if length2 > T::zero() { (centre - piece.start).dot(piece.direction) / length2 } else { T::zero() };
+                let nearest = piece.start + piece.direction * along.max(piece.from).min(piece.to);
+                (nearest - centre).magnitude2() <= radius * radius
+            }
+        }
+    }
+}
+
+/// Whether a piece passes within `distance` of a point
+pub(crate) fn near_piece<T: BaseFloat>(piece: &Piece<T>, point: Point2<T>, distance: T) -> bool {
+    Area::Circle(point, distance).touches(piece)
+}
+
+/// Faces whose cells overlap a rect, in order of their ids
+pub fn faces_in_rect<T: BaseFloat>(diagram: &Diagram<T>, rect: Rect<T>) -> Vec<FaceId> {
+    let max = rect.position + rect.size;
+    let valid = rect.size.x >= T::zero() && rect.size.y >= T::zero() && max.x.is_finite() && max.y.is_finite();
+    if !valid {
+        return Vec::new();
+    }
+    faces_in(diagram, Area::Rect(rect.position, max))
+}
+
+/// Faces whose cells overlap a circle, in order of their ids
+pub fn faces_in_circle<T: BaseFloat>(diagram: &Diagram<T>, centre: Point2<T>, radius: T) -> Vec<FaceId> {
+    let valid = radius >= T::zero() && radius.is_finite() && centre.x.is_finite() && centre.y.is_finite();
+    if !valid {
+        return Vec::new();
+    }
+    faces_in(diagram, Area::Circle(centre, radius))
+}
+
+fn faces_in<T: BaseFloat>(diagram: &Diagram<T>, area: Area<T>) -> Vec<FaceId> {
+    let candidates = match diagram.accelerator() {
+        Some(grid) => {
+            let (min, max) = area.bounds();
+            grid.faces_in_box(min, max)
+        }
+        None => (0..diagram.faces.len() as u32).map(FaceId).collect(),
+    };
+
+    // A cell overlaps the area if one of its edges does, or if the area is all
+    // inside it, when none of the edges of any cell do
+    let mut pieces = Vec::new();
+    let faces = candidates.into_iter().filter(|&face_id| {
+        pieces.clear();
+        face_pieces(diagram, face_id, &mut pieces);
+        pieces.iter().any(|piece| area.touches(piece))
+    }).collect::<Vec<_>>();
+    if !faces.is_empty() {
+        return faces;
+    }
+
+    let centre = area.centre();
+    diagram.locate(centre).into_iter().filter(|&face_id| in_located_face(diagram, face_id, centre)).collect()
+}
+
+/// Whether a point is in the face that `locate` gave for it, rather than outside
+/// the diagram
+pub(crate) fn in_located_face<T: BaseFloat>(diagram: &Diagram<T>, face_id: FaceId, point: Point2<T>) -> bool {
+    // Unbounded cells go on forever, and so does the one cell of an unclipped
+    // diagram with a single site, which has no edges at all
+    if diagram.halfedges.is_empty() {
+        return true;
+    }
+    let bounded = diagram.face_edges(face_id).all(|halfedge_id| diagram.halfedges[halfedge_id.0 as usize].origin.is_some());
+    if bounded {
+        inside_face(diagram, face_id, point.cast())
+    } else {
+        diagram.faces[face_id.0 as usize].first_halfedge.is_some()
+    }
+}
+
+/// Adds the edges of a face to `pieces`, including ones that go off to infinity
+pub(crate) fn face_pieces<T: BaseFloat>(diagram: &Diagram<T>, face_id: FaceId, pieces: &mut Vec<Piece<T>>) {
+    pieces.extend(diagram.face_edges(face_id).filter_map(|halfedge_id| halfedge_piece(diagram, halfedge_id)));
+}
+
+/// The part of a line a half-edge covers, or None if it's missing an end
+pub(crate) fn halfedge_piece<T: BaseFloat>(diagram: &Diagram<T>, halfedge_id: HalfEdgeId) -> Option<Piece<T>> {
+    let coordinates = |vertex_id: VertexId| diagram.vertices[vertex_id.0 as usize].coordinates;
+    match (diagram.origin(halfedge_id)?, diagram.destination(halfedge_id)?) {
+        (Endpoint::Vertex(origin), Endpoint::Vertex(destination)) => Some(Piece {
+            halfedge: halfedge_id,
+            start: coordinates(origin),
+            direction: coordinates(destination) - coordinates(origin),
+            from: T::zero(),
+            to: T::one(),
+        }),
+        (Endpoint::Vertex(origin), Endpoint::InfiniteDirection(direction)) => Some(Piece {
+            halfedge: halfedge_id,
+            start: coordinates(origin),
+            direction,
+            from: T::zero(),
+            to: T::infinity(),
+        }),
+        (Endpoint::InfiniteDirection(direction), Endpoint::Vertex(destination)) => Some(Piece {
+            halfedge: halfedge_id,
+            start: coordinates(destination),
+            direction: -direction,
+            from: T::neg_infinity(),
+            to: T::zero(),
+        }),
+        (Endpoint::InfiniteDirection(direction), Endpoint::InfiniteDirection(_)) => {
+            // The whole bisector between the two sites, through the point halfway
+            // between them
+            let halfedge = &diagram.halfedges[halfedge_id.0 as usize];
+            let twin = &diagram.halfedges[halfedge.twin?.0 as usize];
+            let site = diagram.faces[halfedge.incident_face.0 as usize].site.position;
+            let other = diagram.faces[twin.incident_face.0 as usize].site.position;
+            let half: T = cast(0.5).unwrap();
+            Some(Piece {
+                halfedge: halfedge_id,
+                start: site + (other - site) * half,
+                direction: -direction,
+                from: T::neg_infinity(),
+                to: T::infinity(),
+            })
+        }
+    }
+}
@@ -0,0 +1,212 @@
+//! Diagrams drawn into grids of pixels, for generating textures on the CPU
+//!
+//! Pixels cover a rect in rows, starting from the row along `rect.position.y`
+//! and the pixel at `rect.position.x`, so the rect's position is the top left of
+//! the image when y points down the screen. Each pixel is sampled at its centre,
+//! and the results are in a `Vec` with `width` pixels for each of `height` rows.
+
+use cgmath::{Point2, InnerSpace, BaseFloat};
+
+use build::{DiagramBuilder, Rect, Site};
+use diagram::{Diagram, FaceId};
+use kdtree::KdTree;
+
+/// Value of pixels that aren't nearest to any site, because there weren't any
+pub const NO_SITE: u32 = u32::MAX;
+
+/// The centre of every pixel, row by row
+fn pixel_centres<T: BaseFloat>(rect: Rect<T>, width: u32, height: u32) -> impl Iterator<Item = Point2<T>> {
+    let pixel_width = rect.size.x / T::from(width).unwrap();
+    let pixel_height = rect.size.y / T::from(height).unwrap();
+    let half: T = T::from(0.5).unwrap();
+    (0..height).flat_map(move |row| {
+        let y = rect.position.y + (T::from(row).unwrap() + half) * pixel_height;
+        (0..width).map(move |column| Point2::new(rect.position.x + (T::from(column).unwrap() + half) * pixel_width, y))
+    })
+}
+
+/// Row or column of the first pixel whose centre is at or past `position`, along
+/// an axis starting at `start` with pixels `size` apart
+fn first_pixel(position: f64, start: f64, size: f64, count: u32) -> usize {
+    ((position - start) / size - 0.5).ceil().max(0.0).min(f64::from(count)) as usize
+}
+
+/// Fills in the pixels of each cell of a diagram a row at a time, leaving pixels
+/// outside every cell as `NO_SITE`
+///
+/// A pixel is in a cell if its centre is, counting the bottom and left edges of
+/// the cell but not the top and right ones. Cells next to each other work out
+/// where a row crosses the edge between them the same way, so every pixel on
+/// the edge goes to just one of them. Cells that go off to infinity are skipped.
+fn fill_cells<T: BaseFloat>(diagram: &Diagram<T>, rect: Rect<T>, width: u32, height: u32) -> Vec<u32> {
+    let mut pixels = vec![NO_SITE; width as usize * height as usize];
+    let start = rect.position.cast::<f64>();
+    let pixel_width = rect.size.x.to_f64().unwrap() / f64::from(width);
+    let pixel_height = rect.size.y.to_f64().unwrap() / f64::from(height);
+    if !(pixel_width > 0.0 && pixel_height > 0.0) {
+        return pixels;
+    }
+
+    for (index, face) in diagram.faces.iter().enumerate() {
+        let face_id = FaceId(index as u32);
+        let closed = face.first_halfedge.is_some() && diagram.face_edges(face_id).all(|halfedge| diagram.halfedges[halfedge.0 as usize].origin.is_some());
+        if !closed {
+            continue;
+        }
+        let corners = diagram.face_vertices(face_id).map(|corner| corner.cast::<f64>()).collect::<Vec<_>>();
+        let (bottom, top) = corners.iter().fold((f64::INFINITY, f64::NEG_INFINITY), |(bottom, top), corner| (bottom.min(corner.y), top.max(corner.y)));
+
+        for row in first_pixel(bottom, start.y, pixel_height, height)..first_pixel(top, start.y, pixel_height, height) {
+            let y = start.y + (row as f64 + 0.5) * pixel_height;
+
+            // Cells are convex, so the row crosses the outside of the cell twice.
+            // Each edge is followed from its lower end, so that both cells beside
+            // it get the same answer
+            let (left, right) = corners.iter().enumerate()
+                .map(|(corner, &a)| (a, corners[(corner + 1) % corners.len()]))
+                .map(|(a, b)| if a.y <= b.y { (a, b) } else { (b, a) })
+                .filter(|&(low, high)| low.y <= y && y < high.y)
+                .map(|(low, high)| low.x + (y - low.y) / (high.y - low.y) * (high.x - low.x))
+                .fold((f64::INFINITY, f64::NEG_INFINITY), |(left, right), x| (left.min(x), right.max(x)));
+
+            let row_start = row * width as usize;
+            for pixel in &mut pixels[row_start + first_pixel(left, start.x, pixel_width, width)..row_start + first_pixel(right, start.x, pixel_width, width)] {
+                if *pixel == NO_SITE {
+                    *pixel = index as u32;
+                }
+            }
+        }
+    }
+    pixels
+}
+
+/// The face whose cell each pixel is in
+///
+/// Each cell is filled in a row at a time, so this takes time in proportion to the
+/// number of pixels and the number of edges of the cells, rather than looking up
+/// every pixel. A pixel whose centre is on an edge between cells gets one of them.
+/// Pixels that aren't in any cell, which are those outside the diagram or in the
+/// odd gap left by rounding, get the index of the face `Diagram::locate` finds for
+/// their centre, so in a power diagram they take the weights into account and
+/// outside the diagram they get the face whose cell would have been there if it
+/// wasn't clipped. Every pixel is `NO_SITE` if the diagram has no faces.
+pub fn rasterize<T: BaseFloat>(diagram: &Diagram<T>, rect: Rect<T>, width: u32, height: u32) -> Vec<u32> {
+    let mut pixels = fill_cells(diagram, rect, width, height);
+    for (pixel, centre) in pixels.iter_mut().zip(pixel_centres(rect, width, height)) {
+        if *pixel == NO_SITE {
+            *pixel = diagram.locate(centre).map_or(NO_SITE, |face| face.0);
+        }
+    }
+    pixels
+}
+
+/// The index of the site nearest to each pixel
+///
+/// This builds the diagram of the sites over the rect and the sites together, and
+/// fills in its cells like `rasterize`, so it takes time in proportion to the
+/// number of pixels plus the time to build the diagram. Of sites at the same
+/// position, the first one in the list wins, and a pixel whose centre is the same
+/// distance from two sites gets one of them. Every pixel is `NO_SITE` if there
+/// are no sites.
+pub fn rasterize_sites<T: BaseFloat>(sites: &[Point2<T>], rect: Rect<T>, width: u32, height: u32) -> Vec<u32> {
+    let corners = [rect.position, rect.position + rect.size];
+    let bounds = Rect::around(sites.iter().chain(&corners).cloned(), T::zero());
+    let diagram = DiagramBuilder::new(bounds, sites.iter().enumerate().map(|(index, &site)| Site::new(index as u32, site)).collect()).finish();
+    let mut pixels = fill_cells(&diagram, rect, width, height);
+    for pixel in pixels.iter_mut().filter(|pixel| **pixel != NO_SITE) {
+        *pixel = diagram.faces[*pixel as usize].site.id;
+    }
+
+    // Anything rounding left out is looked up on its own
+    let index = KdTree::new(sites.iter().enumerate().map(|(index, &site)| (FaceId(index as u32), site, T::zero())), false);
+    for (pixel, centre) in pixels.iter_mut().zip(pixel_centres(rect, width, height)) {
+        if *pixel == NO_SITE {
+            *pixel = index.nearest(centre).map_or(NO_SITE, |face| face.0);
+        }
+    }
+    pixels
+}
+
+/// Distances from the centre of each pixel, see `distance_field`
+#[derive(Debug, Clone, PartialEq)]
+pub struct DistanceField<T = f32> {
+    /// Index of the site nearest to each pixel, or `NO_SITE` if there are none
+    pub nearest: Vec<u32>,
+
+    /// Distance from each pixel to its nearest site
+    pub site_distance: Vec<T>,
+
+    /// Distance from each pixel to the nearest edge of the cell it's in, which is
+    /// infinite if there are fewer than two sites
+    pub edge_distance: Vec<T>,
+}
+
+/// Number of sites looked at first when finding the nearest edge, which is doubled
+/// until the rest are too far away to matter
+const EDGE_SEARCH_START: usize = 8;
+
+/// Distance from a point to its nearest site, and to the nearest edge of that
+/// site's cell
+fn distances<T: BaseFloat>(sites: &[Point2<T>], index: &KdTree<T>, point: Point2<T>) -> Option<(FaceId, T, T)> {
+    let two = T::one() + T::one();
+    let mut count = EDGE_SEARCH_START;
+    loop {
+        let found = index.nearest_unweighted(point, count);
+        let nearest = *found.first()?;
+        let site = sites[nearest.0 as usize];
+        let distance = (point - site).magnitude();
+
+        // The edge with another site is along the bisector between them. It's at
+        // least half the difference in their distances away, so once that's further
+        // than the closest edge so far, the sites after it can be skipped
+        let mut edge = T::infinity();
+        let mut finished = found.len() < count;
+        for &other in &found[1..] {
+            let other = sites[other.0 as usize];
+            let other_distance = (point - other).magnitude();
+            if (other_distance - distance) / two >= edge {
+                finished = true;
+                break;
+            }
+            let gap = (other - site).magnitude();
+            if gap > T::zero() {
+                let squares = (point - other).magnitude2() - (point - site).magnitude2();
+                edge = edge.min(squares / (two * gap));
+            }
+        }
+
+        if finished {
+            return Some((nearest, distance, edge));
+        }
+        count *= 2;
+    }
+}
+
+/// The distance from each pixel to the nearest site, and to the nearest edge of
+/// that site's cell, in one pass over the pixels
+///
+/// Edges are the bisectors between sites, without any clip region, so cells of
+/// sites around the outside go on past the rect. Sites at the same position as
+/// the nearest one don't make an edge with it. Distances to edges are useful for
+/// drawing borders between cells in a shader, and distances to sites for cellular
+/// textures.
+pub fn distance_field<T: BaseFloat>(sites: &[Point2<T>], rect: Rect<T>, width: u32, height: u32) -> DistanceField<T> {
+    let index = KdTree::new(sites.iter().enumerate().map(|(index, &site)| (FaceId(index as u32), site, T::zero())), false);
+    let pixels = width as usize * height as usize;
+    let mut field = DistanceField {
+        nearest: Vec::with_capacity(pixels),
+        site_distance: Vec::with_capacity(pixels),
+        edge_distance: Vec::with_capacity(pixels),
+    };
+
+    for centre in pixel_centres(rect, width, height) {
+        let (nearest, site_distance, edge_distance) = match distances(sites, &index, centre) {
+            Some((nearest, site_distance, edge_distance)) => (nearest.0, site_distance, edge_distance),
+            None => (NO_SITE, T::infinity(), T::infinity()),
+        };
+        field.nearest.push(nearest);
+        field.site_distance.push(site_distance);
+        field.edge_distance.push(edge_distance);
+    }
+    field
+}
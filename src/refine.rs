@@ -0,0 +1,425 @@
+//! Refinement of a Delaunay triangulation into a mesh of well shaped triangles
+//!
+//! This is Ruppert's algorithm, with the convex hull of the points as the only
+//! boundary. Triangles with too small an angle or too large an area get a new
+//! point at their circumcentre. A side of the boundary is encroached if a point is
+//! inside the circle that has the side as its diameter, and those sides are split
+//! instead, including when the circumcentre would encroach one.
+//!
+//! Points are added with the Bowyer-Watson algorithm: the triangles whose
+//! circumcircles contain the new point are removed, and the hole is filled with
+//! triangles that fan out from it, so the triangulation stays Delaunay.
+
+use cgmath::{Point2, Vector2, InnerSpace, BaseFloat};
+use cgmath::num_traits::cast;
+
+use delaunay::{Triangle, TriangleId, Triangulation};
+use predicates::{orient2d, incircle};
+
+/// How close the distances of two points from a given point have to be, relative
+/// to their size, for the points to count as being on the same shell
+const SHELL_TOLERANCE: f64 = 1e-9;
+
+/// Refines a Delaunay triangulation by adding points until no triangle has an
+/// angle below a minimum or an area above a maximum
+///
+/// The new points are added after the points of the triangulation, which keep
+/// their indices. The result covers the same convex hull and is still Delaunay.
+/// Triangles at the tip of a corner of the hull that is sharper than 60 degrees
+/// can be left thinner than the minimum.
+#[derive(Debug, Clone)]
+pub struct Refinement<T = f32> {
+    triangulation: Triangulation<T>,
+    min_angle: T,
+    max_area: Option<T>,
+    max_points: Option<usize>,
+}
+
+impl<T: BaseFloat> Refinement<T> {
+    /// Creates a refinement with a minimum angle of 20 degrees and no maximum area
+    pub fn new(triangulation: Triangulation<T>) -> Refinement<T> {
+        Refinement {
+            triangulation,
+            min_angle: cast(20.0).unwrap(),
+            max_area: None,
+            max_points: None,
+        }
+    }
+
+    /// Sets the smallest angle that triangles can have, in degrees
+    ///
+    /// Ruppert's algorithm is only sure to finish for angles up to about 20.7
+    /// degrees, and when no corner of the hull is sharper than 60 degrees. Larger
+    /// angles usually work too, but set a maximum number of points if the input
+    /// isn't known to be safe.
+    pub fn set_min_angle(&mut self, degrees: T) {
+        self.min_angle = degrees;
+    }
+
+    /// Sets the largest area that triangles can have
+    pub fn set_max_area(&mut self, area: T) {
+        self.max_area = Some(area);
+    }
+
+    /// Stops adding points once the triangulation has this many, even if some
+    /// triangles are still too thin or too large
+    pub fn set_max_points(&mut self, count: usize) {
+        self.max_points = Some(count);
+    }
+
+    pub fn finish(self) -> Triangulation<T> {
+        let min_angle = self.min_angle.to_f64().unwrap().to_radians();
+        let max_area = self.max_area.map(|area| area.to_f64().unwrap());
+        let max_points = self.max_points.unwrap_or(usize::MAX);
+
+        let Triangulation { points, triangles } = self.triangulation;
+        let mut mesh = Mesh {
+            removed: vec![false; triangles.len()],
+            free: Vec::new(),
+            given: points.len(),
+            shells: vec![None; points.len()],
+            points: points.into_iter().map(|point| point.cast::<f64>()).collect(),
+            triangles,
+        };
+        mesh.remove_flat_triangles();
+
+        // Encroached sides are split before any more triangles, so their circles
+        // are empty whenever a circumcentre is checked against them
+        let mut sides = Vec::new();
+        let mut thin = Vec::new();
+        mesh.queue((0..mesh.triangles.len()).filter(|&triangle| !mesh.removed[triangle]).collect(), &mut sides, &mut thin);
+
+        while mesh.points.len() < max_points {
+            if let Some((triangle, side)) = sides.pop() {
+                if !mesh.removed[triangle] && mesh.triangles[triangle].neighbours[side].is_none() && mesh.encroached(triangle, side, mesh.corner(triangle, side)) {
+                    let added = mesh.split_side(triangle, side);
+                    mesh.queue(added, &mut sides, &mut thin);
+                }
+                continue;
+            }
+
+            let triangle = match thin.pop() {
+                Some(triangle) => triangle,
+                None => break,
+            };
+            if mesh.removed[triangle] || !mesh.needs_split(triangle, min_angle, max_area) {
+                continue;
+            }
+
+            let centre = match mesh.circumcentre(triangle) {
+                Some(centre) => centre,
+                None => continue,
+            };
+            let added = match mesh.locate(triangle, centre) {
+                Location::Outside(outside, side) => {
+                    thin.push(triangle);
+                    mesh.split_side(outside, side)
+                }
+                Location::Inside(inside) => {
+                    let cavity = mesh.cavity(inside, centre);
+                    match mesh.encroached_side(&cavity, centre) {
+                        Some((encroached, side)) => {
+                            thin.push(triangle);
+                            mesh.split_side(encroached, side)
+                        }
+                        None => mesh.insert(centre, &cavity, None),
+                    }
+                }
+            };
+            mesh.queue(added, &mut sides, &mut thin);
+        }
+
+        mesh.into_triangulation()
+    }
+}
+
+/// Where a point is found by walking towards it
+enum Location {
+    /// Inside the triangle or on one of its edges
+    Inside(usize),
+
+    /// Outside the hull, beyond a side of the triangle that is on the hull
+    Outside(usize, usize),
+}
+
+/// A triangulation that triangles can be removed from and added to
+struct Mesh {
+    points: Vec<Point2<f64>>,
+    triangles: Vec<Triangle>,
+
+    /// Whether each triangle has been removed, which leaves its slot free for a
+    /// new one
+    removed: Vec<bool>,
+    free: Vec<usize>,
+
+    /// How many points there were to begin with
+    given: usize,
+
+    /// The given point that each point on a shell is around
+    shells: Vec<Option<u32>>,
+}
+
+impl Mesh {
+    fn corner(&self, triangle: usize, corner: usize) -> Point2<f64> {
+        self.points[self.triangles[triangle].points[corner % 3] as usize]
+    }
+
+    /// Removes triangles with no area whose longest side is on the hull
+    ///
+    /// Points in a line along the hull can be joined up by these, which would make
+    /// the middle point encroach the side.
+    fn remove_flat_triangles(&mut self) {
+        let mut removed_any = true;
+        while removed_any {
+            removed_any = false;
+            for triangle in 0..self.triangles.len() {
+                let corners = [self.corner(triangle, 0), self.corner(triangle, 1), self.corner(triangle, 2)];
+                if self.removed[triangle] || orient2d(corners[0], corners[1], corners[2]) != 0.0 {
+                    continue;
+                }
+
+                let lengths = [0, 1, 2].map(|corner| (corners[(corner + 2) % 3] - corners[(corner + 1) % 3]).magnitude2());
+                let longest = (0..3).fold(0, |longest, side| if lengths[side] > lengths[longest] { side } else { longest });
+                if self.triangles[triangle].neighbours[longest].is_some() {
+                    continue;
+                }
+
+                for side in 0..3 {
+                    if let Some(TriangleId(neighbour)) = self.triangles[triangle].neighbours[side] {
+                        let neighbours = &mut self.triangles[neighbour as usize].neighbours;
+                        let back = neighbours.iter().position(|&other| other == Some(TriangleId(triangle as u32))).unwrap();
+                        neighbours[back] = None;
+                    }
+                }
+                self.removed[triangle] = true;
+                self.free.push(triangle);
+                removed_any = true;
+            }
+        }
+    }
+
+    /// Adds triangles to the queue of ones to check, along with their sides on the
+    /// hull
+    fn queue(&self, triangles: Vec<usize>, sides: &mut Vec<(usize, usize)>, thin: &mut Vec<usize>) {
+        for triangle in triangles {
+            for side in 0..3 {
+                if self.triangles[triangle].neighbours[side].is_none() {
+                    sides.push((triangle, side));
+                }
+            }
+            thin.push(triangle);
+        }
+    }
+
+    /// Whether a point is inside the circle that has the edge opposite a corner as
+    /// its diameter
+    fn encroached(&self, triangle: usize, corner: usize, point: Point2<f64>) -> bool {
+        let start = self.corner(triangle, corner + 1);
+        let end = self.corner(triangle, corner + 2);
+        (start - point).dot(end - point) < 0.0
+    }
+
+    fn needs_split(&self, triangle: usize, min_angle: f64, max_area: Option<f64>) -> bool {
+        let [a, b, c] = [self.corner(triangle, 0), self.corner(triangle, 1), self.corner(triangle, 2)];
+        let lengths = [(c - b).magnitude(), (a - c).magnitude(), (b - a).magnitude()];
+        let double_area = (b - a).perp_dot(c - a);
+        if double_area <= 0.0 {
+            return false;
+        }
+        if max_area.is_some_and(|max_area| double_area > 2.0 * max_area) {
+            return true;
+        }
+
+        // The smallest angle is opposite the shortest side, and its sine is that
+        // side over the diameter of the circumcircle
+        let shortest = lengths.iter().cloned().fold(f64::INFINITY, f64::min);
+        if self.between_shells(triangle, &lengths, shortest) {
+            return false;
+        }
+        let diameter = lengths[0] * lengths[1] * lengths[2] / double_area;
+        shortest < diameter * min_angle.sin()
+    }
+
+    /// Whether the shortest side of a triangle joins two points on the same shell
+    /// around a given point
+    ///
+    /// These are left alone, because a sharp corner of the hull has a thin triangle
+    /// at its tip however many points are added along its sides, and trying to
+    /// split that would never end.
+    fn between_shells(&self, triangle: usize, lengths: &[f64; 3], shortest: f64) -> bool {
+        let corner = lengths.iter().position(|&length| length == shortest).unwrap();
+        let start = self.triangles[triangle].points[(corner + 1) % 3] as usize;
+        let end = self.triangles[triangle].points[(corner + 2) % 3] as usize;
+        match (self.shells[start], self.shells[end]) {
+            (Some(around), Some(other)) if around == other => {
+                let centre = self.points[around as usize];
+                let (start, end) = ((self.points[start] - centre).magnitude(), (self.points[end] - centre).magnitude());
+                (start - end).abs() <= (start + end) * SHELL_TOLERANCE
+            }
+            _ => false,
+        }
+    }
+
+    fn circumcentre(&self, triangle: usize) -> Option<Point2<f64>> {
+        let a = self.corner(triangle, 0);
+        let b = self.corner(triangle, 1) - a;
+        let c = self.corner(triangle, 2) - a;
+        let denominator = 2.0 * b.perp_dot(c);
+        if denominator == 0.0 {
+            return None;
+        }
+
+        let offset = Vector2::new(
+            c.y * b.magnitude2() - b.y * c.magnitude2(),
+            b.x * c.magnitude2() - c.x * b.magnitude2(),
+        ) / denominator;
+        Some(a + offset)
+    }
+
+    /// Walks from a triangle towards a point, crossing whichever edge the point is
+    /// beyond
+    fn locate(&self, start: usize, point: Point2<f64>) -> Location {
+        let mut triangle = start;
+        'walk: loop {
+            for side in 0..3 {
+                if orient2d(self.corner(triangle, side + 1), self.corner(triangle, side + 2), point) < 0.0 {
+                    match self.triangles[triangle].neighbours[side] {
+                        Some(TriangleId(neighbour)) => {
+                            triangle = neighbour as usize;
+                            continue 'walk;
+                        }
+                        None => return Location::Outside(triangle, side),
+                    }
+                }
+            }
+            return Location::Inside(triangle);
+        }
+    }
+
+    /// The triangles whose circumcircles contain a point, starting from the one it
+    /// is in
+    fn cavity(&self, start: usize, point: Point2<f64>) -> Vec<usize> {
+        let mut cavity = vec![start];
+        let mut next = 0;
+        while next < cavity.len() {
+            let triangle = cavity[next];
+            next += 1;
+            for &neighbour in &self.triangles[triangle].neighbours {
+                if let Some(TriangleId(neighbour)) = neighbour {
+                    let neighbour = neighbour as usize;
+                    if !cavity.contains(&neighbour) && incircle(self.corner(neighbour, 0), self.corner(neighbour, 1), self.corner(neighbour, 2), point) > 0.0 {
+                        cavity.push(neighbour);
+                    }
+                }
+            }
+        }
+        cavity
+    }
+
+    /// A side of the hull around a cavity that a point would encroach
+    fn encroached_side(&self, cavity: &[usize], point: Point2<f64>) -> Option<(usize, usize)> {
+        cavity.iter().flat_map(|&triangle| (0..3).map(move |side| (triangle, side)))
+            .find(|&(triangle, side)| self.triangles[triangle].neighbours[side].is_none() && self.encroached(triangle, side, point))
+    }
+
+    /// Splits a side of the hull
+    ///
+    /// Sides with one end at a given point are split where the distance from it is
+    /// a power of two, so the points added along the two sides of a sharp corner
+    /// stay level with each other instead of cutting it into ever thinner pieces.
+    fn split_side(&mut self, triangle: usize, side: usize) -> Vec<usize> {
+        let start = self.triangles[triangle].points[(side + 1) % 3];
+        let end = self.triangles[triangle].points[(side + 2) % 3];
+        let (from, to) = (self.points[start as usize], self.points[end as usize]);
+        let length = (to - from).magnitude();
+        let shell = |length: f64| 2.0f64.powf((length / 2.0).log2().round());
+        let (point, around) = match ((start as usize) < self.given, (end as usize) < self.given) {
+            (true, false) => (from + (to - from) * (shell(length) / length), Some(start)),
+            (false, true) => (to + (from - to) * (shell(length) / length), Some(end)),
+            _ => (from + (to - from) / 2.0, None),
+        };
+        let cavity = self.cavity(triangle, point);
+        let added = self.insert(point, &cavity, Some((start, end)));
+        self.shells[self.points.len() - 1] = around;
+        added
+    }
+
+    /// Replaces the triangles of a cavity with a fan of triangles around a new
+    /// point, and returns them
+    ///
+    /// If the point is being added on a side of the hull, that side is left out.
+    fn insert(&mut self, point: Point2<f64>, cavity: &[usize], split: Option<(u32, u32)>) -> Vec<usize> {
+        let new_point = self.points.len() as u32;
+        self.points.push(point);
+        self.shells.push(None);
+
+        // Edges around the cavity, along with the triangle on the other side
+        let mut edges = Vec::new();
+        for &triangle in cavity {
+            let (points, neighbours) = (self.triangles[triangle].points, self.triangles[triangle].neighbours);
+            for side in 0..3 {
+                let outside = neighbours[side].filter(|&TriangleId(neighbour)| !cavity.contains(&(neighbour as usize)));
+                let edge = (points[(side + 1) % 3], points[(side + 2) % 3]);
+                if (neighbours[side].is_none() || outside.is_some()) && split != Some(edge) {
+                    edges.push((edge, outside));
+                }
+            }
+        }
+
+        for &triangle in cavity {
+            self.removed[triangle] = true;
+            self.free.push(triangle);
+        }
+        let slots = edges.iter().map(|_| {
+            let slot = self.free.pop().unwrap_or(self.triangles.len());
+            if slot == self.triangles.len() {
+                self.triangles.push(Triangle { points: [0; 3], neighbours: [None; 3] });
+                self.removed.push(false);
+            }
+            self.removed[slot] = false;
+            slot
+        }).collect::<Vec<_>>();
+
+        for (index, &((start, end), outside)) in edges.iter().enumerate() {
+            let slot = slots[index];
+            let after = edges.iter().position(|&((other_start, _), _)| other_start == end).map(|other| TriangleId(slots[other] as u32));
+            let before = edges.iter().position(|&((_, other_end), _)| other_end == start).map(|other| TriangleId(slots[other] as u32));
+            self.triangles[slot] = Triangle {
+                points: [new_point, start, end],
+                neighbours: [outside, after, before],
+            };
+
+            if let Some(TriangleId(outside)) = outside {
+                let neighbour = &mut self.triangles[outside as usize];
+                let side = (0..3).find(|&side| neighbour.points[(side + 1) % 3] == end && neighbour.points[(side + 2) % 3] == start).unwrap();
+                neighbour.neighbours[side] = Some(TriangleId(slot as u32));
+            }
+        }
+
+        slots
+    }
+
+    fn into_triangulation<T: BaseFloat>(self) -> Triangulation<T> {
+        let mut ids = vec![None; self.triangles.len()];
+        let mut count = 0;
+        for (index, &removed) in self.removed.iter().enumerate() {
+            if !removed {
+                ids[index] = Some(TriangleId(count));
+                count += 1;
+            }
+        }
+
+        let triangles = self.triangles.into_iter().zip(&self.removed)
+            .filter(|&(_, &removed)| !removed)
+            .map(|(triangle, _)| Triangle {
+                points: triangle.points,
+                neighbours: triangle.neighbours.map(|neighbour| neighbour.and_then(|TriangleId(index)| ids[index as usize])),
+            })
+            .collect();
+
+        Triangulation {
+            points: self.points.into_iter().map(|point| point.cast::<T>()).collect(),
+            triangles,
+        }
+    }
+}
@@ -0,0 +1,115 @@
+use cgmath::{Point2, BaseFloat};
+
+use diagram::{Diagram, FaceId, HalfEdgeId, RegionId};
+use proximity::neighbour_pairs;
+
+/// The region of every face, see `Diagram::group_faces`
+pub fn group_faces<T: BaseFloat, F: Fn(FaceId, FaceId) -> bool>(diagram: &Diagram<T>, same_region: F) -> Vec<RegionId> {
+    fn find(trees: &mut [u32], face: u32) -> u32 {
+        let mut face = face;
+        while trees[face as usize] != face {
+            let parent = trees[face as usize];
+            trees[face as usize] = trees[parent as usize];
+            face = parent;
+        }
+        face
+    }
+
+    // Each tree is rooted at its face with the lowest id, so numbering the roots
+    // in order numbers the regions by their first face
+    let mut trees = (0..diagram.faces.len() as u32).collect::<Vec<_>>();
+    for (a, b) in neighbour_pairs(diagram) {
+        if same_region(a, b) {
+            let (a, b) = (find(&mut trees, a.0), find(&mut trees, b.0));
+            trees[a.max(b) as usize] = a.min(b);
+        }
+    }
+
+    let mut regions = vec![RegionId(0); trees.len()];
+    let mut count = 0;
+    for face in 0..trees.len() {
+        let root = find(&mut trees, face as u32) as usize;
+        regions[face] = if root == face {
+            count += 1;
+            RegionId(count - 1)
+        } else {
+            regions[root]
+        };
+    }
+    regions
+}
+
+/// The outlines of each region, see `Diagram::region_outlines`
+pub fn region_outlines<T: BaseFloat>(diagram: &Diagram<T>, regions: &[RegionId]) -> Vec<Vec<Vec<Point2<T>>>> {
+    let count = regions.iter().map(|region| region.0 as usize + 1).max().unwrap_or(0);
+    let mut outlines = vec![Vec::new(); count];
+    for (region, ring) in rings(diagram, |face_id| regions.get(face_id.0 as usize).map(|region| region.0)) {
+        outlines[region as usize].push(ring);
+    }
+    outlines
+}
+
+/// The outline of the union of some faces, see `Diagram::region_outline`
+pub fn region_outline<T: BaseFloat>(diagram: &Diagram<T>, faces: &[FaceId]) -> Vec<Vec<Point2<T>>> {
+    let mut inside = vec![false; diagram.faces.len()];
+    for face_id in faces {
+        if let Some(inside) = inside.get_mut(face_id.0 as usize) {
+            *inside = true;
+        }
+    }
+    rings(diagram, |face_id| inside.get(face_id.0 as usize).and_then(|&inside| if inside { Some(0) } else { None }))
+        .into_iter()
+        .map(|(_, ring)| ring)
+        .collect()
+}
+
+/// The rings around groups of faces, with the group of each
+///
+/// A half-edge is on the outline of its face's group if its twin is in another
+/// group, or none. Faces that `group` gives None for aren't in any group. The
+/// faces are on the left of each ring, so outer rings go counter-clockwise and
+/// the rings around holes go clockwise.
+fn rings<T: BaseFloat, F: Fn(FaceId) -> Option<u32>>(diagram: &Diagram<T>, group: F) -> Vec<(u32, Vec<Point2<T>>)> {
+    let halfedges = &diagram.halfedges;
+    let group_of = |halfedge_id: HalfEdgeId| group(halfedges[halfedge_id.0 as usize].incident_face);
+    let on_outline = |halfedge_id: HalfEdgeId| {
+        halfedges[halfedge_id.0 as usize].twin.is_none_or(|twin| group_of(twin) != group_of(halfedge_id))
+    };
+
+    let mut visited = vec![false; halfedges.len()];
+    let mut rings = Vec::new();
+    for index in 0..halfedges.len() {
+        let first = HalfEdgeId(index as u32);
+        let group = match group_of(first) {
+            Some(group) if !visited[index] && on_outline(first) => group,
+            _ => continue,
+        };
+
+        let mut ring = Vec::new();
+        let mut halfedge_id = first;
+        for _ in 0..halfedges.len() {
+            visited[halfedge_id.0 as usize] = true;
+            if let Some(origin) = halfedges[halfedge_id.0 as usize].origin {
+                ring.push(diagram.vertices[origin.0 as usize].coordinates);
+            }
+
+            // Turn round the end of the half-edge, across the edges inside the group,
+            // to the next one on the outline
+            let mut next = halfedges[halfedge_id.0 as usize].next;
+            for _ in 0..halfedges.len() {
+                if on_outline(next) {
+                    break;
+                }
+                let twin = halfedges[next.0 as usize].twin.unwrap_or(next);
+                next = halfedges[twin.0 as usize].next;
+            }
+
+            halfedge_id = next;
+            if halfedge_id == first || visited[halfedge_id.0 as usize] {
+                break;
+            }
+        }
+        rings.push((group, ring));
+    }
+    rings
+}
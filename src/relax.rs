@@ -0,0 +1,59 @@
+use cgmath::BaseFloat;
+
+use build::{DiagramBuilder, Rect, Site};
+use diagram::{Diagram, FaceId};
+
+/// Lloyd relaxation of a set of sites
+///
+/// Each step builds the diagram and moves every site to the centroid of its cell,
+/// which spreads the sites out more evenly. Sites outside the bounding rect don't
+/// have a cell and are dropped on the first step.
+#[derive(Debug, Clone)]
+pub struct Relaxation<T = f32> {
+    bounding_rect: Rect<T>,
+    sites: Vec<Site<T>>,
+}
+
+impl<T: BaseFloat> Relaxation<T> {
+    pub fn new(bounding_rect: Rect<T>, sites: Vec<Site<T>>) -> Relaxation<T> {
+        Relaxation {
+            bounding_rect,
+            sites,
+        }
+    }
+
+    pub fn sites(&self) -> &[Site<T>] {
+        &self.sites
+    }
+
+    pub fn into_sites(self) -> Vec<Site<T>> {
+        self.sites
+    }
+
+    /// Builds the diagram of the current sites
+    pub fn diagram(&self) -> Diagram<T> {
+        DiagramBuilder::new(self.bounding_rect, self.sites.clone()).finish()
+    }
+
+    pub fn step(&mut self) {
+        let diagram = self.diagram();
+
+        self.sites = diagram.faces.iter().enumerate().map(|(index, face)| {
+            Site {
+                position: diagram.face_centroid(FaceId(index as u32)).unwrap_or(face.site.position),
+                ..face.site
+            }
+        }).collect();
+    }
+}
+
+/// Runs the given number of iterations of Lloyd relaxation and returns the moved sites
+pub fn lloyd_relax<T: BaseFloat>(bounding_rect: Rect<T>, sites: Vec<Site<T>>, iterations: u32) -> Vec<Site<T>> {
+    let mut relaxation = Relaxation::new(bounding_rect, sites);
+
+    for _ in 0..iterations {
+        relaxation.step();
+    }
+
+    relaxation.into_sites()
+}
@@ -0,0 +1,48 @@
+//! Random points inside the cells of a diagram
+
+use cgmath::{Point2, BaseFloat};
+use rand::Rng;
+
+use diagram::{Diagram, FaceId};
+
+/// See `Diagram::sample_point_in_face`
+pub fn sample_point_in_face<T: BaseFloat, R: Rng + ?Sized>(diagram: &Diagram<T>, face_id: FaceId, rng: &mut R) -> Option<Point2<T>> {
+    let mut corners = Vec::new();
+    for halfedge_id in diagram.face_edges(face_id) {
+        let origin = diagram.get_half_edge(halfedge_id)?.origin?;
+        corners.push(diagram.get_vertex(origin)?.coordinates.cast::<f64>());
+    }
+
+    // Cells are convex, so they split into a fan of triangles around the first
+    // corner. Each is picked with a chance in proportion to its area
+    let first = *corners.first()?;
+    let triangles = corners.windows(2).skip(1)
+        .map(|pair| {
+            let (b, c) = (pair[0], pair[1]);
+            let area = ((b.x - first.x) * (c.y - first.y) - (c.x - first.x) * (b.y - first.y)).max(0.0);
+            (b, c, area)
+        })
+        .collect::<Vec<_>>();
+    let total = triangles.iter().map(|&(_, _, area)| area).sum::<f64>();
+    if total <= 0.0 || !total.is_finite() {
+        return None;
+    }
+
+    let mut target = rng.random::<f64>() * total;
+    let &(b, c, _) = triangles.iter()
+        .find(|&&(_, _, area)| {
+            target -= area;
+            target < 0.0
+        })
+        .unwrap_or(&triangles[triangles.len() - 1]);
+
+    // A point in the parallelogram on two sides of the triangle, folded back into
+    // the triangle if it lands in the other half
+    let (mut u, mut v) = (rng.random::<f64>(), rng.random::<f64>());
+    if u + v > 1.0 {
+        u = 1.0 - u;
+        v = 1.0 - v;
+    }
+    let point = first + (b - first) * u + (c - first) * v;
+    Some(point.cast::<T>())
+}
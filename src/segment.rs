@@ -0,0 +1,656 @@
+//! Diagrams of line segments as well as points
+//!
+//! The cell of a segment holds the points closer to some point of the segment than
+//! to any other site. The edge between the cells of a point and a segment is an
+//! arc of a parabola where the nearest point of the segment is inside it, and a
+//! straight line where it's one of the ends. All the other edges are straight.
+//!
+//! The cells are found by sampling points along each segment, building the
+//! diagram of all the points and merging the cells of the samples of each segment.
+//! The merged cells have many short edges where there should be curves, so each
+//! run of them between the same two parts of the sites becomes a single half-edge
+//! with its exact curve, and the vertices between them are moved to where the
+//! curves meet. Vertices can still be off where several sites are closer to each
+//! other than the spacing of the samples.
+
+use std::mem;
+
+use cgmath::{Point2, Vector2, InnerSpace, BaseFloat};
+use fnv::FnvHashMap;
+
+use build::{DiagramBuilder, Rect, Site};
+use diagram::{Diagram, Vertex, VertexId, HalfEdge, HalfEdgeId, Face, FaceId};
+
+/// Default number of samples along the longer side of the rect
+const SAMPLES_PER_EXTENT: f64 = 1024.0;
+
+/// A site of a `SegmentDiagram`
+///
+/// Segments must not cross each other or pass through point sites, but they can
+/// share ends, as the edges of a polygon do.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SegmentSite<T = f32> {
+    Point {
+        id: u32,
+        position: Point2<T>,
+    },
+    Segment {
+        id: u32,
+        start: Point2<T>,
+        end: Point2<T>,
+    },
+}
+
+/// The shape of an edge of a `SegmentDiagram`, between its origin and destination
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EdgeCurve<T = f32> {
+    Line,
+
+    /// An arc of the parabola of points as far from `focus` as from the line
+    /// through the two points of `directrix`
+    Parabola {
+        focus: Point2<T>,
+        directrix: (Point2<T>, Point2<T>),
+    },
+}
+
+impl<T: BaseFloat> EdgeCurve<T> {
+    /// Finds points along the curve between two points on it, splitting it into the
+    /// given number of pieces
+    ///
+    /// Lines are split evenly, and parabolas evenly along their directrix.
+    pub fn points(&self, start: Point2<T>, end: Point2<T>, pieces: usize) -> Vec<Point2<T>> {
+        let pieces = pieces.max(1);
+        let fraction = |index: usize| T::from(index).unwrap() / T::from(pieces).unwrap();
+
+        match *self {
+            EdgeCurve::Line => (0..pieces + 1).map(|index| start + (end - start) * fraction(index)).collect(),
+            EdgeCurve::Parabola { focus, directrix: (line_start, line_end) } => {
+                let direction = (line_end - line_start).normalize();
+                let mut normal = Vector2::new(-direction.y, direction.x);
+                if (focus - line_start).dot(normal) < T::zero() {
+                    normal = -normal;
+                }
+                let along = |point: Point2<T>| (point - line_start).dot(direction);
+                let (from, to) = (along(start), along(end));
+
+                (0..pieces + 1).map(|index| {
+                    if index == 0 {
+                        return start;
+                    }
+                    if index == pieces {
+                        return end;
+                    }
+
+                    // The point above the foot that is as far from the focus as from
+                    // the foot
+                    let foot = line_start + direction * (from + (to - from) * fraction(index));
+                    let offset = focus - foot;
+                    let height = offset.magnitude2() / ((T::one() + T::one()) * offset.dot(normal));
+                    foot + normal * height
+                }).collect()
+            }
+        }
+    }
+}
+
+impl<T: BaseFloat> SegmentSite<T> {
+    pub fn id(&self) -> u32 {
+        match *self {
+            SegmentSite::Point { id, .. } | SegmentSite::Segment { id, .. } => id,
+        }
+    }
+
+    /// The position of a point, or the middle of a segment
+    pub fn center(&self) -> Point2<T> {
+        match *self {
+            SegmentSite::Point { position, .. } => position,
+            SegmentSite::Segment { start, end, .. } => {
+                let two = T::one() + T::one();
+                Point2::new((start.x + end.x) / two, (start.y + end.y) / two)
+            }
+        }
+    }
+
+    /// Distance from a point to the nearest point of the site
+    pub fn distance(&self, point: Point2<T>) -> T {
+        match *self {
+            SegmentSite::Point { position, .. } => (point - position).magnitude(),
+            SegmentSite::Segment { start, end, .. } => {
+                let direction = end - start;
+                let length2 = direction.magnitude2();
+                let t = if length2 > T::zero() {
+                    ((point - start).dot(direction) / length2).max(T::zero()).min(T::one())
+                } else {
+                    T::zero()
+                };
+                (point - (start + direction * t)).magnitude()
+            }
+        }
+    }
+}
+
+/// Builds the diagram of a mix of points and line segments
+pub struct SegmentDiagramBuilder<T = f32> {
+    rect: Rect<T>,
+    sites: Vec<SegmentSite<T>>,
+    spacing: Option<T>,
+}
+
+/// A diagram built by `SegmentDiagramBuilder`
+///
+/// Faces are in the same order as the sites they were built from, leaving out
+/// sites outside the rect. The site of each face has the id of its `SegmentSite`
+/// and is at its centre, so `Diagram::locate` and the other searches of the
+/// diagram only work for points. A segment whose cell is cut in two by the rect has
+/// a second face for the other piece, after the rest.
+#[derive(Debug, Clone)]
+pub struct SegmentDiagram<T = f32> {
+    diagram: Diagram<T>,
+
+    /// The site of each face
+    sites: Vec<SegmentSite<T>>,
+
+    /// The curve of each half-edge
+    curves: Vec<EdgeCurve<T>>,
+}
+
+/// Part of a site, which is the nearest part of it to some points
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Part {
+    /// A point site or one end of a segment
+    Point(Point2<f64>),
+
+    /// The inside of a segment
+    Line(Point2<f64>, Point2<f64>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Feature {
+    /// Index of the site
+    site: usize,
+    part: Part,
+}
+
+/// A condition that a vertex must meet
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Constraint {
+    /// Being as far from one feature as the other
+    Between(Feature, Feature),
+
+    /// Being on one side of the rect, where coordinate `axis` is `value`
+    Side(usize, f64),
+}
+
+impl<T: BaseFloat> SegmentDiagramBuilder<T> {
+    pub fn new(rect: Rect<T>, sites: Vec<SegmentSite<T>>) -> SegmentDiagramBuilder<T> {
+        SegmentDiagramBuilder {
+            rect,
+            sites,
+            spacing: None,
+        }
+    }
+
+    /// Sets the distance between the samples along each segment
+    ///
+    /// This defaults to 1/1024 of the longer side of the rect. Closer samples give
+    /// better vertices between sites that are close together, but take longer.
+    pub fn set_spacing(&mut self, spacing: T) {
+        self.spacing = Some(spacing);
+    }
+
+    pub fn finish(self) -> SegmentDiagram<T> {
+        let rect: Rect<f64> = Rect {
+            position: self.rect.position.cast(),
+            size: self.rect.size.cast(),
+        };
+        let extent = rect.size.x.max(rect.size.y);
+        let spacing = self.spacing
+            .and_then(|spacing| spacing.to_f64())
+            .filter(|&spacing| spacing > 0.0)
+            .unwrap_or(extent / SAMPLES_PER_EXTENT);
+
+        // Each sample has the index of its site. Ends shared by several segments are
+        // only sampled for the first of them.
+        let sites = self.sites.iter().map(|site| match *site {
+            SegmentSite::Point { id, position } => SegmentSite::Point { id, position: position.cast() },
+            SegmentSite::Segment { id, start, end } => SegmentSite::Segment { id, start: start.cast(), end: end.cast() },
+        }).collect::<Vec<SegmentSite<f64>>>();
+        let mut samples = Vec::new();
+        let mut sample_sites = Vec::new();
+        let mut positions = FnvHashMap::default();
+        for (index, site) in sites.iter().enumerate() {
+            let positions_on_site = match *site {
+                SegmentSite::Point { position, .. } => vec![position],
+                SegmentSite::Segment { start, end, .. } => {
+                    let pieces = ((end - start).magnitude() / spacing).ceil().max(2.0) as usize;
+                    (0..pieces + 1).map(|piece| start + (end - start) * (piece as f64 / pieces as f64)).collect()
+                }
+            };
+            for position in positions_on_site {
+                if positions.insert((position.x.to_bits(), position.y.to_bits()), ()).is_none() {
+                    sample_sites.push(Site::new(samples.len() as u32, position));
+                    samples.push(index);
+                }
+            }
+        }
+
+        let sampled = DiagramBuilder::new(rect, sample_sites).finish();
+        let face_sites = sampled.faces.iter().map(|face| samples[face.site.id as usize]).collect::<Vec<_>>();
+        let (diagram, keys) = merge(&sampled, &face_sites, &sites, &rect);
+        let diagram = refine(diagram, &keys, &rect, spacing);
+
+        // Faces get the site they came from, in the same order as the sites, with the
+        // second pieces of any cells cut in two at the end
+        let mut face_ids = vec![None; diagram.faces.len()];
+        let mut has_face = vec![false; sites.len()];
+        let mut order = (0..diagram.faces.len()).map(|index| {
+            let site = diagram.faces[index].site.id as usize;
+            (mem::replace(&mut has_face[site], true), site, index)
+        }).collect::<Vec<_>>();
+        order.sort();
+        let order = order.into_iter().map(|(_, _, index)| index).collect::<Vec<_>>();
+        for (new_index, &index) in order.iter().enumerate() {
+            face_ids[index] = Some(FaceId(new_index as u32));
+        }
+
+        let mut output = Diagram {
+            vertices: diagram.vertices.iter().map(|vertex| Vertex {
+                coordinates: vertex.coordinates.cast(),
+                incident_edge: vertex.incident_edge,
+            }).collect(),
+            halfedges: diagram.halfedges.iter().map(|halfedge| HalfEdge {
+                incident_face: face_ids[halfedge.incident_face.0 as usize].unwrap(),
+                ..halfedge.clone()
+            }).collect(),
+            faces: Vec::new(),
+            ..Diagram::default()
+        };
+        let mut sites = Vec::new();
+        for &index in &order {
+            let site = self.sites[diagram.faces[index].site.id as usize];
+            sites.push(site);
+            output.faces.push(Face {
+                first_halfedge: diagram.faces[index].first_halfedge,
+                site: Site::new(site.id(), site.center()),
+            });
+        }
+        output.index_sites(false);
+
+        // The edge between a segment and one of its ends, which can be shared with
+        // another segment, is straight
+        let curves = keys.iter().map(|key| match *key {
+            Some((Feature { part: Part::Point(focus), .. }, Feature { part: Part::Line(start, end), .. })) |
+            Some((Feature { part: Part::Line(start, end), .. }, Feature { part: Part::Point(focus), .. })) if focus != start && focus != end => {
+                EdgeCurve::Parabola {
+                    focus: focus.cast(),
+                    directrix: (start.cast(), end.cast()),
+                }
+            }
+            _ => EdgeCurve::Line,
+        }).collect();
+
+        SegmentDiagram {
+            diagram: output,
+            sites,
+            curves,
+        }
+    }
+}
+
+/// Merges the cells of the samples of each site, joining each run of half-edges
+/// between the same two features into one
+///
+/// The faces of the merged diagram have the index of their site as the id of
+/// their site. Also returns the features on the left and right of each half-edge,
+/// or None for half-edges along the rect. The sampled diagram is clipped to the
+/// rect, so every half-edge in it has an origin.
+fn merge(sampled: &Diagram<f64>, face_sites: &[usize], sites: &[SegmentSite<f64>], rect: &Rect<f64>) -> (Diagram<f64>, Vec<Option<(Feature, Feature)>>) {
+    let halfedges = &sampled.halfedges;
+    let site_of = |halfedge: HalfEdgeId| face_sites[halfedges[halfedge.0 as usize].incident_face.0 as usize];
+    let inside_cell = |halfedge: HalfEdgeId| {
+        halfedges[halfedge.0 as usize].twin.is_some_and(|twin| site_of(twin) == site_of(halfedge))
+    };
+
+    // The next half-edge around a merged cell turns around the destination through
+    // the half-edges inside the cell
+    let next = |halfedge: HalfEdgeId| {
+        let mut next = halfedges[halfedge.0 as usize].next;
+        while inside_cell(next) {
+            next = halfedges[halfedges[next.0 as usize].twin.unwrap().0 as usize].next;
+        }
+        next
+    };
+    let position = |halfedge: HalfEdgeId| sampled.vertices[halfedges[halfedge.0 as usize].origin.unwrap().0 as usize].coordinates;
+
+    // The parts of the sites on either side are the ones nearest the middle of the
+    // half-edge, rather than the ones the samples are on, as the cells of samples
+    // near the end of a segment reach a little past the end
+    let key = |halfedge: HalfEdgeId| {
+        let twin = halfedges[halfedge.0 as usize].twin?;
+        let (start, end) = (position(halfedge), position(twin));
+        let middle = start + (end - start) * 0.5;
+        let feature = |site: usize| Feature { site, part: nearest_part(&sites[site], middle) };
+        Some((feature(site_of(halfedge)), feature(site_of(twin))))
+    };
+    let side = |halfedge: HalfEdgeId| rect_side(position(halfedge), position(halfedges[halfedge.0 as usize].next), rect);
+
+    let kept = (0..halfedges.len()).map(|index| !inside_cell(HalfEdgeId(index as u32))).collect::<Vec<_>>();
+    let mut nexts = vec![None; halfedges.len()];
+    let mut prevs = vec![None; halfedges.len()];
+    for index in (0..halfedges.len()).filter(|&index| kept[index]) {
+        let next = next(HalfEdgeId(index as u32));
+        nexts[index] = Some(next);
+        prevs[next.0 as usize] = Some(HalfEdgeId(index as u32));
+    }
+
+    // A half-edge and the one after it are joined if they're between the same two
+    // features and nothing else meets where they do
+    let mut joined = vec![false; halfedges.len()];
+    for index in (0..halfedges.len()).filter(|&index| kept[index]) {
+        let halfedge = HalfEdgeId(index as u32);
+        let next = nexts[index].unwrap();
+        joined[index] = match (halfedges[index].twin, halfedges[next.0 as usize].twin) {
+            (Some(twin), Some(next_twin)) => key(halfedge) == key(next) && nexts[next_twin.0 as usize] == Some(twin),
+            (None, None) => site_of(halfedge) == site_of(next) && side(halfedge).is_some() && side(halfedge) == side(next),
+            _ => false,
+        };
+    }
+
+    // A cell that is a single run all the way round still needs a vertex, so the
+    // run is broken where it was first found, and its twin at the same place
+    let mut visited = vec![false; halfedges.len()];
+    let mut starts = Vec::new();
+    for pass in 0..2 {
+        for index in 0..halfedges.len() {
+            if !kept[index] || visited[index] {
+                continue;
+            }
+            let prev = prevs[index].unwrap();
+            if joined[prev.0 as usize] {
+                if pass == 0 {
+                    continue;
+                }
+                joined[prev.0 as usize] = false;
+                if let Some(twin) = halfedges[index].twin {
+                    joined[twin.0 as usize] = false;
+                }
+            }
+
+            starts.push(HalfEdgeId(index as u32));
+            let mut halfedge = HalfEdgeId(index as u32);
+            loop {
+                visited[halfedge.0 as usize] = true;
+                if !joined[halfedge.0 as usize] {
+                    break;
+                }
+                halfedge = nexts[halfedge.0 as usize].unwrap();
+            }
+        }
+    }
+
+    // Each run becomes one half-edge, starting where it starts
+    let mut run_ids = vec![None; halfedges.len()];
+    let mut lasts = Vec::with_capacity(starts.len());
+    for (run, &start) in starts.iter().enumerate() {
+        run_ids[start.0 as usize] = Some(HalfEdgeId(run as u32));
+        let mut last = start;
+        while joined[last.0 as usize] {
+            last = nexts[last.0 as usize].unwrap();
+        }
+        lasts.push(last);
+    }
+
+    let mut output = Diagram::default();
+    let mut vertex_ids = vec![None; sampled.vertices.len()];
+    let mut keys = Vec::with_capacity(starts.len());
+    for (run, (&start, &last)) in starts.iter().zip(&lasts).enumerate() {
+        let run_id = HalfEdgeId(run as u32);
+        let origin = halfedges[start.0 as usize].origin.unwrap().0 as usize;
+        let vertex = *vertex_ids[origin].get_or_insert_with(|| {
+            output.vertices.push(Vertex {
+                coordinates: sampled.vertices[origin].coordinates,
+                incident_edge: run_id,
+            });
+            VertexId(output.vertices.len() as u32 - 1)
+        });
+
+        let next = run_ids[nexts[last.0 as usize].unwrap().0 as usize].unwrap();
+        output.halfedges.push(HalfEdge {
+            origin: Some(vertex),
+            twin: halfedges[last.0 as usize].twin.map(|twin| run_ids[twin.0 as usize].unwrap()),
+            incident_face: FaceId(0),
+            next,
+            prev: run_id,
+        });
+        keys.push(key(start));
+    }
+    for index in 0..output.halfedges.len() {
+        let next = output.halfedges[index].next;
+        output.halfedges[next.0 as usize].prev = HalfEdgeId(index as u32);
+    }
+
+    // Each cycle is a face. A site usually has one, but can have more when the rect
+    // cuts its cell in two.
+    let mut face_visited = vec![false; output.halfedges.len()];
+    for index in 0..output.halfedges.len() {
+        if face_visited[index] {
+            continue;
+        }
+        let face_id = FaceId(output.faces.len() as u32);
+        output.faces.push(Face {
+            first_halfedge: Some(HalfEdgeId(index as u32)),
+            site: Site::new(site_of(starts[index]) as u32, Point2::new(0.0, 0.0)),
+        });
+
+        let mut halfedge = index;
+        while !face_visited[halfedge] {
+            face_visited[halfedge] = true;
+            output.halfedges[halfedge].incident_face = face_id;
+            halfedge = output.halfedges[halfedge].next.0 as usize;
+        }
+    }
+
+    (output, keys)
+}
+
+/// Finds the part of a site nearest to a point
+fn nearest_part(site: &SegmentSite<f64>, point: Point2<f64>) -> Part {
+    match *site {
+        SegmentSite::Point { position, .. } => Part::Point(position),
+        SegmentSite::Segment { start, end, .. } => {
+            let t = (point - start).dot(end - start) / (end - start).magnitude2();
+            if t <= 0.0 || start == end {
+                Part::Point(start)
+            } else if t >= 1.0 {
+                Part::Point(end)
+            } else {
+                Part::Line(start, end)
+            }
+        }
+    }
+}
+
+/// Finds which side of the rect a half-edge along it is on, going counter-clockwise
+fn rect_side(start: Point2<f64>, end: Point2<f64>, rect: &Rect<f64>) -> Option<usize> {
+    let direction = end - start;
+    let tolerance = rect.size.x.max(rect.size.y) * f64::EPSILON * 64.0;
+    let on = |axis: usize, value: f64| (start[axis] - value).abs() <= tolerance && (end[axis] - value).abs() <= tolerance;
+    let max = rect.position + rect.size;
+
+    if direction.x > 0.0 && on(1, rect.position.y) {
+        Some(0)
+    } else if direction.y > 0.0 && on(0, max.x) {
+        Some(1)
+    } else if direction.x < 0.0 && on(1, max.y) {
+        Some(2)
+    } else if direction.y < 0.0 && on(0, rect.position.x) {
+        Some(3)
+    } else {
+        None
+    }
+}
+
+/// Moves each vertex to where the curves of its edges meet
+///
+/// Vertices are found with Newton's method from where they are, and stay there if
+/// it doesn't settle close by. The diagram comes from `merge`, so every half-edge
+/// has an origin.
+fn refine(mut diagram: Diagram<f64>, keys: &[Option<(Feature, Feature)>], rect: &Rect<f64>, spacing: f64) -> Diagram<f64> {
+    let mut features = vec![Vec::new(); diagram.vertices.len()];
+    let mut sides = vec![Vec::new(); diagram.vertices.len()];
+    for (index, halfedge) in diagram.halfedges.iter().enumerate() {
+        let start = halfedge.origin.unwrap().0 as usize;
+        let end = diagram.halfedges[halfedge.next.0 as usize].origin.unwrap().0 as usize;
+        for &vertex in &[start, end] {
+            if let Some((left, right)) = keys[index] {
+                for &feature in &[left, right] {
+                    if !features[vertex].contains(&feature) {
+                        features[vertex].push(feature);
+                    }
+                }
+                continue;
+            }
+
+            let (from, to) = (diagram.vertices[start].coordinates, diagram.vertices[end].coordinates);
+            let side = match rect_side(from, to, rect) {
+                Some(0) => Constraint::Side(1, rect.position.y),
+                Some(1) => Constraint::Side(0, rect.position.x + rect.size.x),
+                Some(2) => Constraint::Side(1, rect.position.y + rect.size.y),
+                Some(_) => Constraint::Side(0, rect.position.x),
+                None => continue,
+            };
+            if !sides[vertex].contains(&side) {
+                sides[vertex].push(side);
+            }
+        }
+    }
+
+    let tolerance = rect.size.x.max(rect.size.y) * f64::EPSILON * 64.0;
+    for ((vertex, features), sides) in diagram.vertices.iter_mut().zip(features).zip(sides) {
+        let start = vertex.coordinates;
+
+        // The vertex is as far from each of the features around it as the others, so
+        // any two of those conditions that aren't parallel where it is fix it
+        let mut constraints = sides;
+        for (index, &a) in features.iter().enumerate() {
+            constraints.extend(features[index + 1..].iter().map(|&b| Constraint::Between(a, b)));
+        }
+        let gradient = |constraint: Constraint| evaluate(constraint, start).1;
+        let independent = |a: Vector2<f64>, b: Vector2<f64>| (a.x * b.y - a.y * b.x).abs() > 0.1 * a.magnitude() * b.magnitude();
+        let second = constraints.iter().enumerate().flat_map(|(index, &first)| {
+            constraints[index + 1..].iter().map(move |&second| (first, second))
+        }).find(|&(first, second)| independent(gradient(first), gradient(second)));
+        let (first, second) = match second {
+            Some(pair) => pair,
+            None => continue,
+        };
+
+        let mut point = start;
+        for _ in 0..32 {
+            let (f, a) = evaluate(first, point);
+            let (g, b) = evaluate(second, point);
+            let determinant = a.x * b.y - a.y * b.x;
+            if determinant == 0.0 {
+                break;
+            }
+            let step = Vector2::new(-(f * b.y - g * a.y) / determinant, -(a.x * g - b.x * f) / determinant);
+            point += step;
+            if step.magnitude() <= tolerance {
+                break;
+            }
+        }
+
+        let (f, _) = evaluate(first, point);
+        let (g, _) = evaluate(second, point);
+        let settled = f.abs() <= spacing * 1e-6 && g.abs() <= spacing * 1e-6;
+        let inside = point.x >= rect.position.x - tolerance && point.x <= rect.position.x + rect.size.x + tolerance &&
+            point.y >= rect.position.y - tolerance && point.y <= rect.position.y + rect.size.y + tolerance;
+        if settled && inside && (point - start).magnitude() <= spacing * 4.0 {
+            vertex.coordinates = point;
+        }
+    }
+
+    diagram
+}
+
+/// Distance from a point to part of a site, and its gradient
+fn distance(part: Part, point: Point2<f64>) -> (f64, Vector2<f64>) {
+    match part {
+        Part::Point(position) => {
+            let offset = point - position;
+            let distance = offset.magnitude();
+            let gradient = if distance > 0.0 { offset / distance } else { Vector2::new(0.0, 0.0) };
+            (distance, gradient)
+        }
+        Part::Line(start, end) => {
+            let direction = (end - start).normalize();
+            let normal = Vector2::new(-direction.y, direction.x);
+            let distance = (point - start).dot(normal);
+            (distance.abs(), if distance < 0.0 { -normal } else { normal })
+        }
+    }
+}
+
+/// How far a point is from meeting a constraint, and the gradient of that
+fn evaluate(constraint: Constraint, point: Point2<f64>) -> (f64, Vector2<f64>) {
+    match constraint {
+        Constraint::Side(axis, value) => {
+            let mut gradient = Vector2::new(0.0, 0.0);
+            gradient[axis] = 1.0;
+            (point[axis] - value, gradient)
+        }
+        Constraint::Between(mut a, mut b) => {
+            // Every point on the line through an end of a segment at right angles to
+            // it is as far from the end as from the segment
+            if let Part::Line(..) = a.part {
+                mem::swap(&mut a, &mut b);
+            }
+            if let (Part::Point(end), Part::Line(start, other_end)) = (a.part, b.part) {
+                if end == start || end == other_end {
+                    let direction = (other_end - start).normalize();
+                    return ((point - end).dot(direction), direction);
+                }
+            }
+
+            let (distance_a, gradient_a) = distance(a.part, point);
+            let (distance_b, gradient_b) = distance(b.part, point);
+            (distance_a - distance_b, gradient_a - gradient_b)
+        }
+    }
+}
+
+impl<T: BaseFloat> SegmentDiagram<T> {
+    pub fn diagram(&self) -> &Diagram<T> {
+        &self.diagram
+    }
+
+    pub fn into_diagram(self) -> Diagram<T> {
+        self.diagram
+    }
+
+    /// The site a face was built from
+    pub fn site(&self, face_id: FaceId) -> Option<&SegmentSite<T>> {
+        self.sites.get(face_id.0 as usize)
+    }
+
+    /// The shape of a half-edge
+    pub fn curve(&self, halfedge_id: HalfEdgeId) -> Option<&EdgeCurve<T>> {
+        self.curves.get(halfedge_id.0 as usize)
+    }
+
+    /// Finds points along a half-edge from its origin to its destination, splitting
+    /// it into the given number of pieces
+    pub fn edge_points(&self, halfedge_id: HalfEdgeId, pieces: usize) -> Vec<Point2<T>> {
+        let halfedge = match self.diagram.get_half_edge(halfedge_id) {
+            Some(halfedge) => halfedge,
+            None => return Vec::new(),
+        };
+        let (start, end) = match (halfedge.origin, self.diagram.halfedges[halfedge.next.0 as usize].origin) {
+            (Some(start), Some(end)) => (self.diagram.vertices[start.0 as usize].coordinates, self.diagram.vertices[end.0 as usize].coordinates),
+            _ => return Vec::new(),
+        };
+
+        self.curves[halfedge_id.0 as usize].points(start, end, pieces)
+    }
+}
@@ -0,0 +1,70 @@
+//! The geometry that the sweep and nearest site searches spend most of their time
+//! on, worked out for several arcs or sites at once, used when the "simd" feature
+//! is enabled
+//!
+//! Each lane does the same operations in the same order as the scalar code, and
+//! IEEE arithmetic rounds them the same way, so breakpoints and circles come out
+//! exactly as they would one at a time. Branches are worked out for every lane and
+//! the right result picked afterwards, which costs a few divisions whose results
+//! are thrown away but keeps the lanes together.
+
+use cgmath::Point2;
+#[cfg(not(feature = "exact"))]
+use wide::{CmpEq, CmpGt, CmpLe};
+use wide::{f64x2, f64x4};
+
+/// The x coordinates of two breakpoints between parabolas, see `intersection` in
+/// the beachline
+///
+/// The "exact" feature compares sites to breakpoints without working them out,
+/// so this isn't needed then.
+#[cfg(not(feature = "exact"))]
+pub(crate) fn breakpoints_x(left_foci: [Point2<f64>; 2], right_foci: [Point2<f64>; 2], directrix: f64) -> [f64; 2] {
+    let left_x = f64x2::new([left_foci[0].x, left_foci[1].x]);
+    let left_y = f64x2::new([left_foci[0].y, left_foci[1].y]);
+    let right_x = f64x2::new([right_foci[0].x, right_foci[1].x]);
+    let right_y = f64x2::new([right_foci[0].y, right_foci[1].y]);
+    let directrix = f64x2::splat(directrix);
+    let (one, two, four) = (f64x2::splat(1.0), f64x2::splat(2.0), f64x2::splat(4.0));
+
+    let z_left = two * (left_y - directrix);
+    let z_right = two * (right_y - directrix);
+    let a = one / z_left - one / z_right;
+    let b = -two * (left_x / z_left - right_x / z_right);
+    let c = left_x * left_x / z_left - right_x * right_x / z_right + (left_y - right_y) / two;
+    let discriminant = (b * b - four * a * c).abs().sqrt();
+    let rearranged = b.cmp_le(f64x2::ZERO) & (discriminant - b).cmp_gt(f64x2::ZERO);
+    let quadratic = rearranged.blend(two * c / (discriminant - b), (-b - discriminant) / (two * a));
+
+    // Later checks take priority, as they come first in the scalar version
+    let x = left_y.cmp_eq(directrix).blend(left_x, quadratic);
+    let x = right_y.cmp_eq(directrix).blend(right_x, x);
+    let x = left_y.cmp_eq(right_y).blend((left_x + right_x) / two, x);
+    x.to_array()
+}
+
+/// The centres of two circles through three points, relative to the first of
+/// them, given twice the orientation of each triple
+///
+/// See `circumcircle_of_points` in the beachline.
+pub(crate) fn circumcentres(triples: [[Point2<f64>; 3]; 2], d: [f64; 2]) -> [(f64, f64); 2] {
+    let [[a0, b0, c0], [a1, b1, c1]] = triples;
+    let bx = f64x2::new([b0.x, b1.x]) - f64x2::new([a0.x, a1.x]);
+    let by = f64x2::new([b0.y, b1.y]) - f64x2::new([a0.y, a1.y]);
+    let cx = f64x2::new([c0.x, c1.x]) - f64x2::new([a0.x, a1.x]);
+    let cy = f64x2::new([c0.y, c1.y]) - f64x2::new([a0.y, a1.y]);
+    let b2 = bx * bx + by * by;
+    let c2 = cx * cx + cy * cy;
+
+    let d = f64x2::new(d);
+    let x = ((cy * b2 - by * c2) / d).to_array();
+    let y = ((bx * c2 - cx * b2) / d).to_array();
+    [(x[0], y[0]), (x[1], y[1])]
+}
+
+/// Power distances from a point to four sites
+pub(crate) fn power_distances(point: Point2<f64>, x: [f64; 4], y: [f64; 4], weights: [f64; 4]) -> [f64; 4] {
+    let dx = f64x4::splat(point.x) - f64x4::new(x);
+    let dy = f64x4::splat(point.y) - f64x4::new(y);
+    (dx * dx + dy * dy - f64x4::new(weights)).to_array()
+}
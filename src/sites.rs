@@ -0,0 +1,254 @@
+//! Sites to build diagrams from, on a lattice or at random
+//!
+//! Each generator places its sites strictly inside a `Rect`, so none of them are
+//! dropped when the same rect is used as the clip region. Sites get ids counting
+//! up from zero, in the order they were placed. The random generators and
+//! `jitter` need the "rand" feature.
+
+#[cfg(feature = "rand")]
+use std::f64::consts::PI;
+
+#[cfg(feature = "rand")]
+use cgmath::InnerSpace;
+use cgmath::{Point2, Vector2, BaseFloat};
+#[cfg(feature = "rand")]
+use rand::Rng;
+
+use build::{Rect, Site};
+
+/// Number of tries at placing a new point around each point in `poisson_disk`,
+/// from Bridson's paper
+#[cfg(feature = "rand")]
+const POISSON_DISK_TRIES: usize = 30;
+
+/// Number of tries at moving a site in `jitter` before giving up on it
+#[cfg(feature = "rand")]
+const JITTER_TRIES: usize = 8;
+
+/// A rect in f64, or None if it has no area for sites to go in
+pub(crate) fn rect_f64<T: BaseFloat>(rect: Rect<T>) -> Option<(Point2<f64>, Vector2<f64>)> {
+    let (position, size) = (rect.position.cast::<f64>(), rect.size.cast::<f64>());
+    if size.x > 0.0 && size.y > 0.0 && size.x.is_finite() && size.y.is_finite() {
+        Some((position, size))
+    } else {
+        None
+    }
+}
+
+/// Whether a point is still strictly inside a rect after being turned back into
+/// the scalar type, which can round it onto an edge
+fn strictly_inside<T: BaseFloat>(rect: Rect<T>, point: Point2<T>) -> bool {
+    point.x > rect.position.x && point.y > rect.position.y
+        && point.x < rect.position.x + rect.size.x && point.y < rect.position.y + rect.size.y
+}
+
+/// The spacing in f64, or None if it isn't above zero
+fn spacing_f64<T: BaseFloat>(spacing: T) -> Option<f64> {
+    let spacing = spacing.to_f64().unwrap();
+    if spacing > 0.0 && spacing.is_finite() {
+        Some(spacing)
+    } else {
+        None
+    }
+}
+
+/// Sites at each of some points, leaving out any that rounding put on an edge
+pub(crate) fn sites_at<T: BaseFloat, I: IntoIterator<Item = Point2<f64>>>(rect: Rect<T>, points: I) -> Vec<Site<T>> {
+    points.into_iter()
+        .map(|point| point.cast::<T>())
+        .filter(|&point| strictly_inside(rect, point))
+        .enumerate()
+        .map(|(id, point)| Site::new(id as u32, point))
+        .collect()
+}
+
+/// Sites on a square grid, `spacing` apart, centred in a rect
+///
+/// The gap between the outer sites and the edge of the rect is between a half and
+/// a whole spacing, so the cells along the edge come out a bit bigger than the
+/// others rather than thin. Sites go row by row, from the bottom left. Returns no
+/// sites if the spacing isn't above zero.
+pub fn square_lattice<T: BaseFloat>(rect: Rect<T>, spacing: T) -> Vec<Site<T>> {
+    let ((position, size), spacing) = match (rect_f64(rect), spacing_f64(spacing)) {
+        (Some(rect), Some(spacing)) => (rect, spacing),
+        _ => return Vec::new(),
+    };
+
+    let columns = (size.x / spacing).floor() as usize;
+    let rows = (size.y / spacing).floor() as usize;
+    let start = position + Vector2::new(size.x - (columns as f64 - 1.0) * spacing, size.y - (rows as f64 - 1.0) * spacing) / 2.0;
+    sites_at(rect, (0..rows).flat_map(|row| {
+        (0..columns).map(move |column| start + Vector2::new(column as f64, row as f64) * spacing)
+    }))
+}
+
+/// Sites on a triangular grid, `spacing` apart, centred in a rect
+///
+/// Every site is the same distance from the six around it, so the cells are
+/// regular hexagons, with a point at the top and bottom. Rows are `spacing` times
+/// √3/2 apart, and every other one is shifted right by half a spacing. Sites go row
+/// by row, from the bottom left. Returns no sites if the spacing isn't above zero.
+pub fn hex_lattice<T: BaseFloat>(rect: Rect<T>, spacing: T) -> Vec<Site<T>> {
+    let ((position, size), spacing) = match (rect_f64(rect), spacing_f64(spacing)) {
+        (Some(rect), Some(spacing)) => (rect, spacing),
+        _ => return Vec::new(),
+    };
+
+    let row_spacing = spacing * 3f64.sqrt() / 2.0;
+    let columns = ((size.x - spacing / 2.0) / spacing).floor().max(0.0) as usize;
+    let rows = (size.y / row_spacing).floor() as usize;
+    let width = (columns as f64 - 1.0) * spacing + if rows > 1 { spacing / 2.0 } else { 0.0 };
+    let start = position + Vector2::new(size.x - width, size.y - (rows as f64 - 1.0) * row_spacing) / 2.0;
+    sites_at(rect, (0..rows).flat_map(|row| {
+        let shift = if row % 2 == 1 { spacing / 2.0 } else { 0.0 };
+        (0..columns).map(move |column| start + Vector2::new(column as f64 * spacing + shift, row as f64 * row_spacing))
+    }))
+}
+
+/// Moves each site in a random direction, by up to `radius`, so a lattice looks
+/// less regular
+///
+/// Sites are kept strictly inside the rect. One that doesn't land inside it after
+/// a few tries is left where it was.
+#[cfg(feature = "rand")]
+pub fn jitter<T: BaseFloat, R: Rng + ?Sized>(sites: &mut [Site<T>], rect: Rect<T>, radius: T, rng: &mut R) {
+    let radius = radius.to_f64().unwrap();
+    for site in sites {
+        let position = site.position.cast::<f64>();
+        for _ in 0..JITTER_TRIES {
+            // Uniform over the disk
+            let angle = rng.random::<f64>() * 2.0 * PI;
+            let distance = radius * rng.random::<f64>().sqrt();
+            let moved = (position + Vector2::new(angle.cos(), angle.sin()) * distance).cast::<T>();
+            if strictly_inside(rect, moved) {
+                site.position = moved;
+                break;
+            }
+        }
+    }
+}
+
+/// A point from an area, picked again until it lands strictly inside the rect
+#[cfg(feature = "rand")]
+fn point_in<T: BaseFloat, R: Rng + ?Sized>(rect: Rect<T>, position: Point2<f64>, size: Vector2<f64>, rng: &mut R) -> Point2<T> {
+    loop {
+        let point = Point2::new(position.x + rng.random::<f64>() * size.x, position.y + rng.random::<f64>() * size.y).cast::<T>();
+        if strictly_inside(rect, point) {
+            return point;
+        }
+    }
+}
+
+/// Sites spread uniformly over a rect
+///
+/// Returns no sites if the rect has no area.
+#[cfg(feature = "rand")]
+pub fn uniform<T: BaseFloat, R: Rng + ?Sized>(rect: Rect<T>, count: usize, rng: &mut R) -> Vec<Site<T>> {
+    let (position, size) = match rect_f64(rect) {
+        Some(rect) => rect,
+        None => return Vec::new(),
+    };
+
+    (0..count).map(|id| Site::new(id as u32, point_in(rect, position, size, rng))).collect()
+}
+
+/// One site in each cell of a grid over a rect, anywhere in its cell
+///
+/// This spreads sites out more evenly than `uniform`, without leaving the grid
+/// showing. Sites go row by row, from the bottom left. Returns no sites if the
+/// rect has no area.
+#[cfg(feature = "rand")]
+pub fn jittered_grid<T: BaseFloat, R: Rng + ?Sized>(rect: Rect<T>, columns: u32, rows: u32, rng: &mut R) -> Vec<Site<T>> {
+    let (position, size) = match rect_f64(rect) {
+        Some(rect) => rect,
+        None => return Vec::new(),
+    };
+
+    let cell = Vector2::new(size.x / columns as f64, size.y / rows as f64);
+    let mut sites = Vec::with_capacity(columns as usize * rows as usize);
+    for row in 0..rows {
+        for column in 0..columns {
+            let corner = position + Vector2::new(cell.x * column as f64, cell.y * row as f64);
+            sites.push(Site::new(sites.len() as u32, point_in(rect, corner, cell, rng)));
+        }
+    }
+    sites
+}
+
+/// Sites that are no closer than `radius` to each other, packed in until there's
+/// no room for more
+///
+/// This is Bridson's algorithm. The sites look random but have no clumps or big
+/// gaps, so the cells all come out around the same size. Returns no sites if the
+/// rect has no area or the radius isn't above zero.
+#[cfg(feature = "rand")]
+pub fn poisson_disk<T: BaseFloat, R: Rng + ?Sized>(rect: Rect<T>, radius: T, rng: &mut R) -> Vec<Site<T>> {
+    let (position, size) = match rect_f64(rect) {
+        Some(rect) => rect,
+        None => return Vec::new(),
+    };
+    let radius = radius.to_f64().unwrap();
+    if radius <= 0.0 || !radius.is_finite() {
+        return Vec::new();
+    }
+
+    // Cells of the grid are small enough to hold at most one point each
+    let cell = radius / 2f64.sqrt();
+    let columns = (size.x / cell).ceil() as usize;
+    let rows = (size.y / cell).ceil() as usize;
+    let mut grid = vec![None; columns * rows];
+    let cell_of = |point: Point2<f64>| {
+        let column = (((point.x - position.x) / cell) as usize).min(columns - 1);
+        let row = (((point.y - position.y) / cell) as usize).min(rows - 1);
+        (column, row)
+    };
+
+    let mut points: Vec<Point2<f64>> = Vec::new();
+    let mut sites = Vec::new();
+    let mut active = Vec::new();
+    let mut next = Some(point_in(rect, position, size, rng));
+    loop {
+        if let Some(site) = next.take() {
+            let point = site.cast::<f64>();
+            let (column, row) = cell_of(point);
+            grid[row * columns + column] = Some(points.len());
+            active.push(points.len());
+            points.push(point);
+            sites.push(Site::new(sites.len() as u32, site));
+        }
+        if active.is_empty() {
+            break;
+        }
+
+        let index = rng.random_range(0..active.len());
+        let around = points[active[index]];
+        for _ in 0..POISSON_DISK_TRIES {
+            // Uniform over the ring between one and two radii away
+            let angle = rng.random::<f64>() * 2.0 * PI;
+            let distance = radius * (1.0 + 3.0 * rng.random::<f64>()).sqrt();
+            let site = (around + Vector2::new(angle.cos(), angle.sin()) * distance).cast::<T>();
+            if !strictly_inside(rect, site) {
+                continue;
+            }
+
+            // Points are checked as they'll be stored, after rounding
+            let candidate = site.cast::<f64>();
+            let (column, row) = cell_of(candidate);
+            let clear = (row.saturating_sub(2)..(row + 3).min(rows)).all(|row| {
+                (column.saturating_sub(2)..(column + 3).min(columns)).all(|column| {
+                    grid[row * columns + column].is_none_or(|other: usize| (points[other] - candidate).magnitude2() >= radius * radius)
+                })
+            });
+            if clear {
+                next = Some(site);
+                break;
+            }
+        }
+
+        if next.is_none() {
+            active.swap_remove(index);
+        }
+    }
+
+    sites
+}
@@ -0,0 +1,34 @@
+//! Rounded copies of the polygons of cells, by cutting their corners off
+
+use cgmath::{Point2, BaseFloat};
+use cgmath::num_traits::cast;
+
+use diagram::{Diagram, FaceId};
+use mesh::closed_polygon;
+
+/// One round of Chaikin's algorithm, which replaces each edge with the middle half
+/// of it
+fn cut_corners<T: BaseFloat>(polygon: &[Point2<T>]) -> Vec<Point2<T>> {
+    let quarter: T = cast(0.25).unwrap();
+    let mut cut = Vec::with_capacity(polygon.len() * 2);
+    for (index, &start) in polygon.iter().enumerate() {
+        let edge = polygon[(index + 1) % polygon.len()] - start;
+        cut.push(start + edge * quarter);
+        cut.push(start + edge * (T::one() - quarter));
+    }
+    cut
+}
+
+/// See `Diagram::smooth_cells`
+pub fn smooth_cells<T: BaseFloat>(diagram: &Diagram<T>, iterations: u32) -> Vec<Vec<Point2<T>>> {
+    (0..diagram.faces.len()).map(|index| {
+        let mut polygon = closed_polygon(diagram, FaceId(index as u32)).unwrap_or_default();
+        if polygon.len() < 3 {
+            return polygon;
+        }
+        for _ in 0..iterations {
+            polygon = cut_corners(&polygon);
+        }
+        polygon
+    }).collect()
+}
@@ -0,0 +1,189 @@
+//! A diagram with every field of its vertices, half-edges and faces in a list of
+//! its own
+//!
+//! `Diagram` keeps a list of structs for each of them, which is easy to build and
+//! edit. A walk around a face only follows `next`, though, and still pulls the
+//! rest of every half-edge it passes into the cache. Here each field is its own
+//! contiguous list of plain numbers, so a walk only reads the list it needs, and
+//! the lists can be uploaded to a GPU as storage buffers or written out without
+//! converting them.
+//!
+//! Links are u32 indices into the other lists, with `NO_INDEX` where a link is
+//! missing, like the origin of a half-edge that starts at infinity.
+
+use cgmath::{Point2, BaseFloat};
+#[cfg(feature = "serde")]
+use serde::{Serialize, Deserialize};
+
+use build::{Metric, Site};
+use diagram::{Diagram, Face, FaceId, HalfEdge, HalfEdgeId, Vertex, VertexId};
+
+/// Value of a link that isn't there
+pub const NO_INDEX: u32 = u32::MAX;
+
+/// A diagram stored as a struct of lists, see `Diagram::to_soa`
+///
+/// Lists of the same thing are the same length and indexed by its id.
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct DiagramSoa<T = f32> {
+    pub vertex_x: Vec<T>,
+    pub vertex_y: Vec<T>,
+    pub vertex_incident_edge: Vec<u32>,
+
+    /// Vertex each half-edge starts at, or `NO_INDEX` if it comes from infinity
+    pub halfedge_origin: Vec<u32>,
+
+    /// Twin of each half-edge, or `NO_INDEX` if it doesn't have one
+    pub halfedge_twin: Vec<u32>,
+    pub halfedge_face: Vec<u32>,
+    pub halfedge_next: Vec<u32>,
+    pub halfedge_prev: Vec<u32>,
+
+    /// A half-edge of each face, or `NO_INDEX` if it doesn't have any
+    pub face_first_halfedge: Vec<u32>,
+    pub site_id: Vec<u32>,
+    pub site_x: Vec<T>,
+    pub site_y: Vec<T>,
+    pub site_weight: Vec<T>,
+
+    /// Whether the weights of the sites are used to find the face a point is in,
+    /// as in a power diagram
+    pub weighted: bool,
+
+    /// Metric the cells were built under
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub metric: Metric,
+}
+
+fn index(link: Option<u32>) -> u32 {
+    link.unwrap_or(NO_INDEX)
+}
+
+pub(crate) fn link(index: u32) -> Option<u32> {
+    if index == NO_INDEX {
+        None
+    } else {
+        Some(index)
+    }
+}
+
+impl<T: BaseFloat> DiagramSoa<T> {
+    pub fn vertex_count(&self) -> usize {
+        self.vertex_x.len()
+    }
+
+    pub fn halfedge_count(&self) -> usize {
+        self.halfedge_next.len()
+    }
+
+    pub fn face_count(&self) -> usize {
+        self.face_first_halfedge.len()
+    }
+
+    pub fn coordinates(&self, vertex_id: VertexId) -> Option<Point2<T>> {
+        let index = vertex_id.0 as usize;
+        Some(Point2::new(*self.vertex_x.get(index)?, *self.vertex_y.get(index)?))
+    }
+
+    pub fn origin(&self, halfedge_id: HalfEdgeId) -> Option<VertexId> {
+        link(*self.halfedge_origin.get(halfedge_id.0 as usize)?).map(VertexId)
+    }
+
+    pub fn twin(&self, halfedge_id: HalfEdgeId) -> Option<HalfEdgeId> {
+        link(*self.halfedge_twin.get(halfedge_id.0 as usize)?).map(HalfEdgeId)
+    }
+
+    pub fn next(&self, halfedge_id: HalfEdgeId) -> Option<HalfEdgeId> {
+        self.halfedge_next.get(halfedge_id.0 as usize).map(|&next| HalfEdgeId(next))
+    }
+
+    pub fn prev(&self, halfedge_id: HalfEdgeId) -> Option<HalfEdgeId> {
+        self.halfedge_prev.get(halfedge_id.0 as usize).map(|&prev| HalfEdgeId(prev))
+    }
+
+    pub fn incident_face(&self, halfedge_id: HalfEdgeId) -> Option<FaceId> {
+        self.halfedge_face.get(halfedge_id.0 as usize).map(|&face| FaceId(face))
+    }
+
+    /// Iterates over the half-edges around a face in counter-clockwise order, like
+    /// `Diagram::face_edges`
+    ///
+    /// The walk stops after visiting as many half-edges as there are, so links that
+    /// don't go back round to the start can't make it go on forever.
+    pub fn face_edges(&self, face_id: FaceId) -> impl Iterator<Item = HalfEdgeId> + '_ {
+        let first = self.face_first_halfedge.get(face_id.0 as usize).and_then(|&first| link(first)).map(HalfEdgeId);
+        let mut current = first;
+        (0..self.halfedge_count()).map_while(move |_| {
+            let halfedge_id = current?;
+            current = self.next(halfedge_id).filter(|&next| Some(next) != first);
+            Some(halfedge_id)
+        })
+    }
+
+    /// Builds the diagram back from the lists
+    ///
+    /// Lists of the same thing that aren't the same length are cut down to the
+    /// shortest one.
+    pub fn to_diagram(&self) -> Diagram<T> {
+        let vertices = self.vertex_x.iter().zip(&self.vertex_y).zip(&self.vertex_incident_edge)
+            .map(|((&x, &y), &incident_edge)| Vertex {
+                coordinates: Point2::new(x, y),
+                incident_edge: HalfEdgeId(incident_edge),
+            })
+            .collect();
+
+        let halfedges = self.halfedge_origin.iter().zip(&self.halfedge_twin).zip(&self.halfedge_face).zip(&self.halfedge_next).zip(&self.halfedge_prev)
+            .map(|((((&origin, &twin), &face), &next), &prev)| HalfEdge {
+                origin: link(origin).map(VertexId),
+                twin: link(twin).map(HalfEdgeId),
+                incident_face: FaceId(face),
+                next: HalfEdgeId(next),
+                prev: HalfEdgeId(prev),
+            })
+            .collect();
+
+        let faces = self.face_first_halfedge.iter().zip(&self.site_id).zip(self.site_x.iter().zip(&self.site_y)).zip(&self.site_weight)
+            .map(|(((&first_halfedge, &id), (&x, &y)), &weight)| Face {
+                first_halfedge: link(first_halfedge).map(HalfEdgeId),
+                site: Site {
+                    id,
+                    position: Point2::new(x, y),
+                    weight,
+                },
+            })
+            .collect();
+
+        let mut diagram = Diagram {
+            vertices,
+            halfedges,
+            faces,
+            metric: self.metric,
+            ..Diagram::default()
+        };
+        diagram.index_sites(self.weighted);
+        diagram
+    }
+}
+
+/// See `Diagram::to_soa`
+pub fn to_soa<T: BaseFloat>(diagram: &Diagram<T>) -> DiagramSoa<T> {
+    let (vertices, halfedges, faces) = (&diagram.vertices, &diagram.halfedges, &diagram.faces);
+    DiagramSoa {
+        vertex_x: vertices.iter().map(|vertex| vertex.coordinates.x).collect(),
+        vertex_y: vertices.iter().map(|vertex| vertex.coordinates.y).collect(),
+        vertex_incident_edge: vertices.iter().map(|vertex| vertex.incident_edge.0).collect(),
+        halfedge_origin: halfedges.iter().map(|halfedge| index(halfedge.origin.map(|origin| origin.0))).collect(),
+        halfedge_twin: halfedges.iter().map(|halfedge| index(halfedge.twin.map(|twin| twin.0))).collect(),
+        halfedge_face: halfedges.iter().map(|halfedge| halfedge.incident_face.0).collect(),
+        halfedge_next: halfedges.iter().map(|halfedge| halfedge.next.0).collect(),
+        halfedge_prev: halfedges.iter().map(|halfedge| halfedge.prev.0).collect(),
+        face_first_halfedge: faces.iter().map(|face| index(face.first_halfedge.map(|halfedge| halfedge.0))).collect(),
+        site_id: faces.iter().map(|face| face.site.id).collect(),
+        site_x: faces.iter().map(|face| face.site.position.x).collect(),
+        site_y: faces.iter().map(|face| face.site.position.y).collect(),
+        site_weight: faces.iter().map(|face| face.site.weight).collect(),
+        weighted: diagram.site_index.is_weighted(),
+        metric: diagram.metric,
+    }
+}
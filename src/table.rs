@@ -0,0 +1,95 @@
+//! Tables of the edges and cells of a diagram as delimited text, for loading
+//! into a data frame
+//!
+//! Each table starts with a row of column names. Numbers are written at the
+//! precision of the diagram and no field ever needs quoting, so the tables can be
+//! read with a plain split on the separator.
+
+use std::io::{self, BufWriter, Write};
+use std::mem;
+
+use cgmath::{Point2, BaseFloat};
+
+use diagram::{Diagram, Endpoint, FaceId, HalfEdgeId};
+
+fn write_number<T: BaseFloat, W: Write>(writer: &mut W, value: T) -> io::Result<()> {
+    if mem::size_of::<T>() > 4 {
+        write!(writer, "{}", value.to_f64().unwrap())
+    } else {
+        write!(writer, "{}", value.to_f32().unwrap())
+    }
+}
+
+fn write_point<T: BaseFloat, W: Write>(writer: &mut W, point: Point2<T>, separator: char) -> io::Result<()> {
+    write_number(writer, point.x)?;
+    write!(writer, "{}", separator)?;
+    write_number(writer, point.y)
+}
+
+/// See `Diagram::write_edge_table`
+pub fn write_edge_table<T: BaseFloat, W: Write>(diagram: &Diagram<T>, writer: W, separator: char) -> io::Result<()> {
+    let mut writer = BufWriter::new(writer);
+    let columns = ["site_a", "site_b", "x1", "y1", "x2", "y2"];
+    writeln!(writer, "{}", columns.join(&separator.to_string()))?;
+
+    for (index, halfedge) in diagram.halfedges.iter().enumerate() {
+        // Each edge is written once, from the half-edge with the lower id
+        let twin = match halfedge.twin {
+            Some(twin) if (twin.0 as usize) < index => continue,
+            twin => twin.and_then(|twin| diagram.get_half_edge(twin)),
+        };
+        let halfedge_id = HalfEdgeId(index as u32);
+        let (start, end) = match (diagram.origin(halfedge_id), diagram.destination(halfedge_id)) {
+            (Some(Endpoint::Vertex(start)), Some(Endpoint::Vertex(end))) => (start, end),
+            _ => continue,
+        };
+        let (start, end) = match (diagram.get_vertex(start), diagram.get_vertex(end)) {
+            (Some(start), Some(end)) => (start.coordinates, end.coordinates),
+            _ => continue,
+        };
+        let site = |face_id: FaceId| diagram.faces.get(face_id.0 as usize).map(|face| face.site.id);
+
+        if let Some(site) = site(halfedge.incident_face) {
+            write!(writer, "{}", site)?;
+        }
+        write!(writer, "{}", separator)?;
+        if let Some(site) = twin.and_then(|twin| site(twin.incident_face)) {
+            write!(writer, "{}", site)?;
+        }
+        write!(writer, "{}", separator)?;
+        write_point(&mut writer, start, separator)?;
+        write!(writer, "{}", separator)?;
+        write_point(&mut writer, end, separator)?;
+        writeln!(writer)?;
+    }
+    writer.flush()
+}
+
+/// See `Diagram::write_cell_table`
+pub fn write_cell_table<T: BaseFloat, W: Write>(diagram: &Diagram<T>, writer: W, separator: char) -> io::Result<()> {
+    let mut writer = BufWriter::new(writer);
+    let columns = ["face", "site_id", "area", "centroid_x", "centroid_y", "vertices"];
+    writeln!(writer, "{}", columns.join(&separator.to_string()))?;
+
+    for (index, face) in diagram.faces.iter().enumerate() {
+        let face_id = FaceId(index as u32);
+        write!(writer, "{}{}{}{}", index, separator, face.site.id, separator)?;
+        write_number(&mut writer, diagram.face_area(face_id))?;
+        write!(writer, "{}", separator)?;
+        match diagram.face_centroid(face_id) {
+            Some(centroid) => write_point(&mut writer, centroid, separator)?,
+            None => write!(writer, "{}", separator)?,
+        }
+        write!(writer, "{}", separator)?;
+
+        // The corners go in one field, with spaces between the numbers
+        for (corner, point) in diagram.face_vertices(face_id).enumerate() {
+            if corner > 0 {
+                write!(writer, " ")?;
+            }
+            write_point(&mut writer, point, ' ')?;
+        }
+        writeln!(writer)?;
+    }
+    writer.flush()
+}
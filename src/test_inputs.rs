@@ -0,0 +1,112 @@
+//! Sites that are hard for the sweep to get right, for stress tests and benchmarks
+//!
+//! Each of these hits a different kind of degenerate case: sites that tie in the
+//! event queue, circle events through more than three sites, Delaunay triangles
+//! that are almost flat, and cells much smaller than the clip region. Sites are
+//! placed strictly inside the given `Rect` and get ids counting up from zero.
+//! Generators that scatter sites take a seed, and always give the same sites for
+//! the same seed.
+
+use std::f64::consts::PI;
+
+use cgmath::{Point2, Vector2, BaseFloat};
+
+use build::{Rect, Site};
+use dedup::next_random;
+use sites::{rect_f64, sites_at};
+
+/// Sites evenly spaced along the horizontal line through the middle of a rect
+///
+/// They all have the same y, so every site event ties with every other one, and
+/// no three sites ever make a circle event.
+pub fn collinear<T: BaseFloat>(rect: Rect<T>, count: usize) -> Vec<Site<T>> {
+    let (position, size) = match rect_f64(rect) {
+        Some(rect) => rect,
+        None => return Vec::new(),
+    };
+
+    let step = size.x / (count as f64 + 1.0);
+    sites_at(rect, (1..count + 1).map(|index| Point2::new(position.x + step * index as f64, position.y + size.y / 2.0)))
+}
+
+/// Sites evenly spaced along the diagonal of a rect, from the bottom left corner
+/// to the top right
+///
+/// Unless the coordinates happen to be exact, rounding moves some of them slightly
+/// off the line, so the sweep sees circle events from triples that are almost,
+/// but not quite, collinear.
+pub fn diagonal<T: BaseFloat>(rect: Rect<T>, count: usize) -> Vec<Site<T>> {
+    let (position, size) = match rect_f64(rect) {
+        Some(rect) => rect,
+        None => return Vec::new(),
+    };
+
+    let step = size / (count as f64 + 1.0);
+    sites_at(rect, (1..count + 1).map(|index| position + step * index as f64))
+}
+
+/// A site in the middle of a rect, with rings of sites around it
+///
+/// The sites on each ring are evenly spaced and all the same distance from the
+/// middle, so the circle event of any three of them is also on all the others.
+/// Rings are evenly spaced out to just inside the rect.
+pub fn cocircular_rings<T: BaseFloat>(rect: Rect<T>, rings: usize, per_ring: usize) -> Vec<Site<T>> {
+    let (position, size) = match rect_f64(rect) {
+        Some(rect) => rect,
+        None => return Vec::new(),
+    };
+
+    let centre = position + size / 2.0;
+    let spacing = size.x.min(size.y) / 2.0 / (rings as f64 + 1.0);
+    let ring_points = (1..rings + 1).flat_map(move |ring| {
+        (0..per_ring).map(move |index| {
+            let angle = 2.0 * PI * index as f64 / per_ring as f64;
+            centre + Vector2::new(angle.cos(), angle.sin()) * spacing * ring as f64
+        })
+    });
+    sites_at(rect, Some(centre).into_iter().chain(ring_points))
+}
+
+/// Tight clusters of sites at random places in a rect
+///
+/// Sites are spread uniformly over a disk around the middle of their cluster,
+/// whose radius is `spread` times the shorter side of the rect. A small spread
+/// makes cells in a cluster tiny compared to the ones around the outside, and
+/// their vertices very close together. Sites that land outside the rect are left
+/// out.
+pub fn clusters<T: BaseFloat>(rect: Rect<T>, clusters: usize, per_cluster: usize, spread: T, seed: u64) -> Vec<Site<T>> {
+    let (position, size) = match rect_f64(rect) {
+        Some(rect) => rect,
+        None => return Vec::new(),
+    };
+
+    let radius = spread.to_f64().unwrap() * size.x.min(size.y);
+    let mut state = seed;
+    let mut points = Vec::with_capacity(clusters * per_cluster);
+    for _ in 0..clusters {
+        let centre = Point2::new(position.x + next_random(&mut state) * size.x, position.y + next_random(&mut state) * size.y);
+        for _ in 0..per_cluster {
+            let angle = next_random(&mut state) * 2.0 * PI;
+            let distance = radius * next_random(&mut state).sqrt();
+            points.push(centre + Vector2::new(angle.cos(), angle.sin()) * distance);
+        }
+    }
+    sites_at(rect, points)
+}
+
+/// Sites at the middle of each cell of a grid over a rect
+///
+/// Each group of four sites around a grid corner is on one circle, so every
+/// vertex of the diagram is where four cells meet, and sites in a row all tie in
+/// the event queue.
+pub fn grid<T: BaseFloat>(rect: Rect<T>, columns: usize, rows: usize) -> Vec<Site<T>> {
+    let (position, size) = match rect_f64(rect) {
+        Some(rect) => rect,
+        None => return Vec::new(),
+    };
+
+    let cell = Vector2::new(size.x / columns as f64, size.y / rows as f64);
+    sites_at(rect, (0..rows).flat_map(move |row| {
+        (0..columns).map(move |column| position + Vector2::new(cell.x * (column as f64 + 0.5), cell.y * (row as f64 + 0.5)))
+    }))
+}
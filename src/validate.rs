@@ -0,0 +1,116 @@
+use cgmath::BaseFloat;
+
+use diagram::{Diagram, VertexId, HalfEdgeId, FaceId, ValidationError};
+
+/// Checks the links of a diagram, see `Diagram::validate`
+pub fn validate<T: BaseFloat>(diagram: &Diagram<T>) -> Result<(), ValidationError> {
+    validate_links(diagram, true)
+}
+
+/// Checks the links of a diagram like `validate`, but lets half-edges come from
+/// infinity if `require_origins` is false, as they do in an unclipped diagram
+pub fn validate_links<T: BaseFloat>(diagram: &Diagram<T>, require_origins: bool) -> Result<(), ValidationError> {
+    let halfedge_exists = |halfedge: HalfEdgeId| {
+        if (halfedge.0 as usize) < diagram.halfedges.len() {
+            Ok(())
+        } else {
+            Err(ValidationError::MissingHalfEdge(halfedge))
+        }
+    };
+
+    // Every id must point at something before any of the links are followed
+    for (index, vertex) in diagram.vertices.iter().enumerate() {
+        if vertex.coordinates.x.is_nan() || vertex.coordinates.y.is_nan() {
+            return Err(ValidationError::NanCoordinates(VertexId(index as u32)));
+        }
+        halfedge_exists(vertex.incident_edge)?;
+    }
+
+    for halfedge in &diagram.halfedges {
+        halfedge_exists(halfedge.next)?;
+        halfedge_exists(halfedge.prev)?;
+        if let Some(twin) = halfedge.twin {
+            halfedge_exists(twin)?;
+        }
+        if let Some(origin) = halfedge.origin {
+            if origin.0 as usize >= diagram.vertices.len() {
+                return Err(ValidationError::MissingVertex(origin));
+            }
+        }
+        if halfedge.incident_face.0 as usize >= diagram.faces.len() {
+            return Err(ValidationError::MissingFace(halfedge.incident_face));
+        }
+    }
+
+    for (index, face) in diagram.faces.iter().enumerate() {
+        if face.site.position.x.is_nan() || face.site.position.y.is_nan() {
+            return Err(ValidationError::NanSite(FaceId(index as u32)));
+        }
+        if let Some(first_halfedge) = face.first_halfedge {
+            halfedge_exists(first_halfedge)?;
+        }
+    }
+
+    for (index, halfedge) in diagram.halfedges.iter().enumerate() {
+        let halfedge_id = HalfEdgeId(index as u32);
+        let next = &diagram.halfedges[halfedge.next.0 as usize];
+
+        if require_origins && halfedge.origin.is_none() {
+            return Err(ValidationError::MissingOrigin(halfedge_id));
+        }
+        if next.prev != halfedge_id || diagram.halfedges[halfedge.prev.0 as usize].next != halfedge_id {
+            return Err(ValidationError::NextPrevMismatch(halfedge_id));
+        }
+        if let Some(twin) = halfedge.twin {
+            let twin = &diagram.halfedges[twin.0 as usize];
+            if twin.twin != Some(halfedge_id) || halfedge.twin == Some(halfedge_id) {
+                return Err(ValidationError::TwinMismatch(halfedge_id));
+            }
+
+            // The half-edge ends where its twin starts
+            if next.origin != twin.origin {
+                return Err(ValidationError::DestinationMismatch(halfedge_id));
+            }
+        }
+    }
+
+    for (index, vertex) in diagram.vertices.iter().enumerate() {
+        let vertex_id = VertexId(index as u32);
+        if diagram.halfedges[vertex.incident_edge.0 as usize].origin != Some(vertex_id) {
+            return Err(ValidationError::IncidentEdgeMismatch(vertex_id));
+        }
+    }
+
+    // Walk around each face, which must come back to where it started and visit
+    // every half-edge exactly once between them
+    let mut visited = vec![false; diagram.halfedges.len()];
+    for (index, face) in diagram.faces.iter().enumerate() {
+        let face_id = FaceId(index as u32);
+        let first_halfedge = match face.first_halfedge {
+            Some(first_halfedge) => first_halfedge,
+            None => continue,
+        };
+
+        let mut halfedge_id = first_halfedge;
+        loop {
+            let halfedge = &diagram.halfedges[halfedge_id.0 as usize];
+            if halfedge.incident_face != face_id {
+                return Err(ValidationError::FaceMismatch(halfedge_id));
+            }
+            if visited[halfedge_id.0 as usize] {
+                return Err(ValidationError::OpenCycle(face_id));
+            }
+            visited[halfedge_id.0 as usize] = true;
+
+            halfedge_id = halfedge.next;
+            if halfedge_id == first_halfedge {
+                break;
+            }
+        }
+    }
+
+    match visited.iter().position(|&visited| !visited) {
+        Some(index) => Err(ValidationError::UnreachableHalfEdge(HalfEdgeId(index as u32))),
+        None => Ok(()),
+    }
+}
@@ -0,0 +1,277 @@
+//! Following a line segment across the cells of a diagram
+//!
+//! The walk starts in the face `locate` gives for the start of the segment and
+//! goes from face to face through the edges the segment crosses, so it only
+//! looks at the faces along the way. Where the segment leaves a clipped diagram
+//! the walk looks for where it comes back in, if it does.
+
+use cgmath::{Point2, Vector2, InnerSpace, BaseFloat};
+use cgmath::num_traits::cast;
+#[cfg(feature = "serde")]
+use serde::{Serialize, Deserialize};
+
+use diagram::{Diagram, FaceId, HalfEdgeId, VertexId};
+use range::{Piece, face_pieces, halfedge_piece, in_located_face, near_piece};
+
+/// How many times the precision of the coordinates a point can be off an edge
+/// and still count as on it
+const TOLERANCE: f64 = 256.0;
+
+/// A straight line between two points
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Segment<T = f32> {
+    pub start: Point2<T>,
+    pub end: Point2<T>,
+}
+
+/// Iterator over the faces a segment crosses, see `Diagram::walk_segment`
+#[derive(Debug, Clone)]
+pub struct SegmentWalk<'a, T: 'a = f32> {
+    diagram: &'a Diagram<T>,
+    start: Point2<T>,
+    end: Point2<T>,
+
+    /// Face the walk is in, or None once it's finished
+    face: Option<FaceId>,
+
+    /// How far along the segment the walk has got, from zero at its start to one
+    /// at its end
+    along: T,
+
+    /// How far a point can be off an edge and still count as on it, for
+    /// crossings at corners that rounding puts a little way off
+    tolerance: T,
+
+    /// The tolerance as a fraction of the length of the segment
+    slack: T,
+
+    /// Half-edge of the face that the walk came in through
+    entry: Option<HalfEdgeId>,
+
+    /// Number of faces left to visit before giving up, so that rounding can't
+    /// keep the walk going back and forth forever
+    steps: usize,
+
+    pieces: Vec<Piece<T>>,
+}
+
+fn cross<T: BaseFloat>(a: Vector2<T>, b: Vector2<T>) -> T {
+    a.x * b.y - a.y * b.x
+}
+
+impl<'a, T: BaseFloat> SegmentWalk<'a, T> {
+    pub(crate) fn new(diagram: &'a Diagram<T>, start: Point2<T>, end: Point2<T>) -> SegmentWalk<'a, T> {
+        let extent = start.x.abs().max(start.y.abs()).max(end.x.abs()).max(end.y.abs());
+        let tolerance = extent * T::epsilon() * cast(TOLERANCE).unwrap();
+        let length = (end - start).magnitude();
+        let mut walk = SegmentWalk {
+            diagram,
+            start,
+            end,
+            face: None,
+            along: T::zero(),
+            tolerance,
+            slack: if length > T::zero() { tolerance / length } else { T::zero() },
+            entry: None,
+            steps: diagram.halfedges.len() + 1,
+            pieces: Vec::new(),
+        };
+
+        // Points on the outline count as inside, so that segments along it are
+        // walked
+        let located = diagram.locate(start).filter(|&face_id| {
+            face_pieces(diagram, face_id, &mut walk.pieces);
+            in_located_face(diagram, face_id, start) || walk.pieces.iter().any(|piece| near_piece(piece, start, tolerance))
+        });
+        match located {
+            Some(face_id) => walk.face = Some(face_id),
+            None => walk.enter(false),
+        }
+        walk
+    }
+
+    fn point_at(&self, along: T) -> Point2<T> {
+        if along >= T::one() {
+            self.end
+        } else {
+            self.start + (self.end - self.start) * along
+        }
+    }
+
+    /// Where along the segment it crosses a piece, if it does at or after `after`,
+    /// and where along the piece that is
+    fn crossing(&self, piece: &Piece<T>, after: T) -> Option<(T, T)> {
+        // A segment running along an edge doesn't cross it, even if rounding puts
+        // it a little way to one side, and nor does it cross edges too short to
+        // tell from a point
+        let direction = self.end - self.start;
+        let denominator = cross(direction, piece.direction);
+        let parallel = direction.magnitude() * piece.direction.magnitude() * T::epsilon() * cast(TOLERANCE).unwrap();
+        let short = piece.direction.magnitude() * (piece.to - piece.from) <= self.tolerance;
+        if denominator.abs() <= parallel || short {
+            return None;
+        }
+
+        let offset = piece.start - self.start;
+        let along = cross(offset, piece.direction) / denominator;
+        let position = cross(offset, direction) / denominator;
+        let slack = self.tolerance / piece.direction.magnitude();
+        if along >= after && position >= piece.from - slack && position <= piece.to + slack {
+            Some((along, position))
+        } else {
+            None
+        }
+    }
+
+    /// The vertex at the end of a piece that a crossing is at, if it's at one
+    fn vertex_at(&self, piece: &Piece<T>, position: T) -> Option<VertexId> {
+        let halfedges = &self.diagram.halfedges;
+        let halfedge = &halfedges[piece.halfedge.0 as usize];
+        let slack = self.tolerance / piece.direction.magnitude();
+        if piece.from.is_finite() && position <= piece.from + slack {
+            halfedge.origin
+        } else if piece.to.is_finite() && position >= piece.to - slack {
+            halfedges[halfedge.next.0 as usize].origin
+        } else {
+            None
+        }
+    }
+
+    /// Moves the walk to where the segment next comes into the diagram across its
+    /// outline, or finishes it if it doesn't
+    fn enter(&mut self, strictly_after: bool) {
+        let direction = self.end - self.start;
+        let diagram = self.diagram;
+        let mut nearest: Option<(T, HalfEdgeId)> = None;
+        for (index, halfedge) in diagram.halfedges.iter().enumerate() {
+            if halfedge.twin.is_some() {
+                continue;
+            }
+
+            let piece = match halfedge_piece(diagram, HalfEdgeId(index as u32)) {
+                Some(piece) => piece,
+                None => continue,
+            };
+
+            // Coming in means crossing from the right of the half-edge to its left
+            if cross(piece.direction, direction) <= T::zero() {
+                continue;
+            }
+            if let Some((along, _)) = self.crossing(&piece, self.along) {
+                let later = !strictly_after || along > self.along;
+                if later && along <= T::one() && nearest.is_none_or(|(nearest, _)| along < nearest) {
+                    nearest = Some((along, piece.halfedge));
+                }
+            }
+        }
+
+        match nearest {
+            Some((along, halfedge)) => {
+                self.face = Some(diagram.halfedges[halfedge.0 as usize].incident_face);
+                self.along = along;
+                self.entry = Some(halfedge);
+            }
+            None => self.face = None,
+        }
+    }
+
+    /// Moves the walk on through a vertex, into the face around it that the segment
+    /// goes furthest in
+    ///
+    /// The twin of the edge the segment left through is no good here, as that
+    /// face may only touch the segment at the vertex. Vertices joined by edges too
+    /// short to tell apart count as one, as four or more cells meeting at a point
+    /// give a few vertices there.
+    fn through_vertex(&mut self, vertex: VertexId) {
+        let diagram = self.diagram;
+        let mut vertices = vec![vertex];
+        let mut faces = Vec::new();
+        let mut index = 0;
+        while index < vertices.len() {
+            for halfedge_id in diagram.incident_edges(vertices[index]).take(diagram.halfedges.len()) {
+                let halfedge = &diagram.halfedges[halfedge_id.0 as usize];
+                if !faces.contains(&halfedge.incident_face) {
+                    faces.push(halfedge.incident_face);
+                }
+                let short = halfedge_piece(diagram, halfedge_id).is_some_and(|piece| piece.to.is_finite() && piece.direction.magnitude() <= self.tolerance);
+                if let Some(destination) = diagram.halfedges[halfedge.next.0 as usize].origin.filter(|_| short) {
+                    if !vertices.contains(&destination) {
+                        vertices.push(destination);
+                    }
+                }
+            }
+            index += 1;
+        }
+
+        self.entry = None;
+        let mut best: Option<(T, FaceId)> = None;
+        for face_id in faces {
+            let along = self.exit(face_id).map_or(T::infinity(), |(along, _, _)| along);
+            if best.is_none_or(|(best, _)| along > best) {
+                best = Some((along, face_id));
+            }
+        }
+        match best {
+            Some((along, face_id)) if along > self.along + self.slack => self.face = Some(face_id),
+            _ => self.enter(true),
+        }
+    }
+
+    /// Where the segment leaves a face, as the first edge past where the walk has
+    /// got to that it crosses from left to right, other than the one it came in by
+    fn exit(&mut self, face_id: FaceId) -> Option<(T, T, Piece<T>)> {
+        let direction = self.end - self.start;
+        self.pieces.clear();
+        face_pieces(self.diagram, face_id, &mut self.pieces);
+        self.pieces.iter()
+            .filter(|piece| Some(piece.halfedge) != self.entry && cross(piece.direction, direction) < T::zero())
+            .filter_map(|piece| self.crossing(piece, self.along - self.slack).map(|(along, position)| (along, position, *piece)))
+            .fold(None, |exit: Option<(T, T, Piece<T>)>, (along, position, piece)| match exit {
+                Some((exit_along, _, _)) if exit_along <= along => exit,
+                _ => Some((along, position, piece)),
+            })
+    }
+}
+
+impl<'a, T: BaseFloat> Iterator for SegmentWalk<'a, T> {
+    type Item = (FaceId, Segment<T>);
+
+    fn next(&mut self) -> Option<(FaceId, Segment<T>)> {
+        loop {
+            let face = self.face?;
+            if self.steps == 0 {
+                self.face = None;
+                return None;
+            }
+            self.steps -= 1;
+
+            let exit = self.exit(face);
+            let start = self.point_at(self.along);
+            let (along, position, piece) = match exit {
+                Some((along, position, piece)) if along < T::one() => (along, position, piece),
+                _ => {
+                    self.face = None;
+                    return Some((face, Segment { start, end: self.end }));
+                }
+            };
+
+            let along = along.max(self.along);
+            let end = self.point_at(along);
+            self.along = along;
+            match (self.vertex_at(&piece, position), self.diagram.halfedges[piece.halfedge.0 as usize].twin) {
+                (Some(vertex), _) => self.through_vertex(vertex),
+                (None, Some(twin)) => {
+                    self.face = Some(self.diagram.halfedges[twin.0 as usize].incident_face);
+                    self.entry = Some(twin);
+                }
+                (None, None) => self.enter(true),
+            }
+
+            // Passing through a corner gives next to nothing in the faces around it
+            if (end - start).magnitude() > self.tolerance {
+                return Some((face, Segment { start, end }));
+            }
+        }
+    }
+}
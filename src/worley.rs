@@ -0,0 +1,204 @@
+//! Worley noise, also called cellular noise, from the distances to the nearest
+//! feature points
+//!
+//! F1 is the distance from a point to the nearest feature point, which makes the
+//! cells of the diagram of the feature points show up as bumps, and F2 is the
+//! distance to the second nearest. F2 − F1 is zero along the edges of the cells,
+//! so it's often used for drawing cracks or borders. Feature points are either a
+//! set of sites, or spread one to each square of an endless grid by hashing the
+//! square's position, which needs no memory and tiles forever.
+
+use std::cmp::Ordering;
+
+use cgmath::{Point2, BaseFloat};
+use cgmath::num_traits::cast;
+
+use dedup::next_random;
+use diagram::FaceId;
+use kdtree::KdTree;
+use metric::Metric;
+
+/// Number of sites nearest to a point that are looked at first, which is doubled
+/// until the rest are too far away to matter
+const FIRST_CANDIDATES: usize = 4;
+
+/// The distances from a point to its nearest feature points
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WorleySample<T = f32> {
+    /// Distance to the nearest feature point, or infinity if there aren't any
+    pub f1: T,
+
+    /// Distance to the second nearest feature point, or infinity if there aren't
+    /// two
+    pub f2: T,
+
+    /// The nearest feature point
+    pub nearest: Option<Point2<T>>,
+}
+
+impl<T: BaseFloat> WorleySample<T> {
+    fn new() -> WorleySample<T> {
+        WorleySample {
+            f1: T::infinity(),
+            f2: T::infinity(),
+            nearest: None,
+        }
+    }
+
+    fn offer(&mut self, distance: T, point: Point2<T>) {
+        if distance < self.f1 {
+            self.f2 = self.f1;
+            self.f1 = distance;
+            self.nearest = Some(point);
+        } else if distance < self.f2 {
+            self.f2 = distance;
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum FeaturePoints<T> {
+    Sites {
+        points: Vec<Point2<T>>,
+        index: KdTree<T>,
+    },
+    Grid {
+        cell_size: T,
+        seed: u64,
+    },
+}
+
+/// Worley noise over a set of feature points, under any `Metric`
+#[derive(Debug, Clone)]
+pub struct WorleyNoise<T = f32> {
+    features: FeaturePoints<T>,
+    metric: Metric,
+}
+
+/// How much shorter than the straight line distance a distance under a metric can
+/// be
+fn shortest_ratio(metric: Metric) -> f64 {
+    match metric {
+        Metric::Euclidean | Metric::L1 => 1.0,
+        Metric::LInf => 1.0 / 2f64.sqrt(),
+    }
+}
+
+impl<T: BaseFloat> WorleyNoise<T> {
+    /// Noise with a feature point at each site
+    pub fn from_sites(sites: &[Point2<T>]) -> WorleyNoise<T> {
+        let index = KdTree::new(sites.iter().enumerate().map(|(index, &site)| (FaceId(index as u32), site, T::zero())), false);
+        WorleyNoise {
+            features: FeaturePoints::Sites {
+                points: sites.to_vec(),
+                index,
+            },
+            metric: Metric::Euclidean,
+        }
+    }
+
+    /// Noise with a feature point at a random place in each square of a grid,
+    /// `cell_size` across with a corner at the origin
+    ///
+    /// The same seed always puts the points in the same places. If the cell size
+    /// isn't above zero there are no feature points, and neither are there around
+    /// points that aren't finite or too far out to number the squares with an i64.
+    pub fn from_grid(cell_size: T, seed: u64) -> WorleyNoise<T> {
+        WorleyNoise {
+            features: FeaturePoints::Grid {
+                cell_size,
+                seed,
+            },
+            metric: Metric::Euclidean,
+        }
+    }
+
+    /// Measures distances under another metric, instead of straight lines
+    pub fn with_metric(mut self, metric: Metric) -> WorleyNoise<T> {
+        self.metric = metric;
+        self
+    }
+
+    pub fn metric(&self) -> Metric {
+        self.metric
+    }
+
+    /// The distances from a point to its two nearest feature points
+    pub fn sample(&self, point: Point2<T>) -> WorleySample<T> {
+        match self.features {
+            FeaturePoints::Sites { ref points, ref index } => self.sample_sites(points, index, point),
+            FeaturePoints::Grid { cell_size, seed } => self.sample_grid(cell_size, seed, point),
+        }
+    }
+
+    /// Distance from a point to the nearest feature point
+    pub fn f1(&self, point: Point2<T>) -> T {
+        self.sample(point).f1
+    }
+
+    /// Distance from a point to the second nearest feature point
+    pub fn f2(&self, point: Point2<T>) -> T {
+        self.sample(point).f2
+    }
+
+    fn sample_sites(&self, points: &[Point2<T>], index: &KdTree<T>, point: Point2<T>) -> WorleySample<T> {
+        // Sites are found in order of straight line distance, which under the other
+        // metrics is only a bound on how near they can be
+        let ratio: T = cast(shortest_ratio(self.metric)).unwrap();
+        let mut count = FIRST_CANDIDATES;
+        loop {
+            let found = index.nearest_unweighted(point, count);
+            let mut sample = WorleySample::new();
+            for face in &found {
+                let site = points[face.0 as usize];
+                sample.offer(self.metric.distance(point, site), site);
+            }
+
+            let furthest = found.last().map(|face| Metric::Euclidean.distance(point, points[face.0 as usize]));
+            if found.len() < count || furthest.is_none_or(|furthest| furthest * ratio >= sample.f2) {
+                return sample;
+            }
+            count *= 2;
+        }
+    }
+
+    /// The feature point in a square of the grid
+    fn grid_point(cell_size: T, seed: u64, column: i64, row: i64) -> Point2<T> {
+        let mut state = seed ^ (column as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15) ^ (row as u64).wrapping_mul(0xc2b2_ae3d_27d4_eb4f);
+        let x = column as f64 + next_random(&mut state);
+        let y = row as f64 + next_random(&mut state);
+        Point2::new(cast::<f64, T>(x).unwrap() * cell_size, cast::<f64, T>(y).unwrap() * cell_size)
+    }
+
+    fn sample_grid(&self, cell_size: T, seed: u64, point: Point2<T>) -> WorleySample<T> {
+        let mut sample = WorleySample::new();
+        if cell_size.partial_cmp(&T::zero()) != Some(Ordering::Greater) || !cell_size.is_finite() {
+            return sample;
+        }
+
+        // Squares are looked at in rings around the one the point is in. Every point
+        // in the rings after ring `r` is at least `r` squares away along x or y,
+        // which is as short as any of the metrics can make it
+        let (column, row) = match ((point.x / cell_size).floor().to_i64(), (point.y / cell_size).floor().to_i64()) {
+            (Some(column), Some(row)) => (column, row),
+            _ => return sample,
+        };
+        let mut ring: i64 = 0;
+        loop {
+            for offset_row in -ring..ring + 1 {
+                let step = if offset_row.abs() == ring { 1 } else { 2 * ring.max(1) };
+                let mut offset_column = -ring;
+                while offset_column <= ring {
+                    let feature = WorleyNoise::grid_point(cell_size, seed, column + offset_column, row + offset_row);
+                    sample.offer(self.metric.distance(point, feature), feature);
+                    offset_column += step;
+                }
+            }
+
+            if cast::<i64, T>(ring).unwrap() * cell_size >= sample.f2 {
+                return sample;
+            }
+            ring += 1;
+        }
+    }
+}
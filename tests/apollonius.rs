@@ -0,0 +1,70 @@
+extern crate cgmath;
+extern crate voronoi;
+
+mod common;
+
+use cgmath::{InnerSpace, Point2};
+
+use voronoi::apollonius::{ApolloniusBuilder, ApolloniusDiagram};
+use voronoi::build::Site;
+use voronoi::diagram::FaceId;
+
+use common::{random, rect};
+
+/// How much nearer the nearest site has to be than the next one for a point to
+/// be checked against the outlines of the cells
+const TIE: f64 = 1e-3;
+
+fn weighted_distance(site: &Site<f64>, point: Point2<f64>) -> f64 {
+    (point - site.position).magnitude() - site.weight
+}
+
+/// Whether a point is inside a face, with its curved edges followed closely
+fn face_contains(diagram: &ApolloniusDiagram<f64>, face_id: FaceId, point: Point2<f64>) -> bool {
+    let outline = diagram.diagram().face_edges(face_id)
+        .flat_map(|halfedge_id| {
+            let mut points = diagram.edge_points(halfedge_id, 64);
+            points.pop();
+            points
+        })
+        .collect::<Vec<_>>();
+
+    let mut inside = false;
+    for (index, &a) in outline.iter().enumerate() {
+        let b = outline[(index + 1) % outline.len()];
+        if (a.y > point.y) != (b.y > point.y) && point.x < a.x + (point.y - a.y) / (b.y - a.y) * (b.x - a.x) {
+            inside = !inside;
+        }
+    }
+    inside
+}
+
+#[test]
+fn locate_matches_brute_force() {
+    for seed in 0..4 {
+        let mut state = seed;
+        let sites = (0..60).map(|id| {
+            let position = Point2::new(random(&mut state) * 100.0, random(&mut state) * 100.0);
+            Site::with_weight(id, position, random(&mut state) * 8.0)
+        }).collect::<Vec<_>>();
+        let diagram = ApolloniusBuilder::new(rect(), sites.clone()).finish();
+        assert!(diagram.diagram().validate().is_ok());
+
+        for _ in 0..1000 {
+            let point = Point2::new(random(&mut state) * 100.0, random(&mut state) * 100.0);
+
+            // Every site counts, including any hidden ones without a cell
+            let mut distances = sites.iter().enumerate()
+                .map(|(index, site)| (weighted_distance(site, point), FaceId(index as u32)))
+                .collect::<Vec<_>>();
+            distances.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+            if distances[1].0 - distances[0].0 < TIE {
+                continue;
+            }
+
+            let expected = distances[0].1;
+            assert_eq!(diagram.locate(point), Some(expected), "seed {} at {:?}", seed, point);
+            assert!(face_contains(&diagram, expected, point), "seed {} at {:?}", seed, point);
+        }
+    }
+}
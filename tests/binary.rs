@@ -0,0 +1,147 @@
+extern crate cgmath;
+extern crate voronoi;
+
+mod common;
+
+use std::io::ErrorKind;
+
+use cgmath::Point2;
+
+use voronoi::build::DiagramBuilder;
+use voronoi::diagram::{Diagram, FaceId};
+use voronoi::power::PowerDiagramBuilder;
+
+use common::{assert_same_topology, rect, sites};
+
+/// Bytes in the header, before the first array
+const HEADER: usize = 28;
+
+fn encode<T: cgmath::BaseFloat>(diagram: &Diagram<T>) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    diagram.write_to(&mut bytes).unwrap();
+    bytes
+}
+
+fn set_u32(bytes: &mut [u8], offset: usize, value: u32) {
+    bytes[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+}
+
+fn assert_invalid(bytes: &[u8]) {
+    match Diagram::<f64>::read_from(bytes) {
+        Ok(_) => panic!("damaged data was read as a diagram"),
+        Err(error) => assert_eq!(error.kind(), ErrorKind::InvalidData),
+    }
+}
+
+#[test]
+fn diagram_round_trip() {
+    let diagram = DiagramBuilder::new(rect(), sites()).finish();
+    let other = Diagram::read_from(&encode(&diagram)[..]).unwrap();
+    assert_same_topology(&diagram, &other);
+    assert!(other.validate().is_ok());
+
+    for index in 0..100 {
+        let point = Point2::new(index as f64, (index * 7 % 100) as f64);
+        assert_eq!(diagram.locate(point), other.locate(point));
+    }
+}
+
+#[test]
+fn power_diagram_round_trip() {
+    let diagram = PowerDiagramBuilder::new(rect(), sites()).finish();
+    let other = Diagram::read_from(&encode(&diagram)[..]).unwrap();
+    assert_same_topology(&diagram, &other);
+
+    // Lookups still use the weights after the index is rebuilt
+    for index in 0..100 {
+        let point = Point2::new(index as f64, (index * 7 % 100) as f64);
+        assert_eq!(diagram.locate(point), other.locate(point));
+    }
+}
+
+#[test]
+fn unclipped_round_trip() {
+    let diagram = DiagramBuilder::new(rect(), sites()).finish_unclipped();
+    assert!(diagram.halfedges.iter().any(|halfedge| halfedge.origin.is_none()));
+    let other = Diagram::read_from(&encode(&diagram)[..]).unwrap();
+    assert_same_topology(&diagram, &other);
+}
+
+#[test]
+fn precision_is_converted() {
+    let diagram = DiagramBuilder::new(rect(), sites()).finish();
+    let narrow: Diagram<f32> = Diagram::read_from(&encode(&diagram)[..]).unwrap();
+    let wide: Diagram<f64> = Diagram::read_from(&encode(&narrow)[..]).unwrap();
+    assert_eq!(wide.halfedges.len(), diagram.halfedges.len());
+
+    let area = (0..wide.faces.len()).map(|index| wide.face_area(FaceId(index as u32))).sum::<f64>();
+    assert!((area - 10000.0).abs() < 1e-2);
+}
+
+#[test]
+fn truncated_data_is_rejected() {
+    let bytes = encode(&DiagramBuilder::new(rect(), sites()).finish());
+    for &length in &[0, 3, HEADER - 1, HEADER, bytes.len() / 2, bytes.len() - 1] {
+        assert_invalid(&bytes[..length]);
+    }
+}
+
+#[test]
+fn trailing_data_is_rejected() {
+    let mut bytes = encode(&DiagramBuilder::new(rect(), sites()).finish());
+    bytes.push(0);
+    assert_invalid(&bytes);
+}
+
+#[test]
+fn corrupted_counts_are_rejected() {
+    let diagram = DiagramBuilder::new(rect(), sites()).finish();
+    let bytes = encode(&diagram);
+    let counts = [diagram.vertices.len(), diagram.halfedges.len(), diagram.faces.len()];
+    for (index, &count) in counts.iter().enumerate() {
+        for &damaged in &[0, count / 4, count - 1, count + 1, u32::MAX as usize] {
+            let mut bytes = bytes.clone();
+            set_u32(&mut bytes, 16 + index * 4, damaged as u32);
+            assert_invalid(&bytes);
+        }
+    }
+}
+
+#[test]
+fn corrupted_links_are_rejected() {
+    let diagram = DiagramBuilder::new(rect(), sites()).finish();
+    let bytes = encode(&diagram);
+    let (vertices, halfedges) = (diagram.vertices.len(), diagram.halfedges.len());
+    let halfedge_array = |array: usize| HEADER + vertices * 20 + array * halfedges * 4;
+    let first_halfedge = halfedge_array(5);
+
+    let damaged = [
+        // A link past the end of what it points into
+        (HEADER + vertices * 16, halfedges as u32),
+        (halfedge_array(0), vertices as u32),
+        (halfedge_array(1), halfedges as u32),
+        (halfedge_array(2), diagram.faces.len() as u32),
+        (halfedge_array(3), halfedges as u32),
+        (halfedge_array(4), halfedges as u32),
+        (first_halfedge, halfedges as u32),
+        // Links in range that don't agree with each other
+        (halfedge_array(3), 0),
+        (halfedge_array(1), 0),
+        (halfedge_array(0) + 4, diagram.halfedges[0].origin.unwrap().0),
+    ];
+    for &(offset, value) in &damaged {
+        let mut bytes = bytes.clone();
+        set_u32(&mut bytes, offset, value);
+        assert_invalid(&bytes);
+    }
+}
+
+#[test]
+fn other_formats_are_rejected() {
+    let bytes = encode(&DiagramBuilder::new(rect(), sites()).finish());
+    for &(offset, value) in &[(0, 0), (4, 2), (8, 2)] {
+        let mut bytes = bytes.clone();
+        set_u32(&mut bytes, offset, value);
+        assert_invalid(&bytes);
+    }
+}
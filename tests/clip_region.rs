@@ -0,0 +1,30 @@
+extern crate cgmath;
+extern crate voronoi;
+
+use cgmath::Point2;
+
+use voronoi::build::{BuildError, ClipRegion, DiagramBuilder, Site};
+
+fn try_clip(corners: &[(f64, f64)]) -> Result<(), BuildError> {
+    let region = ClipRegion::ConvexPolygon(corners.iter().map(|&(x, y)| Point2::new(x, y)).collect());
+    let sites = vec![Site::new(0, Point2::new(4.0, 4.0)), Site::new(1, Point2::new(5.0, 3.0))];
+    DiagramBuilder::new(region, sites).try_finish().map(|_| ())
+}
+
+#[test]
+fn convex_polygons_are_accepted() {
+    assert_eq!(try_clip(&[(0.0, 0.0), (10.0, 0.0), (10.0, 10.0), (0.0, 10.0)]), Ok(()));
+
+    // Clockwise, and with a corner in the middle of a side
+    assert_eq!(try_clip(&[(0.0, 0.0), (0.0, 10.0), (10.0, 10.0), (10.0, 5.0), (10.0, 0.0)]), Ok(()));
+}
+
+#[test]
+fn concave_polygons_are_rejected() {
+    let notched = [(0.0, 0.0), (10.0, 0.0), (10.0, 10.0), (5.0, 6.0), (0.0, 10.0)];
+    assert_eq!(try_clip(&notched), Err(BuildError::ConcaveClipRegion));
+
+    // Every corner turns left, but it goes round twice
+    let star = [(10.0, 5.0), (0.5, 8.0), (6.5, 0.0), (6.5, 10.0), (0.5, 2.0)];
+    assert_eq!(try_clip(&star), Err(BuildError::ConcaveClipRegion));
+}
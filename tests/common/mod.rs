@@ -0,0 +1,57 @@
+//! Diagrams and checks shared by the integration tests
+
+#![allow(dead_code)]
+
+use cgmath::{Point2, Vector2};
+
+use voronoi::build::{Rect, Site};
+use voronoi::diagram::Diagram;
+
+pub fn rect() -> Rect<f64> {
+    Rect {
+        position: Point2::new(0.0, 0.0),
+        size: Vector2::new(100.0, 100.0),
+    }
+}
+
+/// A number from zero up to one, the same for the same state every run
+pub fn random(state: &mut u64) -> f64 {
+    *state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+    (*state >> 11) as f64 / (1u64 << 53) as f64
+}
+
+/// Fifty sites spread over `rect` without any two in the same place, with weights
+/// for power diagrams
+pub fn sites() -> Vec<Site<f64>> {
+    (0..50).map(|index| {
+        let position = Point2::new((index * 37 % 100) as f64 + 0.5, (index * 61 % 100) as f64 + 0.25);
+        Site::with_weight(index, position, (index % 7) as f64 * 10.0)
+    }).collect()
+}
+
+/// Checks that two diagrams have the same elements with the same links, in the
+/// same order
+pub fn assert_same_topology(diagram: &Diagram<f64>, other: &Diagram<f64>) {
+    assert_eq!(diagram.vertices.len(), other.vertices.len());
+    for (vertex, other) in diagram.vertices.iter().zip(&other.vertices) {
+        assert_eq!(vertex.coordinates, other.coordinates);
+        assert_eq!(vertex.incident_edge, other.incident_edge);
+    }
+
+    assert_eq!(diagram.halfedges.len(), other.halfedges.len());
+    for (halfedge, other) in diagram.halfedges.iter().zip(&other.halfedges) {
+        assert_eq!(halfedge.origin, other.origin);
+        assert_eq!(halfedge.twin, other.twin);
+        assert_eq!(halfedge.incident_face, other.incident_face);
+        assert_eq!(halfedge.next, other.next);
+        assert_eq!(halfedge.prev, other.prev);
+    }
+
+    assert_eq!(diagram.faces.len(), other.faces.len());
+    for (face, other) in diagram.faces.iter().zip(&other.faces) {
+        assert_eq!(face.first_halfedge, other.first_halfedge);
+        assert_eq!(face.site.id, other.site.id);
+        assert_eq!(face.site.position, other.site.position);
+        assert_eq!(face.site.weight, other.site.weight);
+    }
+}
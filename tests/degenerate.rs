@@ -0,0 +1,49 @@
+extern crate cgmath;
+extern crate voronoi;
+
+use cgmath::{Point2, Vector2, BaseFloat};
+
+use voronoi::build::{Rect, Site};
+use voronoi::invariants::build_and_validate;
+use voronoi::test_inputs;
+
+fn rect<T: BaseFloat>() -> Rect<T> {
+    Rect {
+        position: Point2::new(T::zero(), T::zero()),
+        size: Vector2::new(T::from(100.0).unwrap(), T::from(100.0).unwrap()),
+    }
+}
+
+fn assert_valid<T: BaseFloat>(name: &str, sites: Vec<Site<T>>) {
+    let points = sites.iter().map(|site| site.position).collect::<Vec<_>>();
+    if let Err(error) = build_and_validate(&points) {
+        panic!("{} sites: {}", name, error);
+    }
+}
+
+fn check_all<T: BaseFloat>() {
+    for &count in &[2, 3, 10, 257] {
+        assert_valid("collinear", test_inputs::collinear(rect::<T>(), count));
+        assert_valid("diagonal", test_inputs::diagonal(rect::<T>(), count));
+    }
+    for &(rings, per_ring) in &[(1, 3), (1, 16), (4, 12), (10, 64)] {
+        assert_valid("cocircular", test_inputs::cocircular_rings(rect::<T>(), rings, per_ring));
+    }
+    for &(columns, rows) in &[(1, 5), (2, 2), (7, 3), (30, 30)] {
+        assert_valid("grid", test_inputs::grid(rect::<T>(), columns, rows));
+    }
+    for seed in 0..8 {
+        let spread = T::from(0.0001 * 10f64.powi(seed as i32 % 4)).unwrap();
+        assert_valid("clustered", test_inputs::clusters(rect::<T>(), 5, 40, spread, seed));
+    }
+}
+
+#[test]
+fn degenerate_inputs_f64() {
+    check_all::<f64>();
+}
+
+#[test]
+fn degenerate_inputs_f32() {
+    check_all::<f32>();
+}
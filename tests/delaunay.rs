@@ -0,0 +1,68 @@
+extern crate cgmath;
+extern crate voronoi;
+
+use cgmath::{Point2, Vector2};
+
+use voronoi::build::{DiagramBuilder, Rect, Site};
+use voronoi::delaunay::{TriangleId, Triangulation};
+
+fn triangulation() -> Triangulation<f64> {
+    let rect = Rect {
+        position: Point2::new(0.0, 0.0),
+        size: Vector2::new(100.0, 100.0),
+    };
+    let sites = (0..200).map(|index| {
+        let position = Point2::new((index * 37 % 100) as f64 + 0.5, (index * 61 % 97) as f64 + 0.25);
+        Site::new(index, position)
+    }).collect();
+    DiagramBuilder::new(rect, sites).finish_with_delaunay().1
+}
+
+fn linear(point: Point2<f64>) -> f64 {
+    2.0 * point.x - 3.0 * point.y + 5.0
+}
+
+#[test]
+fn interpolate_reproduces_linear_field() {
+    let triangulation = triangulation();
+    let values = triangulation.points.iter().map(|&point| linear(point)).collect::<Vec<_>>();
+
+    let mut hint = TriangleId(0);
+    let mut inside = 0;
+    for row in 0..100 {
+        for column in 0..100 {
+            let point = Point2::new(column as f64 + 0.3, row as f64 + 0.7);
+            let value = triangulation.interpolate(point, &values);
+            assert_eq!(triangulation.interpolate_with_hint(point, &values, &mut hint), value);
+            if let Some(value) = value {
+                assert!((value - linear(point)).abs() < 1e-9, "{} at {:?}", value, point);
+                inside += 1;
+            }
+        }
+    }
+    assert!(inside > 5000);
+}
+
+#[test]
+fn interpolate_gives_corner_values() {
+    let triangulation = triangulation();
+    let values = (0..triangulation.points.len()).map(|index| index as f64).collect::<Vec<_>>();
+    for (index, &point) in triangulation.points.iter().enumerate() {
+        assert_eq!(triangulation.interpolate(point, &values), Some(index as f64));
+    }
+}
+
+#[test]
+fn hint_follows_the_point() {
+    let triangulation = triangulation();
+    let values = vec![1.0; triangulation.points.len()];
+
+    let mut hint = TriangleId(0);
+    let point = Point2::new(50.2, 40.1);
+    assert!(triangulation.interpolate_with_hint(point, &values, &mut hint).is_some());
+    assert_eq!(triangulation.locate_with_hint(point, TriangleId(0)), Some(hint));
+
+    // Points outside leave the hint alone
+    assert_eq!(triangulation.interpolate_with_hint(Point2::new(-10.0, -10.0), &values, &mut hint), None);
+    assert_eq!(triangulation.locate_with_hint(point, TriangleId(0)), Some(hint));
+}
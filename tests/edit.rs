@@ -0,0 +1,163 @@
+extern crate cgmath;
+extern crate voronoi;
+
+mod common;
+
+use cgmath::Point2;
+
+use voronoi::build::{DiagramBuilder, Site};
+use voronoi::diagram::{Diagram, FaceId, HalfEdgeId, VertexId};
+
+use common::{random, rect};
+
+/// Areas of sums of cells are compared to this much, relative to the rect
+const AREA_TOLERANCE: f64 = 1e-9;
+
+fn diagram(seed: u64) -> Diagram<f64> {
+    let mut state = seed;
+    let sites = (0..100).map(|id| Site::new(id, Point2::new(random(&mut state) * 100.0, random(&mut state) * 100.0))).collect();
+    DiagramBuilder::new(rect(), sites).finish()
+}
+
+fn total_area(diagram: &Diagram<f64>) -> f64 {
+    (0..diagram.faces.len()).map(|index| diagram.face_area(FaceId(index as u32))).sum()
+}
+
+fn coordinates(diagram: &Diagram<f64>, vertex_id: VertexId) -> Point2<f64> {
+    diagram.vertices[vertex_id.0 as usize].coordinates
+}
+
+fn origin(diagram: &Diagram<f64>, halfedge_id: HalfEdgeId) -> VertexId {
+    diagram.halfedges[halfedge_id.0 as usize].origin.unwrap()
+}
+
+fn destination(diagram: &Diagram<f64>, halfedge_id: HalfEdgeId) -> VertexId {
+    origin(diagram, diagram.halfedges[halfedge_id.0 as usize].next)
+}
+
+fn on_boundary(diagram: &Diagram<f64>, vertex_id: VertexId) -> bool {
+    diagram.incident_edges(vertex_id).any(|halfedge_id| diagram.halfedges[halfedge_id.0 as usize].twin.is_none())
+}
+
+/// Half-edges between two cells, with both ends inside the rect
+fn inner_halfedges(diagram: &Diagram<f64>) -> Vec<HalfEdgeId> {
+    (0..diagram.halfedges.len() as u32).map(HalfEdgeId)
+        .filter(|&halfedge_id| diagram.halfedges[halfedge_id.0 as usize].twin.is_some())
+        .filter(|&halfedge_id| !on_boundary(diagram, origin(diagram, halfedge_id)) && !on_boundary(diagram, destination(diagram, halfedge_id)))
+        .collect()
+}
+
+#[test]
+fn split_edge_keeps_area() {
+    for seed in 0..4 {
+        let mut diagram = diagram(seed);
+        let area = total_area(&diagram);
+        for &halfedge_id in inner_halfedges(&diagram).iter().step_by(7) {
+            let (start, end) = (coordinates(&diagram, origin(&diagram, halfedge_id)), coordinates(&diagram, destination(&diagram, halfedge_id)));
+            let face = diagram.halfedges[halfedge_id.0 as usize].incident_face;
+            let face_area = diagram.face_area(face);
+
+            // Off the line between the ends, which moves area from one side to
+            // the other but not out of the diagram
+            let point = Point2::new((start.x + end.x) / 2.0 + (start.y - end.y) * 0.1, (start.y + end.y) / 2.0 + (end.x - start.x) * 0.1);
+            let vertex_id = diagram.split_edge(halfedge_id, point).unwrap();
+            assert_eq!(coordinates(&diagram, vertex_id), point);
+            assert_eq!(destination(&diagram, halfedge_id), vertex_id);
+            assert!(diagram.face_area(face) < face_area);
+        }
+        assert!(diagram.validate().is_ok(), "seed {}", seed);
+        assert!((total_area(&diagram) - area).abs() < area * AREA_TOLERANCE, "seed {}", seed);
+    }
+}
+
+#[test]
+fn collapse_edge_keeps_area_inside() {
+    for seed in 0..4 {
+        let mut diagram = diagram(seed);
+        let area = total_area(&diagram);
+        let vertices = diagram.vertices.len();
+        let mut collapsed = 0;
+        for _ in 0..10 {
+            let halfedge_id = match inner_halfedges(&diagram).into_iter().find(|&halfedge_id| diagram.clone().collapse_edge(halfedge_id).is_some()) {
+                Some(halfedge_id) => halfedge_id,
+                None => break,
+            };
+            let (start, end) = (coordinates(&diagram, origin(&diagram, halfedge_id)), coordinates(&diagram, destination(&diagram, halfedge_id)));
+            let vertex_id = diagram.collapse_edge(halfedge_id).unwrap();
+            assert_eq!(coordinates(&diagram, vertex_id), Point2::new((start.x + end.x) / 2.0, (start.y + end.y) / 2.0));
+            assert!(diagram.validate().is_ok(), "seed {}", seed);
+            collapsed += 1;
+        }
+        assert!(collapsed > 0);
+        assert_eq!(diagram.vertices.len(), vertices - collapsed);
+        assert!((total_area(&diagram) - area).abs() < area * AREA_TOLERANCE, "seed {}", seed);
+    }
+}
+
+#[test]
+fn collapse_edge_moves_boundary() {
+    for seed in 0..4 {
+        let mut diagram = diagram(seed);
+        let area = total_area(&diagram);
+
+        // An edge from inside the rect to a point on one of its sides, which moves
+        // halfway in and takes the boundary with it
+        let halfedge_id = (0..diagram.halfedges.len() as u32).map(HalfEdgeId)
+            .find(|&halfedge_id| {
+                let halfedge = &diagram.halfedges[halfedge_id.0 as usize];
+                let (from, to) = (origin(&diagram, halfedge_id), destination(&diagram, halfedge_id));
+                let corner = coordinates(&diagram, to);
+                let at_corner = (corner.x == 0.0 || corner.x == 100.0) && (corner.y == 0.0 || corner.y == 100.0);
+                halfedge.twin.is_some() && !on_boundary(&diagram, from) && on_boundary(&diagram, to) && !at_corner
+                    && diagram.clone().collapse_edge(halfedge_id).is_some()
+            })
+            .unwrap();
+        let (from, to) = (origin(&diagram, halfedge_id), destination(&diagram, halfedge_id));
+        let boundary_edges = (0..diagram.halfedges.len() as u32).map(HalfEdgeId)
+            .filter(|&halfedge_id| diagram.halfedges[halfedge_id.0 as usize].twin.is_none())
+            .collect::<Vec<_>>();
+        let leaving = boundary_edges.iter().find(|&&halfedge_id| origin(&diagram, halfedge_id) == to).unwrap();
+        let arriving = boundary_edges.iter().find(|&&halfedge_id| destination(&diagram, halfedge_id) == to).unwrap();
+        let (previous_corner, next_corner) = (coordinates(&diagram, origin(&diagram, *arriving)), coordinates(&diagram, destination(&diagram, *leaving)));
+        let (start, end) = (coordinates(&diagram, from), coordinates(&diagram, to));
+        let middle = Point2::new((start.x + end.x) / 2.0, (start.y + end.y) / 2.0);
+
+        let vertex_id = diagram.collapse_edge(halfedge_id).unwrap();
+        assert!(diagram.validate().is_ok(), "seed {}", seed);
+        assert_eq!(coordinates(&diagram, vertex_id), middle);
+        assert!(on_boundary(&diagram, vertex_id));
+
+        // The boundary goes in to the middle of the edge and back out, which cuts
+        // the triangle between them off the diagram
+        let cut_off = ((next_corner.x - previous_corner.x) * (middle.y - previous_corner.y) - (next_corner.y - previous_corner.y) * (middle.x - previous_corner.x)).abs() / 2.0;
+        assert!(cut_off > 0.0);
+        assert!((total_area(&diagram) - (area - cut_off)).abs() < area * AREA_TOLERANCE, "seed {}", seed);
+    }
+}
+
+#[test]
+fn merge_faces_keeps_area() {
+    for seed in 0..4 {
+        let mut diagram = diagram(seed);
+        let area = total_area(&diagram);
+        let mut merged = 0;
+        for index in 0..10 {
+            let face_id = FaceId(index * 7);
+            let other_id = match diagram.neighbours(face_id).next() {
+                Some(other_id) => other_id,
+                None => continue,
+            };
+            let merged_area = diagram.face_area(face_id) + diagram.face_area(other_id);
+            if !diagram.merge_faces(face_id, other_id) {
+                continue;
+            }
+            assert!(diagram.validate().is_ok(), "seed {}", seed);
+            assert!((diagram.face_area(face_id) - merged_area).abs() < area * AREA_TOLERANCE, "seed {}", seed);
+            assert!(diagram.faces[other_id.0 as usize].first_halfedge.is_none());
+            assert!(diagram.face_edges(face_id).all(|halfedge_id| diagram.halfedges[halfedge_id.0 as usize].incident_face == face_id));
+            merged += 1;
+        }
+        assert!(merged > 0);
+        assert!((total_area(&diagram) - area).abs() < area * AREA_TOLERANCE, "seed {}", seed);
+    }
+}
@@ -0,0 +1,40 @@
+extern crate cgmath;
+extern crate voronoi;
+
+mod common;
+
+use cgmath::Point2;
+
+use voronoi::build::{DiagramBuilder, Site};
+use voronoi::kinetic::{KineticDiagram, KineticUpdate};
+
+use common::{random, rect};
+
+#[test]
+fn move_sites_matches_rebuild() {
+    for seed in 0..4 {
+        let mut state = seed;
+        let sites = (0..200).map(|id| Site::new(id, Point2::new(random(&mut state) * 100.0, random(&mut state) * 100.0))).collect::<Vec<_>>();
+        let mut kinetic = KineticDiagram::new(rect(), sites);
+
+        // Small steps keep most triangles the right way round, so the repair is
+        // what gets tested rather than the rebuild
+        let mut repaired = 0;
+        for step in 0..10 {
+            let positions = kinetic.sites().iter().map(|site| {
+                let x = site.position.x + random(&mut state) - 0.5;
+                let y = site.position.y + random(&mut state) - 0.5;
+                Point2::new(x.clamp(0.01, 99.99), y.clamp(0.01, 99.99))
+            }).collect::<Vec<_>>();
+            if let KineticUpdate::Repaired { .. } = kinetic.move_sites(&positions) {
+                repaired += 1;
+            }
+
+            let diagram = kinetic.diagram();
+            assert!(diagram.validate().is_ok(), "seed {} step {}", seed, step);
+            let rebuilt = DiagramBuilder::new(rect(), kinetic.sites().to_vec()).finish();
+            assert!(diagram.eq_topology(&rebuilt), "seed {} step {}", seed, step);
+        }
+        assert!(repaired > 0, "seed {}", seed);
+    }
+}
@@ -0,0 +1,29 @@
+extern crate cgmath;
+extern crate voronoi;
+
+mod common;
+
+use voronoi::build::DiagramBuilder;
+
+use common::{assert_same_topology, sites};
+
+#[test]
+fn clipped_by_default() {
+    let diagram = DiagramBuilder::options().clip(true).dedup_epsilon(1e-6).build(sites()).unwrap().finish();
+    assert!(diagram.validate().is_ok());
+
+    let other = DiagramBuilder::options().dedup_epsilon(1e-6).build(sites()).unwrap().finish();
+    assert_same_topology(&diagram, &other);
+}
+
+#[test]
+fn unclipped_matches_finish_unclipped() {
+    let diagram = DiagramBuilder::options().clip(false).build(sites()).unwrap().finish();
+    let expected = DiagramBuilder::options().build(sites()).unwrap().finish_unclipped();
+    assert!(diagram.halfedges.iter().any(|halfedge| halfedge.origin.is_none()));
+
+    assert_same_topology(&diagram, &expected);
+
+    // Checking the links doesn't trip over the half-edges from infinity
+    assert!(DiagramBuilder::options().clip(false).build(sites()).unwrap().try_finish().is_ok());
+}
@@ -0,0 +1,58 @@
+extern crate cgmath;
+extern crate voronoi;
+
+mod common;
+
+use cgmath::Point2;
+
+use voronoi::build::Site;
+use voronoi::diagram::{FaceId, HalfEdgeId};
+use voronoi::periodic::PeriodicDiagramBuilder;
+
+use common::{random, rect};
+
+/// Sites spread over the rect and the space around it, which get wrapped into it
+fn wrapping_sites(state: &mut u64, count: u32) -> Vec<Site<f64>> {
+    (0..count).map(|id| {
+        Site::new(id, Point2::new(random(state) * 300.0 - 100.0, random(state) * 300.0 - 100.0))
+    }).collect()
+}
+
+#[test]
+fn periodic_diagram_covers_the_rect() {
+    for seed in 0..8 {
+        let mut state = seed;
+        let periodic = PeriodicDiagramBuilder::new(rect(), wrapping_sites(&mut state, 40)).finish();
+        let diagram = periodic.diagram();
+        assert_eq!(diagram.validate(), Ok(()), "seed {}", seed);
+
+        let area = (0..diagram.faces.len()).map(|index| diagram.face_area(FaceId(index as u32))).sum::<f64>();
+        assert!((area - 100.0 * 100.0).abs() < 1e-6, "seed {}: area {}", seed, area);
+
+        // Every piece belongs to the cell of its original face
+        for index in 0..diagram.faces.len() {
+            let face_id = FaceId(index as u32);
+            let original = periodic.original(face_id).unwrap();
+            assert_eq!(periodic.copies(original)[0], original, "seed {}", seed);
+            assert!(periodic.copies(original).contains(&face_id), "seed {}", seed);
+        }
+    }
+}
+
+#[test]
+fn seam_twins_are_symmetric() {
+    let mut state = 3;
+    let periodic = PeriodicDiagramBuilder::new(rect(), wrapping_sites(&mut state, 40)).finish();
+    let diagram = periodic.diagram();
+
+    let mut seams = 0;
+    for index in 0..diagram.halfedges.len() {
+        let halfedge_id = HalfEdgeId(index as u32);
+        if let Some(seam) = periodic.seam_twin(halfedge_id) {
+            assert_eq!(periodic.seam_twin(seam), Some(halfedge_id));
+            assert!(diagram.halfedges[index].twin.is_none());
+            seams += 1;
+        }
+    }
+    assert!(seams > 0);
+}
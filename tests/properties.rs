@@ -0,0 +1,227 @@
+extern crate cgmath;
+extern crate voronoi;
+
+mod common;
+
+use cgmath::{Point2, Vector2};
+
+use voronoi::build::{DiagramBuilder, Metric, Rect, Site};
+use voronoi::diagram::{Diagram, FaceId};
+use voronoi::power::PowerDiagramBuilder;
+
+use common::{random, rect};
+
+/// How much closer the nearest site has to be than the next one for a point to
+/// be checked, so that rounding can't decide which one wins
+const TIE: f64 = 1e-6;
+
+fn random_point(state: &mut u64) -> Point2<f64> {
+    Point2::new(random(state) * 100.0, random(state) * 100.0)
+}
+
+fn random_sites(count: u32, max_weight: f64, seed: u64) -> Vec<Site<f64>> {
+    let mut state = seed;
+    (0..count).map(|id| {
+        let position = random_point(&mut state);
+        Site::with_weight(id, position, random(&mut state) * max_weight)
+    }).collect()
+}
+
+fn power_distance(site: &Site<f64>, point: Point2<f64>) -> f64 {
+    let (dx, dy) = (point.x - site.position.x, point.y - site.position.y);
+    dx * dx + dy * dy - site.weight
+}
+
+/// The face with the smallest distance to a point, or None if another face is
+/// too close to call
+fn brute_force_nearest<F: Fn(&Site<f64>) -> f64>(diagram: &Diagram<f64>, distance: F) -> Option<FaceId> {
+    let mut distances = diagram.faces.iter().enumerate()
+        .map(|(index, face)| (distance(&face.site), FaceId(index as u32)))
+        .collect::<Vec<_>>();
+    distances.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+    match distances.get(1) {
+        Some(&(second, _)) if second - distances[0].0 < TIE => None,
+        _ => distances.first().map(|&(_, face_id)| face_id),
+    }
+}
+
+/// Whether a point is inside a cell, which is convex with its corners counter-clockwise
+fn cell_contains(diagram: &Diagram<f64>, face_id: FaceId, point: Point2<f64>) -> bool {
+    let corners = diagram.face_vertices(face_id).collect::<Vec<_>>();
+    !corners.is_empty() && (0..corners.len()).all(|index| {
+        let (a, b) = (corners[index], corners[(index + 1) % corners.len()]);
+        (b.x - a.x) * (point.y - a.y) - (b.y - a.y) * (point.x - a.x) >= 0.0
+    })
+}
+
+#[cfg(feature = "rayon")]
+#[test]
+fn parallel_matches_serial() {
+    use voronoi::parallel::ParallelDiagramBuilder;
+
+    for seed in 0..4 {
+        let sites = random_sites(2000, 0.0, seed);
+        let serial = DiagramBuilder::new(rect(), sites.clone()).finish();
+        for &strips in &[1, 2, 7] {
+            let mut builder = ParallelDiagramBuilder::new(rect(), sites.clone());
+            builder.set_strips(strips);
+            let parallel = builder.finish();
+            assert!(parallel.validate().is_ok());
+            assert!(parallel.eq_topology(&serial), "seed {} with {} strips", seed, strips);
+        }
+    }
+}
+
+#[test]
+fn insert_site_matches_rebuild() {
+    for seed in 0..4 {
+        let sites = random_sites(200, 0.0, seed);
+        let mut diagram = DiagramBuilder::new(rect(), sites.clone()).finish();
+
+        let mut state = seed + 100;
+        let mut all_sites = sites;
+        for id in 200..250 {
+            let position = random_point(&mut state);
+            assert!(diagram.insert_site(position).is_some());
+            all_sites.push(Site::new(id, position));
+        }
+        assert!(diagram.validate().is_ok());
+
+        let rebuilt = DiagramBuilder::new(rect(), all_sites).finish();
+        assert!(diagram.eq_topology(&rebuilt), "seed {}", seed);
+    }
+}
+
+/// Inserts random sites into a diagram whose cells aren't all the cells of their
+/// sites any more, which has to leave it valid whether or not they go in
+fn insert_into_edited(diagram: &mut Diagram<f64>, seed: u64) -> usize {
+    let mut state = seed;
+    let inserted = (0..50).filter(|_| diagram.insert_site(random_point(&mut state)).is_some()).count();
+    assert!(diagram.validate().is_ok(), "seed {}", seed);
+    inserted
+}
+
+#[test]
+fn insert_site_after_noisy_edges() {
+    for seed in 0..4 {
+        let mut diagram = DiagramBuilder::new(rect(), random_sites(50, 0.0, seed)).finish();
+        diagram.add_noisy_edges(0.5, 2, 3);
+        insert_into_edited(&mut diagram, seed + 100);
+    }
+}
+
+#[test]
+fn insert_site_after_snap_to_grid() {
+    for seed in 0..4 {
+        let mut diagram = DiagramBuilder::new(rect(), random_sites(200, 0.0, seed)).finish();
+        diagram.snap_to_grid(3.0);
+        insert_into_edited(&mut diagram, seed + 100);
+    }
+}
+
+#[test]
+fn insert_site_under_l1() {
+    for seed in 0..4 {
+        let mut builder = DiagramBuilder::new(rect(), random_sites(50, 0.0, seed));
+        builder.set_metric(Metric::L1);
+        let mut diagram = builder.finish();
+        let faces = diagram.faces.len();
+        assert_eq!(insert_into_edited(&mut diagram, seed + 100), 0);
+        assert_eq!(diagram.faces.len(), faces);
+    }
+}
+
+#[test]
+fn insert_site_unclipped() {
+    for seed in 0..4 {
+        // Cells inside the hull are closed and can still take a site, but cells
+        // with edges going off to infinity can't
+        let mut diagram = DiagramBuilder::new(rect(), random_sites(50, 0.0, seed)).finish_unclipped();
+        let mut state = seed + 100;
+        for _ in 0..50 {
+            if let Some(face_id) = diagram.insert_site(random_point(&mut state)) {
+                assert!(diagram.face_edges(face_id).all(|halfedge| diagram.halfedges[halfedge.0 as usize].origin.is_some()));
+            }
+        }
+    }
+}
+
+#[test]
+fn power_cells_match_brute_force() {
+    for seed in 0..4 {
+        let diagram = PowerDiagramBuilder::new(rect(), random_sites(100, 50.0, seed)).finish();
+        assert!(diagram.validate().is_ok());
+
+        let mut state = seed + 100;
+        for _ in 0..1000 {
+            let point = random_point(&mut state);
+            if let Some(expected) = brute_force_nearest(&diagram, |site| power_distance(site, point)) {
+                assert!(cell_contains(&diagram, expected, point), "seed {} at {:?}", seed, point);
+                assert_eq!(diagram.locate(point), Some(expected));
+            }
+        }
+    }
+}
+
+#[test]
+fn locate_and_knn_match_brute_force() {
+    for seed in 0..4 {
+        let diagram = DiagramBuilder::new(rect(), random_sites(300, 0.0, seed)).finish();
+
+        let mut state = seed + 100;
+        for _ in 0..1000 {
+            // Points outside the rect get the face of the nearest site too
+            let point = Point2::new(random(&mut state) * 140.0 - 20.0, random(&mut state) * 140.0 - 20.0);
+            let distance = |site: &Site<f64>| power_distance(site, point);
+            if let Some(expected) = brute_force_nearest(&diagram, distance) {
+                assert_eq!(diagram.locate(point), Some(expected));
+                assert_eq!(diagram.nearest_site(point), Some(expected));
+            }
+
+            let mut by_distance = diagram.faces.iter().map(|face| distance(&face.site)).collect::<Vec<_>>();
+            by_distance.sort_by(|a, b| a.partial_cmp(b).unwrap());
+            let found = diagram.knn(point, 5);
+            assert_eq!(found.len(), 5);
+            for (rank, face_id) in found.into_iter().enumerate() {
+                let found_distance = distance(&diagram.faces[face_id.0 as usize].site);
+                assert!((found_distance - by_distance[rank]).abs() < TIE);
+            }
+        }
+    }
+}
+
+
+/// Whether the centre of a pixel is much nearer one site than any other
+fn clear_winner(diagram: &Diagram<f64>, point: Point2<f64>) -> Option<FaceId> {
+    brute_force_nearest(diagram, |site| power_distance(site, point))
+}
+
+#[test]
+fn rasterize_matches_brute_force() {
+    use voronoi::raster::{rasterize, rasterize_sites};
+
+    for seed in 0..2 {
+        let sites = random_sites(200, 0.0, seed);
+        let diagram = DiagramBuilder::new(rect(), sites.clone()).finish();
+        let positions = sites.iter().map(|site| site.position).collect::<Vec<_>>();
+
+        // The image covers part of the diagram and some of the outside
+        let image = Rect {
+            position: Point2::new(-10.0, 20.0),
+            size: Vector2::new(80.0, 100.0),
+        };
+        let (width, height) = (80, 61);
+        let cells = rasterize(&diagram, image, width, height);
+        let nearest = rasterize_sites(&positions, image, width, height);
+        for row in 0..height {
+            for column in 0..width {
+                let point = Point2::new(-10.0 + (column as f64 + 0.5) * 80.0 / width as f64, 20.0 + (row as f64 + 0.5) * 100.0 / height as f64);
+                if let Some(expected) = clear_winner(&diagram, point) {
+                    let pixel = (row * width + column) as usize;
+                    assert_eq!(cells[pixel], expected.0, "seed {} at {:?}", seed, point);
+                    assert_eq!(nearest[pixel], diagram.faces[expected.0 as usize].site.id, "seed {} at {:?}", seed, point);
+                }
+            }
+        }
+    }
+}
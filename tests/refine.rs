@@ -0,0 +1,87 @@
+extern crate cgmath;
+extern crate voronoi;
+
+mod common;
+
+use cgmath::Point2;
+
+use voronoi::build::{DiagramBuilder, Site};
+use voronoi::delaunay::{TriangleId, Triangulation};
+use voronoi::refine::Refinement;
+
+use common::{random, rect};
+
+/// The triangulation of random points and the corners of a square inside the
+/// rect, so that no corner of the hull is sharper than 60 degrees
+fn triangulation(seed: u64) -> Triangulation<f64> {
+    let mut state = seed;
+    let corners = [(1.0, 1.0), (99.0, 1.0), (99.0, 99.0), (1.0, 99.0)];
+    let sites = corners.iter()
+        .map(|&(x, y)| Point2::new(x, y))
+        .chain((0..60).map(|_| Point2::new(random(&mut state) * 96.0 + 2.0, random(&mut state) * 96.0 + 2.0)))
+        .enumerate()
+        .map(|(index, position)| Site::new(index as u32, position))
+        .collect();
+    DiagramBuilder::new(rect(), sites).finish_with_delaunay().1
+}
+
+fn area(triangulation: &Triangulation<f64>) -> f64 {
+    triangulation.triangles.iter().map(|triangle| {
+        let [a, b, c] = triangle.points.map(|index| triangulation.points[index as usize]);
+        ((b - a).perp_dot(c - a) / 2.0).abs()
+    }).sum()
+}
+
+fn assert_delaunay(triangulation: &Triangulation<f64>) {
+    for index in 0..triangulation.triangles.len() {
+        for corner in 0..3 {
+            assert!(triangulation.is_locally_delaunay(TriangleId(index as u32), corner));
+        }
+    }
+}
+
+#[test]
+fn refine_reaches_min_angle() {
+    for seed in 0..4 {
+        let given = triangulation(seed);
+        let thinnest = |triangulation: &Triangulation<f64>| {
+            triangulation.angles().iter().flat_map(|angles| angles.iter().cloned()).fold(f64::INFINITY, f64::min).to_degrees()
+        };
+        assert!(thinnest(&given) < 20.0, "seed {}", seed);
+
+        let refined = Refinement::new(given.clone()).finish();
+        assert!(thinnest(&refined) >= 20.0 - 1e-9, "seed {}: {} degrees", seed, thinnest(&refined));
+        assert!(refined.points.len() > given.points.len());
+        assert_eq!(&refined.points[..given.points.len()], &given.points[..]);
+        assert!((area(&refined) - area(&given)).abs() < 1e-6, "seed {}", seed);
+        assert_delaunay(&refined);
+    }
+}
+
+#[test]
+fn refine_reaches_max_area() {
+    let given = triangulation(7);
+    let mut refinement = Refinement::new(given.clone());
+    refinement.set_max_area(10.0);
+    let refined = refinement.finish();
+
+    for triangle in &refined.triangles {
+        let [a, b, c] = triangle.points.map(|index| refined.points[index as usize]);
+        assert!(((b - a).perp_dot(c - a) / 2.0).abs() <= 10.0);
+    }
+    assert!((area(&refined) - area(&given)).abs() < 1e-6);
+    assert_delaunay(&refined);
+}
+
+#[test]
+fn refine_stops_at_max_points() {
+    let given = triangulation(2);
+    let mut refinement = Refinement::new(given.clone());
+    refinement.set_min_angle(30.0);
+    refinement.set_max_points(given.points.len() + 20);
+    let refined = refinement.finish();
+
+    assert_eq!(refined.points.len(), given.points.len() + 20);
+    assert!((area(&refined) - area(&given)).abs() < 1e-6);
+    assert_delaunay(&refined);
+}
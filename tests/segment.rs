@@ -0,0 +1,81 @@
+extern crate cgmath;
+extern crate voronoi;
+
+mod common;
+
+use cgmath::Point2;
+
+use voronoi::diagram::FaceId;
+use voronoi::segment::{SegmentDiagram, SegmentDiagramBuilder, SegmentSite};
+
+use common::{random, rect};
+
+/// Spacing of the samples along the segments by default, which is 1/1024 of the
+/// side of the rect. Vertices can be about this far off, so points that are
+/// nearly as close to two sites aren't checked
+const SPACING: f64 = 100.0 / 1024.0;
+
+fn sites() -> Vec<SegmentSite<f64>> {
+    let segment = |id, start: (f64, f64), end: (f64, f64)| SegmentSite::Segment {
+        id,
+        start: Point2::new(start.0, start.1),
+        end: Point2::new(end.0, end.1),
+    };
+    let point = |id, x, y| SegmentSite::Point { id, position: Point2::new(x, y) };
+    vec![
+        segment(0, (20.0, 20.0), (50.0, 25.0)),
+        segment(1, (60.0, 70.0), (80.0, 40.0)),
+        segment(2, (10.0, 80.0), (40.0, 90.0)),
+        segment(3, (40.0, 90.0), (45.0, 60.0)),
+        point(4, 70.0, 15.0),
+        point(5, 30.0, 50.0),
+        point(6, 85.0, 85.0),
+        point(7, 55.0, 45.0),
+    ]
+}
+
+/// Whether a point is inside a face, with its curved edges followed closely
+fn face_contains(diagram: &SegmentDiagram<f64>, face_id: FaceId, point: Point2<f64>) -> bool {
+    let outline = diagram.diagram().face_edges(face_id)
+        .flat_map(|halfedge_id| {
+            let mut points = diagram.edge_points(halfedge_id, 32);
+            points.pop();
+            points
+        })
+        .collect::<Vec<_>>();
+
+    let mut inside = false;
+    for (index, &a) in outline.iter().enumerate() {
+        let b = outline[(index + 1) % outline.len()];
+        if (a.y > point.y) != (b.y > point.y) && point.x < a.x + (point.y - a.y) / (b.y - a.y) * (b.x - a.x) {
+            inside = !inside;
+        }
+    }
+    inside
+}
+
+#[test]
+fn cells_match_brute_force() {
+    let sites = sites();
+    let diagram = SegmentDiagramBuilder::new(rect(), sites.clone()).finish();
+    assert!(diagram.diagram().validate().is_ok());
+
+    let mut state = 0;
+    let mut checked = 0;
+    for _ in 0..2000 {
+        let point = Point2::new(random(&mut state) * 100.0, random(&mut state) * 100.0);
+        let mut distances = sites.iter().map(|site| (site.distance(point), site.id())).collect::<Vec<_>>();
+        distances.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+        if distances[1].0 - distances[0].0 < 2.0 * SPACING {
+            continue;
+        }
+
+        let containing = (0..diagram.diagram().faces.len() as u32).map(FaceId)
+            .filter(|&face_id| face_contains(&diagram, face_id, point))
+            .map(|face_id| diagram.site(face_id).unwrap().id())
+            .collect::<Vec<_>>();
+        assert_eq!(containing, vec![distances[0].1], "at {:?}", point);
+        checked += 1;
+    }
+    assert!(checked > 1500);
+}
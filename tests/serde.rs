@@ -0,0 +1,143 @@
+#![cfg(feature = "serde")]
+
+extern crate cgmath;
+extern crate serde_json;
+extern crate voronoi;
+
+mod common;
+
+use cgmath::Point2;
+
+use voronoi::build::{ClipRegion, DiagramBuilder, Rect, Site};
+use voronoi::diagram::{Diagram, FaceId};
+use voronoi::face_data::FaceData;
+use voronoi::power::PowerDiagramBuilder;
+use voronoi::soa::DiagramSoa;
+
+use common::{assert_same_topology, rect, sites};
+
+fn round_trip(diagram: &Diagram<f64>) -> Diagram<f64> {
+    let json = serde_json::to_string(diagram).unwrap();
+    serde_json::from_str(&json).unwrap()
+}
+
+#[test]
+fn diagram_round_trip() {
+    let diagram = DiagramBuilder::new(rect(), sites()).finish();
+    let other = round_trip(&diagram);
+    assert_same_topology(&diagram, &other);
+
+    for index in 0..100 {
+        let point = Point2::new(index as f64, (index * 7 % 100) as f64);
+        assert_eq!(diagram.locate(point), other.locate(point));
+    }
+}
+
+#[test]
+fn power_diagram_round_trip() {
+    let diagram = PowerDiagramBuilder::new(rect(), sites()).finish();
+    let other = round_trip(&diagram);
+    assert_same_topology(&diagram, &other);
+
+    // Lookups still use the weights after the index is rebuilt
+    for index in 0..100 {
+        let point = Point2::new(index as f64, (index * 7 % 100) as f64);
+        assert_eq!(diagram.locate(point), other.locate(point));
+    }
+}
+
+#[test]
+fn accelerator_round_trip() {
+    let mut diagram = PowerDiagramBuilder::new(rect(), sites()).finish();
+    diagram.build_accelerator();
+    let other = round_trip(&diagram);
+    assert!(other.accelerator().is_some());
+    assert_eq!(diagram.accelerator(), other.accelerator());
+
+    let plain = PowerDiagramBuilder::new(rect(), sites()).finish();
+    for index in 0..100 {
+        let point = Point2::new(index as f64, (index * 7 % 100) as f64);
+        assert_eq!(plain.locate(point), other.locate(point));
+    }
+}
+
+#[test]
+fn soa_round_trip() {
+    let diagram = PowerDiagramBuilder::new(rect(), sites()).finish();
+    let json = serde_json::to_string(&diagram.to_soa()).unwrap();
+    let soa: DiagramSoa<f64> = serde_json::from_str(&json).unwrap();
+    let other = soa.to_diagram();
+    assert_same_topology(&diagram, &other);
+
+    for index in 0..100 {
+        let point = Point2::new(index as f64, (index * 7 % 100) as f64);
+        assert_eq!(diagram.locate(point), other.locate(point));
+    }
+}
+
+#[test]
+fn round_trip_keeps_faces_usable() {
+    let diagram = round_trip(&DiagramBuilder::new(rect(), sites()).finish());
+
+    let area = (0..diagram.faces.len()).map(|index| diagram.face_area(FaceId(index as u32))).sum::<f64>();
+    assert!((area - 10000.0).abs() < 1e-6);
+}
+
+#[test]
+fn builder_inputs_round_trip() {
+    let site = Site::with_weight(3, Point2::new(1.5, -2.0), 4.0);
+    let other: Site<f64> = serde_json::from_str(&serde_json::to_string(&site).unwrap()).unwrap();
+    assert_eq!(other.id, 3);
+    assert_eq!(other.position, site.position);
+    assert_eq!(other.weight, site.weight);
+
+    let other: Rect<f64> = serde_json::from_str(&serde_json::to_string(&rect()).unwrap()).unwrap();
+    assert_eq!(other.position, rect().position);
+    assert_eq!(other.size, rect().size);
+
+    let polygon = ClipRegion::ConvexPolygon(vec![Point2::new(0.0, 0.0), Point2::new(10.0, 0.0), Point2::new(0.0, 10.0)]);
+    let other: ClipRegion<f64> = serde_json::from_str(&serde_json::to_string(&polygon).unwrap()).unwrap();
+    match other {
+        ClipRegion::ConvexPolygon(corners) => assert_eq!(corners.len(), 3),
+        ClipRegion::Rect(_) => panic!("expected a polygon"),
+    }
+}
+
+#[test]
+fn face_data_round_trip() {
+    let diagram = DiagramBuilder::new(rect(), sites()).finish();
+    let data = FaceData::from_faces(&diagram, |face_id, site| (face_id.0, site.id % 3));
+
+    let json = serde_json::to_string(&data).unwrap();
+    let other: FaceData<(u32, u32)> = serde_json::from_str(&json).unwrap();
+    assert_eq!(data, other);
+
+    // The values follow the sites into a diagram built from them in another order
+    let mut reversed = sites();
+    reversed.reverse();
+    let rebuilt = DiagramBuilder::new(rect(), reversed).finish();
+    for (index, face) in rebuilt.faces.iter().enumerate() {
+        let &(original, _) = other.face(&rebuilt, FaceId(index as u32)).unwrap();
+        assert_eq!(diagram.faces[original as usize].site.id, face.site.id);
+    }
+}
+
+#[test]
+fn builder_resumes_after_round_trip() {
+    let expected = DiagramBuilder::new(rect(), sites()).finish();
+
+    for steps in [0, 1, 20, 60, 1000] {
+        let mut builder = DiagramBuilder::new(rect(), sites());
+        for _ in 0..steps {
+            builder.step();
+        }
+
+        let json = serde_json::to_string(&builder).unwrap();
+        let mut other: DiagramBuilder<f64> = serde_json::from_str(&json).unwrap();
+        assert_eq!(other.stats(), builder.stats());
+
+        // Duplicates of sites the builder already had are still found
+        assert!(other.add_site(Site::new(100, sites()[0].position)).is_err());
+        assert_same_topology(&other.finish(), &expected);
+    }
+}
@@ -0,0 +1,85 @@
+extern crate cgmath;
+extern crate voronoi;
+
+mod common;
+
+use cgmath::{InnerSpace, Point2, Vector2};
+
+use voronoi::build::{DiagramBuilder, Site};
+use voronoi::diagram::{Diagram, FaceId};
+use voronoi::walk::Segment;
+
+use common::{random, rect};
+
+fn diagram(seed: u64) -> Diagram<f64> {
+    let mut state = seed;
+    let sites = (0..80).map(|id| {
+        Site::new(id, Point2::new(random(&mut state) * 100.0, random(&mut state) * 100.0))
+    }).collect();
+    DiagramBuilder::new(rect(), sites).finish()
+}
+
+/// Checks that the parts of a walk follow on from each other between two points,
+/// and that each one is inside the face it's given with
+fn check_walk(diagram: &Diagram<f64>, walk: &[(FaceId, Segment<f64>)], start: Point2<f64>, end: Point2<f64>) {
+    assert_eq!(walk.first().unwrap().1.start, start);
+    assert_eq!(walk.last().unwrap().1.end, end);
+    for pair in walk.windows(2) {
+        assert!((pair[0].1.end - pair[1].1.start).magnitude() < 1e-9);
+        assert_ne!(pair[0].0, pair[1].0);
+    }
+
+    let length = walk.iter().map(|&(_, segment)| (segment.end - segment.start).magnitude()).sum::<f64>();
+    assert!((length - (end - start).magnitude()).abs() < 1e-9, "{} along {:?} to {:?}", length, start, end);
+
+    for &(face_id, segment) in walk {
+        for step in 1..10 {
+            let point = segment.start + (segment.end - segment.start) * (step as f64 / 10.0);
+            assert_eq!(diagram.locate(point), Some(face_id), "{:?} in {:?}", point, segment);
+        }
+    }
+}
+
+#[test]
+fn walk_segment_inside() {
+    for seed in 0..4 {
+        let diagram = diagram(seed);
+        let mut state = seed + 100;
+        for _ in 0..50 {
+            let start = Point2::new(random(&mut state) * 100.0, random(&mut state) * 100.0);
+            let end = Point2::new(random(&mut state) * 100.0, random(&mut state) * 100.0);
+            let walk = diagram.walk_segment(start, end).collect::<Vec<_>>();
+            assert_eq!(walk[0].0, diagram.locate(start).unwrap());
+            check_walk(&diagram, &walk, start, end);
+        }
+    }
+}
+
+#[test]
+fn walk_segment_from_outside() {
+    let diagram = diagram(5);
+
+    // Only the part inside the rect is walked, from where it crosses the left edge
+    let start = Point2::new(-50.0, 20.0);
+    let end = Point2::new(150.0, 60.0);
+    let walk = diagram.walk_segment(start, end).collect::<Vec<_>>();
+    check_walk(&diagram, &walk, Point2::new(0.0, 30.0), Point2::new(100.0, 50.0));
+
+    // Starting outside and ending inside
+    let end = Point2::new(40.0, 70.0);
+    let walk = diagram.walk_segment(Point2::new(40.0, 130.0), end).collect::<Vec<_>>();
+    check_walk(&diagram, &walk, Point2::new(40.0, 100.0), end);
+
+    // Missing the rect altogether
+    assert_eq!(diagram.walk_segment(Point2::new(-10.0, -10.0), Point2::new(110.0, -5.0)).count(), 0);
+}
+
+#[test]
+fn walk_segment_within_one_face() {
+    let diagram = diagram(6);
+    let face_id = FaceId(0);
+    let site = diagram.site_of_face(face_id).unwrap().position;
+    let end = site + Vector2::new(1e-3, 1e-3);
+    let walk = diagram.walk_segment(site, end).collect::<Vec<_>>();
+    assert_eq!(walk, vec![(face_id, Segment { start: site, end })]);
+}